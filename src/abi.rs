@@ -0,0 +1,230 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use acvm::acir::native_types::Witness;
+use acvm::FieldElement;
+use ark_ff::PrimeField;
+use noirc_abi::{input_parser::InputValue, Abi, AbiType, AbiVisibility, Sign};
+use thiserror::Error;
+
+// Helpers for relating the ABI of a compiled program to the witnesses of its ACIR.
+//
+// Noir lays out the parameters in declaration order starting at `Witness(0)`, each parameter
+// occupying `field_count()` consecutive witnesses.
+
+/// The integer type of a witness, as declared in the ABI.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IntegerType {
+    pub signed: bool,
+    pub width: u32,
+}
+
+impl fmt::Display for IntegerType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefix = if self.signed { 'i' } else { 'u' };
+        write!(f, "{prefix}{}", self.width)
+    }
+}
+
+/// A witness whose value does not fit in the integer type the ABI declares for it.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error(
+    "witness {} holds {value}, which overflows {typ}{}",
+    .witness.0,
+    .gate_index.map(|index| format!(" (gate {index})")).unwrap_or_default()
+)]
+pub struct IntegerOverflow {
+    pub witness: Witness,
+    /// The first gate referring to the witness, if any.
+    pub gate_index: Option<usize>,
+    pub typ: IntegerType,
+    pub value: String,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AbiEncodingError {
+    #[error("the ABI has no public parameter named `{0}`")]
+    UnknownPublicParameter(String),
+    #[error("parameter `{0}` is not a single field element, integer or boolean")]
+    NotScalar(String),
+    #[error("{value} is out of range for parameter `{name}` of type {typ}")]
+    OutOfRange {
+        name: String,
+        value: i128,
+        typ: IntegerType,
+    },
+}
+
+/// Returns the witnesses assigned to each parameter of `abi`.
+pub fn param_witnesses(abi: &Abi) -> BTreeMap<String, Vec<Witness>> {
+    let mut next_witness = 0;
+    abi.parameters
+        .iter()
+        .map(|param| {
+            let num_fields = param.typ.field_count();
+            let witnesses = (next_witness..next_witness + num_fields)
+                .map(Witness)
+                .collect();
+            next_witness += num_fields;
+            (param.name.clone(), witnesses)
+        })
+        .collect()
+}
+
+/// Returns the integer type of every witness that the ABI declares as an integer (including
+/// booleans and string characters), for both the parameters and the return value.
+///
+/// `return_witnesses` are the circuit's return value witnesses in ascending order.
+pub fn integer_witnesses(
+    abi: &Abi,
+    return_witnesses: &[Witness],
+) -> BTreeMap<Witness, IntegerType> {
+    let params = abi.parameters.iter().map(|param| &param.typ);
+    let param_types = params.flat_map(flatten_integer_types);
+    let param_witnesses = (0..).map(Witness);
+
+    let return_types = abi
+        .return_type
+        .iter()
+        .flat_map(|return_type| flatten_integer_types(&return_type.abi_type));
+
+    param_witnesses
+        .zip(param_types)
+        .chain(return_witnesses.iter().copied().zip(return_types))
+        .filter_map(|(witness, typ)| Some((witness, typ?)))
+        .collect()
+}
+
+/// Flattens `typ` into one entry per field element, `None` for native field elements.
+fn flatten_integer_types(typ: &AbiType) -> Vec<Option<IntegerType>> {
+    match typ {
+        AbiType::Field => vec![None],
+        AbiType::Integer { sign, width } => vec![Some(IntegerType {
+            signed: matches!(sign, Sign::Signed),
+            width: *width,
+        })],
+        AbiType::Boolean => vec![Some(IntegerType {
+            signed: false,
+            width: 1,
+        })],
+        AbiType::String { length } => {
+            vec![
+                Some(IntegerType {
+                    signed: false,
+                    width: 8
+                });
+                *length as usize
+            ]
+        }
+        AbiType::Array { length, typ } => flatten_integer_types(typ).repeat(*length as usize),
+        AbiType::Struct { fields, .. } => fields
+            .iter()
+            .flat_map(|(_, typ)| flatten_integer_types(typ))
+            .collect(),
+        AbiType::Tuple { fields } => fields.iter().flat_map(flatten_integer_types).collect(),
+    }
+}
+
+/// Encodes `value` as the field element Noir assigns to the public parameter `name`.
+///
+/// Every value is mapped into the full field range without any sign interpretation of the
+/// resulting element: negative field values become `p - |value|`, while signed integers are
+/// stored in two's complement over their width, e.g. `-5: i32` becomes `2^32 - 5`. A verifier
+/// must pass the same representation as public input as the prover's witness holds.
+pub fn public_input_as_field<F: PrimeField>(
+    name: &str,
+    abi: &Abi,
+    value: i128,
+) -> Result<F, AbiEncodingError> {
+    let param = abi
+        .parameters
+        .iter()
+        .find(|param| param.name == name && matches!(param.visibility, AbiVisibility::Public))
+        .ok_or_else(|| AbiEncodingError::UnknownPublicParameter(name.to_string()))?;
+
+    let typ = match &param.typ {
+        AbiType::Field => return Ok(F::from(value)),
+        AbiType::Integer { sign, width } => IntegerType {
+            signed: matches!(sign, Sign::Signed),
+            width: *width,
+        },
+        AbiType::Boolean => IntegerType {
+            signed: false,
+            width: 1,
+        },
+        _ => return Err(AbiEncodingError::NotScalar(name.to_string())),
+    };
+
+    // The range of values representable by `typ`, saturating for types wider than an i128
+    let magnitude_bits = if typ.signed { typ.width - 1 } else { typ.width };
+    let max = if magnitude_bits >= 127 {
+        i128::MAX
+    } else {
+        (1 << magnitude_bits) - 1
+    };
+    let min = if typ.signed { -max - 1 } else { 0 };
+    if value < min || value > max {
+        return Err(AbiEncodingError::OutOfRange {
+            name: name.to_string(),
+            value,
+            typ,
+        });
+    }
+
+    if value < 0 {
+        Ok(F::from(2u64).pow([u64::from(typ.width)]) + F::from(value))
+    } else {
+        Ok(F::from(value))
+    }
+}
+
+/// Flattens `value` into the field elements of its witnesses, laid out as `typ` prescribes.
+///
+/// Returns `None` if `value` does not have the shape of `typ`.
+pub fn flatten_input_value(typ: &AbiType, value: &InputValue) -> Option<Vec<FieldElement>> {
+    match (typ, value) {
+        (AbiType::Field | AbiType::Integer { .. } | AbiType::Boolean, InputValue::Field(value)) => {
+            Some(vec![*value])
+        }
+        (AbiType::String { length }, InputValue::String(string)) => {
+            (string.len() == *length as usize).then(|| {
+                string
+                    .bytes()
+                    .map(|byte| FieldElement::from(byte as u128))
+                    .collect()
+            })
+        }
+        (AbiType::Array { length, typ }, InputValue::Vec(elements)) => {
+            if elements.len() != *length as usize {
+                return None;
+            }
+            let elements: Option<Vec<_>> = elements
+                .iter()
+                .map(|element| flatten_input_value(typ, element))
+                .collect();
+            Some(elements?.concat())
+        }
+        (AbiType::Tuple { fields }, InputValue::Vec(elements)) => {
+            if elements.len() != fields.len() {
+                return None;
+            }
+            let elements: Option<Vec<_>> = fields
+                .iter()
+                .zip(elements)
+                .map(|(typ, element)| flatten_input_value(typ, element))
+                .collect();
+            Some(elements?.concat())
+        }
+        (AbiType::Struct { fields, .. }, InputValue::Struct(members)) => {
+            if members.len() != fields.len() {
+                return None;
+            }
+            let members: Option<Vec<_>> = fields
+                .iter()
+                .map(|(name, typ)| flatten_input_value(typ, members.get(name)?))
+                .collect();
+            Some(members?.concat())
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,575 @@
+use std::collections::BTreeMap;
+
+use acvm::acir::native_types::Witness;
+use ark_ff::PrimeField;
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError, Variable};
+
+use crate::R1csSize;
+
+// Lowering of `BlackBoxFuncCall::AES128Encrypt` to R1CS.
+//
+// Following the ACVM solver, the plaintext is encrypted with AES-128 in CBC mode after PKCS#7
+// padding. The number of output bytes is fixed by the circuit, so the padding is made of
+// constant bytes, whose value is the number of padding bytes, between 1 and 16.
+//
+// The plaintext, IV and key bytes are decomposed into 8 little-endian bits, which also
+// range-constrains them, and the cipher is applied to the bits. ShiftRows only rewires bytes,
+// and every XOR of two bits costs one constraint unless one of them is constant, as the padding
+// and the round constants are. MixColumns multiplies by 2 in GF(2^8) by shifting and XORing the
+// reduction polynomial with the top bit. The S-box is a table lookup: the products of the low
+// four bits of the byte give each of the 16 rows of the table, indexed by the high four bits,
+// as a linear combination of the 16 monomials, and the high bits select the row in a tree of
+// 15 selections. The looked-up byte is decomposed into bits again, for 35 constraints per
+// lookup. The output bytes must recompose from the bits of the ciphertext.
+//
+// The constraints are only generated with the `aes` feature, as they dwarf most circuits.
+
+/// A single `BlackBoxFuncCall::AES128Encrypt`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Aes128EncryptGate {
+    pub(crate) inputs: Vec<Witness>,
+    pub(crate) iv: Vec<Witness>,
+    pub(crate) key: Vec<Witness>,
+    pub(crate) outputs: Vec<Witness>,
+    pub(crate) source_opcode: usize,
+}
+
+impl Aes128EncryptGate {
+    pub(crate) fn witnesses(&self) -> impl Iterator<Item = Witness> + '_ {
+        self.inputs
+            .iter()
+            .chain(&self.iv)
+            .chain(&self.key)
+            .chain(&self.outputs)
+            .copied()
+    }
+
+    pub(crate) fn witnesses_mut(&mut self) -> impl Iterator<Item = &mut Witness> {
+        self.inputs
+            .iter_mut()
+            .chain(self.iv.iter_mut())
+            .chain(self.key.iter_mut())
+            .chain(self.outputs.iter_mut())
+    }
+}
+
+/// Counts the constraints and witnesses [`enforce_aes128_encrypt_gates`] allocates, none
+/// without the `aes` feature.
+#[cfg_attr(not(feature = "aes"), allow(unused_variables))]
+pub(crate) fn r1cs_size(gates: &[Aes128EncryptGate]) -> R1csSize {
+    #[cfg(feature = "aes")]
+    return cipher::r1cs_size(gates);
+
+    #[cfg(not(feature = "aes"))]
+    R1csSize::default()
+}
+
+/// Enforces every encryption. Fails with `SynthesisError::Unsatisfiable` if the number of
+/// output bytes does not match the PKCS#7 padding of the plaintext, or if there are any without
+/// the `aes` feature.
+#[cfg_attr(not(feature = "aes"), allow(unused_variables))]
+pub(crate) fn enforce_aes128_encrypt_gates<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    gates: &[Aes128EncryptGate],
+    variables: &BTreeMap<Witness, Variable>,
+) -> Result<(), SynthesisError> {
+    #[cfg(feature = "aes")]
+    return cipher::enforce_aes128_encrypt_gates(cs, gates, variables);
+
+    #[cfg(not(feature = "aes"))]
+    match gates.is_empty() {
+        true => Ok(()),
+        false => Err(SynthesisError::Unsatisfiable),
+    }
+}
+
+#[cfg(feature = "aes")]
+mod cipher {
+    use std::collections::BTreeMap;
+
+    use acvm::acir::native_types::Witness;
+    use ark_ff::PrimeField;
+    use ark_relations::{
+        lc,
+        r1cs::{ConstraintSystemRef, LinearCombination, SynthesisError, Variable},
+    };
+
+    use super::Aes128EncryptGate;
+    use crate::gadgets::{alloc_bits, enforce_bit_decomposition, pack_bits};
+    use crate::R1csSize;
+
+    const BLOCK_BYTES: usize = 16;
+    const ROUNDS: usize = 10;
+
+    /// The constants XORed into the first byte of the first word of each round key.
+    const ROUND_CONSTANTS: [u8; ROUNDS] =
+        [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+    const SBOX: [u8; 256] = [
+        0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab,
+        0x76, 0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4,
+        0x72, 0xc0, 0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71,
+        0xd8, 0x31, 0x15, 0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2,
+        0xeb, 0x27, 0xb2, 0x75, 0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6,
+        0xb3, 0x29, 0xe3, 0x2f, 0x84, 0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb,
+        0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf, 0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45,
+        0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8, 0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5,
+        0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2, 0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44,
+        0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73, 0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a,
+        0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb, 0xe0, 0x32, 0x3a, 0x0a, 0x49,
+        0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79, 0xe7, 0xc8, 0x37, 0x6d,
+        0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08, 0xba, 0x78, 0x25,
+        0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a, 0x70, 0x3e,
+        0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e, 0xe1,
+        0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+        0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb,
+        0x16,
+    ];
+
+    /// A bit of the state, with its value unless in setup mode. A constant bit has no witness,
+    /// its linear combination being empty or the constant one.
+    #[derive(Clone)]
+    struct Bit<F: PrimeField> {
+        lc: LinearCombination<F>,
+        value: Option<bool>,
+        is_constant: bool,
+    }
+
+    impl<F: PrimeField> Bit<F> {
+        fn constant(value: bool) -> Self {
+            Bit {
+                lc: match value {
+                    true => lc!() + Variable::One,
+                    false => lc!(),
+                },
+                value: Some(value),
+                is_constant: true,
+            }
+        }
+
+        fn not(&self) -> Self {
+            Bit {
+                lc: lc!() + Variable::One - &self.lc,
+                value: self.value.map(|value| !value),
+                is_constant: self.is_constant,
+            }
+        }
+    }
+
+    /// The little-endian bits of a byte.
+    type Byte<F> = Vec<Bit<F>>;
+
+    fn constant_byte<F: PrimeField>(value: u8) -> Byte<F> {
+        (0..8).map(|i| Bit::constant(value >> i & 1 == 1)).collect()
+    }
+
+    fn byte_value<F: PrimeField>(byte: &[Bit<F>]) -> Option<u8> {
+        byte.iter().enumerate().try_fold(0u8, |acc, (i, bit)| {
+            bit.value.map(|bit| acc | u8::from(bit) << i)
+        })
+    }
+
+    /// The number of padding bytes, if the output has room for the padded plaintext.
+    fn padding(gate: &Aes128EncryptGate) -> Option<usize> {
+        let padding = gate.outputs.len().checked_sub(gate.inputs.len())?;
+        let is_valid =
+            gate.outputs.len() % BLOCK_BYTES == 0 && (1..=BLOCK_BYTES).contains(&padding);
+        is_valid.then_some(padding)
+    }
+
+    pub(super) fn r1cs_size(gates: &[Aes128EncryptGate]) -> R1csSize {
+        let size = |constraints: usize, witnesses: usize| R1csSize {
+            constraints,
+            witnesses,
+            public_inputs: 0,
+        };
+        let block_bits = 8 * BLOCK_BYTES;
+        // The products of the low bits and the selections, then the decomposition
+        let (lookup_constraints, lookup_witnesses) = (11 + 15 + 9, 11 + 15 + 8);
+        // XORing the four bytes, then per byte XORing it with the next one, the reduction
+        // polynomial, and the XOR of all four bytes
+        let mix_columns = 4 * (3 * 8 + 4 * (8 + 3 + 2 * 8));
+
+        let mut total = R1csSize::default();
+        for gate in gates {
+            let Some(padding) = padding(gate) else {
+                continue;
+            };
+            let num_blocks = gate.outputs.len() / BLOCK_BYTES;
+            let num_bytes = gate.inputs.len() + gate.iv.len() + gate.key.len();
+            // The decomposition of the input bytes, and the packing of the output bytes
+            total += size(9 * num_bytes + gate.outputs.len(), 8 * num_bytes);
+            // The key schedule looks up and XORs a word per round key, and XORs three more
+            let key_xors = ROUNDS * block_bits;
+            total += size(
+                ROUNDS * 4 * lookup_constraints + key_xors,
+                ROUNDS * 4 * lookup_witnesses + key_xors,
+            );
+            // Chaining XORs the previous block into every plaintext bit except the padding
+            let chaining = num_blocks * block_bits - 8 * padding;
+            // Each round looks up every byte and XORs the round key, all but the last mixing
+            // the columns, after XORing the first round key
+            let per_block = (ROUNDS + 1) * block_bits + (ROUNDS - 1) * mix_columns;
+            total += size(
+                chaining + num_blocks * (per_block + ROUNDS * BLOCK_BYTES * lookup_constraints),
+                chaining + num_blocks * (per_block + ROUNDS * BLOCK_BYTES * lookup_witnesses),
+            );
+        }
+        total
+    }
+
+    pub(super) fn enforce_aes128_encrypt_gates<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        gates: &[Aes128EncryptGate],
+        variables: &BTreeMap<Witness, Variable>,
+    ) -> Result<(), SynthesisError> {
+        for gate in gates {
+            let padding = padding(gate).ok_or(SynthesisError::Unsatisfiable)?;
+            let bytes = |witnesses: &[Witness]| {
+                witnesses
+                    .iter()
+                    .map(|witness| {
+                        let bits = enforce_bit_decomposition(cs, variables[witness], 8)?;
+                        Ok(bits
+                            .into_iter()
+                            .map(|bit| Bit {
+                                lc: lc!() + bit,
+                                value: cs.assigned_value(bit).map(|value| value.is_one()),
+                                is_constant: false,
+                            })
+                            .collect())
+                    })
+                    .collect::<Result<Vec<Byte<F>>, SynthesisError>>()
+            };
+            let mut plaintext = bytes(&gate.inputs)?;
+            let iv = bytes(&gate.iv)?;
+            let key = bytes(&gate.key)?;
+            plaintext.resize(gate.outputs.len(), constant_byte(padding as u8));
+
+            let round_keys = expand_key(cs, key)?;
+            let mut previous = iv;
+            let mut ciphertext = Vec::new();
+            for block in plaintext.chunks(BLOCK_BYTES) {
+                let block = block
+                    .iter()
+                    .zip(&previous)
+                    .map(|(byte, previous)| xor_bytes(cs, byte, previous))
+                    .collect::<Result<Vec<_>, _>>()?;
+                previous = encrypt_block(cs, block, &round_keys)?;
+                ciphertext.extend(previous.iter().cloned());
+            }
+
+            for (byte, output) in ciphertext.iter().zip(&gate.outputs) {
+                let packed = byte.iter().enumerate().fold(lc!(), |packed, (i, bit)| {
+                    packed + (F::from(1u64 << i), &bit.lc)
+                });
+                cs.enforce_constraint(lc!() + Variable::One, packed, lc!() + variables[output])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the 11 round keys of `key`, each as 16 bytes.
+    fn expand_key<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        key: Vec<Byte<F>>,
+    ) -> Result<Vec<Vec<Byte<F>>>, SynthesisError> {
+        let mut round_keys = vec![key];
+        for round_constant in ROUND_CONSTANTS {
+            let previous = round_keys.last().expect("there is a round key");
+            // The last word, rotated, looked up and XORed with the round constant
+            let mut word = previous[12..]
+                .iter()
+                .cycle()
+                .skip(1)
+                .take(4)
+                .map(|byte| substitute(cs, byte))
+                .collect::<Result<Vec<_>, _>>()?;
+            word[0] = xor_bytes(cs, &word[0], &constant_byte(round_constant))?;
+
+            let mut round_key: Vec<Byte<F>> = Vec::new();
+            for (index, byte) in previous.iter().enumerate() {
+                let rhs = match index < 4 {
+                    true => &word[index],
+                    false => &round_key[index - 4],
+                };
+                let byte = xor_bytes(cs, byte, rhs)?;
+                round_key.push(byte);
+            }
+            round_keys.push(round_key);
+        }
+        Ok(round_keys)
+    }
+
+    /// Encrypts a block of 16 bytes, in column-major order.
+    fn encrypt_block<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        block: Vec<Byte<F>>,
+        round_keys: &[Vec<Byte<F>>],
+    ) -> Result<Vec<Byte<F>>, SynthesisError> {
+        let add_round_key = |state: &[Byte<F>], round_key: &[Byte<F>]| {
+            state
+                .iter()
+                .zip(round_key)
+                .map(|(byte, key)| xor_bytes(cs, byte, key))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        let mut state = add_round_key(&block, &round_keys[0])?;
+        for (round, round_key) in round_keys.iter().enumerate().skip(1) {
+            let substituted = state
+                .iter()
+                .map(|byte| substitute(cs, byte))
+                .collect::<Result<Vec<_>, _>>()?;
+            // The row `r` of the column `c` is the byte `r + 4 * c`, and moves `r` columns left
+            let shifted: Vec<Byte<F>> = (0..BLOCK_BYTES)
+                .map(|index| {
+                    let (row, column) = (index % 4, index / 4);
+                    substituted[row + 4 * ((column + row) % 4)].clone()
+                })
+                .collect();
+            let mixed = match round < ROUNDS {
+                true => mix_columns(cs, &shifted)?,
+                false => shifted,
+            };
+            state = add_round_key(&mixed, round_key)?;
+        }
+        Ok(state)
+    }
+
+    fn mix_columns<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        state: &[Byte<F>],
+    ) -> Result<Vec<Byte<F>>, SynthesisError> {
+        let mut mixed = Vec::new();
+        for column in state.chunks(4) {
+            let mut all = xor_bytes(cs, &column[0], &column[1])?;
+            all = xor_bytes(cs, &all, &column[2])?;
+            all = xor_bytes(cs, &all, &column[3])?;
+            // 2 * a[i] + 3 * a[i + 1] + a[i + 2] + a[i + 3] == a[i] + all + 2 * (a[i] + a[i + 1])
+            for row in 0..4 {
+                let sum = xor_bytes(cs, &column[row], &column[(row + 1) % 4])?;
+                let doubled = double(cs, &sum)?;
+                let byte = xor_bytes(cs, &column[row], &all)?;
+                mixed.push(xor_bytes(cs, &byte, &doubled)?);
+            }
+        }
+        Ok(mixed)
+    }
+
+    /// Multiplies `byte` by 2 in GF(2^8): shifts it up, and reduces the top bit with the
+    /// polynomial `0x1b`.
+    fn double<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        byte: &[Bit<F>],
+    ) -> Result<Byte<F>, SynthesisError> {
+        let top = &byte[7];
+        let mut doubled = vec![top.clone()];
+        for (i, bit) in byte[..7].iter().enumerate() {
+            doubled.push(match 0x1b >> (i + 1) & 1 == 1 {
+                true => xor(cs, bit, top)?,
+                false => bit.clone(),
+            });
+        }
+        Ok(doubled)
+    }
+
+    /// Looks `byte` up in the S-box, in 35 constraints.
+    fn substitute<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        byte: &[Bit<F>],
+    ) -> Result<Byte<F>, SynthesisError> {
+        // The products of each subset of the low bits, indexed by the subset's mask
+        let mut monomials: Vec<(LinearCombination<F>, Option<bool>)> =
+            vec![(lc!() + Variable::One, Some(true))];
+        for mask in 1..16usize {
+            let low = mask.trailing_zeros() as usize;
+            let rest = mask & (mask - 1);
+            let bit = &byte[low];
+            let monomial = match rest {
+                0 => (bit.lc.clone(), bit.value),
+                _ => {
+                    let (rest_lc, rest_value) = monomials[rest].clone();
+                    let value = rest_value.zip(bit.value).map(|(lhs, rhs)| lhs && rhs);
+                    let product = cs.new_witness_variable(|| {
+                        value.map(F::from).ok_or(SynthesisError::AssignmentMissing)
+                    })?;
+                    cs.enforce_constraint(rest_lc, bit.lc.clone(), lc!() + product)?;
+                    (lc!() + product, value)
+                }
+            };
+            monomials.push(monomial);
+        }
+
+        // Each row of the table as a linear combination of the monomials, whose coefficients
+        // are the Möbius transform of the row over the subsets of the low bits
+        let low_value = byte_value(&byte[..4]);
+        let mut rows = Vec::new();
+        for high in 0..16 {
+            let mut coefficients: Vec<i64> = (0..16)
+                .map(|low| i64::from(SBOX[16 * high + low]))
+                .collect();
+            for i in 0..4 {
+                for mask in 0..16 {
+                    if mask >> i & 1 == 1 {
+                        coefficients[mask] -= coefficients[mask ^ 1 << i];
+                    }
+                }
+            }
+            let row = coefficients.iter().zip(&monomials).fold(
+                lc!(),
+                |row, (coefficient, (monomial, _))| {
+                    let coefficient = match *coefficient < 0 {
+                        true => -F::from(coefficient.unsigned_abs()),
+                        false => F::from(*coefficient as u64),
+                    };
+                    row + (coefficient, monomial)
+                },
+            );
+            let value = low_value.map(|low| F::from(SBOX[16 * high + low as usize]));
+            rows.push((row, value));
+        }
+
+        // The high bits select the row, from the least significant
+        for bit in &byte[4..] {
+            rows = rows
+                .chunks(2)
+                .map(|pair| {
+                    let [(when_false, false_value), (when_true, true_value)] = pair else {
+                        unreachable!("the rows are paired")
+                    };
+                    let value = bit.value.and_then(|bit| match bit {
+                        true => *true_value,
+                        false => *false_value,
+                    });
+                    let selected =
+                        cs.new_witness_variable(|| value.ok_or(SynthesisError::AssignmentMissing))?;
+                    // bit * (when_true - when_false) == selected - when_false
+                    cs.enforce_constraint(
+                        bit.lc.clone(),
+                        when_true.clone() - when_false,
+                        lc!() + selected - when_false,
+                    )?;
+                    Ok((lc!() + selected, value))
+                })
+                .collect::<Result<Vec<_>, SynthesisError>>()?;
+        }
+
+        let (looked_up, value) = rows.pop().expect("a row is selected");
+        let bits = alloc_bits(cs, value, 8)?;
+        cs.enforce_constraint(lc!() + Variable::One, pack_bits(&bits), looked_up)?;
+        let value = byte_value(byte).map(|byte| SBOX[byte as usize]);
+        Ok(bits
+            .into_iter()
+            .enumerate()
+            .map(|(i, bit)| Bit {
+                lc: lc!() + bit,
+                value: value.map(|value| value >> i & 1 == 1),
+                is_constant: false,
+            })
+            .collect())
+    }
+
+    fn xor_bytes<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        lhs: &[Bit<F>],
+        rhs: &[Bit<F>],
+    ) -> Result<Byte<F>, SynthesisError> {
+        lhs.iter()
+            .zip(rhs)
+            .map(|(lhs, rhs)| xor(cs, lhs, rhs))
+            .collect()
+    }
+
+    fn xor<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        lhs: &Bit<F>,
+        rhs: &Bit<F>,
+    ) -> Result<Bit<F>, SynthesisError> {
+        // A constant bit flips the other one or leaves it unchanged
+        match (lhs.is_constant, rhs.is_constant) {
+            (true, _) if lhs.value == Some(true) => return Ok(rhs.not()),
+            (true, _) => return Ok(rhs.clone()),
+            (_, true) if rhs.value == Some(true) => return Ok(lhs.not()),
+            (_, true) => return Ok(lhs.clone()),
+            _ => {}
+        }
+        let value = lhs.value.zip(rhs.value).map(|(lhs, rhs)| lhs ^ rhs);
+        let output = cs
+            .new_witness_variable(|| value.map(F::from).ok_or(SynthesisError::AssignmentMissing))?;
+        // 2 * lhs * rhs == lhs + rhs - output
+        cs.enforce_constraint(
+            lhs.lc.clone() * F::from(2u64),
+            rhs.lc.clone(),
+            lhs.lc.clone() + &rhs.lc - output,
+        )?;
+        Ok(Bit {
+            lc: lc!() + output,
+            value,
+            is_constant: false,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "aes"))]
+mod test {
+    use acvm::acir::circuit::opcodes::{BlackBoxFuncCall, FunctionInput};
+    use acvm::acir::circuit::Opcode;
+    use acvm::acir::native_types::WitnessMap;
+    use acvm::FieldElement;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+
+    use super::*;
+    use crate::test::circuit_with_opcodes;
+    use crate::CurveAcir;
+
+    #[test]
+    fn encrypts_a_block() {
+        // CBC-AES128.Encrypt from NIST SP 800-38A, F.2.1, with the block of PKCS#7 padding
+        let key = [
+            0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf,
+            0x4f, 0x3c,
+        ];
+        let iv: [u8; 16] = std::array::from_fn(|index| index as u8);
+        let plaintext = [
+            0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93,
+            0x17, 0x2a,
+        ];
+        let ciphertext = [
+            0x76, 0x49, 0xab, 0xac, 0x81, 0x19, 0xb2, 0x46, 0xce, 0xe9, 0x8e, 0x9b, 0x12, 0xe9,
+            0x19, 0x7d, 0x89, 0x64, 0xe0, 0xb1, 0x49, 0xc1, 0x0b, 0x7b, 0x68, 0x2e, 0x6e, 0x39,
+            0xaa, 0xeb, 0x73, 0x1c,
+        ];
+
+        let byte = |index: usize| FunctionInput {
+            witness: Witness(index as u32),
+            num_bits: 8,
+        };
+        let encrypt = Opcode::BlackBoxFuncCall(BlackBoxFuncCall::AES128Encrypt {
+            inputs: (0..16).map(byte).collect(),
+            iv: Box::new(std::array::from_fn(|index| byte(16 + index))),
+            key: Box::new(std::array::from_fn(|index| byte(32 + index))),
+            outputs: (48..80).map(Witness).collect(),
+        });
+        let circuit = circuit_with_opcodes(79, vec![encrypt], &[]);
+
+        let is_satisfied = |ciphertext: &[u8; 32]| {
+            let mut witness_map = WitnessMap::new();
+            let bytes = plaintext.iter().chain(&iv).chain(&key).chain(ciphertext);
+            for (index, byte) in bytes.enumerate() {
+                witness_map.insert(Witness(index as u32), FieldElement::from(*byte as u128));
+            }
+            let circuit = CurveAcir::from((&circuit, witness_map));
+            let size = circuit.r1cs_size();
+            let cs = ConstraintSystem::new_ref();
+            circuit.generate_constraints(cs.clone()).unwrap();
+            assert_eq!(cs.num_constraints(), size.constraints);
+            cs.is_satisfied().unwrap()
+        };
+        assert!(is_satisfied(&ciphertext));
+
+        let mut tampered = ciphertext;
+        tampered[0] ^= 1;
+        assert!(!is_satisfied(&tampered));
+    }
+}
@@ -0,0 +1,716 @@
+use std::collections::BTreeMap;
+
+use acvm::acir::native_types::Witness;
+use ark_ff::PrimeField;
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError, Variable};
+
+use crate::R1csSize;
+
+// Lowering of the `BlackBoxFuncCall::BigInt*` opcodes to R1CS.
+//
+// A BigInt is only known to the ACVM by its id: `BigIntFromLeBytes` creates it from witness
+// bytes along with its modulus, the arithmetic opcodes derive new ones, and `BigIntToLeBytes`
+// writes one back to 32 witness bytes. Here each BigInt is held as four 64-bit limbs, whose
+// value is computed while the constraints are generated, and the opcodes are enforced in order.
+//
+// A BigInt created from bytes is not reduced, as in the ACVM, while the result `r` of an
+// operation is allocated in range-constrained limbs and constrained to `r < modulus`. The
+// operation is checked as the integer identity `lhs == q * modulus + r`, where `lhs` is the
+// sum or product of the operands, shifted by a multiple of the modulus when it subtracts. The
+// schoolbook products of the limbs are allocated, and the columns of the identity are carried
+// into the next, the carries being range-constrained so that no column wraps around the field
+// modulus. Division multiplies the result back, and the result of a division by zero is zero.
+//
+// The modulus must be a prime below `2^256` for division to agree with the ACVM, which
+// inverts through Fermat's little theorem. The constraints are only generated with the
+// `bigint` feature.
+
+/// An arithmetic BigInt opcode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BigIntOperation {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// A single BigInt opcode, referring to BigInts by their id.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum BigIntCall {
+    /// `BlackBoxFuncCall::BigIntFromLeBytes`, with the little-endian bytes of the modulus.
+    FromLeBytes {
+        inputs: Vec<Witness>,
+        modulus: Vec<u8>,
+        output: u32,
+    },
+    Operation {
+        operation: BigIntOperation,
+        lhs: u32,
+        rhs: u32,
+        output: u32,
+    },
+    /// `BlackBoxFuncCall::BigIntToLeBytes`.
+    ToLeBytes { input: u32, outputs: Vec<Witness> },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct BigIntGate {
+    pub(crate) call: BigIntCall,
+    pub(crate) source_opcode: usize,
+}
+
+impl BigIntGate {
+    pub(crate) fn witnesses(&self) -> impl Iterator<Item = Witness> + '_ {
+        let witnesses: &[Witness] = match &self.call {
+            BigIntCall::FromLeBytes { inputs, .. } => inputs,
+            BigIntCall::Operation { .. } => &[],
+            BigIntCall::ToLeBytes { outputs, .. } => outputs,
+        };
+        witnesses.iter().copied()
+    }
+
+    pub(crate) fn witnesses_mut(&mut self) -> impl Iterator<Item = &mut Witness> {
+        let witnesses: &mut [Witness] = match &mut self.call {
+            BigIntCall::FromLeBytes { inputs, .. } => inputs,
+            BigIntCall::Operation { .. } => &mut [],
+            BigIntCall::ToLeBytes { outputs, .. } => outputs,
+        };
+        witnesses.iter_mut()
+    }
+}
+
+/// Counts the constraints and witnesses [`enforce_bigint_gates`] allocates, none without the
+/// `bigint` feature.
+#[cfg_attr(not(feature = "bigint"), allow(unused_variables))]
+pub(crate) fn r1cs_size(gates: &[BigIntGate]) -> R1csSize {
+    #[cfg(feature = "bigint")]
+    return arithmetic::r1cs_size(gates);
+
+    #[cfg(not(feature = "bigint"))]
+    R1csSize::default()
+}
+
+/// Enforces every BigInt opcode, in order. Fails with `SynthesisError::Unsatisfiable` if an
+/// opcode refers to an unknown BigInt or mixes moduli, if a BigInt does not fit 32 bytes or a
+/// modulus is not between 2 and `2^256`, or if there are any without the `bigint` feature.
+#[cfg_attr(not(feature = "bigint"), allow(unused_variables))]
+pub(crate) fn enforce_bigint_gates<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    gates: &[BigIntGate],
+    variables: &BTreeMap<Witness, Variable>,
+) -> Result<(), SynthesisError> {
+    #[cfg(feature = "bigint")]
+    return arithmetic::enforce_bigint_gates(cs, gates, variables);
+
+    #[cfg(not(feature = "bigint"))]
+    match gates.is_empty() {
+        true => Ok(()),
+        false => Err(SynthesisError::Unsatisfiable),
+    }
+}
+
+#[cfg(feature = "bigint")]
+mod arithmetic {
+    use std::collections::BTreeMap;
+
+    use acvm::acir::native_types::Witness;
+    use ark_ff::{BigInteger, PrimeField};
+    use ark_relations::{
+        lc,
+        r1cs::{ConstraintSystemRef, LinearCombination, SynthesisError, Variable},
+    };
+    use num_bigint::{BigInt, BigUint};
+
+    use super::{BigIntCall, BigIntGate, BigIntOperation};
+    use crate::gadgets::enforce_bit_decomposition;
+    use crate::R1csSize;
+
+    const LIMB_BITS: u32 = 64;
+    const NUM_LIMBS: usize = 4;
+    const NUM_BYTES: usize = 32;
+    /// The limbs of a quotient, which is below `2^512` as the modulus is at least 2.
+    const QUOTIENT_LIMBS: usize = 8;
+    /// The columns of an identity: those of the quotient times the modulus, which are the most.
+    const IDENTITY_COLUMNS: usize = QUOTIENT_LIMBS + NUM_LIMBS - 1;
+    /// The width of a carry between columns, offset to be non-negative.
+    const CARRY_BITS: u32 = 70;
+
+    /// Range-constrained limbs, then the difference to the modulus and its carries.
+    const REDUCED_SIZE: (usize, usize) = (
+        2 * NUM_LIMBS * (LIMB_BITS as usize + 1) + 2 * NUM_LIMBS - 1,
+        2 * NUM_LIMBS * (LIMB_BITS as usize + 1) + NUM_LIMBS - 1,
+    );
+    /// The quotient, the carries between columns and the last column.
+    const IDENTITY_SIZE: (usize, usize) = (
+        QUOTIENT_LIMBS * (LIMB_BITS as usize + 1)
+            + (IDENTITY_COLUMNS - 1) * (CARRY_BITS as usize + 2)
+            + 1,
+        QUOTIENT_LIMBS * (LIMB_BITS as usize + 1)
+            + (IDENTITY_COLUMNS - 1) * (CARRY_BITS as usize + 1),
+    );
+    /// The products of the limbs of two operands.
+    const PRODUCT_SIZE: usize = NUM_LIMBS * NUM_LIMBS;
+
+    fn to_field<F: PrimeField>(value: &BigInt) -> F {
+        let magnitude = F::from_le_bytes_mod_order(&value.magnitude().to_bytes_le());
+        match value.sign() {
+            num_bigint::Sign::Minus => -magnitude,
+            _ => magnitude,
+        }
+    }
+
+    /// Splits `value` into `num_limbs` little-endian 64-bit limbs.
+    fn split(value: &BigUint, num_limbs: usize) -> Vec<BigUint> {
+        let mask = (BigUint::from(1u8) << LIMB_BITS) - 1u8;
+        (0..num_limbs)
+            .map(|i| (value >> (LIMB_BITS as usize * i)) & &mask)
+            .collect()
+    }
+
+    /// A BigInt as little-endian 64-bit limbs, with its value unless in setup mode.
+    #[derive(Clone)]
+    struct Int<F: PrimeField> {
+        limbs: Vec<LinearCombination<F>>,
+        value: Option<BigUint>,
+    }
+
+    impl<F: PrimeField> Int<F> {
+        fn constant(value: &BigUint, num_limbs: usize) -> Self {
+            let limbs = split(value, num_limbs)
+                .iter()
+                .map(|limb| lc!() + (to_field::<F>(&limb.clone().into()), Variable::One))
+                .collect();
+            Int {
+                limbs,
+                value: Some(value.clone()),
+            }
+        }
+
+        /// Allocates `value` as `num_limbs` limbs range-constrained to 64 bits.
+        fn alloc(
+            cs: &ConstraintSystemRef<F>,
+            value: Option<BigUint>,
+            num_limbs: usize,
+        ) -> Result<Self, SynthesisError> {
+            let limb_values = value.as_ref().map(|value| split(value, num_limbs));
+            let mut limbs = Vec::with_capacity(num_limbs);
+            for i in 0..num_limbs {
+                let limb = cs.new_witness_variable(|| {
+                    limb_values
+                        .as_ref()
+                        .map(|limbs| to_field(&limbs[i].clone().into()))
+                        .ok_or(SynthesisError::AssignmentMissing)
+                })?;
+                enforce_bit_decomposition(cs, limb, LIMB_BITS)?;
+                limbs.push(lc!() + limb);
+            }
+            Ok(Int { limbs, value })
+        }
+
+        /// Allocates `value` reduced modulo `modulus`, constrained to be below it.
+        fn alloc_reduced(
+            cs: &ConstraintSystemRef<F>,
+            value: Option<BigUint>,
+            modulus: &BigUint,
+        ) -> Result<Self, SynthesisError> {
+            let value = value.map(|value| value % modulus);
+            let reduced = Int::alloc(cs, value.clone(), NUM_LIMBS)?;
+
+            // reduced + difference + 1 == modulus, with boolean carries between the limbs
+            let difference = value.map(|value| modulus - value - 1u8);
+            let difference = Int::alloc(cs, difference, NUM_LIMBS)?;
+            let limb_values = reduced.limb_values().zip(difference.limb_values());
+            let mut carry = lc!() + Variable::One;
+            let mut carry_value = Some(BigUint::from(1u8));
+            let shift = F::from(2u64).pow([LIMB_BITS as u64]);
+            for (i, modulus_limb) in split(modulus, NUM_LIMBS).into_iter().enumerate() {
+                let sum = reduced.limbs[i].clone() + &difference.limbs[i] + &carry;
+                let modulus_limb = to_field::<F>(&modulus_limb.into());
+                if i + 1 == NUM_LIMBS {
+                    cs.enforce_constraint(
+                        lc!() + Variable::One,
+                        sum,
+                        lc!() + (modulus_limb, Variable::One),
+                    )?;
+                    break;
+                }
+                carry_value =
+                    carry_value
+                        .zip(limb_values.as_ref())
+                        .map(|(carry, (reduced, difference))| {
+                            (carry + &reduced[i] + &difference[i]) >> LIMB_BITS
+                        });
+                let next = cs.new_witness_variable(|| {
+                    carry_value
+                        .as_ref()
+                        .map(|carry| to_field(&carry.clone().into()))
+                        .ok_or(SynthesisError::AssignmentMissing)
+                })?;
+                cs.enforce_constraint(lc!() + next, lc!() + next, lc!() + next)?;
+                cs.enforce_constraint(
+                    lc!() + Variable::One,
+                    sum,
+                    lc!() + (modulus_limb, Variable::One) + (shift, next),
+                )?;
+                carry = lc!() + next;
+            }
+            Ok(reduced)
+        }
+
+        fn limb_values(&self) -> Option<Vec<BigUint>> {
+            Some(split(self.value.as_ref()?, self.limbs.len()))
+        }
+    }
+
+    /// The columns of an identity between limb polynomials, with their values unless in setup
+    /// mode.
+    struct Columns<F: PrimeField> {
+        lcs: Vec<LinearCombination<F>>,
+        values: Option<Vec<BigInt>>,
+    }
+
+    impl<F: PrimeField> Columns<F> {
+        fn new() -> Self {
+            Columns {
+                lcs: vec![lc!(); IDENTITY_COLUMNS],
+                values: Some(vec![BigInt::default(); IDENTITY_COLUMNS]),
+            }
+        }
+
+        /// Adds `coeff * lc`, whose value is `value`, to the column `k`.
+        fn add(&mut self, k: usize, coeff: F, lc: &LinearCombination<F>, value: Option<BigInt>) {
+            self.lcs[k] = std::mem::replace(&mut self.lcs[k], lc!()) + (coeff, lc);
+            self.values = self.values.take().zip(value).map(|(mut values, value)| {
+                values[k] += value;
+                values
+            });
+        }
+    }
+
+    /// Enforces the integer identity `sum(lhs * rhs) + sum(terms) + constant == q * modulus +
+    /// remainder` for a quotient `q` supplied by the prover, where the left-hand side is
+    /// non-negative and below `2^513`.
+    fn enforce_identity<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        products: &[(&Int<F>, &Int<F>)],
+        terms: &[(i64, &Int<F>)],
+        constant: &BigUint,
+        modulus: &BigUint,
+        remainder: &Int<F>,
+    ) -> Result<(), SynthesisError> {
+        let mut columns = Columns::new();
+
+        for (lhs, rhs) in products {
+            let limb_values = lhs.limb_values().zip(rhs.limb_values());
+            for i in 0..NUM_LIMBS {
+                for j in 0..NUM_LIMBS {
+                    let value = limb_values
+                        .as_ref()
+                        .map(|(lhs, rhs)| BigInt::from(&lhs[i] * &rhs[j]));
+                    let product = cs.new_witness_variable(|| {
+                        value
+                            .as_ref()
+                            .map(to_field)
+                            .ok_or(SynthesisError::AssignmentMissing)
+                    })?;
+                    cs.enforce_constraint(
+                        lhs.limbs[i].clone(),
+                        rhs.limbs[j].clone(),
+                        lc!() + product,
+                    )?;
+                    columns.add(i + j, F::one(), &(lc!() + product), value);
+                }
+            }
+        }
+        for (sign, term) in terms {
+            let limb_values = term.limb_values();
+            for (i, limb) in term.limbs.iter().enumerate() {
+                let value = limb_values
+                    .as_ref()
+                    .map(|limbs| BigInt::from(limbs[i].clone()) * sign);
+                columns.add(i, to_field(&BigInt::from(*sign)), limb, value);
+            }
+        }
+        let constant = Int::<F>::constant(constant, NUM_LIMBS + 1);
+        for (i, (limb, value)) in constant
+            .limbs
+            .iter()
+            .zip(constant.limb_values().expect("a constant has a value"))
+            .enumerate()
+        {
+            columns.add(i, F::one(), limb, Some(value.into()));
+        }
+        let remainder_values = remainder.limb_values();
+        for (i, limb) in remainder.limbs.iter().enumerate() {
+            let value = remainder_values
+                .as_ref()
+                .map(|limbs| -BigInt::from(limbs[i].clone()));
+            columns.add(i, -F::one(), limb, value);
+        }
+
+        // The prover's quotient, which is zero if the left-hand side is not congruent
+        let lhs = columns
+            .values
+            .as_ref()
+            .map(|values| {
+                values
+                    .iter()
+                    .enumerate()
+                    .fold(BigInt::default(), |sum, (k, value)| {
+                        sum + (value << (LIMB_BITS as usize * k))
+                    })
+            })
+            .map(|lhs| lhs / BigInt::from(modulus.clone()));
+        let quotient = lhs.map(|quotient| quotient.to_biguint().unwrap_or_default());
+        let quotient = Int::alloc(cs, quotient, QUOTIENT_LIMBS)?;
+        let quotient_values = quotient.limb_values();
+        for (j, modulus_limb) in split(modulus, NUM_LIMBS).into_iter().enumerate() {
+            let modulus_limb = BigInt::from(modulus_limb);
+            for (i, limb) in quotient.limbs.iter().enumerate() {
+                let value = quotient_values
+                    .as_ref()
+                    .map(|limbs| -(BigInt::from(limbs[i].clone()) * &modulus_limb));
+                columns.add(i + j, -to_field::<F>(&modulus_limb), limb, value);
+            }
+        }
+
+        // Each column plus the incoming carry must be a multiple of 2^64, with the outgoing
+        // carry as the quotient, and the last column must cancel the last carry
+        let radix = F::from(2u64).pow([LIMB_BITS as u64]);
+        let carry_offset = BigInt::from(1u8) << (CARRY_BITS - 1);
+        let mut carry = lc!();
+        let mut carry_value = Some(BigInt::default());
+        for k in 0..IDENTITY_COLUMNS {
+            let sum = carry.clone() + &columns.lcs[k];
+            if k + 1 == IDENTITY_COLUMNS {
+                cs.enforce_constraint(lc!() + Variable::One, sum, lc!())?;
+                break;
+            }
+            carry_value = carry_value
+                .zip(columns.values.as_ref())
+                .map(|(carry, values)| (carry + &values[k]) >> LIMB_BITS);
+            let shifted = cs.new_witness_variable(|| {
+                carry_value
+                    .as_ref()
+                    .map(|carry| to_field(&(carry + &carry_offset)))
+                    .ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            enforce_bit_decomposition(cs, shifted, CARRY_BITS)?;
+            carry = lc!() + shifted - (to_field::<F>(&carry_offset), Variable::One);
+            cs.enforce_constraint(lc!() + Variable::One, sum, carry.clone() * radix)?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether the reduced `value` is zero, as its limbs are non-negative and only sum
+    /// to zero if they all are.
+    fn is_zero<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        value: &Int<F>,
+    ) -> Result<(LinearCombination<F>, Option<bool>), SynthesisError> {
+        let sum = value.limbs.iter().fold(lc!(), |sum, limb| sum + limb);
+        let sum_value = value.limb_values().map(|limbs| {
+            let sum = limbs.into_iter().sum::<BigUint>();
+            to_field::<F>(&sum.into())
+        });
+        let inverse = cs.new_witness_variable(|| {
+            let value = sum_value.ok_or(SynthesisError::AssignmentMissing)?;
+            Ok(value.inverse().unwrap_or_default())
+        })?;
+        let is_zero = sum_value.map(|value| value.is_zero());
+        let flag = cs.new_witness_variable(|| {
+            is_zero
+                .map(F::from)
+                .ok_or(SynthesisError::AssignmentMissing)
+        })?;
+        // sum * inverse == 1 - flag, so the flag is set if the sum has no inverse
+        cs.enforce_constraint(sum.clone(), lc!() + inverse, lc!() + Variable::One - flag)?;
+        // sum * flag == 0, so the flag is clear if the sum is not zero
+        cs.enforce_constraint(sum, lc!() + flag, lc!())?;
+        Ok((lc!() + flag, is_zero))
+    }
+
+    /// Returns `lhs <operation> rhs mod modulus`, computed like the ACVM.
+    fn apply(
+        operation: BigIntOperation,
+        lhs: &BigUint,
+        rhs: &BigUint,
+        modulus: &BigUint,
+    ) -> BigUint {
+        let result = match operation {
+            BigIntOperation::Add => lhs + rhs,
+            BigIntOperation::Sub => lhs + modulus * (rhs / modulus + 1u8) - rhs,
+            BigIntOperation::Mul => lhs * rhs,
+            BigIntOperation::Div => lhs * rhs.modpow(&(modulus - 2u8), modulus),
+        };
+        result % modulus
+    }
+
+    fn enforce_operation<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        operation: BigIntOperation,
+        lhs: &Int<F>,
+        rhs: &Int<F>,
+        modulus: &BigUint,
+    ) -> Result<Int<F>, SynthesisError> {
+        let value = lhs
+            .value
+            .as_ref()
+            .zip(rhs.value.as_ref())
+            .map(|(lhs, rhs)| apply(operation, lhs, rhs, modulus));
+        let output = Int::alloc_reduced(cs, value, modulus)?;
+        // A multiple of the modulus above any BigInt, so that subtracting one stays positive
+        let shift = modulus * ((BigUint::from(1u8) << (NUM_BYTES * 8)) / modulus + 1u8);
+        let zero = BigUint::default();
+        match operation {
+            BigIntOperation::Add => {
+                enforce_identity(cs, &[], &[(1, lhs), (1, rhs)], &zero, modulus, &output)?
+            }
+            BigIntOperation::Sub => {
+                enforce_identity(cs, &[], &[(1, lhs), (-1, rhs)], &shift, modulus, &output)?
+            }
+            BigIntOperation::Mul => {
+                enforce_identity(cs, &[(lhs, rhs)], &[], &zero, modulus, &output)?
+            }
+            BigIntOperation::Div => {
+                let divisor = Int::alloc_reduced(cs, rhs.value.clone(), modulus)?;
+                enforce_identity(cs, &[], &[(1, rhs)], &zero, modulus, &divisor)?;
+                let (is_zero, is_zero_value) = is_zero(cs, &divisor)?;
+
+                // The output is zero when dividing by zero, and lhs is then added back so that
+                // output * rhs == lhs holds trivially
+                for limb in &output.limbs {
+                    cs.enforce_constraint(is_zero.clone(), limb.clone(), lc!())?;
+                }
+                let limb_values = lhs.limb_values();
+                let mut limbs = Vec::with_capacity(NUM_LIMBS);
+                for (i, limb) in lhs.limbs.iter().enumerate() {
+                    let value = is_zero_value
+                        .zip(limb_values.as_ref())
+                        .map(|(is_zero, limbs)| match is_zero {
+                            true => limbs[i].clone(),
+                            false => BigUint::default(),
+                        });
+                    let masked = cs.new_witness_variable(|| {
+                        value
+                            .as_ref()
+                            .map(|value| to_field(&value.clone().into()))
+                            .ok_or(SynthesisError::AssignmentMissing)
+                    })?;
+                    cs.enforce_constraint(is_zero.clone(), limb.clone(), lc!() + masked)?;
+                    limbs.push(lc!() + masked);
+                }
+                let masked = Int {
+                    limbs,
+                    value: is_zero_value.zip(lhs.value.as_ref()).map(
+                        |(is_zero, lhs)| match is_zero {
+                            true => lhs.clone(),
+                            false => BigUint::default(),
+                        },
+                    ),
+                };
+                enforce_identity(
+                    cs,
+                    &[(&output, rhs)],
+                    &[(1, &masked), (-1, lhs)],
+                    &shift,
+                    modulus,
+                    &Int::constant(&zero, NUM_LIMBS),
+                )?;
+            }
+        }
+        Ok(output)
+    }
+
+    pub(super) fn enforce_bigint_gates<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        gates: &[BigIntGate],
+        variables: &BTreeMap<Witness, Variable>,
+    ) -> Result<(), SynthesisError> {
+        let max_modulus = BigUint::from(1u8) << (NUM_BYTES * 8);
+        let mut bigints: BTreeMap<u32, (Int<F>, BigUint)> = BTreeMap::new();
+        for gate in gates {
+            match &gate.call {
+                BigIntCall::FromLeBytes {
+                    inputs,
+                    modulus,
+                    output,
+                } => {
+                    let modulus = BigUint::from_bytes_le(modulus);
+                    let is_supported = inputs.len() <= NUM_BYTES
+                        && modulus >= BigUint::from(2u8)
+                        && modulus < max_modulus;
+                    if !is_supported {
+                        return Err(SynthesisError::Unsatisfiable);
+                    }
+                    let mut limbs = vec![lc!(); NUM_LIMBS];
+                    let mut value = Some(BigUint::default());
+                    for (position, input) in inputs.iter().enumerate() {
+                        let byte = variables[input];
+                        enforce_bit_decomposition(cs, byte, 8)?;
+                        let shift = 8 * (position % 8);
+                        limbs[position / 8] += (F::from(1u64 << shift), byte);
+                        value = value.zip(cs.assigned_value(byte)).map(|(value, byte)| {
+                            let byte = BigUint::from_bytes_le(&byte.into_bigint().to_bytes_le());
+                            value + (byte << (8 * position))
+                        });
+                    }
+                    bigints.insert(*output, (Int { limbs, value }, modulus));
+                }
+                BigIntCall::Operation {
+                    operation,
+                    lhs,
+                    rhs,
+                    output,
+                } => {
+                    let (lhs, modulus) = bigints.get(lhs).ok_or(SynthesisError::Unsatisfiable)?;
+                    let (rhs, rhs_modulus) =
+                        bigints.get(rhs).ok_or(SynthesisError::Unsatisfiable)?;
+                    if modulus != rhs_modulus {
+                        return Err(SynthesisError::Unsatisfiable);
+                    }
+                    let result = enforce_operation(cs, *operation, lhs, rhs, modulus)?;
+                    let modulus = modulus.clone();
+                    bigints.insert(*output, (result, modulus));
+                }
+                BigIntCall::ToLeBytes { input, outputs } => {
+                    let (input, _) = bigints.get(input).ok_or(SynthesisError::Unsatisfiable)?;
+                    if outputs.len() != NUM_BYTES {
+                        return Err(SynthesisError::Unsatisfiable);
+                    }
+                    for (limb, bytes) in input.limbs.iter().zip(outputs.chunks(8)) {
+                        let mut packed = lc!();
+                        for (position, output) in bytes.iter().enumerate() {
+                            let byte = variables[output];
+                            enforce_bit_decomposition(cs, byte, 8)?;
+                            packed += (F::from(1u64 << (8 * position)), byte);
+                        }
+                        cs.enforce_constraint(lc!() + Variable::One, packed, limb.clone())?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn r1cs_size(gates: &[BigIntGate]) -> R1csSize {
+        let size = |(constraints, witnesses): (usize, usize)| R1csSize {
+            constraints,
+            witnesses,
+            public_inputs: 0,
+        };
+        let mut total = R1csSize::default();
+        for gate in gates {
+            match &gate.call {
+                BigIntCall::FromLeBytes { inputs, .. } => {
+                    total += size((9 * inputs.len(), 8 * inputs.len()))
+                }
+                BigIntCall::Operation { operation, .. } => {
+                    total += size(REDUCED_SIZE);
+                    total += size(IDENTITY_SIZE);
+                    match operation {
+                        BigIntOperation::Add | BigIntOperation::Sub => {}
+                        BigIntOperation::Mul => total += size((PRODUCT_SIZE, PRODUCT_SIZE)),
+                        BigIntOperation::Div => {
+                            // The reduced divisor, its zero check, and masking the output and
+                            // the dividend, before multiplying back
+                            total += size(REDUCED_SIZE);
+                            total += size(IDENTITY_SIZE);
+                            total += size((2 + 2 * NUM_LIMBS, 2 + NUM_LIMBS));
+                            total += size((PRODUCT_SIZE, PRODUCT_SIZE));
+                        }
+                    }
+                }
+                BigIntCall::ToLeBytes { outputs, .. } => {
+                    total += size((9 * outputs.len() + NUM_LIMBS, 8 * outputs.len()))
+                }
+            }
+        }
+        total
+    }
+}
+
+#[cfg(all(test, feature = "bigint"))]
+mod test {
+    use acvm::acir::circuit::opcodes::{BlackBoxFuncCall, FunctionInput};
+    use acvm::acir::circuit::Opcode;
+    use acvm::acir::native_types::WitnessMap;
+    use acvm::FieldElement;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+    use num_bigint::BigUint;
+
+    use super::*;
+    use crate::test::circuit_with_opcodes;
+    use crate::CurveAcir;
+
+    #[test]
+    fn multiplies_modulo_a_prime() {
+        // BN254's base field, with operands above it which the multiplication reduces
+        let modulus = BigUint::parse_bytes(
+            b"30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd47",
+            16,
+        )
+        .unwrap();
+        let lhs = (BigUint::from(1u8) << 256u32) - 1u8;
+        let rhs = &modulus + BigUint::from(0x1234_5678_9abc_def0u64);
+        let mut product = (&lhs * &rhs % &modulus).to_bytes_le();
+        product.resize(32, 0);
+
+        let bytes = |offset: u32| {
+            (offset..offset + 32)
+                .map(|index| FunctionInput {
+                    witness: Witness(index),
+                    num_bits: 8,
+                })
+                .collect()
+        };
+        let opcodes = vec![
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntFromLeBytes {
+                inputs: bytes(0),
+                modulus: modulus.to_bytes_le(),
+                output: 0,
+            }),
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntFromLeBytes {
+                inputs: bytes(32),
+                modulus: modulus.to_bytes_le(),
+                output: 1,
+            }),
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntMul {
+                lhs: 0,
+                rhs: 1,
+                output: 2,
+            }),
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntToLeBytes {
+                input: 2,
+                outputs: (64..96).map(Witness).collect(),
+            }),
+        ];
+        let circuit = circuit_with_opcodes(95, opcodes, &[]);
+
+        let is_satisfied = |product: &[u8]| {
+            let mut witness_map = WitnessMap::new();
+            let mut rhs = rhs.to_bytes_le();
+            rhs.resize(32, 0);
+            let bytes = lhs
+                .to_bytes_le()
+                .into_iter()
+                .chain(rhs)
+                .chain(product.to_vec());
+            for (index, byte) in bytes.enumerate() {
+                witness_map.insert(Witness(index as u32), FieldElement::from(byte as u128));
+            }
+            let circuit = CurveAcir::from((&circuit, witness_map));
+            let size = circuit.r1cs_size();
+            let cs = ConstraintSystem::new_ref();
+            circuit.generate_constraints(cs.clone()).unwrap();
+            assert_eq!(cs.num_constraints(), size.constraints);
+            cs.is_satisfied().unwrap()
+        };
+        assert!(is_satisfied(&product));
+
+        // The unreduced product, which is congruent but not canonical
+        let mut unreduced = (&lhs * &rhs % &modulus + &modulus).to_bytes_le();
+        unreduced.resize(32, 0);
+        assert!(!is_satisfied(&unreduced));
+    }
+}
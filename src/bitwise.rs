@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+
+use acvm::acir::native_types::Witness;
+use ark_ff::PrimeField;
+use ark_relations::{
+    lc,
+    r1cs::{ConstraintSystemRef, SynthesisError, Variable},
+};
+
+use crate::bridge::BridgeError;
+use crate::gadgets::{enforce_bit_decomposition, pack_bits};
+use crate::R1csSize;
+
+// Lowering of the bitwise black box functions (`BlackBoxFuncCall::AND` and `::XOR`) to R1CS.
+//
+// Both operands are decomposed into `num_bits` little-endian boolean witnesses, which also
+// range-constrains them. Each output bit is constrained as a function of the two input bits,
+// and the output witness must recompose from the output bits.
+//
+// ACIR gives each operand its own width. Operands of differing widths are rejected rather than
+// zero-extended, matching the ACVM solver, which refuses to solve them.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BitwiseOperation {
+    And,
+    Xor,
+}
+
+/// A single bitwise `BlackBoxFuncCall`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct BitwiseGate {
+    pub(crate) operation: BitwiseOperation,
+    pub(crate) lhs: Witness,
+    pub(crate) lhs_bits: u32,
+    pub(crate) rhs: Witness,
+    pub(crate) rhs_bits: u32,
+    pub(crate) output: Witness,
+    pub(crate) source_opcode: usize,
+}
+
+impl BitwiseGate {
+    pub(crate) fn witnesses(&self) -> impl Iterator<Item = Witness> {
+        [self.lhs, self.rhs, self.output].into_iter()
+    }
+}
+
+/// Reports the first bitwise gate whose operands have different widths.
+pub(crate) fn check_widths(gates: &[BitwiseGate]) -> Result<(), BridgeError> {
+    match gates.iter().find(|gate| gate.lhs_bits != gate.rhs_bits) {
+        Some(gate) => Err(BridgeError::BitwiseWidthMismatch {
+            opcode: gate.source_opcode,
+            lhs_bits: gate.lhs_bits,
+            rhs_bits: gate.rhs_bits,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Counts the constraints and witnesses [`enforce_bitwise_gates`] allocates.
+pub(crate) fn r1cs_size(gates: &[BitwiseGate]) -> R1csSize {
+    let mut size = R1csSize::default();
+    for gate in gates {
+        let (lhs_bits, rhs_bits) = (gate.lhs_bits as usize, gate.rhs_bits as usize);
+        let output_bits = lhs_bits.min(rhs_bits);
+        // Both decompositions, then each output bit and the recomposition of the output
+        size.constraints += lhs_bits + 1 + rhs_bits + 1 + output_bits + 1;
+        size.witnesses += lhs_bits + rhs_bits + output_bits;
+    }
+    size
+}
+
+/// Enforces every bitwise gate. Fails with `SynthesisError::Unsatisfiable` on operands of
+/// different widths, see [`check_widths`] for the details.
+pub(crate) fn enforce_bitwise_gates<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    gates: &[BitwiseGate],
+    variables: &BTreeMap<Witness, Variable>,
+) -> Result<(), SynthesisError> {
+    check_widths(gates).map_err(|_| SynthesisError::Unsatisfiable)?;
+
+    for gate in gates {
+        let lhs_bits = enforce_bit_decomposition(cs, variables[&gate.lhs], gate.lhs_bits)?;
+        let rhs_bits = enforce_bit_decomposition(cs, variables[&gate.rhs], gate.rhs_bits)?;
+
+        let output_bits = lhs_bits
+            .iter()
+            .zip(&rhs_bits)
+            .map(|(lhs, rhs)| {
+                let output = cs.new_witness_variable(|| {
+                    let lhs = cs.assigned_value(*lhs);
+                    let rhs = cs.assigned_value(*rhs);
+                    lhs.zip(rhs)
+                        .map(|(lhs, rhs)| match gate.operation {
+                            BitwiseOperation::And => lhs * rhs,
+                            BitwiseOperation::Xor => lhs + rhs - lhs * rhs.double(),
+                        })
+                        .ok_or(SynthesisError::AssignmentMissing)
+                })?;
+                match gate.operation {
+                    // lhs * rhs == output
+                    BitwiseOperation::And => {
+                        cs.enforce_constraint(lc!() + *lhs, lc!() + *rhs, lc!() + output)?;
+                    }
+                    // 2 * lhs * rhs == lhs + rhs - output
+                    BitwiseOperation::Xor => {
+                        cs.enforce_constraint(
+                            lc!() + (F::from(2u64), *lhs),
+                            lc!() + *rhs,
+                            lc!() + *lhs + *rhs - output,
+                        )?;
+                    }
+                }
+                Ok(output)
+            })
+            .collect::<Result<Vec<_>, SynthesisError>>()?;
+
+        cs.enforce_constraint(
+            lc!() + Variable::One,
+            pack_bits(&output_bits),
+            lc!() + variables[&gate.output],
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use acvm::acir::circuit::opcodes::{BlackBoxFuncCall, FunctionInput};
+    use acvm::acir::circuit::Opcode;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+
+    use super::*;
+    use crate::test::{circuit_with_opcodes, witness_map};
+    use crate::CurveAcir;
+
+    fn and(lhs_bits: u32, rhs_bits: u32) -> Opcode<acvm::FieldElement> {
+        Opcode::BlackBoxFuncCall(BlackBoxFuncCall::AND {
+            lhs: FunctionInput {
+                witness: Witness(0),
+                num_bits: lhs_bits,
+            },
+            rhs: FunctionInput {
+                witness: Witness(1),
+                num_bits: rhs_bits,
+            },
+            output: Witness(2),
+        })
+    }
+
+    fn is_satisfied(circuit: &CurveAcir) -> bool {
+        let cs = ConstraintSystem::new_ref();
+        circuit.clone().generate_constraints(cs.clone()).unwrap();
+        cs.is_satisfied().unwrap()
+    }
+
+    #[test]
+    fn and_matches_native_result() {
+        let circuit = circuit_with_opcodes(2, vec![and(8, 8)], &[]);
+        let lower =
+            |output| CurveAcir::from((&circuit, witness_map(&[(0, 5), (1, 3), (2, output)])));
+        // 0b101 & 0b011 == 0b001
+        assert!(is_satisfied(&lower(1)));
+        assert!(!is_satisfied(&lower(7)));
+
+        // single bit operands
+        let circuit = circuit_with_opcodes(2, vec![and(1, 1)], &[]);
+        for (lhs, rhs) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            let values = witness_map(&[(0, lhs), (1, rhs), (2, lhs & rhs)]);
+            assert!(is_satisfied(&CurveAcir::from((&circuit, values))));
+        }
+        // an operand wider than its declared width
+        let values = witness_map(&[(0, 2), (1, 1), (2, 0)]);
+        assert!(!is_satisfied(&CurveAcir::from((&circuit, values))));
+
+        let circuit = circuit_with_opcodes(2, vec![and(8, 4)], &[]);
+        let circuit = CurveAcir::from((&circuit, witness_map(&[(0, 5), (1, 3), (2, 1)])));
+        assert_eq!(
+            circuit.check_bitwise_gates(),
+            Err(BridgeError::BitwiseWidthMismatch {
+                opcode: 0,
+                lhs_bits: 8,
+                rhs_bits: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn xor_matches_native_result() {
+        let xor = Opcode::BlackBoxFuncCall(BlackBoxFuncCall::XOR {
+            lhs: FunctionInput {
+                witness: Witness(0),
+                num_bits: 8,
+            },
+            rhs: FunctionInput {
+                witness: Witness(1),
+                num_bits: 8,
+            },
+            output: Witness(2),
+        });
+        let circuit = circuit_with_opcodes(2, vec![xor], &[]);
+        let lower =
+            |output| CurveAcir::from((&circuit, witness_map(&[(0, 6), (1, 3), (2, output)])));
+        // 0b110 ^ 0b011 == 0b101
+        assert!(is_satisfied(&lower(5)));
+        assert!(!is_satisfied(&lower(7)));
+    }
+}
@@ -0,0 +1,434 @@
+use std::collections::BTreeMap;
+
+use acvm::acir::native_types::Witness;
+use ark_ff::PrimeField;
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError, Variable};
+
+use crate::R1csSize;
+
+// Lowering of `BlackBoxFuncCall::Blake2s` to R1CS.
+//
+// The message bytes are decomposed into 8 little-endian bits, which also range-constrains them,
+// and packed into the little-endian 32-bit words of 64-byte blocks, the padding being constant
+// zero bits. The message length is the number of input bytes, which is fixed by the circuit, so
+// the block counter and the final block flag are constants. Each block is compressed in 10
+// rounds of 8 mixing steps on the bits of the words: rotations only rewire bits, every XOR of
+// two bits costs one constraint, and a XOR with a constant word is free, as the initial state
+// words of the first block are. Additions modulo 2^32 are taken on the recomposed words as in
+// the `sha256` module. The output bytes must recompose from the bits of the final state.
+//
+// The constraints are only generated with the `blake2s` feature, as they dwarf most circuits.
+
+/// A single `BlackBoxFuncCall::Blake2s`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Blake2sGate {
+    pub(crate) inputs: Vec<Witness>,
+    pub(crate) outputs: Vec<Witness>,
+    pub(crate) source_opcode: usize,
+}
+
+impl Blake2sGate {
+    pub(crate) fn witnesses(&self) -> impl Iterator<Item = Witness> + '_ {
+        self.inputs.iter().chain(&self.outputs).copied()
+    }
+
+    pub(crate) fn witnesses_mut(&mut self) -> impl Iterator<Item = &mut Witness> {
+        self.inputs.iter_mut().chain(self.outputs.iter_mut())
+    }
+}
+
+/// Counts the constraints and witnesses [`enforce_blake2s_gates`] allocates, none without the
+/// `blake2s` feature.
+#[cfg_attr(not(feature = "blake2s"), allow(unused_variables))]
+pub(crate) fn r1cs_size(gates: &[Blake2sGate]) -> R1csSize {
+    #[cfg(feature = "blake2s")]
+    return hash::r1cs_size(gates);
+
+    #[cfg(not(feature = "blake2s"))]
+    R1csSize::default()
+}
+
+/// Enforces every hash. Without the `blake2s` feature this fails with
+/// `SynthesisError::Unsatisfiable` if there are any.
+#[cfg_attr(not(feature = "blake2s"), allow(unused_variables))]
+pub(crate) fn enforce_blake2s_gates<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    gates: &[Blake2sGate],
+    variables: &BTreeMap<Witness, Variable>,
+) -> Result<(), SynthesisError> {
+    #[cfg(feature = "blake2s")]
+    return hash::enforce_blake2s_gates(cs, gates, variables);
+
+    #[cfg(not(feature = "blake2s"))]
+    match gates.is_empty() {
+        true => Ok(()),
+        false => Err(SynthesisError::Unsatisfiable),
+    }
+}
+
+#[cfg(feature = "blake2s")]
+pub(crate) mod hash {
+    use std::collections::BTreeMap;
+
+    use acvm::acir::native_types::Witness;
+    use ark_ff::PrimeField;
+    use ark_relations::{
+        lc,
+        r1cs::{ConstraintSystemRef, LinearCombination, SynthesisError, Variable},
+    };
+
+    use super::Blake2sGate;
+    use crate::gadgets::{alloc_bits, enforce_bit_decomposition, pack_bits};
+    use crate::R1csSize;
+
+    const WORD_BITS: u32 = 32;
+    const BLOCK_BYTES: usize = 64;
+    const ROUNDS: usize = 10;
+
+    /// The initialization vector, shared with SHA-256.
+    const IV: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+
+    /// The order in which each round mixes the message words.
+    const SIGMA: [[usize; 16]; 10] = [
+        [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+        [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+        [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+        [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+        [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+        [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+        [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+        [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+        [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+        [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+    ];
+
+    /// The state words each mixing step of a round updates: the four columns, then the four
+    /// diagonals.
+    const MIXES: [[usize; 4]; 8] = [
+        [0, 4, 8, 12],
+        [1, 5, 9, 13],
+        [2, 6, 10, 14],
+        [3, 7, 11, 15],
+        [0, 5, 10, 15],
+        [1, 6, 11, 12],
+        [2, 7, 8, 13],
+        [3, 4, 9, 14],
+    ];
+
+    /// A bit of a word, with its value unless in setup mode. A constant bit has no witness, its
+    /// linear combination being empty or the constant one.
+    #[derive(Clone)]
+    pub(crate) struct Bit<F: PrimeField> {
+        pub(crate) lc: LinearCombination<F>,
+        pub(crate) value: Option<bool>,
+        is_constant: bool,
+    }
+
+    impl<F: PrimeField> Bit<F> {
+        /// The bit `variable`, which must be constrained to be boolean.
+        pub(crate) fn from_variable(cs: &ConstraintSystemRef<F>, variable: Variable) -> Self {
+            Bit {
+                lc: lc!() + variable,
+                value: cs.assigned_value(variable).map(|value| value.is_one()),
+                is_constant: false,
+            }
+        }
+
+        pub(crate) fn constant(value: bool) -> Self {
+            Bit {
+                lc: match value {
+                    true => lc!() + Variable::One,
+                    false => lc!(),
+                },
+                value: Some(value),
+                is_constant: true,
+            }
+        }
+
+        fn not(&self) -> Self {
+            Bit {
+                lc: lc!() + Variable::One - &self.lc,
+                value: self.value.map(|value| !value),
+                is_constant: self.is_constant,
+            }
+        }
+    }
+
+    /// The little-endian bits of a 32-bit word.
+    type Word<F> = Vec<Bit<F>>;
+
+    fn constant_word<F: PrimeField>(value: u32) -> Word<F> {
+        (0..WORD_BITS)
+            .map(|i| Bit::constant(value >> i & 1 == 1))
+            .collect()
+    }
+
+    /// The number of blocks a message of `num_bytes` bytes is compressed in, at least one.
+    fn num_blocks(num_bytes: usize) -> usize {
+        ((num_bytes + BLOCK_BYTES - 1) / BLOCK_BYTES).max(1)
+    }
+
+    pub(super) fn r1cs_size(gates: &[Blake2sGate]) -> R1csSize {
+        let mut size = R1csSize::default();
+        for gate in gates {
+            // The decomposition of the input bytes, and the packing of the output bytes
+            size += R1csSize {
+                constraints: 9 * gate.inputs.len() + gate.outputs.len(),
+                witnesses: 8 * gate.inputs.len(),
+                public_inputs: 0,
+            };
+            size += hash_r1cs_size(gate.inputs.len());
+        }
+        size
+    }
+
+    /// Counts the constraints and witnesses [`hash_bits`] allocates for a message of
+    /// `num_bytes` bytes.
+    pub(crate) fn hash_r1cs_size(num_bytes: usize) -> R1csSize {
+        let word_bits = WORD_BITS as usize;
+        // The bits of a sum of two and three words with their carries, and the recomposition
+        let (two_terms, three_terms) = (
+            (word_bits + 2, word_bits + 1),
+            (word_bits + 3, word_bits + 2),
+        );
+        // A mixing step sums three words twice and two words twice, and XORs four words
+        let mix_constraints = 2 * (two_terms.0 + three_terms.0) + 4 * word_bits;
+        let mix_witnesses = 2 * (two_terms.1 + three_terms.1) + 4 * word_bits;
+
+        let num_blocks = num_blocks(num_bytes);
+        // The XORs with the constant words of the first round: the last four state words of
+        // every block, and the middle four of the first block. Finalizing XORs three words, the
+        // first being constant for the first block.
+        let free_xors = (4 * num_blocks + 4) * word_bits;
+        let finalization = (2 * num_blocks - 1) * 8 * word_bits;
+        let per_block = ROUNDS * MIXES.len();
+
+        R1csSize {
+            constraints: num_blocks * per_block * mix_constraints + finalization - free_xors,
+            witnesses: num_blocks * per_block * mix_witnesses + finalization - free_xors,
+            public_inputs: 0,
+        }
+    }
+
+    pub(super) fn enforce_blake2s_gates<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        gates: &[Blake2sGate],
+        variables: &BTreeMap<Witness, Variable>,
+    ) -> Result<(), SynthesisError> {
+        for gate in gates {
+            let mut message = Vec::new();
+            for input in &gate.inputs {
+                let bits = enforce_bit_decomposition(cs, variables[input], 8)?;
+                message.extend(bits.into_iter().map(|bit| Bit::from_variable(cs, bit)));
+            }
+
+            let digest = hash_bits(cs, message)?;
+            for (bits, output) in digest.chunks(8).zip(&gate.outputs) {
+                let packed = bits.iter().enumerate().fold(lc!(), |packed, (i, bit)| {
+                    packed + (F::from(1u64 << i), &bit.lc)
+                });
+                cs.enforce_constraint(lc!() + Variable::One, packed, lc!() + variables[output])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the bits of the digest of the message whose bytes are given as consecutive
+    /// little-endian bits, each byte's digest bits being little-endian too.
+    pub(crate) fn hash_bits<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        mut message: Vec<Bit<F>>,
+    ) -> Result<Vec<Bit<F>>, SynthesisError> {
+        let num_bytes = message.len() / 8;
+        let num_blocks = num_blocks(num_bytes);
+        message.resize(num_blocks * BLOCK_BYTES * 8, Bit::constant(false));
+
+        // No key and a 32-byte digest
+        let mut state: Vec<Word<F>> = IV.iter().map(|word| constant_word(*word)).collect();
+        state[0] = constant_word(IV[0] ^ 0x0101_0020);
+
+        for (index, block) in message.chunks(BLOCK_BYTES * 8).enumerate() {
+            let is_last = index + 1 == num_blocks;
+            let counter = match is_last {
+                true => num_bytes,
+                false => (index + 1) * BLOCK_BYTES,
+            } as u64;
+            let words: Vec<Word<F>> = block
+                .chunks(WORD_BITS as usize)
+                .map(<[Bit<F>]>::to_vec)
+                .collect();
+            state = compress(cs, state, &words, counter, is_last)?;
+        }
+        Ok(state.concat())
+    }
+
+    /// Compresses a block of 16 message words into the chained state.
+    fn compress<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        state: Vec<Word<F>>,
+        message: &[Word<F>],
+        counter: u64,
+        is_last: bool,
+    ) -> Result<Vec<Word<F>>, SynthesisError> {
+        let mut work = state.clone();
+        work.extend(IV.iter().map(|word| constant_word(*word)));
+        work[12] = constant_word(IV[4] ^ counter as u32);
+        work[13] = constant_word(IV[5] ^ (counter >> 32) as u32);
+        if is_last {
+            work[14] = constant_word(!IV[6]);
+        }
+
+        for sigma in SIGMA {
+            for (step, [a, b, c, d]) in MIXES.into_iter().enumerate() {
+                let (x, y) = (&message[sigma[2 * step]], &message[sigma[2 * step + 1]]);
+                work[a] = add(cs, &[&work[a], &work[b], x])?;
+                work[d] = rotate(&xor(cs, &work[d], &work[a])?, 16);
+                work[c] = add(cs, &[&work[c], &work[d]])?;
+                work[b] = rotate(&xor(cs, &work[b], &work[c])?, 12);
+                work[a] = add(cs, &[&work[a], &work[b], y])?;
+                work[d] = rotate(&xor(cs, &work[d], &work[a])?, 8);
+                work[c] = add(cs, &[&work[c], &work[d]])?;
+                work[b] = rotate(&xor(cs, &work[b], &work[c])?, 7);
+            }
+        }
+
+        state
+            .iter()
+            .zip(work[..8].iter().zip(&work[8..]))
+            .map(|(word, (lhs, rhs))| xor(cs, word, &xor(cs, lhs, rhs)?))
+            .collect()
+    }
+
+    /// Rotates a word towards its least significant bit.
+    fn rotate<F: PrimeField>(word: &[Bit<F>], amount: usize) -> Word<F> {
+        let mut rotated = word.to_vec();
+        rotated.rotate_left(amount);
+        rotated
+    }
+
+    fn xor<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        lhs: &[Bit<F>],
+        rhs: &[Bit<F>],
+    ) -> Result<Word<F>, SynthesisError> {
+        lhs.iter()
+            .zip(rhs)
+            .map(|(lhs, rhs)| {
+                // A constant bit flips the other one or leaves it unchanged
+                match (lhs.is_constant, rhs.is_constant) {
+                    (true, _) if lhs.value == Some(true) => return Ok(rhs.not()),
+                    (true, _) => return Ok(rhs.clone()),
+                    (_, true) if rhs.value == Some(true) => return Ok(lhs.not()),
+                    (_, true) => return Ok(lhs.clone()),
+                    _ => {}
+                }
+                let value = lhs.value.zip(rhs.value).map(|(lhs, rhs)| lhs ^ rhs);
+                let output = cs.new_witness_variable(|| {
+                    value.map(F::from).ok_or(SynthesisError::AssignmentMissing)
+                })?;
+                // 2 * lhs * rhs == lhs + rhs - output
+                cs.enforce_constraint(
+                    lhs.lc.clone() * F::from(2u64),
+                    rhs.lc.clone(),
+                    lhs.lc.clone() + &rhs.lc - output,
+                )?;
+                Ok(Bit {
+                    lc: lc!() + output,
+                    value,
+                    is_constant: false,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the sum of `words` modulo 2^32. The sum is decomposed into bits, with enough
+    /// carry bits for it not to overflow, and the carries are dropped.
+    fn add<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        words: &[&Word<F>],
+    ) -> Result<Word<F>, SynthesisError> {
+        let carry_bits = usize::BITS - (words.len() - 1).leading_zeros();
+
+        let mut sum = lc!();
+        let mut value = Some(0u64);
+        for word in words {
+            for (i, bit) in word.iter().enumerate() {
+                sum = sum + (F::from(1u64 << i), &bit.lc);
+            }
+            let word_value = word.iter().enumerate().try_fold(0u64, |acc, (i, bit)| {
+                bit.value.map(|bit| acc | u64::from(bit) << i)
+            });
+            value = value.zip(word_value).map(|(sum, word)| sum + word);
+        }
+
+        let bits = alloc_bits(cs, value.map(F::from), WORD_BITS + carry_bits)?;
+        cs.enforce_constraint(lc!() + Variable::One, pack_bits(&bits), sum)?;
+        Ok(bits
+            .into_iter()
+            .take(WORD_BITS as usize)
+            .enumerate()
+            .map(|(i, bit)| Bit {
+                lc: lc!() + bit,
+                value: value.map(|value| value >> i & 1 == 1),
+                is_constant: false,
+            })
+            .collect())
+    }
+}
+
+#[cfg(all(test, feature = "blake2s"))]
+mod test {
+    use acvm::acir::circuit::opcodes::{BlackBoxFuncCall, FunctionInput};
+    use acvm::acir::circuit::Opcode;
+    use acvm::acir::native_types::WitnessMap;
+    use acvm::FieldElement;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+
+    use super::*;
+    use crate::test::circuit_with_opcodes;
+    use crate::CurveAcir;
+
+    #[test]
+    fn hashes_abc() {
+        let message = b"abc";
+        // blake2s("abc"), from RFC 7693
+        let digest = [
+            0x50, 0x8c, 0x5e, 0x8c, 0x32, 0x7c, 0x14, 0xe2, 0xe1, 0xa7, 0x2b, 0xa3, 0x4e, 0xeb,
+            0x45, 0x2f, 0x37, 0x45, 0x8b, 0x20, 0x9e, 0xd6, 0x3a, 0x29, 0x4d, 0x99, 0x9b, 0x4c,
+            0x86, 0x67, 0x59, 0x82,
+        ];
+
+        let hash = Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Blake2s {
+            inputs: (0..3)
+                .map(|index| FunctionInput {
+                    witness: Witness(index),
+                    num_bits: 8,
+                })
+                .collect(),
+            outputs: Box::new(std::array::from_fn(|index| Witness(3 + index as u32))),
+        });
+        let circuit = circuit_with_opcodes(34, vec![hash], &[]);
+        let lower = |digest: &[u8; 32]| {
+            let mut witness_map = WitnessMap::new();
+            for (index, byte) in message.iter().chain(digest).enumerate() {
+                witness_map.insert(Witness(index as u32), FieldElement::from(*byte as u128));
+            }
+            CurveAcir::from((&circuit, witness_map))
+        };
+        let is_satisfied = |circuit: CurveAcir| {
+            let size = circuit.r1cs_size();
+            let cs = ConstraintSystem::new_ref();
+            circuit.generate_constraints(cs.clone()).unwrap();
+            assert_eq!(cs.num_constraints(), size.constraints);
+            cs.is_satisfied().unwrap()
+        };
+        assert!(is_satisfied(lower(&digest)));
+
+        let mut tampered = digest;
+        tampered[31] ^= 1;
+        assert!(!is_satisfied(lower(&tampered)));
+    }
+}
@@ -1,15 +1,31 @@
-use std::collections::BTreeMap;
-use std::convert::TryInto;
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet};
+use std::ops::Range;
 
-use acvm::acir::circuit::PublicInputs;
+use acvm::acir::circuit::{opcodes::BlockId, PublicInputs};
 use acvm::acir::native_types::Witness;
-use ark_ff::Field;
+use ark_ff::{Field, PrimeField};
 use ark_relations::{
     lc,
     r1cs::{
         ConstraintSynthesizer, ConstraintSystemRef, LinearCombination, SynthesisError, Variable,
     },
 };
+use thiserror::Error;
+
+use crate::aes::{self, Aes128EncryptGate};
+use crate::bigint::{self, BigIntGate};
+use crate::bitwise::{self, BitwiseGate};
+use crate::blake2s::{self, Blake2sGate};
+use crate::ecdsa::{self, EcdsaSecp256k1Gate};
+use crate::embedded_curve::{self, EmbeddedCurveAddGate, MultiScalarMulGate};
+use crate::gadgets::enforce_bit_decomposition;
+use crate::keccak::{self, KeccakGate};
+use crate::memory::{self, MemoryAccess};
+use crate::pedersen::{self, PedersenHashGate};
+use crate::poseidon2::{self, Poseidon2Gate, PoseidonConfig};
+use crate::schnorr::{self, SchnorrVerifyGate};
+use crate::sha256::{self, Sha256Gate};
+use crate::R1csSize;
 
 // AcirCircuit and AcirArithGate are structs that arkworks can synthesise.
 //
@@ -31,63 +47,1379 @@ pub struct AcirCircuit<F: Field> {
     pub(crate) gates: Vec<AcirArithGate<F>>,
     pub(crate) public_inputs: PublicInputs,
     pub(crate) values: BTreeMap<Witness, F>,
+    pub(crate) layout: CommitmentLayout,
+    pub(crate) domain: Vec<F>,
+    // The witnesses each memory block is initialized with by `Opcode::MemoryInit`
+    pub(crate) memory_blocks: BTreeMap<BlockId, Vec<Witness>>,
+    // The witnesses declared as private inputs, as opposed to intermediate witnesses
+    pub(crate) private_parameters: BTreeSet<Witness>,
+    // The witnesses the circuit returns, which are also among the public inputs
+    pub(crate) return_values: BTreeSet<Witness>,
+    // The `Opcode::MemoryOp`s, in opcode order
+    pub(crate) memory_ops: Vec<MemoryAccess<F>>,
+    pub(crate) memory_mode: MemoryMode,
+    // The static assertion messages, by the index of the opcode they are attached to
+    pub(crate) assert_messages: BTreeMap<usize, String>,
+    // The bit width each `BlackBoxFuncCall::RANGE` constrains a witness to, the narrowest if
+    // several do
+    pub(crate) range_checks: BTreeMap<Witness, u32>,
+    // The bitwise `BlackBoxFuncCall`s, in opcode order
+    pub(crate) bitwise_gates: Vec<BitwiseGate>,
+    // The `BlackBoxFuncCall::Poseidon2Permutation`s, in opcode order
+    pub(crate) poseidon2_gates: Vec<Poseidon2Gate>,
+    // The parameters the permutations are constrained with, if the field has any
+    pub(crate) poseidon_config: Option<PoseidonConfig<F>>,
+    // The `BlackBoxFuncCall::Keccakf1600`s, in opcode order
+    pub(crate) keccak_gates: Vec<KeccakGate>,
+    // The `BlackBoxFuncCall::EmbeddedCurveAdd`s, in opcode order
+    pub(crate) embedded_curve_adds: Vec<EmbeddedCurveAddGate>,
+    // The `BlackBoxFuncCall::MultiScalarMul`s, in opcode order
+    pub(crate) multi_scalar_muls: Vec<MultiScalarMulGate>,
+    // The `BlackBoxFuncCall::Sha256Compression`s, in opcode order
+    pub(crate) sha256_gates: Vec<Sha256Gate>,
+    // The `BlackBoxFuncCall::EcdsaSecp256k1`s, in opcode order
+    pub(crate) ecdsa_secp256k1_gates: Vec<EcdsaSecp256k1Gate>,
+    // The `BlackBoxFuncCall::Blake2s`s, in opcode order
+    pub(crate) blake2s_gates: Vec<Blake2sGate>,
+    // The `BlackBoxFuncCall::PedersenHash`es, in opcode order
+    pub(crate) pedersen_hash_gates: Vec<PedersenHashGate>,
+    // The `BlackBoxFuncCall::SchnorrVerify`s, in opcode order
+    pub(crate) schnorr_verify_gates: Vec<SchnorrVerifyGate>,
+    // The `BlackBoxFuncCall::AES128Encrypt`s, in opcode order
+    pub(crate) aes128_encrypt_gates: Vec<Aes128EncryptGate>,
+    // The `BlackBoxFuncCall::BigInt*` opcodes, in opcode order
+    pub(crate) bigint_gates: Vec<BigIntGate>,
     // pub(crate) num_variables: usize,
 }
 
-#[derive(Clone, Debug)]
-pub(crate) struct AcirArithGate<F: Field> {
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum BridgeError {
+    #[error("memory block {} is never initialized", .0.0)]
+    UnknownBlock(BlockId),
+    #[error("memory blocks {} and {} have different lengths ({lhs_len} and {rhs_len})", .lhs.0, .rhs.0)]
+    BlockLengthMismatch {
+        lhs: BlockId,
+        rhs: BlockId,
+        lhs_len: usize,
+        rhs_len: usize,
+    },
+    #[error("memory block {} is read at index {index}, which is never initialized", .block.0)]
+    UninitializedRead { block: BlockId, index: usize },
+    #[error("opcode {opcode} accesses memory block {} at an index too large for the platform", .block.0)]
+    MemoryIndexOutOfRange { block: BlockId, opcode: usize },
+    #[error(
+        "bitwise opcode {opcode} has operands of different widths ({lhs_bits} and {rhs_bits} bits)"
+    )]
+    BitwiseWidthMismatch {
+        opcode: usize,
+        lhs_bits: u32,
+        rhs_bits: u32,
+    },
+    #[error(
+        "opcode {opcode} is a Poseidon2 permutation, but no Poseidon parameters are configured"
+    )]
+    MissingPoseidonConfig { opcode: usize },
+    #[error("Poseidon2 opcode {opcode} permutes {width} elements, but the configured permutation permutes {expected}")]
+    PoseidonWidthMismatch {
+        opcode: usize,
+        width: usize,
+        expected: usize,
+    },
+}
+
+/// A gate which does not hold for the witness values of an [`AcirCircuit`].
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error(
+    "gate {gate_index} is not satisfied{}",
+    .message.as_ref().map(|message| format!(": '{message}'")).unwrap_or_default()
+)]
+pub struct ConstraintFailure {
+    pub gate_index: usize,
+    /// The ACIR opcode the gate was lowered from, unless the backend synthesized it.
+    pub source_opcode: Option<usize>,
+    /// The assertion message attached to the opcode, if any.
+    pub message: Option<String>,
+}
+
+/// The constraints an arithmetic gate was synthesized into, as recorded by
+/// [`AcirCircuit::generate_constraints_traced`].
+///
+/// Pass `source_opcode` to [`crate::source_location_for_gate`] to map an unsatisfied constraint
+/// back to the Noir source.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConstraintTraceEntry {
+    pub gate_index: usize,
+    /// The ACIR opcode the gate was lowered from, unless the backend synthesized it.
+    pub source_opcode: Option<usize>,
+    /// The indices of the gate's constraints in the constraint system, including those binding
+    /// the products it is the first to use.
+    pub constraints: Range<usize>,
+}
+
+/// The order in which the witnesses of an [`AcirCircuit`] are allocated in the constraint system.
+///
+/// The layout only affects variable ordering, never the constraints themselves.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CommitmentLayout {
+    /// Allocate every witness in ascending `Witness` order, then the multiplication
+    /// outputs as the gates are processed.
+    #[default]
+    WitnessOrder,
+    /// Place related witnesses contiguously for Merkle-committed R1CS: public inputs first,
+    /// then the witnesses referenced by each gate (followed by its multiplication outputs)
+    /// in gate order, and finally any auxiliary witnesses no gate refers to.
+    GateGrouped,
+}
+
+/// How reads of uninitialized memory cells are handled.
+///
+/// Every cell a block is initialized with by `Opcode::MemoryInit` is initialized, but a write
+/// past the end of a block grows it and leaves the cells in between uninitialized.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MemoryMode {
+    /// Reject reads of uninitialized cells at a constant index.
+    #[default]
+    Strict,
+    /// Read uninitialized cells as zero.
+    Lenient,
+}
+
+/// An arithmetic gate, asserting that the sum of its terms is zero.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AcirArithGate<F: Field> {
     pub(crate) mul_terms: Vec<(F, Witness, Witness)>,
     pub(crate) add_terms: Vec<(F, Witness)>,
     pub(crate) constant_term: F,
+    // Index of the ACIR opcode this gate was lowered from, or `SYNTHESIZED_GATE`
+    pub(crate) source_opcode: usize,
 }
 
-impl<ConstraintF: Field> ConstraintSynthesizer<ConstraintF> for AcirCircuit<ConstraintF> {
+// `source_opcode` of the gates added by the backend itself rather than lowered from an opcode
+pub(crate) const SYNTHESIZED_GATE: usize = usize::MAX;
+
+impl<F: Field> AcirCircuit<F> {
+    /// Sets the order in which witnesses are allocated by `generate_constraints`.
+    pub fn with_layout(mut self, layout: CommitmentLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Binds the circuit to a domain separator such as a chain id or nonce.
+    ///
+    /// The domain values are allocated as the first public inputs, ahead of the circuit's own
+    /// public inputs. A proof therefore only verifies when the verifier supplies the same domain
+    /// (see [`crate::groth16::verify_with_domain`]). Only the number of domain values is part of
+    /// the circuit's shape, so one set of keys serves every domain of that length.
+    pub fn with_domain(mut self, domain: Vec<F>) -> Self {
+        self.domain = domain;
+        self
+    }
+
+    /// Sets how reads of uninitialized memory cells are handled.
+    pub fn with_memory_mode(mut self, memory_mode: MemoryMode) -> Self {
+        self.memory_mode = memory_mode;
+        self
+    }
+
+    /// Checks the memory operations for constant indices too large for a `usize`, and for reads
+    /// of uninitialized cells at a constant index.
+    ///
+    /// Synthesis fails on such an index, and in [`MemoryMode::Strict`] on such a read which is
+    /// always executed; this reports which block it is. In [`MemoryMode::Lenient`] reads of
+    /// uninitialized cells are allowed.
+    pub fn check_memory(&self) -> Result<(), BridgeError> {
+        memory::check_accesses(&self.memory_ops, &self.memory_blocks, self.memory_mode)
+    }
+
+    /// Checks that the operands of every bitwise opcode have the same width.
+    ///
+    /// Synthesis fails on operands of different widths; this reports which opcode has them.
+    pub fn check_bitwise_gates(&self) -> Result<(), BridgeError> {
+        bitwise::check_widths(&self.bitwise_gates)
+    }
+
+    /// Sets the parameters the Poseidon2 permutations of the circuit are constrained with.
+    ///
+    /// Lowering selects Noir's parameters when the field is the BN254 scalar field; they must
+    /// be given explicitly for any other field.
+    pub fn with_poseidon_config(mut self, config: PoseidonConfig<F>) -> Self {
+        self.poseidon_config = Some(config);
+        self
+    }
+
+    /// Checks that every Poseidon2 permutation can be constrained with the configured
+    /// parameters.
+    ///
+    /// Synthesis fails on permutations without parameters of the same width; this reports
+    /// which opcode has them.
+    pub fn check_poseidon2_gates(&self) -> Result<(), BridgeError> {
+        poseidon2::check_gates(&self.poseidon2_gates, self.poseidon_config.as_ref())
+    }
+
+    /// The values of the circuit's public inputs, in the order `generate_constraints` allocates
+    /// them and a verifier expects them.
+    ///
+    /// The domain separator is not included; see [`crate::groth16::verify_with_domain`].
+    pub fn public_input_values(&self) -> Vec<F> {
+        self.values
+            .iter()
+            .filter(|(witness, _)| self.public_inputs.contains(witness.0 as usize))
+            .map(|(_, value)| *value)
+            .collect()
+    }
+
+    /// The values of the witnesses the circuit returns, in the order they appear in
+    /// [`AcirCircuit::public_input_values`].
+    ///
+    /// Return values are allocated as public inputs, so a verifier checks the computed outputs
+    /// along with the public parameters.
+    pub fn public_output_values(&self) -> Vec<F> {
+        self.values
+            .iter()
+            .filter(|(witness, _)| self.return_values.contains(witness))
+            .map(|(_, value)| *value)
+            .collect()
+    }
+
+    /// The witnesses of the circuit's declared private inputs.
+    ///
+    /// Every other non-public witness is an intermediate value computed while solving. The
+    /// distinction is informational only: both kinds are constrained identically.
+    pub fn private_parameter_witnesses(&self) -> &BTreeSet<Witness> {
+        &self.private_parameters
+    }
+
+    /// The number of witnesses the circuit has values for.
+    pub fn witness_count(&self) -> usize {
+        self.values.len()
+    }
+
+    /// The number of public inputs, return values included but not the domain separator.
+    pub fn public_input_count(&self) -> usize {
+        self.public_inputs.0.len()
+    }
+
+    /// The number of arithmetic gates, not counting the gates of black box calls and memory
+    /// accesses.
+    pub fn gate_count(&self) -> usize {
+        self.gates.len()
+    }
+
+    /// The arithmetic gates lowered from ACIR opcodes, each with the index of its opcode.
+    ///
+    /// Gates the backend adds itself, such as those of [`AcirCircuit::enforce_blocks_equal`],
+    /// are skipped.
+    pub fn gates_with_origin(&self) -> impl Iterator<Item = (usize, &AcirArithGate<F>)> {
+        self.gates
+            .iter()
+            .filter(|gate| gate.source_opcode != SYNTHESIZED_GATE)
+            .map(|gate| (gate.source_opcode, gate))
+    }
+}
+
+impl<F: Field> AcirCircuit<F> {
+    /// Constrains the memory blocks `lhs` and `rhs` to be element-wise equal.
+    ///
+    /// The blocks are compared by the witnesses they are initialized with, so both must have
+    /// been initialized with the same length.
+    pub fn enforce_blocks_equal(&mut self, lhs: BlockId, rhs: BlockId) -> Result<(), BridgeError> {
+        let block = |id: BlockId| {
+            self.memory_blocks
+                .get(&id)
+                .ok_or(BridgeError::UnknownBlock(id))
+        };
+        let (lhs_init, rhs_init) = (block(lhs)?, block(rhs)?);
+        if lhs_init.len() != rhs_init.len() {
+            return Err(BridgeError::BlockLengthMismatch {
+                lhs,
+                rhs,
+                lhs_len: lhs_init.len(),
+                rhs_len: rhs_init.len(),
+            });
+        }
+
+        let equalities: Vec<_> = lhs_init
+            .iter()
+            .zip(rhs_init)
+            .map(|(lhs, rhs)| AcirArithGate {
+                mul_terms: Vec::new(),
+                add_terms: vec![(F::one(), *lhs), (-F::one(), *rhs)],
+                constant_term: F::zero(),
+                source_opcode: SYNTHESIZED_GATE,
+            })
+            .collect();
+        self.gates.extend(equalities);
+        Ok(())
+    }
+}
+
+impl<F: Field> AcirCircuit<F> {
+    /// Returns the bit width of every witness constrained by a RANGE opcode.
+    ///
+    /// This recovers the integer widths the ABI would declare for the witnesses the circuit
+    /// range-checks, including intermediate ones, without needing the ABI.
+    pub fn inferred_witness_widths(&self) -> BTreeMap<Witness, u32> {
+        self.range_checks.clone()
+    }
+
+    /// Finds the private witnesses which a gate of the form `c * public - c * private = 0`
+    /// constrains to equal a public input, mapped to that public input.
+    pub fn public_aliases(&self) -> BTreeMap<Witness, Witness> {
+        let is_public = |witness: Witness| self.public_inputs.contains(witness.0 as usize);
+
+        let mut aliases = BTreeMap::new();
+        for gate in self.gates.iter().filter_map(AcirArithGate::as_alias) {
+            let (public, private) = match gate {
+                (lhs, rhs) if is_public(lhs) && !is_public(rhs) => (lhs, rhs),
+                (lhs, rhs) if is_public(rhs) && !is_public(lhs) => (rhs, lhs),
+                _ => continue,
+            };
+            aliases.entry(private).or_insert(public);
+        }
+        aliases
+    }
+
+    /// Replaces every private witness found by [`AcirCircuit::public_aliases`] with the public
+    /// input it aliases, so that only the public input is allocated.
+    ///
+    /// The public inputs are unchanged, so proofs for the collapsed circuit verify against the
+    /// same public inputs as the original. Gates which become trivial, such as the aliasing
+    /// gates themselves, are removed, and so are the collapsed witnesses from the private
+    /// parameters.
+    pub fn collapse_public_aliases(mut self) -> Self {
+        let aliases = self.public_aliases();
+        if aliases.is_empty() {
+            return self;
+        }
+        let resolve = |witness: Witness| aliases.get(&witness).copied().unwrap_or(witness);
+        self.map_witnesses(resolve);
+        self.gates.retain(|gate| {
+            let mut canonical = gate.clone();
+            canonical.canonicalize();
+            !(canonical.mul_terms.is_empty()
+                && canonical.add_terms.is_empty()
+                && canonical.constant_term.is_zero())
+        });
+        for private in aliases.keys() {
+            self.values.remove(private);
+            self.private_parameters.remove(private);
+        }
+        self
+    }
+
+    /// Replaces each witness the constraints refer to with `resolve(witness)`, merging the range
+    /// checks of witnesses which resolve to the same one.
+    ///
+    /// The values and the public, private and returned witnesses are left to the caller.
+    fn map_witnesses(&mut self, resolve: impl Fn(Witness) -> Witness) {
+        for gate in self.gates.iter_mut() {
+            for (_, lhs, rhs) in gate.mul_terms.iter_mut() {
+                *lhs = resolve(*lhs);
+                *rhs = resolve(*rhs);
+            }
+            for (_, witness) in gate.add_terms.iter_mut() {
+                *witness = resolve(*witness);
+            }
+        }
+        for init in self.memory_blocks.values_mut() {
+            for witness in init.iter_mut() {
+                *witness = resolve(*witness);
+            }
+        }
+        for access in self.memory_ops.iter_mut() {
+            for expr in access.expressions_mut() {
+                for (_, witness) in expr.add_terms.iter_mut() {
+                    *witness = resolve(*witness);
+                }
+            }
+        }
+        for gate in self.bitwise_gates.iter_mut() {
+            for witness in [&mut gate.lhs, &mut gate.rhs, &mut gate.output] {
+                *witness = resolve(*witness);
+            }
+        }
+        for gate in self.poseidon2_gates.iter_mut() {
+            for witness in gate.inputs.iter_mut().chain(gate.outputs.iter_mut()) {
+                *witness = resolve(*witness);
+            }
+        }
+        for gate in self.keccak_gates.iter_mut() {
+            for witness in gate.inputs.iter_mut().chain(gate.outputs.iter_mut()) {
+                *witness = resolve(*witness);
+            }
+        }
+        for gate in self.embedded_curve_adds.iter_mut() {
+            for witness in gate.witnesses_mut() {
+                *witness = resolve(*witness);
+            }
+        }
+        for gate in self.multi_scalar_muls.iter_mut() {
+            for witness in gate.witnesses_mut() {
+                *witness = resolve(*witness);
+            }
+        }
+        for gate in self.sha256_gates.iter_mut() {
+            for witness in gate.witnesses_mut() {
+                *witness = resolve(*witness);
+            }
+        }
+        for gate in self.ecdsa_secp256k1_gates.iter_mut() {
+            for witness in gate.witnesses_mut() {
+                *witness = resolve(*witness);
+            }
+        }
+        for gate in self.blake2s_gates.iter_mut() {
+            for witness in gate.witnesses_mut() {
+                *witness = resolve(*witness);
+            }
+        }
+        for gate in self.pedersen_hash_gates.iter_mut() {
+            for witness in gate.witnesses_mut() {
+                *witness = resolve(*witness);
+            }
+        }
+        for gate in self.schnorr_verify_gates.iter_mut() {
+            for witness in gate.witnesses_mut() {
+                *witness = resolve(*witness);
+            }
+        }
+        for gate in self.aes128_encrypt_gates.iter_mut() {
+            for witness in gate.witnesses_mut() {
+                *witness = resolve(*witness);
+            }
+        }
+        for gate in self.bigint_gates.iter_mut() {
+            for witness in gate.witnesses_mut() {
+                *witness = resolve(*witness);
+            }
+        }
+        for (witness, num_bits) in std::mem::take(&mut self.range_checks) {
+            let width = self
+                .range_checks
+                .entry(resolve(witness))
+                .or_insert(num_bits);
+            *width = (*width).min(num_bits);
+        }
+    }
+}
+
+impl<F: Field> AcirCircuit<F> {
+    /// Returns the index of the first gate which does not hold for the circuit's values.
+    pub(crate) fn first_unsatisfied_gate(&self) -> Option<usize> {
+        self.gates
+            .iter()
+            .position(|gate| !gate.evaluate(&self.values).is_zero())
+    }
+
+    /// Returns the assertion message attached to the opcode `gate_index` was lowered from.
+    pub(crate) fn assert_message(&self, gate_index: usize) -> Option<&str> {
+        let source_opcode = self.gates.get(gate_index)?.source_opcode;
+        self.assert_messages.get(&source_opcode).map(String::as_str)
+    }
+
+    /// Checks that every gate holds for the circuit's values, reporting the first one which does
+    /// not along with its assertion message.
+    ///
+    /// Only the arithmetic gates are checked, not memory accesses or range checks.
+    pub fn check_satisfied(&self) -> Result<(), ConstraintFailure> {
+        let Some(gate_index) = self.first_unsatisfied_gate() else {
+            return Ok(());
+        };
+        let source_opcode = self.gates[gate_index].source_opcode;
+        Err(ConstraintFailure {
+            gate_index,
+            source_opcode: (source_opcode != SYNTHESIZED_GATE).then_some(source_opcode),
+            message: self.assert_message(gate_index).map(str::to_owned),
+        })
+    }
+}
+
+/// Evaluates every arithmetic gate of `circuit` on its values in the native field, returning
+/// the indices of the gates which do not evaluate to zero.
+///
+/// No constraint system is built, so this is a cheap check of a witness before proving. Like
+/// [`AcirCircuit::check_satisfied`], memory accesses, range checks and black box calls are not
+/// checked.
+pub fn witness_satisfies<F: Field>(circuit: &AcirCircuit<F>) -> Result<(), Vec<usize>> {
+    let unsatisfied: Vec<usize> = circuit
+        .gates
+        .iter()
+        .enumerate()
+        .filter(|(_, gate)| !gate.evaluate(&circuit.values).is_zero())
+        .map(|(index, _)| index)
+        .collect();
+    if unsatisfied.is_empty() {
+        Ok(())
+    } else {
+        Err(unsatisfied)
+    }
+}
+
+impl<F: Field> AcirArithGate<F> {
+    /// The terms `c * lhs * rhs` of the gate, as `(c, lhs, rhs)`.
+    pub fn mul_terms(&self) -> &[(F, Witness, Witness)] {
+        &self.mul_terms
+    }
+
+    /// The terms `c * w` of the gate, as `(c, w)`.
+    pub fn add_terms(&self) -> &[(F, Witness)] {
+        &self.add_terms
+    }
+
+    /// The constant term of the gate.
+    pub fn constant_term(&self) -> F {
+        self.constant_term
+    }
+
+    /// Evaluates the gate's expression, treating missing witnesses as zero.
+    pub(crate) fn evaluate(&self, values: &BTreeMap<Witness, F>) -> F {
+        let value = |witness: &Witness| values.get(witness).copied().unwrap_or_default();
+        let mul_terms = self
+            .mul_terms
+            .iter()
+            .map(|(coeff, lhs, rhs)| *coeff * value(lhs) * value(rhs));
+        let add_terms = self
+            .add_terms
+            .iter()
+            .map(|(coeff, witness)| *coeff * value(witness));
+        mul_terms.chain(add_terms).sum::<F>() + self.constant_term
+    }
+
+    /// Iterates over every witness referenced by this gate, in term order.
+    pub(crate) fn witnesses(&self) -> impl Iterator<Item = Witness> + '_ {
+        self.mul_terms
+            .iter()
+            .flat_map(|(_, lhs, rhs)| [*lhs, *rhs])
+            .chain(self.add_terms.iter().map(|(_, witness)| *witness))
+    }
+
+    /// Returns the two witnesses of a gate of the form `c * lhs - c * rhs = 0`.
+    fn as_alias(&self) -> Option<(Witness, Witness)> {
+        let mut canonical = self.clone();
+        canonical.canonicalize();
+        match (
+            canonical.mul_terms.as_slice(),
+            canonical.add_terms.as_slice(),
+        ) {
+            ([], [(lhs_coeff, lhs), (rhs_coeff, rhs)])
+                if canonical.constant_term.is_zero() && *lhs_coeff == -*rhs_coeff =>
+            {
+                Some((*lhs, *rhs))
+            }
+            _ => None,
+        }
+    }
+
+    /// Rewrites the gate into its canonical form so that equivalent gates compare equal.
+    ///
+    /// Each mul term is ordered as `(min, max)` witness, terms over the same witnesses are merged
+    /// by summing their coefficients, zero coefficients are dropped and both term lists are
+    /// sorted by witness. Finally the gate is scaled so that its leading non-zero coefficient,
+    /// the first mul term's, else the first add term's, else the constant, is one.
+    pub fn canonicalize(&mut self) {
+        let mut mul_terms = BTreeMap::new();
+        for (coeff, lhs, rhs) in self.mul_terms.drain(..) {
+            let key = if lhs <= rhs { (lhs, rhs) } else { (rhs, lhs) };
+            *mul_terms.entry(key).or_insert_with(F::zero) += coeff;
+        }
+        self.mul_terms = mul_terms
+            .into_iter()
+            .filter(|(_, coeff)| !coeff.is_zero())
+            .map(|((lhs, rhs), coeff)| (coeff, lhs, rhs))
+            .collect();
+
+        let mut add_terms = BTreeMap::new();
+        for (coeff, witness) in self.add_terms.drain(..) {
+            *add_terms.entry(witness).or_insert_with(F::zero) += coeff;
+        }
+        self.add_terms = add_terms
+            .into_iter()
+            .filter(|(_, coeff)| !coeff.is_zero())
+            .map(|(witness, coeff)| (coeff, witness))
+            .collect();
+
+        let leading = self
+            .mul_terms
+            .first()
+            .map(|(coeff, _, _)| *coeff)
+            .or_else(|| self.add_terms.first().map(|(coeff, _)| *coeff))
+            .unwrap_or(self.constant_term);
+        if let Some(scale) = leading.inverse() {
+            for (coeff, _, _) in self.mul_terms.iter_mut() {
+                *coeff *= scale;
+            }
+            for (coeff, _) in self.add_terms.iter_mut() {
+                *coeff *= scale;
+            }
+            self.constant_term *= scale;
+        }
+    }
+}
+
+impl<F: Field> AcirCircuit<F> {
+    /// Counts the constraints and variables `generate_constraints` allocates. This mirrors the
+    /// synthesis, so any change to the allocations there must be reflected here.
+    pub(crate) fn r1cs_size(&self) -> R1csSize {
+        let num_public_values = self
+            .values
+            .keys()
+            .filter(|witness| self.public_inputs.contains(witness.0 as usize))
+            .count();
+        // Products shared by several gates are allocated once
+        let products: BTreeSet<_> = self
+            .gates
+            .iter()
+            .flat_map(|gate| &gate.mul_terms)
+            .map(|(_, lhs, rhs)| (*lhs.min(rhs), *lhs.max(rhs)))
+            .collect();
+        let mut size = R1csSize {
+            constraints: self.gates.len() + products.len(),
+            witnesses: self.values.len() - num_public_values + products.len(),
+            public_inputs: self.domain.len() + num_public_values,
+        };
+
+        size += memory::r1cs_size(&self.memory_ops, &self.memory_blocks);
+        for num_bits in self.range_checks.values() {
+            // A single booleanity constraint for one bit, otherwise the bits with their
+            // booleanity and the recomposition
+            size += match num_bits {
+                1 => R1csSize {
+                    constraints: 1,
+                    witnesses: 0,
+                    public_inputs: 0,
+                },
+                _ => R1csSize {
+                    constraints: *num_bits as usize + 1,
+                    witnesses: *num_bits as usize,
+                    public_inputs: 0,
+                },
+            };
+        }
+        size += bitwise::r1cs_size(&self.bitwise_gates);
+        size += poseidon2::r1cs_size(&self.poseidon2_gates, self.poseidon_config.as_ref());
+        size += keccak::r1cs_size(&self.keccak_gates);
+        size += embedded_curve::r1cs_size(&self.embedded_curve_adds);
+        size += embedded_curve::multi_scalar_mul_r1cs_size(&self.multi_scalar_muls);
+        size += sha256::r1cs_size(&self.sha256_gates);
+        size += ecdsa::r1cs_size(&self.ecdsa_secp256k1_gates);
+        size += blake2s::r1cs_size(&self.blake2s_gates);
+        size += pedersen::r1cs_size(&self.pedersen_hash_gates);
+        size += schnorr::r1cs_size(&self.schnorr_verify_gates);
+        size += aes::r1cs_size(&self.aes128_encrypt_gates);
+        size += bigint::r1cs_size(&self.bigint_gates);
+        size
+    }
+}
+
+impl<ConstraintF: PrimeField> ConstraintSynthesizer<ConstraintF> for AcirCircuit<ConstraintF> {
     fn generate_constraints(
         self,
         cs: ConstraintSystemRef<ConstraintF>,
     ) -> Result<(), SynthesisError> {
-        let mut variables = Vec::with_capacity(self.values.len());
+        self.generate_constraints_with_map(cs).map(|_| ())
+    }
+}
 
-        // First create all of the witness indices by adding the values into the constraint system
-        for (i, val) in self.values.iter() {
-            let var = if self.public_inputs.contains(i.0.try_into().unwrap()) {
-                cs.new_input_variable(|| Ok(*val))?
-            } else {
-                cs.new_witness_variable(|| Ok(*val))?
-            };
+impl<ConstraintF: PrimeField> AcirCircuit<ConstraintF> {
+    /// Synthesizes the circuit like `generate_constraints` and returns the variable each witness
+    /// was allocated to.
+    ///
+    /// This lets callers embedding the circuit in a larger constraint system add their own
+    /// constraints over specific witnesses.
+    pub fn generate_constraints_with_map(
+        self,
+        cs: ConstraintSystemRef<ConstraintF>,
+    ) -> Result<BTreeMap<Witness, Variable>, SynthesisError> {
+        self.synthesize(cs, None)
+    }
+
+    /// Synthesizes the circuit like `generate_constraints`, recording which arithmetic gate
+    /// produced each of their constraints, in gate order.
+    ///
+    /// The constraints of memory accesses, range checks and black box calls are not traced.
+    pub fn generate_constraints_traced(
+        self,
+        cs: ConstraintSystemRef<ConstraintF>,
+    ) -> Result<Vec<ConstraintTraceEntry>, SynthesisError> {
+        let mut trace = Vec::new();
+        self.synthesize(cs, Some(&mut trace))?;
+        Ok(trace)
+    }
 
-            variables.push(var);
+    fn synthesize(
+        self,
+        cs: ConstraintSystemRef<ConstraintF>,
+        trace: Option<&mut Vec<ConstraintTraceEntry>>,
+    ) -> Result<BTreeMap<Witness, Variable>, SynthesisError> {
+        // Allocate the domain separator, which precedes all other public inputs. No constraint
+        // is needed: the Groth16 reduction adds an `x * 0 == 0` row for every instance variable,
+        // which gives each input its own term in the verifying key and binds the proof to it.
+        for value in self.domain.iter() {
+            cs.new_input_variable(|| Ok(*value))?;
         }
 
-        // Now iterate each gate and add it to the constraint system
-        for gate in self.gates {
-            let mut arith_gate = LinearCombination::<ConstraintF>::new();
+        // First create the witness indices by adding the values into the constraint system.
+        // Public inputs always come first; the remaining witnesses are allocated here only
+        // for the default layout.
+        let mut variables = allocate_inputs(&cs, &self.values, &self.public_inputs.0, self.layout)?;
+
+        // Now add each gate to the constraint system
+        emit_gates(&cs, self.gates, &self.values, &mut variables, trace)?;
 
-            // Process mul terms
-            for mul_term in gate.mul_terms {
-                let coeff = mul_term.0;
-                let left_val = self.values[&mul_term.1];
-                let right_val = self.values[&mul_term.2];
+        // Finally allocate the auxiliary witnesses which no gate refers to
+        let auxiliary: Vec<_> = self
+            .values
+            .iter()
+            .filter(|(witness, _)| !variables.contains_key(witness))
+            .map(|(witness, value)| Ok((*witness, cs.new_witness_variable(|| Ok(*value))?)))
+            .collect::<Result<_, SynthesisError>>()?;
+        variables.extend(auxiliary);
 
-                let out_val = left_val * right_val;
+        memory::enforce_memory_accesses(
+            &cs,
+            &self.memory_ops,
+            &self.memory_blocks,
+            self.memory_mode,
+            &variables,
+            &self.values,
+        )?;
 
-                let out_var = cs.new_witness_variable(|| Ok(out_val))?;
-                arith_gate += (coeff, out_var);
+        // Range check each witness constrained by a RANGE opcode through its bit decomposition,
+        // except for booleans, which only need `x * (x - 1) == 0`
+        for (witness, num_bits) in &self.range_checks {
+            let var = variables[witness];
+            match num_bits {
+                1 => cs.enforce_constraint(
+                    lc!() + var,
+                    lc!() + var - (ConstraintF::one(), Variable::One),
+                    lc!(),
+                )?,
+                _ => {
+                    enforce_bit_decomposition(&cs, var, *num_bits)?;
+                }
             }
+        }
+
+        bitwise::enforce_bitwise_gates(&cs, &self.bitwise_gates, &variables)?;
+        poseidon2::enforce_poseidon2_gates(
+            &cs,
+            &self.poseidon2_gates,
+            self.poseidon_config.as_ref(),
+            &variables,
+        )?;
+        keccak::enforce_keccak_gates(&cs, &self.keccak_gates, &variables)?;
+        embedded_curve::enforce_embedded_curve_adds(&cs, &self.embedded_curve_adds, &variables)?;
+        embedded_curve::enforce_multi_scalar_muls(&cs, &self.multi_scalar_muls, &variables)?;
+        sha256::enforce_sha256_gates(&cs, &self.sha256_gates, &variables)?;
+        ecdsa::enforce_ecdsa_secp256k1_gates(&cs, &self.ecdsa_secp256k1_gates, &variables)?;
+        blake2s::enforce_blake2s_gates(&cs, &self.blake2s_gates, &variables)?;
+        pedersen::enforce_pedersen_hash_gates(&cs, &self.pedersen_hash_gates, &variables)?;
+        schnorr::enforce_schnorr_verify_gates(&cs, &self.schnorr_verify_gates, &variables)?;
+        aes::enforce_aes128_encrypt_gates(&cs, &self.aes128_encrypt_gates, &variables)?;
+        bigint::enforce_bigint_gates(&cs, &self.bigint_gates, &variables)?;
+
+        Ok(variables)
+    }
+
+    /// Appends the circuit to `cs`, which may already hold the constraints of other circuits,
+    /// with every witness index shifted up by `witness_offset`.
+    ///
+    /// Variables are allocated from the current end of `cs`. Returns the offset past this
+    /// circuit's highest witness, from which the next circuit can be appended so that witness
+    /// indices of different circuits never collide. Fails with
+    /// `SynthesisError::Unsatisfiable` if a shifted index does not fit a `Witness`.
+    pub fn generate_constraints_into(
+        &self,
+        cs: ConstraintSystemRef<ConstraintF>,
+        witness_offset: usize,
+    ) -> Result<usize, SynthesisError> {
+        let num_witnesses = self
+            .values
+            .keys()
+            .chain(self.public_inputs.0.iter())
+            .map(|witness| witness.0 as usize + 1)
+            .max()
+            .unwrap_or(0);
+        let next_offset = witness_offset + num_witnesses;
+        let offset = match u32::try_from(next_offset) {
+            Ok(_) => witness_offset as u32,
+            Err(_) => return Err(SynthesisError::Unsatisfiable),
+        };
+        let shift = |witness: &Witness| Witness(witness.0 + offset);
+
+        let mut circuit = self.clone();
+        circuit.map_witnesses(|witness| shift(&witness));
+        circuit.values = self
+            .values
+            .iter()
+            .map(|(witness, value)| (shift(witness), *value))
+            .collect();
+        circuit.public_inputs = PublicInputs(self.public_inputs.0.iter().map(shift).collect());
+        circuit.private_parameters = self.private_parameters.iter().map(shift).collect();
+        circuit.return_values = self.return_values.iter().map(shift).collect();
+        circuit.generate_constraints_with_map(cs)?;
+
+        Ok(next_offset)
+    }
+}
+
+/// Allocates the public inputs and, for [`CommitmentLayout::WitnessOrder`], every other witness,
+/// in ascending `Witness` order.
+///
+/// Both `values` and `public_inputs` are sorted, so public witnesses are picked out by walking
+/// the two in step rather than looking each witness up, and the variables are collected into a
+/// map in one pass.
+fn allocate_inputs<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    values: &BTreeMap<Witness, F>,
+    public_inputs: &BTreeSet<Witness>,
+    layout: CommitmentLayout,
+) -> Result<BTreeMap<Witness, Variable>, SynthesisError> {
+    let mut public_inputs = public_inputs.iter().peekable();
+    let mut variables = Vec::with_capacity(match layout {
+        CommitmentLayout::WitnessOrder => values.len(),
+        CommitmentLayout::GateGrouped => public_inputs.len(),
+    });
+    for (witness, value) in values {
+        while public_inputs.next_if(|public| *public < witness).is_some() {}
+        let var = if public_inputs.next_if_eq(&witness).is_some() {
+            cs.new_input_variable(|| Ok(*value))?
+        } else if layout == CommitmentLayout::WitnessOrder {
+            cs.new_witness_variable(|| Ok(*value))?
+        } else {
+            continue;
+        };
+        variables.push((*witness, var));
+    }
+    Ok(variables.into_iter().collect())
+}
 
-            // Process Add terms
-            for add_term in gate.add_terms {
-                let coeff = add_term.0;
-                let add_var = variables[add_term.1.as_usize()];
-                arith_gate += (coeff, add_var);
+/// Enforces each arithmetic gate in order, allocating the witnesses it refers to which have no
+/// variable yet.
+fn emit_gates<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    gates: Vec<AcirArithGate<F>>,
+    values: &BTreeMap<Witness, F>,
+    variables: &mut BTreeMap<Witness, Variable>,
+    mut trace: Option<&mut Vec<ConstraintTraceEntry>>,
+) -> Result<(), SynthesisError> {
+    // The witness allocated for each product, keyed on the `(min, max)` pair of factors so
+    // that a product shared by several gates is only allocated once
+    let mut products = BTreeMap::new();
+
+    for (gate_index, gate) in gates.into_iter().enumerate() {
+        let first_constraint = cs.num_constraints();
+        let source_opcode = gate.source_opcode;
+        for witness in gate.witnesses() {
+            if let Entry::Vacant(entry) = variables.entry(witness) {
+                let val = values[&witness];
+                entry.insert(cs.new_witness_variable(|| Ok(val))?);
             }
+        }
 
-            // Process constant term
-            arith_gate += (gate.constant_term, Variable::One);
+        let mut arith_gate = LinearCombination::<F>::new();
 
-            cs.enforce_constraint(lc!() + Variable::One, arith_gate, lc!())?;
+        // Process mul terms
+        for (coeff, lhs, rhs) in gate.mul_terms {
+            let key = if lhs <= rhs { (lhs, rhs) } else { (rhs, lhs) };
+            let out_var = match products.entry(key) {
+                Entry::Occupied(entry) => *entry.get(),
+                Entry::Vacant(entry) => {
+                    let out_val = values[&lhs] * values[&rhs];
+                    let out_var = cs.new_witness_variable(|| Ok(out_val))?;
+                    // lhs * rhs == out, so that the prover cannot choose the product
+                    cs.enforce_constraint(
+                        lc!() + variables[&lhs],
+                        lc!() + variables[&rhs],
+                        lc!() + out_var,
+                    )?;
+                    *entry.insert(out_var)
+                }
+            };
+            arith_gate += (coeff, out_var);
         }
 
-        Ok(())
+        // Process Add terms
+        for add_term in gate.add_terms {
+            let coeff = add_term.0;
+            let add_var = variables[&add_term.1];
+            arith_gate += (coeff, add_var);
+        }
+
+        // Process constant term. ACIR asserts that the whole expression is zero, so the
+        // constant sits on the same side as the other terms, whatever their visibility
+        arith_gate += (gate.constant_term, Variable::One);
+
+        cs.enforce_constraint(lc!() + Variable::One, arith_gate, lc!())?;
+
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.push(ConstraintTraceEntry {
+                gate_index,
+                source_opcode: (source_opcode != SYNTHESIZED_GATE).then_some(source_opcode),
+                constraints: first_constraint..cs.num_constraints(),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use acvm::acir::circuit::opcodes::{BlackBoxFuncCall, FunctionInput};
+    use acvm::acir::circuit::{Opcode, PublicInputs};
+    use acvm::acir::native_types::Witness;
+    use ark_relations::lc;
+    use ark_relations::r1cs::{
+        ConstraintSynthesizer, ConstraintSystem, ConstraintSystemRef, Variable,
+    };
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+
+    use super::{
+        allocate_inputs, witness_satisfies, AcirArithGate, AcirCircuit, CommitmentLayout,
+        ConstraintFailure,
+    };
+    use crate::groth16::{prove, setup, verify};
+    use crate::test::{circuit_with_gates, test_circuit, test_circuit_witness_map};
+    use crate::{CurveAcir, Fr};
+
+    #[test]
+    fn canonicalize_ignores_term_order() {
+        let (a, b, c) = (Witness(1), Witness(2), Witness(3));
+        let fr = |value: u64| Fr::from(value);
+
+        let mut lhs = AcirArithGate {
+            mul_terms: vec![(fr(2), b, a), (fr(3), c, c)],
+            add_terms: vec![(fr(1), c), (fr(4), a), (fr(1), c)],
+            constant_term: fr(5),
+            source_opcode: 0,
+        };
+        let mut rhs = AcirArithGate {
+            mul_terms: vec![(fr(3), c, c), (fr(1), a, b), (fr(1), b, a), (fr(0), a, c)],
+            add_terms: vec![(fr(4), a), (fr(2), c), (fr(0), b)],
+            constant_term: fr(5),
+            source_opcode: 0,
+        };
+        assert_ne!(lhs, rhs);
+
+        let mut scaled = AcirArithGate {
+            mul_terms: vec![(fr(6), c, c), (fr(4), b, a)],
+            add_terms: vec![(fr(4), c), (fr(8), a)],
+            constant_term: fr(10),
+            source_opcode: 0,
+        };
+
+        lhs.canonicalize();
+        rhs.canonicalize();
+        scaled.canonicalize();
+        assert_eq!(lhs, rhs);
+        assert_eq!(lhs, scaled);
+        let half = fr(2).inverse().unwrap();
+        assert_eq!(lhs.mul_terms, vec![(fr(1), a, b), (fr(3) * half, c, c)]);
+        assert_eq!(lhs.add_terms, vec![(fr(2), a), (fr(1), c)]);
+        assert_eq!(lhs.constant_term, fr(5) * half);
+    }
+
+    #[test]
+    fn collapsing_public_aliases_saves_a_variable() {
+        let (public, private, square) = (Witness(1), Witness(2), Witness(3));
+        let fr = |value: u64| Fr::from(value);
+
+        // public == private, private * private == square
+        let mut circuit = circuit_with_gates(
+            vec![
+                AcirArithGate {
+                    mul_terms: vec![],
+                    add_terms: vec![(fr(1), public), (-fr(1), private)],
+                    constant_term: fr(0),
+                    source_opcode: 0,
+                },
+                AcirArithGate {
+                    mul_terms: vec![(fr(1), private, private)],
+                    add_terms: vec![(-fr(1), square)],
+                    constant_term: fr(0),
+                    source_opcode: 1,
+                },
+            ],
+            &[public],
+            &[(public, 3), (private, 3), (square, 9)],
+        );
+        circuit.private_parameters = [private].into();
+        assert_eq!(circuit.public_aliases(), [(private, public)].into());
+        let collapsed = circuit.clone().collapse_public_aliases();
+        assert!(collapsed.private_parameter_witnesses().is_empty());
+
+        let num_variables = |circuit: &CurveAcir| {
+            let cs = ConstraintSystem::new_ref();
+            circuit.clone().generate_constraints(cs.clone()).unwrap();
+            assert!(cs.is_satisfied().unwrap());
+            cs.num_instance_variables() + cs.num_witness_variables()
+        };
+        assert_eq!(num_variables(&collapsed), num_variables(&circuit) - 1);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for circuit in [circuit, collapsed] {
+            let (pk, vk) = setup(&circuit, &mut rng).unwrap();
+            let proof = prove(&pk, &circuit, &mut rng).unwrap();
+            assert!(verify(&vk, &[fr(3)], &proof).unwrap());
+            assert!(!verify(&vk, &[fr(4)], &proof).unwrap());
+        }
+    }
+
+    #[test]
+    fn witness_map_allows_external_constraints() {
+        let circuit = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        let embed = |expected: u64| {
+            let cs = ConstraintSystem::new_ref();
+            let variables = circuit
+                .clone()
+                .generate_constraints_with_map(cs.clone())
+                .unwrap();
+
+            // w4 = w0 * w2 = 8
+            cs.enforce_constraint(
+                lc!() + Variable::One,
+                lc!() + variables[&Witness(4)] - (Fr::from(expected), Variable::One),
+                lc!(),
+            )
+            .unwrap();
+            cs.is_satisfied().unwrap()
+        };
+        assert!(embed(8));
+        assert!(!embed(9));
+    }
+
+    #[test]
+    fn circuits_append_into_one_constraint_system() {
+        let circuit = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        let single = ConstraintSystem::new_ref();
+        circuit
+            .clone()
+            .generate_constraints(single.clone())
+            .unwrap();
+
+        let cs = ConstraintSystem::new_ref();
+        let offset = circuit.generate_constraints_into(cs.clone(), 0).unwrap();
+        // the test circuit's witnesses are w0 to w5
+        assert_eq!(offset, 6);
+        let offset = circuit
+            .generate_constraints_into(cs.clone(), offset)
+            .unwrap();
+        assert_eq!(offset, 12);
+
+        assert_eq!(cs.num_constraints(), 2 * single.num_constraints());
+        assert_eq!(
+            cs.num_witness_variables(),
+            2 * single.num_witness_variables()
+        );
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn counts_test_circuit() {
+        let circuit = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        assert_eq!(circuit.witness_count(), 6);
+        assert_eq!(circuit.public_input_count(), 4);
+        assert_eq!(circuit.gate_count(), 2);
+    }
+
+    #[test]
+    fn gates_keep_their_opcode_index() {
+        let range = |witness| {
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::RANGE {
+                input: FunctionInput {
+                    witness: Witness(witness),
+                    num_bits: 8,
+                },
+            })
+        };
+        let mut circuit = test_circuit();
+        circuit.opcodes.insert(0, range(0));
+        circuit.opcodes.insert(2, range(1));
+
+        let circuit = CurveAcir::from((&circuit, test_circuit_witness_map()));
+        let origins: Vec<usize> = circuit
+            .gates_with_origin()
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(origins, [1, 3]);
+    }
+
+    #[test]
+    fn traces_the_gate_of_each_constraint() {
+        let circuit = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        let gate_count = circuit.gate_count();
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let trace = circuit.generate_constraints_traced(cs.clone()).unwrap();
+
+        assert_eq!(trace.len(), gate_count);
+        // Each gate binds its product, then asserts its expression
+        let constraints: Vec<_> = trace
+            .iter()
+            .map(|entry| entry.constraints.clone())
+            .collect();
+        assert_eq!(constraints, [0..2, 2..4]);
+        let origins: Vec<_> = trace.iter().map(|entry| entry.source_opcode).collect();
+        assert_eq!(origins, [Some(0), Some(1)]);
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn public_inputs_are_instance_variables() {
+        let circuit = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        let num_public_inputs = circuit.public_inputs.0.len();
+        let cs = ConstraintSystem::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+
+        // the constant one, then the public inputs in witness order
+        assert_eq!(cs.num_instance_variables(), num_public_inputs + 1);
+        let to_fr = |values: &[u64]| values.iter().map(|v| Fr::from(*v)).collect::<Vec<_>>();
+        assert_eq!(
+            cs.borrow().unwrap().instance_assignment,
+            to_fr(&[1, 2, 3, 8, 15])
+        );
+    }
+
+    #[test]
+    fn public_input_values_match_instance_assignment() {
+        let circuit = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        let public_input_values = circuit.public_input_values();
+        let expected: Vec<Fr> = test_circuit()
+            .public_inputs()
+            .indices()
+            .into_iter()
+            .map(|index| circuit.values[&Witness(index)])
+            .collect();
+        assert_eq!(public_input_values, expected);
+
+        let cs = ConstraintSystem::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        // the instance assignment starts with the constant one
+        assert_eq!(
+            cs.borrow().unwrap().instance_assignment[1..],
+            public_input_values
+        );
+    }
+
+    #[test]
+    fn return_values_are_public_outputs() {
+        // `test_circuit` returns the products 2 * 4 and 3 * 5
+        let circuit = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        let outputs = circuit.public_output_values();
+        assert_eq!(outputs, vec![Fr::from(8u64), Fr::from(15u64)]);
+
+        let cs = ConstraintSystem::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        let instance = cs.borrow().unwrap().instance_assignment.clone();
+        assert!(outputs.iter().all(|output| instance[1..].contains(output)));
+    }
+
+    #[test]
+    fn failed_checks_report_assert_messages() {
+        let x = Witness(0);
+        let fr = |value: u64| Fr::from(value);
+
+        // x == 3, with a message, then x == 4 without one
+        let gate = |constant: u64, source_opcode| AcirArithGate {
+            mul_terms: vec![],
+            add_terms: vec![(fr(1), x)],
+            constant_term: -fr(constant),
+            source_opcode,
+        };
+        let mut circuit = circuit_with_gates(vec![gate(3, 0), gate(4, 1)], &[], &[(x, 4)]);
+        circuit.assert_messages = [(0, "x must be 3".to_string())].into();
+
+        let failure = circuit.check_satisfied().unwrap_err();
+        assert_eq!(
+            failure,
+            ConstraintFailure {
+                gate_index: 0,
+                source_opcode: Some(0),
+                message: Some("x must be 3".to_string()),
+            }
+        );
+        assert_eq!(
+            failure.to_string(),
+            "gate 0 is not satisfied: 'x must be 3'"
+        );
+
+        circuit.values.insert(x, fr(3));
+        assert_eq!(circuit.check_satisfied().unwrap_err().message, None);
+    }
+
+    #[test]
+    fn witness_satisfies_reports_every_unsatisfied_gate() {
+        let mut circuit = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        assert_eq!(witness_satisfies(&circuit), Ok(()));
+
+        // w5 = w1 * w3 = 15
+        circuit.values.insert(Witness(5), Fr::from(16u64));
+        let product = circuit
+            .gates
+            .iter()
+            .position(|gate| gate.witnesses().any(|witness| witness == Witness(5)))
+            .unwrap();
+        assert_eq!(witness_satisfies(&circuit), Err(vec![product]));
+    }
+
+    #[test]
+    fn tampered_products_are_rejected() {
+        let (a, b, c) = (Witness(0), Witness(1), Witness(2));
+        let fr = |value: u64| Fr::from(value);
+
+        // c == a * b
+        let circuit = circuit_with_gates(
+            vec![AcirArithGate {
+                mul_terms: vec![(fr(1), a, b)],
+                add_terms: vec![(-fr(1), c)],
+                constant_term: fr(0),
+                source_opcode: 0,
+            }],
+            &[],
+            &[(a, 2), (b, 3), (c, 6)],
+        );
+        let cs = ConstraintSystem::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        // Claim a * b == 7 consistently in both the product witness and c
+        let mut cs = cs.borrow_mut().unwrap();
+        let product = cs.witness_assignment.len() - 1;
+        cs.witness_assignment[product] = fr(7);
+        cs.witness_assignment[c.as_usize()] = fr(7);
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn shared_products_are_allocated_once() {
+        let (a, b, c, d) = (Witness(0), Witness(1), Witness(2), Witness(3));
+        let fr = |value: u64| Fr::from(value);
+
+        // c == a * b, d == 2 * b * a
+        let circuit = circuit_with_gates(
+            vec![
+                AcirArithGate {
+                    mul_terms: vec![(fr(1), a, b)],
+                    add_terms: vec![(-fr(1), c)],
+                    constant_term: fr(0),
+                    source_opcode: 0,
+                },
+                AcirArithGate {
+                    mul_terms: vec![(fr(2), b, a)],
+                    add_terms: vec![(-fr(1), d)],
+                    constant_term: fr(0),
+                    source_opcode: 1,
+                },
+            ],
+            &[],
+            &[(a, 3), (b, 5), (c, 15), (d, 30)],
+        );
+
+        let cs = ConstraintSystem::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        // the four witnesses and a single product
+        assert_eq!(cs.num_witness_variables(), 5);
+    }
+
+    #[test]
+    fn curve_acir_synthesizes_like_acir_circuit() {
+        let circuit: AcirCircuit<Fr> =
+            AcirCircuit::from((&test_circuit(), test_circuit_witness_map()));
+        let acir_cs = ConstraintSystem::new_ref();
+        circuit
+            .clone()
+            .generate_constraints_with_map(acir_cs.clone())
+            .unwrap();
+
+        let curve_acir: CurveAcir = circuit;
+        let curve_cs = ConstraintSystem::new_ref();
+        ConstraintSynthesizer::<Fr>::generate_constraints(curve_acir, curve_cs.clone()).unwrap();
+
+        assert_eq!(acir_cs.num_constraints(), curve_cs.num_constraints());
+        assert_eq!(
+            acir_cs.num_witness_variables(),
+            curve_cs.num_witness_variables()
+        );
+        assert_eq!(
+            acir_cs.num_instance_variables(),
+            curve_cs.num_instance_variables()
+        );
+        assert!(curve_cs.is_satisfied().unwrap());
+    }
+
+    /// The allocation loop as it was, looking up each witness and inserting it on its own.
+    fn allocate_per_witness(
+        cs: &ConstraintSystemRef<Fr>,
+        values: &BTreeMap<Witness, Fr>,
+        public_inputs: &BTreeSet<Witness>,
+    ) -> BTreeMap<Witness, Variable> {
+        let public = PublicInputs(public_inputs.clone());
+        let mut variables = BTreeMap::new();
+        for (witness, value) in values.iter() {
+            let var = if public.contains(witness.0 as usize) {
+                cs.new_input_variable(|| Ok(*value)).unwrap()
+            } else {
+                cs.new_witness_variable(|| Ok(*value)).unwrap()
+            };
+            variables.insert(*witness, var);
+        }
+        variables
+    }
+
+    /// `num_witnesses` witnesses, every tenth of them public.
+    fn inputs_to_allocate(num_witnesses: u32) -> (BTreeMap<Witness, Fr>, BTreeSet<Witness>) {
+        let values: BTreeMap<Witness, Fr> = (0..num_witnesses)
+            .map(|index| (Witness(index), Fr::from(u64::from(index))))
+            .collect();
+        let public_inputs = values.keys().copied().step_by(10).collect();
+        (values, public_inputs)
+    }
+
+    #[test]
+    fn allocates_inputs_in_one_pass() {
+        let (values, public_inputs) = inputs_to_allocate(1000);
+        let per_witness =
+            allocate_per_witness(&ConstraintSystem::new_ref(), &values, &public_inputs);
+
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let one_pass =
+            allocate_inputs(&cs, &values, &public_inputs, CommitmentLayout::WitnessOrder).unwrap();
+        assert_eq!(one_pass, per_witness);
+        // the constant one is an instance variable too
+        assert_eq!(cs.num_instance_variables(), 1 + 100);
+        assert_eq!(cs.num_witness_variables(), 900);
+    }
+
+    /// Compares both allocation loops on a large circuit. Run it with
+    /// `cargo test --release -- --ignored --nocapture allocation_benchmark`.
+    #[test]
+    #[ignore]
+    fn allocation_benchmark() {
+        use std::time::Instant;
+
+        let (values, public_inputs) = inputs_to_allocate(1 << 20);
+
+        let start = Instant::now();
+        let per_witness =
+            allocate_per_witness(&ConstraintSystem::new_ref(), &values, &public_inputs);
+        let per_witness_time = start.elapsed();
+
+        let start = Instant::now();
+        let one_pass = allocate_inputs(
+            &ConstraintSystem::new_ref(),
+            &values,
+            &public_inputs,
+            CommitmentLayout::WitnessOrder,
+        )
+        .unwrap();
+        let one_pass_time = start.elapsed();
+
+        assert_eq!(one_pass, per_witness);
+        println!(
+            "{} witnesses: {per_witness_time:?} per witness, {one_pass_time:?} in one pass",
+            values.len()
+        );
     }
 }
@@ -0,0 +1,1020 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{Read, Write};
+
+use acvm::acir::{
+    circuit::{opcodes::BlockId, PublicInputs},
+    native_types::Witness,
+};
+use ark_groth16::ProvingKey;
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, SerializationError, Valid, Validate,
+};
+use thiserror::Error;
+
+use crate::aes::Aes128EncryptGate;
+use crate::bigint::{BigIntCall, BigIntGate, BigIntOperation};
+use crate::bitwise::{BitwiseGate, BitwiseOperation};
+use crate::blake2s::Blake2sGate;
+use crate::bridge::{AcirArithGate, AcirCircuit, CommitmentLayout, MemoryMode};
+use crate::concrete_cfg::{Curve, CurveAcir, Fr};
+use crate::ecdsa::EcdsaSecp256k1Gate;
+use crate::embedded_curve::{EmbeddedCurveAddGate, MultiScalarMulGate, PointWitnesses};
+use crate::keccak::KeccakGate;
+use crate::memory::{MemoryAccess, MemoryIndex, MemoryOperation};
+use crate::pedersen::PedersenHashGate;
+use crate::poseidon2::{Poseidon2Gate, PoseidonConfig};
+use crate::schnorr::SchnorrVerifyGate;
+use crate::sha256::Sha256Gate;
+
+// A `ProvingBundle` packages everything a worker needs to produce a proof: the lowered circuit
+// with its witness values, the order of the public inputs and optionally the proving key.
+// It is serialized as a single blob so the worker neither re-reads the program artifact nor
+// re-solves the witness.
+//
+// Layout: the `MAGIC` bytes, the format version, then the bundle contents encoded with
+// arkworks' compressed canonical serialization. The circuit and its gates implement the arkworks
+// traits field by field, see `impl_canonical_struct`.
+
+const MAGIC: &[u8; 4] = b"ACRB";
+
+/// Version of the proving bundle format written by [`ProvingBundle::write`].
+///
+/// Bundles of any other version are rejected by [`ProvingBundle::read`], so this is bumped
+/// whenever the layout of the circuit changes, e.g. when a new kind of gate is serialized.
+/// Version 1 predates the memory, range, black box and return value sections, version 2 wrote
+/// the multi-scalar multiplication points and scalars flattened into witness lists.
+pub const BUNDLE_VERSION: u32 = 3;
+
+#[derive(Debug, Error)]
+pub enum BundleError {
+    #[error("Error: data is not a proving bundle")]
+    InvalidMagic,
+    #[error("Error: unsupported proving bundle version {0}, expected {BUNDLE_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("Error: could not (de)serialize proving bundle: {0}")]
+    Serialization(#[from] SerializationError),
+}
+
+#[derive(Clone)]
+pub struct ProvingBundle {
+    pub circuit: CurveAcir,
+    /// The public input witnesses, in the order the verifier expects their values.
+    /// Any domain separator set on the circuit precedes them.
+    pub public_inputs: Vec<Witness>,
+    pub proving_key: Option<ProvingKey<Curve>>,
+}
+
+impl ProvingBundle {
+    pub fn new(circuit: CurveAcir, proving_key: Option<ProvingKey<Curve>>) -> Self {
+        let public_inputs = circuit.public_inputs.0.iter().copied().collect();
+        ProvingBundle {
+            circuit,
+            public_inputs,
+            proving_key,
+        }
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), BundleError> {
+        writer
+            .write_all(MAGIC)
+            .map_err(SerializationError::IoError)?;
+        BUNDLE_VERSION.serialize_compressed(&mut writer)?;
+
+        self.circuit.serialize_compressed(&mut writer)?;
+        self.public_inputs
+            .serialize_field(&mut writer, Compress::Yes)?;
+        self.proving_key.serialize_compressed(&mut writer)?;
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, BundleError> {
+        let mut magic = [0u8; 4];
+        reader
+            .read_exact(&mut magic)
+            .map_err(SerializationError::IoError)?;
+        if &magic != MAGIC {
+            return Err(BundleError::InvalidMagic);
+        }
+        let version = u32::deserialize_compressed(&mut reader)?;
+        if version != BUNDLE_VERSION {
+            return Err(BundleError::UnsupportedVersion(version));
+        }
+
+        let circuit = CurveAcir::deserialize_compressed(&mut reader)?;
+        let public_inputs =
+            Vec::<Witness>::deserialize_field(&mut reader, Compress::Yes, Validate::Yes)?;
+        let proving_key = Option::<ProvingKey<Curve>>::deserialize_compressed(&mut reader)?;
+        Ok(ProvingBundle {
+            circuit,
+            public_inputs,
+            proving_key,
+        })
+    }
+}
+
+/// Encoding of a value within a bundle.
+///
+/// `Witness`, `BlockId` and `PublicInputs` are foreign types which cannot implement the arkworks
+/// traits, so the fields of the circuit are encoded through this trait instead. It forwards to
+/// [`CanonicalSerialize`] and [`CanonicalDeserialize`] for the types which implement them, and
+/// encodes collections as arkworks does: a `u64` length followed by the elements.
+trait BundleField: Sized {
+    fn serialize_field<W: Write>(
+        &self,
+        writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError>;
+
+    fn field_size(&self, compress: Compress) -> usize;
+
+    fn deserialize_field<R: Read>(
+        reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError>;
+}
+
+macro_rules! impl_forwarded_field {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl BundleField for $ty {
+                fn serialize_field<W: Write>(
+                    &self,
+                    writer: W,
+                    compress: Compress,
+                ) -> Result<(), SerializationError> {
+                    self.serialize_with_mode(writer, compress)
+                }
+
+                fn field_size(&self, compress: Compress) -> usize {
+                    self.serialized_size(compress)
+                }
+
+                fn deserialize_field<R: Read>(
+                    reader: R,
+                    compress: Compress,
+                    validate: Validate,
+                ) -> Result<Self, SerializationError> {
+                    Self::deserialize_with_mode(reader, compress, validate)
+                }
+            }
+        )*
+    };
+}
+
+/// Implements the arkworks traits for a struct by encoding its fields in the order listed.
+///
+/// Deserialization builds the struct from exactly these fields, so a field missing from the
+/// list fails to compile rather than being silently dropped from the bundle.
+macro_rules! impl_canonical_struct {
+    ($name:ident $(<$param:ty>)? { $($field:ident),* $(,)? }) => {
+        impl CanonicalSerialize for $name $(<$param>)? {
+            fn serialize_with_mode<W: Write>(
+                &self,
+                mut writer: W,
+                compress: Compress,
+            ) -> Result<(), SerializationError> {
+                $(self.$field.serialize_field(&mut writer, compress)?;)*
+                Ok(())
+            }
+
+            fn serialized_size(&self, compress: Compress) -> usize {
+                0 $(+ self.$field.field_size(compress))*
+            }
+        }
+
+        impl Valid for $name $(<$param>)? {
+            // Every field is validated as it is deserialized
+            fn check(&self) -> Result<(), SerializationError> {
+                Ok(())
+            }
+        }
+
+        impl CanonicalDeserialize for $name $(<$param>)? {
+            fn deserialize_with_mode<R: Read>(
+                mut reader: R,
+                compress: Compress,
+                validate: Validate,
+            ) -> Result<Self, SerializationError> {
+                Ok($name {
+                    $($field: BundleField::deserialize_field(&mut reader, compress, validate)?,)*
+                })
+            }
+        }
+
+        impl_forwarded_field!($name $(<$param>)?);
+    };
+}
+
+/// Implements [`BundleField`] for a fieldless enum, encoding each variant as a `u8` tag.
+macro_rules! impl_tagged_field {
+    ($name:ident { $($variant:ident = $tag:literal),* $(,)? }) => {
+        impl BundleField for $name {
+            fn serialize_field<W: Write>(
+                &self,
+                writer: W,
+                compress: Compress,
+            ) -> Result<(), SerializationError> {
+                let tag: u8 = match self {
+                    $($name::$variant => $tag,)*
+                };
+                tag.serialize_with_mode(writer, compress)
+            }
+
+            fn field_size(&self, compress: Compress) -> usize {
+                0u8.serialized_size(compress)
+            }
+
+            fn deserialize_field<R: Read>(
+                reader: R,
+                compress: Compress,
+                validate: Validate,
+            ) -> Result<Self, SerializationError> {
+                match u8::deserialize_with_mode(reader, compress, validate)? {
+                    $($tag => Ok($name::$variant),)*
+                    _ => Err(SerializationError::InvalidData),
+                }
+            }
+        }
+    };
+}
+
+/// Implements [`BundleField`] for a foreign newtype by encoding its only field.
+macro_rules! impl_newtype_field {
+    ($($name:ident($inner:ty)),* $(,)?) => {
+        $(
+            impl BundleField for $name {
+                fn serialize_field<W: Write>(
+                    &self,
+                    writer: W,
+                    compress: Compress,
+                ) -> Result<(), SerializationError> {
+                    self.0.serialize_field(writer, compress)
+                }
+
+                fn field_size(&self, compress: Compress) -> usize {
+                    self.0.field_size(compress)
+                }
+
+                fn deserialize_field<R: Read>(
+                    reader: R,
+                    compress: Compress,
+                    validate: Validate,
+                ) -> Result<Self, SerializationError> {
+                    <$inner>::deserialize_field(reader, compress, validate).map($name)
+                }
+            }
+        )*
+    };
+}
+
+impl_forwarded_field!(u8, u32, u64, usize, String, Fr);
+
+impl_newtype_field!(Witness(u32), BlockId(u32), PublicInputs(BTreeSet<Witness>),);
+
+/// Writes the length of a collection followed by its elements.
+fn serialize_elements<'a, T: BundleField + 'a, W: Write>(
+    len: usize,
+    elements: impl Iterator<Item = &'a T>,
+    mut writer: W,
+    compress: Compress,
+) -> Result<(), SerializationError> {
+    (len as u64).serialize_with_mode(&mut writer, compress)?;
+    for element in elements {
+        element.serialize_field(&mut writer, compress)?;
+    }
+    Ok(())
+}
+
+/// Reads the length of a collection followed by its elements.
+fn deserialize_elements<T: BundleField, R: Read>(
+    mut reader: R,
+    compress: Compress,
+    validate: Validate,
+) -> Result<Vec<T>, SerializationError> {
+    let len = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+    (0..len)
+        .map(|_| T::deserialize_field(&mut reader, compress, validate))
+        .collect()
+}
+
+impl<T: BundleField> BundleField for Vec<T> {
+    fn serialize_field<W: Write>(
+        &self,
+        writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        serialize_elements(self.len(), self.iter(), writer, compress)
+    }
+
+    fn field_size(&self, compress: Compress) -> usize {
+        let elements: usize = self
+            .iter()
+            .map(|element| element.field_size(compress))
+            .sum();
+        0u64.serialized_size(compress) + elements
+    }
+
+    fn deserialize_field<R: Read>(
+        reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        deserialize_elements(reader, compress, validate)
+    }
+}
+
+impl<T: BundleField + Ord> BundleField for BTreeSet<T> {
+    fn serialize_field<W: Write>(
+        &self,
+        writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        serialize_elements(self.len(), self.iter(), writer, compress)
+    }
+
+    fn field_size(&self, compress: Compress) -> usize {
+        let elements: usize = self
+            .iter()
+            .map(|element| element.field_size(compress))
+            .sum();
+        0u64.serialized_size(compress) + elements
+    }
+
+    fn deserialize_field<R: Read>(
+        reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        Ok(deserialize_elements(reader, compress, validate)?
+            .into_iter()
+            .collect())
+    }
+}
+
+impl<K: BundleField + Ord, V: BundleField> BundleField for BTreeMap<K, V> {
+    fn serialize_field<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        (self.len() as u64).serialize_with_mode(&mut writer, compress)?;
+        for (key, value) in self {
+            key.serialize_field(&mut writer, compress)?;
+            value.serialize_field(&mut writer, compress)?;
+        }
+        Ok(())
+    }
+
+    fn field_size(&self, compress: Compress) -> usize {
+        let entries: usize = self
+            .iter()
+            .map(|(key, value)| key.field_size(compress) + value.field_size(compress))
+            .sum();
+        0u64.serialized_size(compress) + entries
+    }
+
+    fn deserialize_field<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let len = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+        (0..len)
+            .map(|_| {
+                let key = K::deserialize_field(&mut reader, compress, validate)?;
+                let value = V::deserialize_field(&mut reader, compress, validate)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+}
+
+impl<T: BundleField> BundleField for Option<T> {
+    fn serialize_field<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        self.is_some().serialize_with_mode(&mut writer, compress)?;
+        match self {
+            Some(value) => value.serialize_field(writer, compress),
+            None => Ok(()),
+        }
+    }
+
+    fn field_size(&self, compress: Compress) -> usize {
+        let value = self.as_ref().map_or(0, |value| value.field_size(compress));
+        true.serialized_size(compress) + value
+    }
+
+    fn deserialize_field<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        match bool::deserialize_with_mode(&mut reader, compress, validate)? {
+            true => T::deserialize_field(reader, compress, validate).map(Some),
+            false => Ok(None),
+        }
+    }
+}
+
+impl<A: BundleField, B: BundleField> BundleField for (A, B) {
+    fn serialize_field<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        self.0.serialize_field(&mut writer, compress)?;
+        self.1.serialize_field(writer, compress)
+    }
+
+    fn field_size(&self, compress: Compress) -> usize {
+        self.0.field_size(compress) + self.1.field_size(compress)
+    }
+
+    fn deserialize_field<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let a = A::deserialize_field(&mut reader, compress, validate)?;
+        let b = B::deserialize_field(reader, compress, validate)?;
+        Ok((a, b))
+    }
+}
+
+impl<A: BundleField, B: BundleField, C: BundleField> BundleField for (A, B, C) {
+    fn serialize_field<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        self.0.serialize_field(&mut writer, compress)?;
+        self.1.serialize_field(&mut writer, compress)?;
+        self.2.serialize_field(writer, compress)
+    }
+
+    fn field_size(&self, compress: Compress) -> usize {
+        self.0.field_size(compress) + self.1.field_size(compress) + self.2.field_size(compress)
+    }
+
+    fn deserialize_field<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let a = A::deserialize_field(&mut reader, compress, validate)?;
+        let b = B::deserialize_field(&mut reader, compress, validate)?;
+        let c = C::deserialize_field(reader, compress, validate)?;
+        Ok((a, b, c))
+    }
+}
+
+impl_tagged_field!(CommitmentLayout {
+    WitnessOrder = 0,
+    GateGrouped = 1,
+});
+impl_tagged_field!(MemoryMode {
+    Strict = 0,
+    Lenient = 1,
+});
+impl_tagged_field!(MemoryOperation { Read = 0, Write = 1 });
+impl_tagged_field!(BitwiseOperation { And = 0, Xor = 1 });
+impl_tagged_field!(BigIntOperation {
+    Add = 0,
+    Sub = 1,
+    Mul = 2,
+    Div = 3,
+});
+
+impl BundleField for MemoryIndex<Fr> {
+    fn serialize_field<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        match self {
+            MemoryIndex::Constant(index) => {
+                0u8.serialize_with_mode(&mut writer, compress)?;
+                index.serialize_field(writer, compress)
+            }
+            MemoryIndex::Dynamic(index) => {
+                1u8.serialize_with_mode(&mut writer, compress)?;
+                index.serialize_field(writer, compress)
+            }
+            MemoryIndex::OutOfRange => 2u8.serialize_with_mode(writer, compress),
+        }
+    }
+
+    fn field_size(&self, compress: Compress) -> usize {
+        let index = match self {
+            MemoryIndex::Constant(index) => index.field_size(compress),
+            MemoryIndex::Dynamic(index) => index.field_size(compress),
+            MemoryIndex::OutOfRange => 0,
+        };
+        0u8.serialized_size(compress) + index
+    }
+
+    fn deserialize_field<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        match u8::deserialize_with_mode(&mut reader, compress, validate)? {
+            0 => BundleField::deserialize_field(reader, compress, validate)
+                .map(MemoryIndex::Constant),
+            1 => {
+                BundleField::deserialize_field(reader, compress, validate).map(MemoryIndex::Dynamic)
+            }
+            2 => Ok(MemoryIndex::OutOfRange),
+            _ => Err(SerializationError::InvalidData),
+        }
+    }
+}
+
+impl BundleField for BigIntCall {
+    fn serialize_field<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        match self {
+            BigIntCall::FromLeBytes {
+                inputs,
+                modulus,
+                output,
+            } => {
+                0u8.serialize_with_mode(&mut writer, compress)?;
+                inputs.serialize_field(&mut writer, compress)?;
+                modulus.serialize_field(&mut writer, compress)?;
+                output.serialize_field(writer, compress)
+            }
+            BigIntCall::Operation {
+                operation,
+                lhs,
+                rhs,
+                output,
+            } => {
+                1u8.serialize_with_mode(&mut writer, compress)?;
+                operation.serialize_field(&mut writer, compress)?;
+                lhs.serialize_field(&mut writer, compress)?;
+                rhs.serialize_field(&mut writer, compress)?;
+                output.serialize_field(writer, compress)
+            }
+            BigIntCall::ToLeBytes { input, outputs } => {
+                2u8.serialize_with_mode(&mut writer, compress)?;
+                input.serialize_field(&mut writer, compress)?;
+                outputs.serialize_field(writer, compress)
+            }
+        }
+    }
+
+    fn field_size(&self, compress: Compress) -> usize {
+        let call = match self {
+            BigIntCall::FromLeBytes {
+                inputs,
+                modulus,
+                output,
+            } => {
+                inputs.field_size(compress)
+                    + modulus.field_size(compress)
+                    + output.field_size(compress)
+            }
+            BigIntCall::Operation {
+                operation,
+                lhs,
+                rhs,
+                output,
+            } => {
+                operation.field_size(compress)
+                    + lhs.field_size(compress)
+                    + rhs.field_size(compress)
+                    + output.field_size(compress)
+            }
+            BigIntCall::ToLeBytes { input, outputs } => {
+                input.field_size(compress) + outputs.field_size(compress)
+            }
+        };
+        0u8.serialized_size(compress) + call
+    }
+
+    fn deserialize_field<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        match u8::deserialize_with_mode(&mut reader, compress, validate)? {
+            0 => Ok(BigIntCall::FromLeBytes {
+                inputs: BundleField::deserialize_field(&mut reader, compress, validate)?,
+                modulus: BundleField::deserialize_field(&mut reader, compress, validate)?,
+                output: BundleField::deserialize_field(&mut reader, compress, validate)?,
+            }),
+            1 => Ok(BigIntCall::Operation {
+                operation: BundleField::deserialize_field(&mut reader, compress, validate)?,
+                lhs: BundleField::deserialize_field(&mut reader, compress, validate)?,
+                rhs: BundleField::deserialize_field(&mut reader, compress, validate)?,
+                output: BundleField::deserialize_field(&mut reader, compress, validate)?,
+            }),
+            2 => Ok(BigIntCall::ToLeBytes {
+                input: BundleField::deserialize_field(&mut reader, compress, validate)?,
+                outputs: BundleField::deserialize_field(&mut reader, compress, validate)?,
+            }),
+            _ => Err(SerializationError::InvalidData),
+        }
+    }
+}
+
+impl_canonical_struct!(AcirCircuit<Fr> {
+    gates,
+    public_inputs,
+    values,
+    layout,
+    domain,
+    memory_blocks,
+    private_parameters,
+    return_values,
+    memory_ops,
+    memory_mode,
+    assert_messages,
+    range_checks,
+    bitwise_gates,
+    poseidon2_gates,
+    poseidon_config,
+    keccak_gates,
+    embedded_curve_adds,
+    multi_scalar_muls,
+    sha256_gates,
+    ecdsa_secp256k1_gates,
+    blake2s_gates,
+    pedersen_hash_gates,
+    schnorr_verify_gates,
+    aes128_encrypt_gates,
+    bigint_gates,
+});
+impl_canonical_struct!(AcirArithGate<Fr> {
+    mul_terms,
+    add_terms,
+    constant_term,
+    source_opcode,
+});
+impl_canonical_struct!(MemoryAccess<Fr> {
+    block_id,
+    operation,
+    index,
+    value,
+    predicate,
+    source_opcode,
+});
+impl_canonical_struct!(PoseidonConfig<Fr> {
+    full_rounds,
+    partial_rounds,
+    alpha,
+    round_constants,
+    internal_diagonal,
+});
+impl_canonical_struct!(BitwiseGate {
+    operation,
+    lhs,
+    lhs_bits,
+    rhs,
+    rhs_bits,
+    output,
+    source_opcode,
+});
+impl_canonical_struct!(Poseidon2Gate {
+    inputs,
+    outputs,
+    source_opcode,
+});
+impl_canonical_struct!(KeccakGate {
+    inputs,
+    outputs,
+    source_opcode,
+});
+impl_canonical_struct!(PointWitnesses { x, y, is_infinite });
+impl_canonical_struct!(EmbeddedCurveAddGate {
+    lhs,
+    rhs,
+    output,
+    source_opcode,
+});
+impl_canonical_struct!(MultiScalarMulGate {
+    points,
+    scalars,
+    output,
+    source_opcode,
+});
+impl_canonical_struct!(Sha256Gate {
+    inputs,
+    hash_values,
+    outputs,
+    source_opcode,
+});
+impl_canonical_struct!(EcdsaSecp256k1Gate {
+    public_key_x,
+    public_key_y,
+    signature,
+    hashed_message,
+    output,
+    source_opcode,
+});
+impl_canonical_struct!(Blake2sGate {
+    inputs,
+    outputs,
+    source_opcode,
+});
+impl_canonical_struct!(PedersenHashGate {
+    inputs,
+    domain_separator,
+    output,
+    source_opcode,
+});
+impl_canonical_struct!(SchnorrVerifyGate {
+    public_key_x,
+    public_key_y,
+    signature,
+    message,
+    output,
+    source_opcode,
+});
+impl_canonical_struct!(Aes128EncryptGate {
+    inputs,
+    iv,
+    key,
+    outputs,
+    source_opcode,
+});
+impl_canonical_struct!(BigIntGate {
+    call,
+    source_opcode,
+});
+
+#[cfg(test)]
+mod test {
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::groth16::{prove, setup, verify};
+    use crate::test::{circuit_with_gates, test_circuit, test_circuit_witness_map};
+
+    #[test]
+    fn bundle_round_trip_proves() {
+        let circuit = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        let mut rng = StdRng::seed_from_u64(0);
+        let (pk, vk) = setup(&circuit, &mut rng).unwrap();
+
+        let mut bytes = Vec::new();
+        ProvingBundle::new(circuit, Some(pk))
+            .write(&mut bytes)
+            .unwrap();
+        let bundle = ProvingBundle::read(bytes.as_slice()).unwrap();
+
+        let public_inputs: Vec<Fr> = bundle
+            .public_inputs
+            .iter()
+            .map(|witness| bundle.circuit.values[witness])
+            .collect();
+        let pk = bundle.proving_key.as_ref().unwrap();
+        let proof = prove(pk, &bundle.circuit, &mut rng).unwrap();
+        assert!(verify(&vk, &public_inputs, &proof).unwrap());
+    }
+
+    #[test]
+    fn circuit_round_trip_keeps_every_gate() {
+        let w = Witness;
+        let ws = |indices: &[u32]| indices.iter().copied().map(Witness).collect::<Vec<_>>();
+        let fr = |value: u64| Fr::from(value);
+        let point = |index: u32| PointWitnesses {
+            x: w(index),
+            y: w(index + 1),
+            is_infinite: w(index + 2),
+        };
+        let gate = AcirArithGate {
+            mul_terms: vec![(fr(2), w(0), w(1))],
+            add_terms: vec![(fr(3), w(2))],
+            constant_term: fr(4),
+            source_opcode: 0,
+        };
+
+        let mut circuit = circuit_with_gates(vec![gate.clone()], &[w(0)], &[(w(0), 5), (w(1), 6)]);
+        circuit.layout = CommitmentLayout::GateGrouped;
+        circuit.domain = vec![fr(7)];
+        circuit.memory_blocks = [(BlockId(0), ws(&[1, 2]))].into();
+        circuit.private_parameters = [w(1)].into();
+        circuit.return_values = [w(2)].into();
+        circuit.memory_ops = vec![
+            MemoryAccess {
+                block_id: BlockId(0),
+                operation: MemoryOperation::Write,
+                index: MemoryIndex::Dynamic(gate.clone()),
+                value: gate.clone(),
+                predicate: Some(gate.clone()),
+                source_opcode: 1,
+            },
+            MemoryAccess {
+                block_id: BlockId(0),
+                operation: MemoryOperation::Read,
+                index: MemoryIndex::Constant(1),
+                value: gate.clone(),
+                predicate: None,
+                source_opcode: 2,
+            },
+            MemoryAccess {
+                block_id: BlockId(0),
+                operation: MemoryOperation::Read,
+                index: MemoryIndex::OutOfRange,
+                value: gate,
+                predicate: None,
+                source_opcode: 3,
+            },
+        ];
+        circuit.memory_mode = MemoryMode::Lenient;
+        circuit.assert_messages = [(0, "message".to_string())].into();
+        circuit.range_checks = [(w(1), 8)].into();
+        circuit.bitwise_gates = vec![BitwiseGate {
+            operation: BitwiseOperation::Xor,
+            lhs: w(1),
+            lhs_bits: 8,
+            rhs: w(2),
+            rhs_bits: 16,
+            output: w(3),
+            source_opcode: 4,
+        }];
+        circuit.poseidon2_gates = vec![Poseidon2Gate {
+            inputs: ws(&[1, 2]),
+            outputs: ws(&[3, 4]),
+            source_opcode: 5,
+        }];
+        circuit.poseidon_config = Some(PoseidonConfig {
+            full_rounds: 8,
+            partial_rounds: 56,
+            alpha: 5,
+            round_constants: vec![vec![fr(1), fr(2)], vec![fr(3)]],
+            internal_diagonal: vec![fr(4)],
+        });
+        circuit.keccak_gates = vec![KeccakGate {
+            inputs: ws(&[1, 2]),
+            outputs: ws(&[3]),
+            source_opcode: 6,
+        }];
+        circuit.embedded_curve_adds = vec![EmbeddedCurveAddGate {
+            lhs: point(1),
+            rhs: point(4),
+            output: point(7),
+            source_opcode: 7,
+        }];
+        circuit.multi_scalar_muls = vec![MultiScalarMulGate {
+            points: vec![point(1), point(4)],
+            scalars: vec![(w(7), w(8)), (w(9), w(10))],
+            output: point(11),
+            source_opcode: 8,
+        }];
+        circuit.sha256_gates = vec![Sha256Gate {
+            inputs: ws(&[1, 2]),
+            hash_values: ws(&[3, 4]),
+            outputs: ws(&[5, 6]),
+            source_opcode: 9,
+        }];
+        circuit.ecdsa_secp256k1_gates = vec![EcdsaSecp256k1Gate {
+            public_key_x: ws(&[1]),
+            public_key_y: ws(&[2]),
+            signature: ws(&[3, 4]),
+            hashed_message: ws(&[5]),
+            output: w(6),
+            source_opcode: 10,
+        }];
+        circuit.blake2s_gates = vec![Blake2sGate {
+            inputs: ws(&[1, 2]),
+            outputs: ws(&[3]),
+            source_opcode: 11,
+        }];
+        circuit.pedersen_hash_gates = vec![PedersenHashGate {
+            inputs: ws(&[1, 2]),
+            domain_separator: 3,
+            output: w(4),
+            source_opcode: 12,
+        }];
+        circuit.schnorr_verify_gates = vec![SchnorrVerifyGate {
+            public_key_x: w(1),
+            public_key_y: w(2),
+            signature: ws(&[3, 4]),
+            message: ws(&[5]),
+            output: w(6),
+            source_opcode: 13,
+        }];
+        circuit.aes128_encrypt_gates = vec![Aes128EncryptGate {
+            inputs: ws(&[1]),
+            iv: ws(&[2]),
+            key: ws(&[3]),
+            outputs: ws(&[4]),
+            source_opcode: 14,
+        }];
+        circuit.bigint_gates = vec![
+            BigIntGate {
+                call: BigIntCall::FromLeBytes {
+                    inputs: ws(&[1, 2]),
+                    modulus: vec![7],
+                    output: 0,
+                },
+                source_opcode: 15,
+            },
+            BigIntGate {
+                call: BigIntCall::Operation {
+                    operation: BigIntOperation::Div,
+                    lhs: 0,
+                    rhs: 0,
+                    output: 1,
+                },
+                source_opcode: 16,
+            },
+            BigIntGate {
+                call: BigIntCall::ToLeBytes {
+                    input: 1,
+                    outputs: ws(&[3, 4]),
+                },
+                source_opcode: 17,
+            },
+        ];
+
+        let mut bytes = Vec::new();
+        circuit.serialize_compressed(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), circuit.compressed_size());
+        let read = CurveAcir::deserialize_compressed(bytes.as_slice()).unwrap();
+
+        // destructured without `..`, so that a field added to the circuit is compared here too
+        let AcirCircuit {
+            gates,
+            public_inputs,
+            values,
+            layout,
+            domain,
+            memory_blocks,
+            private_parameters,
+            return_values,
+            memory_ops,
+            memory_mode,
+            assert_messages,
+            range_checks,
+            bitwise_gates,
+            poseidon2_gates,
+            poseidon_config,
+            keccak_gates,
+            embedded_curve_adds,
+            multi_scalar_muls,
+            sha256_gates,
+            ecdsa_secp256k1_gates,
+            blake2s_gates,
+            pedersen_hash_gates,
+            schnorr_verify_gates,
+            aes128_encrypt_gates,
+            bigint_gates,
+        } = circuit;
+        assert_eq!(read.gates, gates);
+        assert_eq!(read.public_inputs, public_inputs);
+        assert_eq!(read.values, values);
+        assert_eq!(read.layout, layout);
+        assert_eq!(read.domain, domain);
+        assert_eq!(read.memory_blocks, memory_blocks);
+        assert_eq!(read.private_parameters, private_parameters);
+        assert_eq!(read.return_values, return_values);
+        assert_eq!(read.memory_ops, memory_ops);
+        assert_eq!(read.memory_mode, memory_mode);
+        assert_eq!(read.assert_messages, assert_messages);
+        assert_eq!(read.range_checks, range_checks);
+        assert_eq!(read.bitwise_gates, bitwise_gates);
+        assert_eq!(read.poseidon2_gates, poseidon2_gates);
+        assert_eq!(read.poseidon_config, poseidon_config);
+        assert_eq!(read.keccak_gates, keccak_gates);
+        assert_eq!(read.embedded_curve_adds, embedded_curve_adds);
+        assert_eq!(read.multi_scalar_muls, multi_scalar_muls);
+        assert_eq!(read.sha256_gates, sha256_gates);
+        assert_eq!(read.ecdsa_secp256k1_gates, ecdsa_secp256k1_gates);
+        assert_eq!(read.blake2s_gates, blake2s_gates);
+        assert_eq!(read.pedersen_hash_gates, pedersen_hash_gates);
+        assert_eq!(read.schnorr_verify_gates, schnorr_verify_gates);
+        assert_eq!(read.aes128_encrypt_gates, aes128_encrypt_gates);
+        assert_eq!(read.bigint_gates, bigint_gates);
+    }
+
+    #[test]
+    fn rejects_bundles_of_another_version() {
+        let circuit = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        let mut bytes = Vec::new();
+        ProvingBundle::new(circuit, None).write(&mut bytes).unwrap();
+
+        for version in [BUNDLE_VERSION - 1, BUNDLE_VERSION + 1] {
+            // the version follows the magic bytes
+            bytes[4..8].copy_from_slice(&version.to_le_bytes());
+            let err = ProvingBundle::read(bytes.as_slice()).err().unwrap();
+            assert!(matches!(err, BundleError::UnsupportedVersion(found) if found == version));
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "Error: unsupported proving bundle version {version}, expected {BUNDLE_VERSION}"
+                )
+            );
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::VecDeque;
+
+use acvm::acir::acir_field::GenericFieldElement;
+use acvm::acir::native_types::WitnessMap;
+use ark_ff::PrimeField;
+
+use crate::bridge::AcirCircuit;
+use crate::ProgramArtifactGeneric;
+
+// A least-recently-used cache of lowered circuits, keyed by the `hash` of the program they were
+// lowered from.
+//
+// Lowering only depends on the program, so a service proving the same program repeatedly can
+// lower it once and attach each request's witness values to a copy of the cached structure.
+
+pub struct CircuitCache<F: PrimeField> {
+    capacity: usize,
+    /// Cached structures, most recently used first.
+    entries: VecDeque<(u64, AcirCircuit<F>)>,
+    lowerings: usize,
+}
+
+impl<F: PrimeField> CircuitCache<F> {
+    /// Creates a cache holding the structures of at most `capacity` programs.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "cache capacity must be non-zero");
+        CircuitCache {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+            lowerings: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The number of programs lowered so far, i.e. the number of cache misses.
+    pub fn lowerings(&self) -> usize {
+        self.lowerings
+    }
+
+    /// Lowers the main function of `program`, reusing the cached structure if a program with the
+    /// same hash was lowered before, and attaches the values of `witness_map`.
+    pub fn get_or_lower(
+        &mut self,
+        program: &ProgramArtifactGeneric<F>,
+        witness_map: WitnessMap<GenericFieldElement<F>>,
+    ) -> AcirCircuit<F> {
+        self.get_or_lower_with(
+            program.hash,
+            || AcirCircuit::from((&program.bytecode.functions[0], WitnessMap::new())),
+            witness_map,
+        )
+    }
+
+    /// Like [`CircuitCache::get_or_lower`], but calls `lower` to produce the structure of the
+    /// program identified by `hash` on a cache miss.
+    ///
+    /// The values `lower` assigns are discarded; only its gates and metadata are cached.
+    pub fn get_or_lower_with(
+        &mut self,
+        hash: u64,
+        lower: impl FnOnce() -> AcirCircuit<F>,
+        witness_map: WitnessMap<GenericFieldElement<F>>,
+    ) -> AcirCircuit<F> {
+        let entry = match self.entries.iter().position(|(key, _)| *key == hash) {
+            Some(position) => self.entries.remove(position).unwrap(),
+            None => {
+                self.lowerings += 1;
+                if self.entries.len() == self.capacity {
+                    self.entries.pop_back();
+                }
+                (hash, lower())
+            }
+        };
+        let circuit = entry.1.clone().with_witness_map(witness_map);
+        self.entries.push_front(entry);
+        circuit
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use acvm::acir::native_types::Witness;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+
+    use super::*;
+    use crate::test::{test_circuit, test_circuit_witness_map, witness_map};
+    use crate::{CurveAcir, Fr};
+
+    #[test]
+    fn repeated_programs_are_lowered_once() {
+        let circuit = test_circuit();
+        let lower = || CurveAcir::from(&circuit);
+        let mut cache = CircuitCache::<Fr>::new(1);
+
+        let first = cache.get_or_lower_with(7, lower, test_circuit_witness_map());
+        // inputs [1, 1] and [6, 7]
+        let second_values = witness_map(&[(0, 1), (1, 1), (2, 6), (3, 7), (4, 6), (5, 7)]);
+        let second = cache.get_or_lower_with(7, lower, second_values);
+        assert_eq!(cache.lowerings(), 1);
+        assert_eq!(first.gates, second.gates);
+        assert_eq!(second.values[&Witness(5)], Fr::from(7u64));
+
+        let cs = ConstraintSystem::new_ref();
+        second.generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        // a different program evicts the least recently used one
+        cache.get_or_lower_with(8, lower, test_circuit_witness_map());
+        cache.get_or_lower_with(7, lower, test_circuit_witness_map());
+        assert_eq!(cache.lowerings(), 3);
+        assert_eq!(cache.len(), 1);
+    }
+}
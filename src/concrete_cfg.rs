@@ -1,27 +1,67 @@
 use crate::bridge::{AcirArithGate, AcirCircuit};
-use acvm::FieldElement;
+use crate::field::{field_from_be_bytes, FieldConversionError, FieldMismatch};
+use acvm::acir::acir_field::GenericFieldElement;
+use ark_ff::{BigInteger, PrimeField};
 
-pub type CurveAcir = AcirCircuit<Fr>;
+/// A lowered circuit, over the scalar field of the configured curve unless `F` says otherwise.
+///
+/// This is an [`AcirCircuit`], so it is synthesized by the same `ConstraintSynthesizer`
+/// implementation whatever the field.
+pub type CurveAcir<F = Fr> = AcirCircuit<F>;
 pub(crate) type CurveAcirArithGate = AcirArithGate<Fr>;
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "bn254")] {
         pub use ark_bn254::{Bn254 as Curve, Fr};
+        /// The scalar field of Grumpkin, which is BN254's base field. Circuits lowered as
+        /// `CurveAcir<GrumpkinFr>` are constrained over it, for the other half of the
+        /// BN254/Grumpkin cycle.
+        pub use ark_bn254::Fq as GrumpkinFr;
 
-        // Converts a FieldElement to a Fr
-        // noir_field uses arkworks for bn254
-        pub fn from_fe(fe : FieldElement) -> Fr {
-            fe.into_repr()
-        }
+        /// The coefficients `(a, b)` of the curve `y^2 = x^3 + a x + b` embedded in the scalar
+        /// field, Grumpkin.
+        pub const EMBEDDED_CURVE_COEFFICIENTS: Option<(i64, i64)> = Some((0, -17));
     } else if #[cfg(feature = "bls12_381")] {
         pub use ark_bls12_381::{Bls12_381 as Curve, Fr};
 
-        // Converts a FieldElement to a Fr
-        // noir_field uses arkworks for bls12_381
-        pub fn from_fe(fe : FieldElement) -> Fr {
-            fe.into_repr()
-        }
+        /// The curve embedded in the scalar field, Jubjub, is a twisted Edwards curve, so
+        /// `BlackBoxFuncCall::EmbeddedCurveAdd` cannot be lowered.
+        pub const EMBEDDED_CURVE_COEFFICIENTS: Option<(i64, i64)> = None;
     } else {
         compile_error!("please specify a field to compile with");
     }
 }
+
+// Converts a FieldElement to its arkworks field element
+// noir_field uses arkworks for every supported field
+pub fn from_fe<F: PrimeField>(fe: GenericFieldElement<F>) -> F {
+    fe.into_repr()
+}
+
+/// Converts a field element of the field `S` to the field `F`, failing unless both fields have
+/// the same modulus.
+///
+/// Converting through the integer representation would silently reduce values not smaller than
+/// `F`'s modulus, so a circuit compiled over another field is rejected instead.
+pub fn try_from_fe<S: PrimeField, F: PrimeField>(
+    fe: GenericFieldElement<S>,
+) -> Result<F, FieldMismatch> {
+    if S::MODULUS.to_bytes_le() != F::MODULUS.to_bytes_le() {
+        return Err(FieldMismatch);
+    }
+    Ok(F::from_le_bytes_mod_order(
+        &fe.into_repr().into_bigint().to_bytes_le(),
+    ))
+}
+
+/// Converts a field element of any field `S` to the native field [`Fr`], failing unless its value
+/// is smaller than `Fr`'s modulus.
+///
+/// Unlike [`try_from_fe`], an element of a larger field is accepted when it is in range, so
+/// values computed over another field convert exactly or not at all, never wrapping around.
+pub fn try_into_native_field<S: PrimeField>(
+    fe: GenericFieldElement<S>,
+) -> Result<Fr, FieldConversionError> {
+    field_from_be_bytes(&fe.into_repr().into_bigint().to_bytes_be())
+        .map_err(|_| FieldConversionError)
+}
@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use acvm::acir::native_types::Witness;
+use ark_ff::Field;
+use noirc_abi::Abi;
+
+use crate::abi::param_witnesses;
+use crate::bridge::AcirCircuit;
+
+// Graphviz export of a lowered circuit, for debugging.
+//
+// The graph is bipartite: every witness is an ellipse (a double circle for public inputs) and
+// every gate a box, with an edge from each gate to the witnesses it refers to.
+
+impl<F: Field> AcirCircuit<F> {
+    /// Renders the circuit as an undirected Graphviz graph.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with_names(&BTreeMap::new())
+    }
+
+    /// Renders the circuit like [`AcirCircuit::to_dot`], labelling the witnesses of the
+    /// program's parameters with their ABI names.
+    pub fn to_dot_with_abi(&self, abi: &Abi) -> String {
+        let mut names = BTreeMap::new();
+        for (name, witnesses) in param_witnesses(abi) {
+            if let [witness] = witnesses.as_slice() {
+                names.insert(*witness, name);
+                continue;
+            }
+            for (index, witness) in witnesses.into_iter().enumerate() {
+                names.insert(witness, format!("{name}[{index}]"));
+            }
+        }
+        self.to_dot_with_names(&names)
+    }
+
+    fn to_dot_with_names(&self, names: &BTreeMap<Witness, String>) -> String {
+        let mut dot = String::from("graph circuit {\n");
+
+        for witness in self.values.keys() {
+            let shape = if self.public_inputs.contains(witness.0 as usize) {
+                "doublecircle"
+            } else {
+                "ellipse"
+            };
+            let label = match names.get(witness) {
+                Some(name) => format!("{name}\\n_{}", witness.0),
+                None => format!("_{}", witness.0),
+            };
+            writeln!(dot, "  w{} [shape={shape}, label=\"{label}\"];", witness.0).unwrap();
+        }
+
+        for (index, gate) in self.gates.iter().enumerate() {
+            writeln!(dot, "  g{index} [shape=box, label=\"gate {index}\"];").unwrap();
+            let mut witnesses: Vec<Witness> = gate.witnesses().collect();
+            witnesses.sort();
+            witnesses.dedup();
+            for witness in witnesses {
+                writeln!(dot, "  g{index} -- w{};", witness.0).unwrap();
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{test_circuit, test_circuit_witness_map};
+    use crate::CurveAcir;
+
+    #[test]
+    fn dot_has_a_node_per_witness_and_edges_per_gate() {
+        let circuit = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        let dot = circuit.to_dot();
+
+        for witness in 0..6 {
+            assert!(dot.contains(&format!("  w{witness} [")));
+        }
+        assert!(dot.contains("  w0 [shape=doublecircle"));
+        assert!(dot.contains("  w2 [shape=ellipse"));
+        // w4 = w0 * w2 and w5 = w1 * w3
+        for edge in [
+            "g0 -- w0", "g0 -- w2", "g0 -- w4", "g1 -- w1", "g1 -- w3", "g1 -- w5",
+        ] {
+            assert!(dot.contains(edge));
+        }
+        assert_eq!(dot.matches(" -- ").count(), 6);
+    }
+}
@@ -0,0 +1,1272 @@
+use std::collections::BTreeMap;
+
+use acvm::acir::native_types::Witness;
+use ark_ff::PrimeField;
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError, Variable};
+
+use crate::R1csSize;
+
+// Lowering of `BlackBoxFuncCall::EcdsaSecp256k1` to R1CS.
+//
+// The public key coordinates, the signature `(r, s)` and the hashed message arrive as big-endian
+// bytes, which are range-constrained and packed into four 64-bit limbs per 256-bit integer.
+// Arithmetic modulo secp256k1's base field prime `p` and group order `n` is emulated on the
+// limbs: a congruence such as `a * b == r (mod p)` is checked as the integer identity
+// `a * b + offset == q * p + r` between limb polynomials, where the limbs of each product are
+// interpolated from 7 evaluations and the carries between 128-bit halves are range-constrained.
+//
+// The output is one exactly when acvm's solver accepts the signature: the key is a point on the
+// curve with canonical coordinates, `1 <= r < n`, `1 <= s <= (n - 1) / 2`, and the x coordinate
+// of `u1 * G + u2 * Q` reduced modulo `n` is `r`. An invalid key or `s` is replaced by a valid
+// one before the scalar multiplication, so that the circuit stays satisfiable with an output of
+// zero.
+//
+// `u1 * G + u2 * Q` is computed with Shamir's trick on affine points, starting from a point `A`
+// of unknown discrete logarithm so that no intermediate sum hits an exceptional case of the
+// chord and tangent formulas, and adding `-2^256 * A` at the end. Every addition constrains the
+// difference of the x coordinates to be invertible, so an exceptional case leaves the circuit
+// unsatisfiable rather than the slope unconstrained. A key of `G` or `-G` makes the table point
+// `G + Q` exceptional, so signatures under it cannot be proven.
+//
+// The constraints are only generated with the `ecdsa` feature: a verification lowers to
+// 1536072 constraints.
+
+/// A single `BlackBoxFuncCall::EcdsaSecp256k1`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct EcdsaSecp256k1Gate {
+    pub(crate) public_key_x: Vec<Witness>,
+    pub(crate) public_key_y: Vec<Witness>,
+    pub(crate) signature: Vec<Witness>,
+    pub(crate) hashed_message: Vec<Witness>,
+    pub(crate) output: Witness,
+    pub(crate) source_opcode: usize,
+}
+
+impl EcdsaSecp256k1Gate {
+    pub(crate) fn witnesses(&self) -> impl Iterator<Item = Witness> + '_ {
+        self.public_key_x
+            .iter()
+            .chain(&self.public_key_y)
+            .chain(&self.signature)
+            .chain(&self.hashed_message)
+            .chain(std::iter::once(&self.output))
+            .copied()
+    }
+
+    pub(crate) fn witnesses_mut(&mut self) -> impl Iterator<Item = &mut Witness> {
+        self.public_key_x
+            .iter_mut()
+            .chain(self.public_key_y.iter_mut())
+            .chain(self.signature.iter_mut())
+            .chain(self.hashed_message.iter_mut())
+            .chain(std::iter::once(&mut self.output))
+    }
+}
+
+/// Counts the constraints and witnesses [`enforce_ecdsa_secp256k1_gates`] allocates, none
+/// without the `ecdsa` feature.
+#[cfg_attr(not(feature = "ecdsa"), allow(unused_variables))]
+pub(crate) fn r1cs_size(gates: &[EcdsaSecp256k1Gate]) -> R1csSize {
+    #[cfg(feature = "ecdsa")]
+    return verification::r1cs_size(gates);
+
+    #[cfg(not(feature = "ecdsa"))]
+    R1csSize::default()
+}
+
+/// Enforces every signature verification. Without the `ecdsa` feature this fails with
+/// `SynthesisError::Unsatisfiable` if there are any.
+#[cfg_attr(not(feature = "ecdsa"), allow(unused_variables))]
+pub(crate) fn enforce_ecdsa_secp256k1_gates<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    gates: &[EcdsaSecp256k1Gate],
+    variables: &BTreeMap<Witness, Variable>,
+) -> Result<(), SynthesisError> {
+    #[cfg(feature = "ecdsa")]
+    return verification::enforce_ecdsa_secp256k1_gates(cs, gates, variables);
+
+    #[cfg(not(feature = "ecdsa"))]
+    match gates.is_empty() {
+        true => Ok(()),
+        false => Err(SynthesisError::Unsatisfiable),
+    }
+}
+
+#[cfg(feature = "ecdsa")]
+mod verification {
+    use std::collections::BTreeMap;
+
+    use acvm::acir::native_types::Witness;
+    use ark_ff::{BigInteger, PrimeField};
+    use ark_relations::{
+        lc,
+        r1cs::{ConstraintSystemRef, LinearCombination, SynthesisError, Variable},
+    };
+    use num_bigint::{BigInt, BigUint, Sign};
+    use sha2::{Digest, Sha256};
+
+    use super::EcdsaSecp256k1Gate;
+    use crate::gadgets::enforce_bit_decomposition;
+    use crate::R1csSize;
+
+    const LIMB_BITS: u32 = 64;
+    const NUM_LIMBS: usize = 4;
+    /// The width of the top limb of a quotient, which may exceed `2^256`.
+    const QUOTIENT_TOP_BITS: u32 = 68;
+    /// The width of a carry between 128-bit halves, offset to be non-negative.
+    const CARRY_BITS: u32 = 75;
+    /// The offset added to each congruence exceeds `2^OFFSET_BITS`, and so any right-hand side.
+    const OFFSET_BITS: usize = 515;
+    /// The limbs of an identity: those of the offset, which are the most.
+    const IDENTITY_LIMBS: usize = 9;
+
+    /// secp256k1's parameters, and the points the scalar multiplication is offset by.
+    pub(super) struct Secp256k1 {
+        pub(super) p: BigUint,
+        pub(super) n: BigUint,
+        pub(super) generator: (BigUint, BigUint),
+        /// `A`, the starting point of the scalar multiplication.
+        offset: (BigUint, BigUint),
+        /// `2^256 * A`, which the scalar multiplication ends on when `u1 * G + u2 * Q` is zero.
+        offset_end: (BigUint, BigUint),
+    }
+
+    impl Secp256k1 {
+        pub(super) fn new() -> Self {
+            let hex = |digits: &str| BigUint::parse_bytes(digits.as_bytes(), 16).unwrap();
+            let mut curve = Secp256k1 {
+                p: hex("fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f"),
+                n: hex("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141"),
+                generator: (
+                    hex("79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"),
+                    hex("483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8"),
+                ),
+                offset: Default::default(),
+                offset_end: Default::default(),
+            };
+
+            // Hash to the curve by incrementing x until x^3 + 7 is a square, whose root is its
+            // (p + 1) / 4-th power as p = 3 (mod 4)
+            let digest = Sha256::digest(b"arkworks_backend secp256k1 offset");
+            let mut x = BigUint::from_bytes_be(&digest) % &curve.p;
+            curve.offset = loop {
+                let square = (x.modpow(&BigUint::from(3u8), &curve.p) + 7u8) % &curve.p;
+                let y = square.modpow(&((&curve.p + 1u8) >> 2), &curve.p);
+                if (&y * &y) % &curve.p == square {
+                    break (x, y);
+                }
+                x += 1u8;
+            };
+            let mut end = Some(curve.offset.clone());
+            for _ in 0..256 {
+                end = curve.add(&end, &end);
+            }
+            curve.offset_end = end.expect("A has order n");
+            curve
+        }
+
+        /// Adds two points, `None` being the point at infinity.
+        pub(super) fn add(
+            &self,
+            lhs: &Option<(BigUint, BigUint)>,
+            rhs: &Option<(BigUint, BigUint)>,
+        ) -> Option<(BigUint, BigUint)> {
+            let p = &self.p;
+            let ((x1, y1), (x2, y2)) = match (lhs, rhs) {
+                (None, point) | (point, None) => return point.clone(),
+                (Some(lhs), Some(rhs)) => (lhs, rhs),
+            };
+            let slope = if x1 != x2 {
+                sub_mod(y2, y1, p) * inverse(&sub_mod(x2, x1, p), p) % p
+            } else if y1 == y2 && *y1 != BigUint::default() {
+                BigUint::from(3u8) * x1 * x1 * inverse(&(BigUint::from(2u8) * y1), p) % p
+            } else {
+                return None;
+            };
+            let x3 = sub_mod(&(&slope * &slope), &(x1 + x2), p);
+            let y3 = sub_mod(&(&slope * sub_mod(x1, &x3, p)), y1, p);
+            Some((x3, y3))
+        }
+
+        /// Multiplies `point` by `scalar` by double-and-add.
+        pub(super) fn mul(
+            &self,
+            scalar: &BigUint,
+            point: &(BigUint, BigUint),
+        ) -> Option<(BigUint, BigUint)> {
+            let mut product = None;
+            for i in (0..scalar.bits()).rev() {
+                product = self.add(&product, &product);
+                if scalar.bit(i) {
+                    product = self.add(&product, &Some(point.clone()));
+                }
+            }
+            product
+        }
+    }
+
+    fn sub_mod(lhs: &BigUint, rhs: &BigUint, modulus: &BigUint) -> BigUint {
+        (lhs % modulus + modulus - rhs % modulus) % modulus
+    }
+
+    /// The inverse of `value` modulo the prime `modulus`, or zero if there is none.
+    pub(super) fn inverse(value: &BigUint, modulus: &BigUint) -> BigUint {
+        value.modpow(&(modulus - 2u8), modulus)
+    }
+
+    fn to_field<F: PrimeField>(value: &BigInt) -> F {
+        let magnitude = F::from_le_bytes_mod_order(&value.magnitude().to_bytes_le());
+        match value.sign() {
+            Sign::Minus => -magnitude,
+            _ => magnitude,
+        }
+    }
+
+    fn alloc<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        value: Option<BigInt>,
+    ) -> Result<Variable, SynthesisError> {
+        cs.new_witness_variable(|| {
+            value
+                .as_ref()
+                .map(to_field)
+                .ok_or(SynthesisError::AssignmentMissing)
+        })
+    }
+
+    /// A boolean, with its value unless in setup mode.
+    #[derive(Clone)]
+    struct Bit<F: PrimeField> {
+        lc: LinearCombination<F>,
+        value: Option<bool>,
+    }
+
+    impl<F: PrimeField> Bit<F> {
+        fn from_variable(cs: &ConstraintSystemRef<F>, variable: Variable) -> Self {
+            Bit {
+                lc: lc!() + variable,
+                value: cs.assigned_value(variable).map(|value| value.is_one()),
+            }
+        }
+
+        fn not(&self) -> Self {
+            Bit {
+                lc: lc!() + Variable::One - &self.lc,
+                value: self.value.map(|value| !value),
+            }
+        }
+
+        fn and(&self, cs: &ConstraintSystemRef<F>, other: &Self) -> Result<Self, SynthesisError> {
+            let value = self.value.zip(other.value).map(|(lhs, rhs)| lhs && rhs);
+            let both = alloc(cs, value.map(|value| BigInt::from(value as u8)))?;
+            cs.enforce_constraint(self.lc.clone(), other.lc.clone(), lc!() + both)?;
+            Ok(Bit {
+                lc: lc!() + both,
+                value,
+            })
+        }
+
+        fn or(&self, cs: &ConstraintSystemRef<F>, other: &Self) -> Result<Self, SynthesisError> {
+            Ok(self.not().and(cs, &other.not())?.not())
+        }
+    }
+
+    /// Returns whether `value` is zero.
+    fn is_zero<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        lc: LinearCombination<F>,
+        value: Option<BigInt>,
+    ) -> Result<Bit<F>, SynthesisError> {
+        let value = value.as_ref().map(to_field::<F>);
+        let inverse = cs.new_witness_variable(|| {
+            let value = value.ok_or(SynthesisError::AssignmentMissing)?;
+            Ok(value.inverse().unwrap_or_default())
+        })?;
+        let is_zero = value.map(|value| value.is_zero());
+        let flag = alloc(cs, is_zero.map(|is_zero| BigInt::from(is_zero as u8)))?;
+        // value * inverse == 1 - flag, so the flag is set if the value has no inverse
+        cs.enforce_constraint(lc.clone(), lc!() + inverse, lc!() + Variable::One - flag)?;
+        // value * flag == 0, so the flag is clear if the value is not zero
+        cs.enforce_constraint(lc, lc!() + flag, lc!())?;
+        Ok(Bit {
+            lc: lc!() + flag,
+            value: is_zero,
+        })
+    }
+
+    /// A non-negative integer as little-endian 64-bit limbs, with its value unless in setup
+    /// mode. Only the top limb of a quotient may be wider.
+    #[derive(Clone)]
+    struct Int<F: PrimeField> {
+        limbs: Vec<LinearCombination<F>>,
+        value: Option<BigUint>,
+        /// The little-endian bits of the limbs, if they were allocated.
+        bits: Vec<Variable>,
+    }
+
+    impl<F: PrimeField> Int<F> {
+        fn constant(value: &BigUint) -> Self {
+            let limbs = split(value, NUM_LIMBS)
+                .iter()
+                .map(|limb| lc!() + (to_field::<F>(&limb.clone().into()), Variable::One))
+                .collect();
+            Int {
+                limbs,
+                value: Some(value.clone()),
+                bits: Vec::new(),
+            }
+        }
+
+        /// Packs big-endian bytes, which are range-constrained to 8 bits.
+        fn from_bytes(
+            cs: &ConstraintSystemRef<F>,
+            bytes: &[Variable],
+        ) -> Result<Self, SynthesisError> {
+            let mut limbs = vec![lc!(); NUM_LIMBS];
+            let mut value = Some(BigUint::default());
+            for (position, byte) in bytes.iter().rev().enumerate() {
+                enforce_bit_decomposition(cs, *byte, 8)?;
+                let shift = 8 * (position % 8);
+                limbs[position / 8] += (F::from(1u64 << shift), *byte);
+                value = value.zip(cs.assigned_value(*byte)).map(|(value, byte)| {
+                    let byte = BigUint::from_bytes_le(&byte.into_bigint().to_bytes_le());
+                    value + (byte << (8 * position))
+                });
+            }
+            Ok(Int {
+                limbs,
+                value,
+                bits: Vec::new(),
+            })
+        }
+
+        /// Allocates `value` as range-constrained limbs, the top one of `top_bits` bits.
+        fn alloc(
+            cs: &ConstraintSystemRef<F>,
+            value: Option<BigUint>,
+            top_bits: u32,
+        ) -> Result<Self, SynthesisError> {
+            let limb_values = value.as_ref().map(|value| split(value, NUM_LIMBS));
+            let mut limbs = Vec::with_capacity(NUM_LIMBS);
+            let mut bits = Vec::new();
+            for i in 0..NUM_LIMBS {
+                let limb_value = limb_values.as_ref().map(|limbs| limbs[i].clone().into());
+                let limb = alloc(cs, limb_value)?;
+                let num_bits = if i + 1 == NUM_LIMBS {
+                    top_bits
+                } else {
+                    LIMB_BITS
+                };
+                bits.extend(enforce_bit_decomposition(cs, limb, num_bits)?);
+                limbs.push(lc!() + limb);
+            }
+            Ok(Int { limbs, value, bits })
+        }
+
+        fn limb_values(&self) -> Option<Vec<BigInt>> {
+            let value = self.value.as_ref()?;
+            Some(
+                split(value, self.limbs.len())
+                    .into_iter()
+                    .map(BigInt::from)
+                    .collect(),
+            )
+        }
+
+        /// Returns `if_true` if `bit` is set, otherwise `if_false`.
+        fn select(
+            cs: &ConstraintSystemRef<F>,
+            bit: &Bit<F>,
+            if_true: &Self,
+            if_false: &Self,
+        ) -> Result<Self, SynthesisError> {
+            let value = bit.value.and_then(|bit| match bit {
+                true => if_true.value.clone(),
+                false => if_false.value.clone(),
+            });
+            let limb_values = if_true.limb_values().zip(if_false.limb_values());
+            let mut limbs = Vec::with_capacity(NUM_LIMBS);
+            for i in 0..NUM_LIMBS {
+                let limb_value =
+                    bit.value
+                        .zip(limb_values.as_ref())
+                        .map(|(bit, limbs)| match bit {
+                            true => limbs.0[i].clone(),
+                            false => limbs.1[i].clone(),
+                        });
+                let limb = alloc(cs, limb_value)?;
+                // bit * (if_true - if_false) == limb - if_false
+                cs.enforce_constraint(
+                    bit.lc.clone(),
+                    if_true.limbs[i].clone() - &if_false.limbs[i],
+                    lc!() + limb - &if_false.limbs[i],
+                )?;
+                limbs.push(lc!() + limb);
+            }
+            Ok(Int {
+                limbs,
+                value,
+                bits: Vec::new(),
+            })
+        }
+    }
+
+    /// Splits `value` into `num_limbs` little-endian 64-bit limbs, the top one holding the rest.
+    fn split(value: &BigUint, num_limbs: usize) -> Vec<BigUint> {
+        let mask = (BigUint::from(1u8) << LIMB_BITS) - 1u8;
+        (0..num_limbs)
+            .map(|i| {
+                let limb = value >> (LIMB_BITS as usize * i);
+                match i + 1 == num_limbs {
+                    true => limb,
+                    false => limb & &mask,
+                }
+            })
+            .collect()
+    }
+
+    /// The limbs of a polynomial in `2^64`, with their values unless in setup mode.
+    struct Poly<F: PrimeField> {
+        coeffs: Vec<LinearCombination<F>>,
+        values: Option<Vec<BigInt>>,
+    }
+
+    impl<F: PrimeField> Poly<F> {
+        fn zero(len: usize) -> Self {
+            Poly {
+                coeffs: vec![lc!(); len],
+                values: Some(vec![BigInt::default(); len]),
+            }
+        }
+
+        /// Adds `scale * coeffs`, whose values are `values`.
+        fn add(
+            &mut self,
+            scale: i64,
+            coeffs: &[LinearCombination<F>],
+            values: Option<Vec<BigInt>>,
+        ) {
+            let factor = to_field::<F>(&BigInt::from(scale));
+            for (sum, coeff) in self.coeffs.iter_mut().zip(coeffs) {
+                *sum = std::mem::replace(sum, lc!()) + (factor, coeff);
+            }
+            self.values = self.values.take().zip(values).map(|(mut sums, values)| {
+                for (sum, value) in sums.iter_mut().zip(values) {
+                    *sum += value * scale;
+                }
+                sums
+            });
+        }
+    }
+
+    /// The limbs of the product of `lhs` and `rhs`.
+    ///
+    /// The 7 limbs are allocated and constrained to agree with the product at 7 points, which
+    /// determines them as the product has degree 6.
+    fn product<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        lhs: &Int<F>,
+        rhs: &Int<F>,
+    ) -> Result<Poly<F>, SynthesisError> {
+        let len = 2 * NUM_LIMBS - 1;
+        let values = lhs.limb_values().zip(rhs.limb_values()).map(|(lhs, rhs)| {
+            let mut values = vec![BigInt::default(); len];
+            for (i, lhs) in lhs.iter().enumerate() {
+                for (j, rhs) in rhs.iter().enumerate() {
+                    values[i + j] += lhs * rhs;
+                }
+            }
+            values
+        });
+        let coeffs = (0..len)
+            .map(|k| Ok(lc!() + alloc(cs, values.as_ref().map(|values| values[k].clone()))?))
+            .collect::<Result<Vec<_>, SynthesisError>>()?;
+
+        let evaluate = |limbs: &[LinearCombination<F>], point: u64| {
+            let mut power = F::one();
+            let mut sum = lc!();
+            for limb in limbs {
+                sum = sum + (power, limb);
+                power *= F::from(point);
+            }
+            sum
+        };
+        for point in 0..len as u64 {
+            cs.enforce_constraint(
+                evaluate(&lhs.limbs, point),
+                evaluate(&rhs.limbs, point),
+                evaluate(&coeffs, point),
+            )?;
+        }
+        Ok(Poly { coeffs, values })
+    }
+
+    /// A sum of products and of integers, each with a small positive coefficient.
+    #[derive(Default)]
+    struct Sum<'a, F: PrimeField> {
+        products: Vec<(i64, &'a Int<F>, &'a Int<F>)>,
+        terms: Vec<(i64, &'a Int<F>)>,
+    }
+
+    impl<'a, F: PrimeField> Sum<'a, F> {
+        fn value(&self) -> Option<BigUint> {
+            let mut sum = BigUint::default();
+            for (scale, lhs, rhs) in &self.products {
+                sum += BigUint::from(*scale as u64) * lhs.value.as_ref()? * rhs.value.as_ref()?;
+            }
+            for (scale, term) in &self.terms {
+                sum += BigUint::from(*scale as u64) * term.value.as_ref()?;
+            }
+            Some(sum)
+        }
+
+        /// Adds `sign` times the sum to `identity`.
+        fn add_to(
+            &self,
+            cs: &ConstraintSystemRef<F>,
+            identity: &mut Poly<F>,
+            sign: i64,
+        ) -> Result<(), SynthesisError> {
+            for (scale, lhs, rhs) in &self.products {
+                let product = product(cs, lhs, rhs)?;
+                identity.add(sign * scale, &product.coeffs, product.values);
+            }
+            for (scale, term) in &self.terms {
+                identity.add(sign * scale, &term.limbs, term.limb_values());
+            }
+            Ok(())
+        }
+    }
+
+    /// Enforces `lhs == rhs (mod modulus)`, where both sides are below `2^514`.
+    ///
+    /// The prover supplies the quotient `q` of `lhs + offset - rhs` by `modulus`, where the
+    /// offset is a multiple of `modulus` exceeding any right-hand side. The integer identity
+    /// `lhs + offset == q * modulus + rhs` is checked on the limbs, carrying between 128-bit
+    /// halves so that no intermediate sum wraps around the field modulus.
+    fn enforce_congruence<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        lhs: Sum<F>,
+        rhs: Sum<F>,
+        modulus: &BigUint,
+    ) -> Result<(), SynthesisError> {
+        let offset = modulus * ((BigUint::from(1u8) << OFFSET_BITS) / modulus + 1u8);
+        let quotient = lhs
+            .value()
+            .zip(rhs.value())
+            .map(|(lhs, rhs)| (lhs + &offset - rhs) / modulus);
+        let quotient = Int::alloc(cs, quotient, QUOTIENT_TOP_BITS)?;
+
+        let mut identity = Poly::zero(IDENTITY_LIMBS);
+        lhs.add_to(cs, &mut identity, 1)?;
+        let offset_limbs = split(&offset, IDENTITY_LIMBS);
+        let offset_coeffs: Vec<_> = offset_limbs
+            .iter()
+            .map(|limb| lc!() + (to_field::<F>(&limb.clone().into()), Variable::One))
+            .collect();
+        identity.add(
+            1,
+            &offset_coeffs,
+            Some(offset_limbs.into_iter().map(BigInt::from).collect()),
+        );
+        // q * modulus, whose limbs are linear in those of q
+        let mut scaled = Poly::zero(2 * NUM_LIMBS - 1);
+        for (j, limb) in split(modulus, NUM_LIMBS).into_iter().enumerate() {
+            let limb = BigInt::from(limb);
+            let factor = to_field::<F>(&limb);
+            for (i, quotient_limb) in quotient.limbs.iter().enumerate() {
+                let coeff = std::mem::replace(&mut scaled.coeffs[i + j], lc!());
+                scaled.coeffs[i + j] = coeff + (factor, quotient_limb);
+            }
+            scaled.values =
+                scaled
+                    .values
+                    .take()
+                    .zip(quotient.limb_values())
+                    .map(|(mut values, quotient)| {
+                        for (i, quotient_limb) in quotient.iter().enumerate() {
+                            values[i + j] += quotient_limb * &limb;
+                        }
+                        values
+                    });
+        }
+        identity.add(-1, &scaled.coeffs, scaled.values);
+        rhs.add_to(cs, &mut identity, -1)?;
+
+        // Each pair of limbs plus the incoming carry must be a multiple of 2^128, with the
+        // outgoing carry as the quotient, and the top pair must cancel the last carry
+        let two = F::from(2u64);
+        let radix = two.pow([2 * LIMB_BITS as u64]);
+        let carry_offset = two.pow([CARRY_BITS as u64 - 1]);
+        let mut carry = lc!();
+        let mut carry_value = Some(BigInt::default());
+        let num_pairs = (IDENTITY_LIMBS + 1) / 2;
+        for pair in 0..num_pairs {
+            let mut sum = carry.clone();
+            for (k, shift) in [(2 * pair, 0), (2 * pair + 1, LIMB_BITS)] {
+                if let Some(coeff) = identity.coeffs.get(k) {
+                    sum = sum + (two.pow([shift as u64]), coeff);
+                }
+            }
+            let sum_value = carry_value
+                .zip(identity.values.as_ref())
+                .map(|(carry, values)| {
+                    let high = values.get(2 * pair + 1).cloned().unwrap_or_default();
+                    carry + &values[2 * pair] + (high << LIMB_BITS)
+                });
+            if pair + 1 == num_pairs {
+                cs.enforce_constraint(lc!() + Variable::One, sum, lc!())?;
+                break;
+            }
+
+            carry_value = sum_value.map(|sum| sum >> (2 * LIMB_BITS));
+            let shifted = carry_value
+                .as_ref()
+                .map(|carry| carry + (BigInt::from(1u8) << (CARRY_BITS - 1)));
+            let shifted = alloc(cs, shifted)?;
+            enforce_bit_decomposition(cs, shifted, CARRY_BITS)?;
+            carry = lc!() + shifted - (carry_offset, Variable::One);
+            cs.enforce_constraint(lc!() + Variable::One, sum, carry.clone() * radix)?;
+        }
+        Ok(())
+    }
+
+    /// Returns `lhs * rhs mod modulus`, in range-constrained limbs.
+    fn mul_mod<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        lhs: &Int<F>,
+        rhs: &Int<F>,
+        modulus: &BigUint,
+    ) -> Result<Int<F>, SynthesisError> {
+        let value = lhs
+            .value
+            .as_ref()
+            .zip(rhs.value.as_ref())
+            .map(|(lhs, rhs)| lhs * rhs % modulus);
+        let output = Int::alloc(cs, value, LIMB_BITS)?;
+        enforce_congruence(
+            cs,
+            Sum {
+                products: vec![(1, lhs, rhs)],
+                ..Sum::default()
+            },
+            Sum {
+                terms: vec![(1, &output)],
+                ..Sum::default()
+            },
+            modulus,
+        )?;
+        Ok(output)
+    }
+
+    /// Returns whether `value` is less than `bound`, at most `2^256`.
+    ///
+    /// `value + 2^256 - bound` is recomputed limb by limb with boolean carries, the last of
+    /// which is set exactly when `value >= bound`.
+    fn less_than<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        value: &Int<F>,
+        bound: &BigUint,
+    ) -> Result<Bit<F>, SynthesisError> {
+        let complement = (BigUint::from(1u8) << (LIMB_BITS as usize * NUM_LIMBS)) - bound;
+        let mask = (BigUint::from(1u8) << (LIMB_BITS as usize * NUM_LIMBS)) - 1u8;
+        let low = value
+            .value
+            .as_ref()
+            .map(|value| (value + &complement) & mask);
+        let low = Int::alloc(cs, low, LIMB_BITS)?;
+        let limb_values = value.limb_values();
+        let mut carry = lc!();
+        let mut carry_value = Some(BigInt::default());
+        for (i, complement) in split(&complement, NUM_LIMBS).into_iter().enumerate() {
+            let complement = BigInt::from(complement);
+            let total = carry_value
+                .zip(limb_values.as_ref())
+                .map(|(carry, limbs)| carry + &limbs[i] + &complement);
+            carry_value = total.map(|total| total >> LIMB_BITS);
+            let next = alloc(cs, carry_value.clone())?;
+            cs.enforce_constraint(lc!() + next, lc!() + next, lc!() + next)?;
+            // value + complement + carry == low + 2^64 * next
+            cs.enforce_constraint(
+                lc!() + Variable::One,
+                value.limbs[i].clone() + (to_field::<F>(&complement), Variable::One) + &carry,
+                low.limbs[i].clone() + (F::from(2u64).pow([LIMB_BITS as u64]), next),
+            )?;
+            carry = lc!() + next;
+        }
+        let at_least = Bit {
+            lc: carry,
+            value: carry_value.map(|carry| carry == BigInt::from(1u8)),
+        };
+        Ok(at_least.not())
+    }
+
+    /// Returns whether two integers with canonical 64-bit limbs are equal.
+    fn equals<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        lhs: &Int<F>,
+        rhs: &Int<F>,
+    ) -> Result<Bit<F>, SynthesisError> {
+        let values = lhs.limb_values().zip(rhs.limb_values());
+        // Compare the 128-bit halves, whose differences cannot wrap around the field modulus
+        let mut halves = Vec::with_capacity(2);
+        for half in 0..NUM_LIMBS / 2 {
+            let (low, high) = (2 * half, 2 * half + 1);
+            let shift = F::from(2u64).pow([LIMB_BITS as u64]);
+            let difference = lhs.limbs[low].clone() - &rhs.limbs[low] + (shift, &lhs.limbs[high])
+                - (shift, &rhs.limbs[high]);
+            let value = values
+                .as_ref()
+                .map(|(lhs, rhs)| &lhs[low] - &rhs[low] + ((&lhs[high] - &rhs[high]) << LIMB_BITS));
+            halves.push(is_zero(cs, difference, value)?);
+        }
+        halves[0].and(cs, &halves[1])
+    }
+
+    /// Returns whether `1 <= value < bound`.
+    fn in_range<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        value: &Int<F>,
+        bound: &BigUint,
+    ) -> Result<Bit<F>, SynthesisError> {
+        let below = less_than(cs, value, bound)?;
+        // The limbs are non-negative, so their sum only vanishes if they all do
+        let sum = value.limbs.iter().fold(lc!(), |sum, limb| sum + limb);
+        let sum_value = value
+            .limb_values()
+            .map(|limbs| limbs.into_iter().sum::<BigInt>());
+        let zero = is_zero(cs, sum, sum_value)?;
+        below.and(cs, &zero.not())
+    }
+
+    /// An affine point, with coordinates in 64-bit limbs.
+    #[derive(Clone)]
+    struct Point<F: PrimeField> {
+        x: Int<F>,
+        y: Int<F>,
+    }
+
+    impl<F: PrimeField> Point<F> {
+        fn constant((x, y): &(BigUint, BigUint)) -> Self {
+            Point {
+                x: Int::constant(x),
+                y: Int::constant(y),
+            }
+        }
+
+        fn select(
+            cs: &ConstraintSystemRef<F>,
+            bit: &Bit<F>,
+            if_true: &Self,
+            if_false: &Self,
+        ) -> Result<Self, SynthesisError> {
+            Ok(Point {
+                x: Int::select(cs, bit, &if_true.x, &if_false.x)?,
+                y: Int::select(cs, bit, &if_true.y, &if_false.y)?,
+            })
+        }
+
+        fn values(&self) -> Option<(&BigUint, &BigUint)> {
+            self.x.value.as_ref().zip(self.y.value.as_ref())
+        }
+
+        /// Adds `other`, which must differ from both this point and its negation: the circuit is
+        /// unsatisfiable otherwise.
+        fn add(
+            &self,
+            cs: &ConstraintSystemRef<F>,
+            curve: &Secp256k1,
+            other: &Self,
+        ) -> Result<Self, SynthesisError> {
+            let p = &curve.p;
+            // Without this, a slope through two points with the same x coordinate would be
+            // unconstrained when they are equal
+            let difference_inverse = self
+                .x
+                .value
+                .as_ref()
+                .zip(other.x.value.as_ref())
+                .map(|(x1, x2)| inverse(&sub_mod(x2, x1, p), p));
+            let difference_inverse = Int::alloc(cs, difference_inverse, LIMB_BITS)?;
+            let one = Int::constant(&BigUint::from(1u8));
+            // difference_inverse * (x2 - x1) == 1
+            enforce_congruence(
+                cs,
+                Sum {
+                    products: vec![(1, &difference_inverse, &other.x)],
+                    ..Sum::default()
+                },
+                Sum {
+                    products: vec![(1, &difference_inverse, &self.x)],
+                    terms: vec![(1, &one)],
+                },
+                p,
+            )?;
+
+            let slope = self
+                .values()
+                .zip(other.values())
+                .map(|((x1, y1), (x2, y2))| {
+                    sub_mod(y2, y1, p) * inverse(&sub_mod(x2, x1, p), p) % p
+                });
+            let slope = Int::alloc(cs, slope, LIMB_BITS)?;
+            // slope * (x2 - x1) == y2 - y1
+            enforce_congruence(
+                cs,
+                Sum {
+                    products: vec![(1, &slope, &other.x)],
+                    terms: vec![(1, &self.y)],
+                },
+                Sum {
+                    products: vec![(1, &slope, &self.x)],
+                    terms: vec![(1, &other.y)],
+                },
+                p,
+            )?;
+            self.chord(cs, curve, &slope, &other.x)
+        }
+
+        fn double(
+            &self,
+            cs: &ConstraintSystemRef<F>,
+            curve: &Secp256k1,
+        ) -> Result<Self, SynthesisError> {
+            let p = &curve.p;
+            let slope = self.values().map(|(x, y)| {
+                BigUint::from(3u8) * x * x % p * inverse(&(BigUint::from(2u8) * y), p) % p
+            });
+            let slope = Int::alloc(cs, slope, LIMB_BITS)?;
+            // slope * 2y == 3x^2
+            enforce_congruence(
+                cs,
+                Sum {
+                    products: vec![(2, &slope, &self.y)],
+                    ..Sum::default()
+                },
+                Sum {
+                    products: vec![(3, &self.x, &self.x)],
+                    ..Sum::default()
+                },
+                p,
+            )?;
+            self.chord(cs, curve, &slope, &self.x)
+        }
+
+        /// The sum of this point and the point with x coordinate `other_x` on the line through
+        /// this point with the given slope.
+        fn chord(
+            &self,
+            cs: &ConstraintSystemRef<F>,
+            curve: &Secp256k1,
+            slope: &Int<F>,
+            other_x: &Int<F>,
+        ) -> Result<Self, SynthesisError> {
+            let p = &curve.p;
+            let x = slope
+                .value
+                .as_ref()
+                .zip(self.x.value.as_ref().zip(other_x.value.as_ref()))
+                .map(|(slope, (x1, x2))| sub_mod(&(slope * slope), &(x1 + x2), p));
+            let x = Int::alloc(cs, x, LIMB_BITS)?;
+            // slope^2 == x + x1 + x2
+            enforce_congruence(
+                cs,
+                Sum {
+                    products: vec![(1, slope, slope)],
+                    ..Sum::default()
+                },
+                Sum {
+                    terms: vec![(1, &x), (1, &self.x), (1, other_x)],
+                    ..Sum::default()
+                },
+                p,
+            )?;
+
+            let y = slope
+                .value
+                .as_ref()
+                .zip(self.values().zip(x.value.as_ref()))
+                .map(|(slope, ((x1, y1), x))| sub_mod(&(slope * sub_mod(x1, x, p)), y1, p));
+            let y = Int::alloc(cs, y, LIMB_BITS)?;
+            // slope * (x1 - x) == y + y1
+            enforce_congruence(
+                cs,
+                Sum {
+                    products: vec![(1, slope, &self.x)],
+                    ..Sum::default()
+                },
+                Sum {
+                    products: vec![(1, slope, &x)],
+                    terms: vec![(1, &y), (1, &self.y)],
+                },
+                p,
+            )?;
+            Ok(Point { x, y })
+        }
+    }
+
+    /// Returns whether `y^2 == x^3 + 7 (mod p)`.
+    fn is_on_curve<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        curve: &Secp256k1,
+        x: &Int<F>,
+        y: &Int<F>,
+    ) -> Result<Bit<F>, SynthesisError> {
+        let p = &curve.p;
+        let square = mul_mod(cs, x, x, p)?;
+        let cube = mul_mod(cs, &square, x, p)?;
+        let seven = Int::constant(&BigUint::from(7u8));
+        // The canonical residue of y^2 - x^3 - 7, which is zero on the curve
+        let residue = y
+            .value
+            .as_ref()
+            .zip(cube.value.as_ref())
+            .map(|(y, cube)| sub_mod(&(y * y), &(cube + 7u8), p));
+        let residue = Int::alloc(cs, residue, LIMB_BITS)?;
+        enforce_congruence(
+            cs,
+            Sum {
+                products: vec![(1, y, y)],
+                ..Sum::default()
+            },
+            Sum {
+                terms: vec![(1, &cube), (1, &seven), (1, &residue)],
+                ..Sum::default()
+            },
+            p,
+        )?;
+        let canonical = less_than(cs, &residue, p)?;
+        cs.enforce_constraint(lc!() + Variable::One, canonical.lc, lc!() + Variable::One)?;
+
+        let sum = residue.limbs.iter().fold(lc!(), |sum, limb| sum + limb);
+        let sum_value = residue
+            .limb_values()
+            .map(|limbs| limbs.into_iter().sum::<BigInt>());
+        is_zero(cs, sum, sum_value)
+    }
+
+    /// Returns `value - n` if `subtract` is set, otherwise `value`, which must not be smaller
+    /// than what is subtracted.
+    fn subtract_if<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        value: &Int<F>,
+        subtract: &Bit<F>,
+        n: &BigUint,
+    ) -> Result<Int<F>, SynthesisError> {
+        let difference = value
+            .value
+            .as_ref()
+            .zip(subtract.value)
+            .map(|(value, subtract)| match subtract {
+                true => value - n.min(value),
+                false => value.clone(),
+            });
+        let difference = Int::alloc(cs, difference, LIMB_BITS)?;
+        let differences = difference.limb_values();
+        let mut carry = lc!();
+        let mut carry_value = Some(BigInt::default());
+        for (i, limb) in split(n, NUM_LIMBS).into_iter().enumerate() {
+            let limb = BigInt::from(limb);
+            // difference + subtract * n + carry == value + 2^64 * next
+            let mut sum =
+                difference.limbs[i].clone() + (to_field::<F>(&limb), &subtract.lc) + &carry;
+            if i + 1 == NUM_LIMBS {
+                cs.enforce_constraint(lc!() + Variable::One, sum, value.limbs[i].clone())?;
+                break;
+            }
+            carry_value = carry_value
+                .zip(differences.as_ref().zip(subtract.value))
+                .map(|(carry, (differences, subtract))| {
+                    (carry + &differences[i] + &limb * BigInt::from(subtract as u8)) >> LIMB_BITS
+                });
+            let next = alloc(cs, carry_value.clone())?;
+            cs.enforce_constraint(lc!() + next, lc!() + next, lc!() + next)?;
+            sum = sum - (F::from(2u64).pow([LIMB_BITS as u64]), next);
+            cs.enforce_constraint(lc!() + Variable::One, sum, value.limbs[i].clone())?;
+            carry = lc!() + next;
+        }
+        Ok(difference)
+    }
+
+    fn enforce_verification<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        curve: &Secp256k1,
+        gate: &EcdsaSecp256k1Gate,
+        variables: &BTreeMap<Witness, Variable>,
+    ) -> Result<(), SynthesisError> {
+        let (p, n) = (&curve.p, &curve.n);
+        let int = |witnesses: &[Witness]| {
+            let bytes: Vec<_> = witnesses.iter().map(|witness| variables[witness]).collect();
+            Int::from_bytes(cs, &bytes)
+        };
+        let key = Point {
+            x: int(&gate.public_key_x)?,
+            y: int(&gate.public_key_y)?,
+        };
+        let r = int(&gate.signature[..32])?;
+        let s = int(&gate.signature[32..])?;
+        let message = int(&gate.hashed_message)?;
+
+        let key_valid = less_than(cs, &key.x, p)?
+            .and(cs, &less_than(cs, &key.y, p)?)?
+            .and(cs, &is_on_curve(cs, curve, &key.x, &key.y)?)?;
+        let r_valid = in_range(cs, &r, n)?;
+        // acvm only accepts signatures with a low s
+        let s_valid = in_range(cs, &s, &((n + 1u8) >> 1))?;
+
+        let one = Int::constant(&BigUint::from(1u8));
+        let s = Int::select(cs, &s_valid, &s, &one)?;
+        let generator = Point::constant(&curve.generator);
+        let key = Point::select(cs, &key_valid, &key, &generator)?;
+
+        // u1 = z / s and u2 = r / s
+        let s_inverse = s.value.as_ref().map(|s| inverse(s, n));
+        let s_inverse = Int::alloc(cs, s_inverse, LIMB_BITS)?;
+        enforce_congruence(
+            cs,
+            Sum {
+                products: vec![(1, &s, &s_inverse)],
+                ..Sum::default()
+            },
+            Sum {
+                terms: vec![(1, &one)],
+                ..Sum::default()
+            },
+            n,
+        )?;
+        let u1 = mul_mod(cs, &message, &s_inverse, n)?;
+        let u2 = mul_mod(cs, &r, &s_inverse, n)?;
+
+        // A + u1 * G + u2 * Q by Shamir's trick, adding G, Q or G + Q for each pair of bits
+        let both = generator.add(cs, curve, &key)?;
+        let mut sum = Point::constant(&curve.offset);
+        for (bit1, bit2) in u1.bits.iter().zip(&u2.bits).rev() {
+            let (bit1, bit2) = (Bit::from_variable(cs, *bit1), Bit::from_variable(cs, *bit2));
+            sum = sum.double(cs, curve)?;
+            let addend = Point::select(cs, &bit1, &both, &key)?;
+            let addend = Point::select(cs, &bit2, &addend, &generator)?;
+            let added = sum.add(cs, curve, &addend)?;
+            sum = Point::select(cs, &bit1.or(cs, &bit2)?, &added, &sum)?;
+        }
+
+        // Subtract 2^256 * A, unless the sum is that point and the result is at infinity
+        let end = Point::constant(&curve.offset_end);
+        let is_infinity = equals(cs, &sum.x, &end.x)?;
+        let sum = Point::select(cs, &is_infinity, &generator, &sum)?;
+        let (end_x, end_y) = &curve.offset_end;
+        let correction = Point::constant(&(end_x.clone(), p - end_y));
+        let point = sum.add(cs, curve, &correction)?;
+
+        // x mod n, from the canonical x coordinate
+        let canonical = less_than(cs, &point.x, p)?;
+        cs.enforce_constraint(lc!() + Variable::One, canonical.lc, lc!() + Variable::One)?;
+        let at_least_n = less_than(cs, &point.x, n)?.not();
+        let reduced = subtract_if(cs, &point.x, &at_least_n, n)?;
+        let matches = equals(cs, &reduced, &r)?;
+
+        let valid = key_valid
+            .and(cs, &r_valid)?
+            .and(cs, &s_valid)?
+            .and(cs, &is_infinity.not())?
+            .and(cs, &matches)?;
+        cs.enforce_constraint(
+            lc!() + Variable::One,
+            valid.lc,
+            lc!() + variables[&gate.output],
+        )?;
+        Ok(())
+    }
+
+    pub(super) fn enforce_ecdsa_secp256k1_gates<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        gates: &[EcdsaSecp256k1Gate],
+        variables: &BTreeMap<Witness, Variable>,
+    ) -> Result<(), SynthesisError> {
+        if gates.is_empty() {
+            return Ok(());
+        }
+        let curve = Secp256k1::new();
+        for gate in gates {
+            enforce_verification(cs, &curve, gate, variables)?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn r1cs_size(gates: &[EcdsaSecp256k1Gate]) -> R1csSize {
+        let size = |constraints: usize, witnesses: usize| R1csSize {
+            constraints,
+            witnesses,
+            public_inputs: 0,
+        };
+        let sum = |sizes: &[(usize, R1csSize)]| {
+            let mut total = R1csSize::default();
+            for (count, size) in sizes {
+                total += R1csSize {
+                    constraints: count * size.constraints,
+                    witnesses: count * size.witnesses,
+                    public_inputs: 0,
+                };
+            }
+            total
+        };
+
+        // A limb and its bits, and the recomposition
+        let int = size(4 * (LIMB_BITS as usize + 1), 4 * (LIMB_BITS as usize + 1));
+        let quotient = 3 * (LIMB_BITS as usize + 1) + QUOTIENT_TOP_BITS as usize + 1;
+        let carries = (IDENTITY_LIMBS - 1) / 2 * (CARRY_BITS as usize + 1);
+        let congruence = size(
+            quotient + carries + (IDENTITY_LIMBS + 1) / 2,
+            quotient + carries,
+        );
+        let product = size(2 * NUM_LIMBS - 1, 2 * NUM_LIMBS - 1);
+        let mul_mod = sum(&[(1, int), (1, congruence), (1, product)]);
+        let less_than = sum(&[(1, int), (1, size(2 * NUM_LIMBS, NUM_LIMBS))]);
+        let is_zero = size(2, 2);
+        let and = size(1, 1);
+        let select = size(2 * NUM_LIMBS, 2 * NUM_LIMBS);
+        let equals = sum(&[(2, is_zero), (1, and)]);
+        let in_range = sum(&[(1, less_than), (1, is_zero), (1, and)]);
+        let double = sum(&[(3, int), (3, congruence), (5, product)]);
+        // The inverse of x2 - x1 on top of a doubling's constraints
+        let add = sum(&[(4, int), (4, congruence), (7, product)]);
+        let on_curve = sum(&[
+            (2, mul_mod),
+            (1, int),
+            (1, congruence),
+            (1, product),
+            (1, less_than),
+            (1, size(1, 0)),
+            (1, is_zero),
+        ]);
+        let subtract_if = sum(&[(1, int), (1, size(2 * NUM_LIMBS - 1, NUM_LIMBS - 1))]);
+
+        let verification = sum(&[
+            // The bytes of the key, signature and message
+            (160, size(9, 8)),
+            (2, less_than),
+            (1, on_curve),
+            (2, and),
+            (2, in_range),
+            // s and the key, replaced when invalid
+            (1, size(NUM_LIMBS, NUM_LIMBS)),
+            (1, select),
+            // 1 / s, u1 and u2
+            (1, int),
+            (1, congruence),
+            (1, product),
+            (2, mul_mod),
+            // G + Q, then a doubling, two selections of the addend, an addition and a selection
+            // of the sum per bit
+            (1, add),
+            (256, sum(&[(1, double), (1, add), (3, select), (1, and)])),
+            // The correction by 2^256 * A
+            (1, equals),
+            (1, select),
+            (1, add),
+            // The reduction of the x coordinate modulo n
+            (2, less_than),
+            (1, size(1, 0)),
+            (1, subtract_if),
+            (1, equals),
+            (4, and),
+            (1, size(1, 0)),
+        ]);
+        sum(&[(gates.len(), verification)])
+    }
+}
+
+#[cfg(all(test, feature = "ecdsa"))]
+mod test {
+    use acvm::acir::circuit::opcodes::{BlackBoxFuncCall, FunctionInput};
+    use acvm::acir::circuit::Opcode;
+    use acvm::acir::native_types::WitnessMap;
+    use acvm::FieldElement;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+    use num_bigint::BigUint;
+    use sha2::{Digest, Sha256};
+
+    use super::verification::{inverse, Secp256k1};
+    use super::*;
+    use crate::test::circuit_with_opcodes;
+    use crate::CurveAcir;
+
+    /// Signs sha256(`message`) with `secret` and a fixed nonce, returning the key coordinates,
+    /// the signature `r || s` with a low `s`, and the digest as bytes.
+    fn sign(secret: u64, message: &[u8]) -> Vec<u8> {
+        let curve = Secp256k1::new();
+        let n = &curve.n;
+        let secret = BigUint::from(secret);
+        let nonce = BigUint::from(0xdead_beefu64);
+        let (key_x, key_y) = curve.mul(&secret, &curve.generator).unwrap();
+        let digest = Sha256::digest(message);
+
+        let r = curve.mul(&nonce, &curve.generator).unwrap().0 % n;
+        let hash = BigUint::from_bytes_be(&digest);
+        let mut s = inverse(&nonce, n) * ((hash + &r * &secret) % n) % n;
+        if s > n >> 1 {
+            s = n - s;
+        }
+
+        let bytes = |value: &BigUint| {
+            let bytes = value.to_bytes_be();
+            let mut padded = vec![0u8; 32 - bytes.len()];
+            padded.extend(bytes);
+            padded
+        };
+        [
+            bytes(&key_x),
+            bytes(&key_y),
+            bytes(&r),
+            bytes(&s),
+            digest.to_vec(),
+        ]
+        .concat()
+    }
+
+    /// Whether the verification of `inputs` with the given output is satisfied.
+    fn is_satisfied(inputs: &[u8], output: u8) -> bool {
+        let byte = |index: u32| FunctionInput {
+            witness: Witness(index),
+            num_bits: 8,
+        };
+        let verification = Opcode::BlackBoxFuncCall(BlackBoxFuncCall::EcdsaSecp256k1 {
+            public_key_x: Box::new(std::array::from_fn(|index| byte(index as u32))),
+            public_key_y: Box::new(std::array::from_fn(|index| byte(32 + index as u32))),
+            signature: Box::new(std::array::from_fn(|index| byte(64 + index as u32))),
+            hashed_message: Box::new(std::array::from_fn(|index| byte(128 + index as u32))),
+            output: Witness(160),
+        });
+        let circuit = circuit_with_opcodes(160, vec![verification], &[]);
+        let mut witness_map = WitnessMap::new();
+        for (index, byte) in inputs.iter().chain(&[output]).enumerate() {
+            witness_map.insert(Witness(index as u32), FieldElement::from(*byte as u128));
+        }
+
+        let cs = ConstraintSystem::new_ref();
+        CurveAcir::from((&circuit, witness_map))
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert_eq!(cs.num_constraints(), 1536072);
+        cs.is_satisfied().unwrap()
+    }
+
+    #[test]
+    fn verifies_signatures() {
+        let inputs = sign(0x1234_5678, b"hello");
+        assert!(is_satisfied(&inputs, 1));
+        assert!(!is_satisfied(&inputs, 0));
+
+        let mut corrupted = inputs;
+        corrupted[159] ^= 1;
+        assert!(is_satisfied(&corrupted, 0));
+        assert!(!is_satisfied(&corrupted, 1));
+    }
+
+    #[test]
+    fn rejects_the_generator_as_key() {
+        // G + Q doubles G, whose slope would otherwise be free for the prover to choose
+        let inputs = sign(1, b"hello");
+        assert!(!is_satisfied(&inputs, 1));
+        assert!(!is_satisfied(&inputs, 0));
+    }
+}
@@ -0,0 +1,675 @@
+use std::collections::BTreeMap;
+
+use acvm::acir::native_types::Witness;
+use ark_ff::{BigInteger, PrimeField};
+use ark_relations::{
+    lc,
+    r1cs::{ConstraintSystemRef, LinearCombination, SynthesisError, Variable},
+};
+
+use crate::concrete_cfg::{self, Fr};
+use crate::gadgets::enforce_bit_decomposition;
+use crate::R1csSize;
+
+// Lowering of `BlackBoxFuncCall::EmbeddedCurveAdd` and `BlackBoxFuncCall::MultiScalarMul` to
+// R1CS.
+//
+// Points are given in affine coordinates on the curve `y^2 = x^3 + a x + b` embedded in the
+// scalar field, with a flag marking the point at infinity. Following the ACVM solver, adding the
+// point at infinity returns the other operand unchanged, and a finite sum which cancels out is
+// the point at infinity with zero coordinates.
+//
+// Finite operands must lie on the curve. For distinct `x` coordinates the slope of the sum is
+// the chord's, and for equal points the tangent's. Since both operands are on the curve, equal
+// `x` coordinates with different `y` coordinates mean the operands are opposite. Every
+// selector between those cases is a witness constrained by a product, so an addition lowers to
+// 34 constraints.
+//
+// A multi-scalar multiplication decomposes each scalar, given as its low and high 128 bits, and
+// multiplies its point by double-and-add from the most significant bit, selecting after each
+// doubling between the doubled point and its sum with the point. Every doubling and addition is
+// the addition above, so a zero scalar leaves the point at infinity, and the products are then
+// summed. A scalar at or above the order of the curve wraps around instead of being rejected as
+// the ACVM solver does.
+
+/// The witnesses of a point in affine coordinates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct PointWitnesses {
+    pub(crate) x: Witness,
+    pub(crate) y: Witness,
+    pub(crate) is_infinite: Witness,
+}
+
+impl PointWitnesses {
+    fn witnesses(&self) -> [Witness; 3] {
+        [self.x, self.y, self.is_infinite]
+    }
+}
+
+/// A single `BlackBoxFuncCall::EmbeddedCurveAdd`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct EmbeddedCurveAddGate {
+    pub(crate) lhs: PointWitnesses,
+    pub(crate) rhs: PointWitnesses,
+    pub(crate) output: PointWitnesses,
+    pub(crate) source_opcode: usize,
+}
+
+impl EmbeddedCurveAddGate {
+    pub(crate) fn witnesses(&self) -> impl Iterator<Item = Witness> {
+        [self.lhs, self.rhs, self.output]
+            .into_iter()
+            .flat_map(|point| point.witnesses())
+    }
+
+    pub(crate) fn witnesses_mut(&mut self) -> impl Iterator<Item = &mut Witness> {
+        [&mut self.lhs, &mut self.rhs, &mut self.output]
+            .into_iter()
+            .flat_map(|point| [&mut point.x, &mut point.y, &mut point.is_infinite])
+    }
+}
+
+/// A single `BlackBoxFuncCall::MultiScalarMul`, the sum of `scalars[i] * points[i]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct MultiScalarMulGate {
+    pub(crate) points: Vec<PointWitnesses>,
+    /// The low and high 128 bits of each scalar.
+    pub(crate) scalars: Vec<(Witness, Witness)>,
+    pub(crate) output: PointWitnesses,
+    pub(crate) source_opcode: usize,
+}
+
+impl MultiScalarMulGate {
+    pub(crate) fn witnesses(&self) -> impl Iterator<Item = Witness> + '_ {
+        self.points
+            .iter()
+            .chain(std::iter::once(&self.output))
+            .flat_map(|point| point.witnesses())
+            .chain(self.scalars.iter().flat_map(|(lo, hi)| [*lo, *hi]))
+    }
+
+    pub(crate) fn witnesses_mut(&mut self) -> impl Iterator<Item = &mut Witness> {
+        self.points
+            .iter_mut()
+            .chain(std::iter::once(&mut self.output))
+            .flat_map(|point| [&mut point.x, &mut point.y, &mut point.is_infinite])
+            .chain(self.scalars.iter_mut().flat_map(|(lo, hi)| [lo, hi]))
+    }
+}
+
+/// The width of each of the two limbs of a multi-scalar multiplication's scalars.
+const SCALAR_LIMB_BITS: u32 = 128;
+
+/// The coefficients `(a, b)` of the embedded curve, if `F` is the scalar field of the configured
+/// curve and that has one.
+pub(crate) fn coefficients<F: PrimeField>() -> Option<(F, F)> {
+    if F::MODULUS.to_bytes_le() != Fr::MODULUS.to_bytes_le() {
+        return None;
+    }
+    let from_i64 = |value: i64| match value < 0 {
+        true => -F::from(value.unsigned_abs()),
+        false => F::from(value as u64),
+    };
+    let (a, b) = concrete_cfg::EMBEDDED_CURVE_COEFFICIENTS?;
+    Some((from_i64(a), from_i64(b)))
+}
+
+/// Counts the constraints and witnesses [`enforce_embedded_curve_adds`] allocates.
+pub(crate) fn r1cs_size(gates: &[EmbeddedCurveAddGate]) -> R1csSize {
+    R1csSize {
+        constraints: 34 * gates.len(),
+        witnesses: 26 * gates.len(),
+        public_inputs: 0,
+    }
+}
+
+/// Counts the constraints and witnesses [`enforce_multi_scalar_muls`] allocates.
+pub(crate) fn multi_scalar_mul_r1cs_size(gates: &[MultiScalarMulGate]) -> R1csSize {
+    let size = |constraints: usize, witnesses: usize| R1csSize {
+        constraints,
+        witnesses,
+        public_inputs: 0,
+    };
+    let mut total = R1csSize::default();
+    for gate in gates {
+        let num_terms = gate.points.len();
+        let num_steps = 2 * SCALAR_LIMB_BITS as usize - 1;
+        // The point at infinity, and the output tied to the sum
+        total += size(6, 3);
+        // Per term the decomposition of both limbs, the selection of the point or infinity by
+        // the top bit, then a doubling, an addition and a selection for every other bit
+        total += size(
+            num_terms * (2 * SCALAR_LIMB_BITS as usize + 2 + 3 + num_steps * (2 * 34 + 3)),
+            num_terms * (2 * SCALAR_LIMB_BITS as usize + 3 + num_steps * (2 * 29 + 3)),
+        );
+        // The sum of the products
+        total += size(
+            34 * num_terms.saturating_sub(1),
+            29 * num_terms.saturating_sub(1),
+        );
+    }
+    total
+}
+
+/// Enforces every addition. Fails with `SynthesisError::Unsatisfiable` if there are any but the
+/// field has no embedded curve.
+pub(crate) fn enforce_embedded_curve_adds<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    gates: &[EmbeddedCurveAddGate],
+    variables: &BTreeMap<Witness, Variable>,
+) -> Result<(), SynthesisError> {
+    if gates.is_empty() {
+        return Ok(());
+    }
+    let coefficients = coefficients::<F>().ok_or(SynthesisError::Unsatisfiable)?;
+
+    for gate in gates {
+        let point = |point: &PointWitnesses| point.witnesses().map(|witness| variables[&witness]);
+        enforce_addition(
+            cs,
+            point(&gate.lhs),
+            point(&gate.rhs),
+            point(&gate.output),
+            coefficients,
+        )?;
+    }
+    Ok(())
+}
+
+/// Enforces `output == lhs + rhs` on points given as `[x, y, is_infinite]`, in 34 constraints
+/// and 26 witnesses.
+fn enforce_addition<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    [x1, y1, i1]: [Variable; 3],
+    [x2, y2, i2]: [Variable; 3],
+    [x3, y3, i3]: [Variable; 3],
+    (a, b): (F, F),
+) -> Result<(), SynthesisError> {
+    // lhs * rhs == product
+    let multiply = |lhs: LinearCombination<F>, rhs: LinearCombination<F>, value: Option<F>| {
+        let product = cs.new_witness_variable(|| value.ok_or(SynthesisError::AssignmentMissing))?;
+        cs.enforce_constraint(lhs, rhs, lc!() + product)?;
+        Ok::<_, SynthesisError>(product)
+    };
+    let one = || lc!() + Variable::One;
+
+    let value = |variable| cs.assigned_value(variable);
+    let values = [x1, y1, i1, x2, y2, i2].map(value);
+    let values: Option<[F; 6]> = values
+        .iter()
+        .all(Option::is_some)
+        .then(|| values.map(Option::unwrap));
+    let hint = values.map(|values| AdditionHint::new(values, a));
+    let hinted = |field: fn(&AdditionHint<F>) -> F| hint.as_ref().map(field);
+
+    // The infinity flags are boolean, and finite operands are on the curve
+    let mut squares = Vec::new();
+    for (x, y, is_infinite) in [(x1, y1, i1), (x2, y2, i2)] {
+        cs.enforce_constraint(lc!() + is_infinite, one() - is_infinite, lc!())?;
+        let square = multiply(lc!() + x, lc!() + x, value(x).map(|x| x.square()))?;
+        let cube = multiply(lc!() + square, lc!() + x, value(x).map(|x| x.square() * x))?;
+        let y_square = multiply(lc!() + y, lc!() + y, value(y).map(|y| y.square()))?;
+        cs.enforce_constraint(
+            one() - is_infinite,
+            lc!() + y_square - cube - (a, x) - (b, Variable::One),
+            lc!(),
+        )?;
+        squares.push(square);
+    }
+    let x1_square = squares[0];
+
+    // `same_x` is one exactly when x1 == x2, `same_y` when y1 == y2
+    let is_equal = |lhs: Variable, rhs: Variable, equal: Option<bool>| {
+        let difference = value(rhs).zip(value(lhs)).map(|(rhs, lhs)| rhs - lhs);
+        let inverse = cs.new_witness_variable(|| {
+            let difference = difference.ok_or(SynthesisError::AssignmentMissing)?;
+            Ok(difference.inverse().unwrap_or_default())
+        })?;
+        let equal = cs
+            .new_witness_variable(|| equal.map(F::from).ok_or(SynthesisError::AssignmentMissing))?;
+        cs.enforce_constraint(lc!() + rhs - lhs, lc!() + inverse, one() - equal)?;
+        cs.enforce_constraint(lc!() + rhs - lhs, lc!() + equal, lc!())?;
+        Ok::<_, SynthesisError>(equal)
+    };
+    let same_x = is_equal(x1, x2, hint.as_ref().map(|hint| hint.same_x))?;
+    let same_y = is_equal(y1, y2, hint.as_ref().map(|hint| hint.same_y))?;
+
+    // Selectors: both operands finite, only the right one infinite, equal points, and the
+    // chord and tangent cases of a finite sum
+    let finite = multiply(
+        one() - i1,
+        one() - i2,
+        hinted(|hint| F::from(!hint.lhs_infinite && !hint.rhs_infinite)),
+    )?;
+    let only_rhs_infinite = multiply(
+        one() - i1,
+        lc!() + i2,
+        hinted(|hint| F::from(!hint.lhs_infinite && hint.rhs_infinite)),
+    )?;
+    let doubling = multiply(
+        lc!() + same_x,
+        lc!() + same_y,
+        hinted(|hint| F::from(hint.same_x && hint.same_y)),
+    )?;
+    let chord = multiply(
+        lc!() + finite,
+        one() - same_x,
+        hinted(|hint| F::from(hint.is_finite() && !hint.same_x)),
+    )?;
+    let tangent = multiply(
+        lc!() + finite,
+        lc!() + doubling,
+        hinted(|hint| F::from(hint.is_finite() && hint.same_x && hint.same_y)),
+    )?;
+
+    // chord: slope * (x2 - x1) == y2 - y1
+    let slope = cs.new_witness_variable(|| {
+        hinted(|hint| hint.slope).ok_or(SynthesisError::AssignmentMissing)
+    })?;
+    let rise = multiply(
+        lc!() + slope,
+        lc!() + x2 - x1,
+        hinted(|hint| hint.slope * (hint.x2 - hint.x1)),
+    )?;
+    cs.enforce_constraint(lc!() + chord, lc!() + rise - y2 + y1, lc!())?;
+    // tangent: 2 * slope * y1 == 3 * x1^2 + a
+    let slope_y1 = multiply(
+        lc!() + slope,
+        lc!() + y1,
+        hinted(|hint| hint.slope * hint.y1),
+    )?;
+    cs.enforce_constraint(
+        lc!() + tangent,
+        lc!() + (F::from(2u64), slope_y1) - (F::from(3u64), x1_square) - (a, Variable::One),
+        lc!(),
+    )?;
+
+    // The finite sum, with x == slope^2 - x1 - x2 and y == slope * (x1 - x) - y1
+    let slope_square = multiply(
+        lc!() + slope,
+        lc!() + slope,
+        hinted(|hint| hint.slope.square()),
+    )?;
+    let sum_x = lc!() + slope_square - x1 - x2;
+    let sum_y = cs.new_witness_variable(|| {
+        hinted(|hint| hint.sum_y()).ok_or(SynthesisError::AssignmentMissing)
+    })?;
+    cs.enforce_constraint(
+        lc!() + slope,
+        lc!() + x1 - sum_x.clone(),
+        lc!() + sum_y + y1,
+    )?;
+
+    // The output is the right operand if the left one is infinite, the left operand if
+    // only the right one is, and otherwise the finite sum unless the operands cancel out.
+    // `finite * (1 - same_x + doubling)` selects the finite sum.
+    let sum_is_finite = multiply(
+        lc!() + finite,
+        one() - same_x + doubling,
+        hinted(|hint| F::from(hint.is_finite() && !hint.cancels())),
+    )?;
+    let coordinates = [(x1, x2, sum_x, x3), (y1, y2, lc!() + sum_y, y3)];
+    for (lhs, rhs, sum, output) in coordinates {
+        let from_rhs = multiply(
+            lc!() + i1,
+            lc!() + rhs,
+            value(i1).zip(value(rhs)).map(|(i1, rhs)| i1 * rhs),
+        )?;
+        let from_lhs = multiply(
+            lc!() + only_rhs_infinite,
+            lc!() + lhs,
+            value(only_rhs_infinite)
+                .zip(value(lhs))
+                .map(|(only_rhs_infinite, lhs)| only_rhs_infinite * lhs),
+        )?;
+        cs.enforce_constraint(
+            lc!() + sum_is_finite,
+            sum,
+            lc!() + output - from_rhs - from_lhs,
+        )?;
+    }
+    // is_infinite == i1 * i2 + finite * (same_x - doubling)
+    let both_infinite = multiply(
+        lc!() + i1,
+        lc!() + i2,
+        hinted(|hint| F::from(hint.lhs_infinite && hint.rhs_infinite)),
+    )?;
+    cs.enforce_constraint(
+        lc!() + finite,
+        lc!() + same_x - doubling,
+        lc!() + i3 - both_infinite,
+    )?;
+    Ok(())
+}
+
+/// Enforces every multi-scalar multiplication. Fails with `SynthesisError::Unsatisfiable` if
+/// there are any but the field has no embedded curve.
+pub(crate) fn enforce_multi_scalar_muls<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    gates: &[MultiScalarMulGate],
+    variables: &BTreeMap<Witness, Variable>,
+) -> Result<(), SynthesisError> {
+    if gates.is_empty() {
+        return Ok(());
+    }
+    let coefficients = coefficients::<F>().ok_or(SynthesisError::Unsatisfiable)?;
+
+    for gate in gates {
+        let point = |point: &PointWitnesses| point.witnesses().map(|witness| variables[&witness]);
+        let mut infinity = [Variable::One; 3];
+        for (variable, coordinate) in infinity.iter_mut().zip([F::zero(), F::zero(), F::one()]) {
+            *variable = cs.new_witness_variable(|| Ok(coordinate))?;
+            cs.enforce_constraint(
+                lc!() + Variable::One,
+                lc!() + (coordinate, Variable::One),
+                lc!() + *variable,
+            )?;
+        }
+
+        let mut sum = None;
+        for (point_witnesses, (lo, hi)) in gate.points.iter().zip(&gate.scalars) {
+            let point = point(point_witnesses);
+            // The bits of the scalar, most significant first
+            let mut bits = enforce_bit_decomposition(cs, variables[lo], SCALAR_LIMB_BITS)?;
+            bits.extend(enforce_bit_decomposition(
+                cs,
+                variables[hi],
+                SCALAR_LIMB_BITS,
+            )?);
+            bits.reverse();
+
+            let product = alloc_product(cs, &bits, point, infinity, coefficients)?;
+            sum = Some(match sum {
+                None => product,
+                Some(sum) => alloc_sum(cs, sum, product, coefficients)?,
+            });
+        }
+
+        for (sum, output) in sum.unwrap_or(infinity).into_iter().zip(point(&gate.output)) {
+            cs.enforce_constraint(lc!() + Variable::One, lc!() + sum, lc!() + output)?;
+        }
+    }
+    Ok(())
+}
+
+/// Allocates `lhs + rhs` and enforces it with [`enforce_addition`].
+pub(crate) fn alloc_sum<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    lhs: [Variable; 3],
+    rhs: [Variable; 3],
+    coefficients: (F, F),
+) -> Result<[Variable; 3], SynthesisError> {
+    let values = |point: [Variable; 3]| {
+        let [x, y, is_infinite] = point.map(|variable| cs.assigned_value(variable));
+        Some([x?, y?, is_infinite?])
+    };
+    let sum = values(lhs)
+        .zip(values(rhs))
+        .map(|(lhs, rhs)| add_values(lhs, rhs, coefficients.0));
+    let mut output = [Variable::One; 3];
+    for (coordinate, variable) in output.iter_mut().enumerate() {
+        *variable = cs.new_witness_variable(|| {
+            sum.map(|sum| sum[coordinate])
+                .ok_or(SynthesisError::AssignmentMissing)
+        })?;
+    }
+    enforce_addition(cs, lhs, rhs, output, coefficients)?;
+    Ok(output)
+}
+
+/// Allocates the product of `point` with the scalar whose `bits` are given from the most
+/// significant, by double-and-add from `infinity`, in 3 constraints then 71 per bit after the
+/// first.
+pub(crate) fn alloc_product<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    bits: &[Variable],
+    point: [Variable; 3],
+    infinity: [Variable; 3],
+    coefficients: (F, F),
+) -> Result<[Variable; 3], SynthesisError> {
+    let (top, bits) = bits.split_first().expect("the scalar has bits");
+    let mut product = select(cs, *top, point, infinity)?;
+    for bit in bits {
+        let doubled = alloc_sum(cs, product, product, coefficients)?;
+        let added = alloc_sum(cs, doubled, point, coefficients)?;
+        product = select(cs, *bit, added, doubled)?;
+    }
+    Ok(product)
+}
+
+/// Allocates the point `when_true` if `bit` is one and `when_false` otherwise, in 3 constraints.
+pub(crate) fn select<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    bit: Variable,
+    when_true: [Variable; 3],
+    when_false: [Variable; 3],
+) -> Result<[Variable; 3], SynthesisError> {
+    let value = |variable| {
+        cs.assigned_value(variable)
+            .ok_or(SynthesisError::AssignmentMissing)
+    };
+    let mut selected = [Variable::One; 3];
+    for (selected, (when_true, when_false)) in selected
+        .iter_mut()
+        .zip(when_true.into_iter().zip(when_false))
+    {
+        *selected = cs.new_witness_variable(|| {
+            let (bit, when_true, when_false) = (value(bit)?, value(when_true)?, value(when_false)?);
+            Ok(bit * (when_true - when_false) + when_false)
+        })?;
+        // bit * (when_true - when_false) == selected - when_false
+        cs.enforce_constraint(
+            lc!() + bit,
+            lc!() + when_true - when_false,
+            lc!() + *selected - when_false,
+        )?;
+    }
+    Ok(selected)
+}
+
+/// The value [`enforce_addition`] constrains `lhs + rhs` to, on points given as
+/// `[x, y, is_infinite]`.
+pub(crate) fn add_values<F: PrimeField>(lhs: [F; 3], rhs: [F; 3], a: F) -> [F; 3] {
+    let [x1, y1, i1] = lhs;
+    let [x2, y2, i2] = rhs;
+    let hint = AdditionHint::new([x1, y1, i1, x2, y2, i2], a);
+    if hint.lhs_infinite {
+        rhs
+    } else if hint.rhs_infinite {
+        lhs
+    } else if hint.cancels() {
+        [F::zero(), F::zero(), F::one()]
+    } else {
+        [hint.sum_x(), hint.sum_y(), F::zero()]
+    }
+}
+
+/// The values of the intermediate witnesses of an addition.
+struct AdditionHint<F> {
+    x1: F,
+    y1: F,
+    x2: F,
+    lhs_infinite: bool,
+    rhs_infinite: bool,
+    same_x: bool,
+    same_y: bool,
+    slope: F,
+}
+
+impl<F: PrimeField> AdditionHint<F> {
+    fn new([x1, y1, i1, x2, y2, i2]: [F; 6], a: F) -> Self {
+        let mut hint = AdditionHint {
+            x1,
+            y1,
+            x2,
+            lhs_infinite: !i1.is_zero(),
+            rhs_infinite: !i2.is_zero(),
+            same_x: x1 == x2,
+            same_y: y1 == y2,
+            slope: F::zero(),
+        };
+        // An unsatisfiable slope is left zero, the constraints then fail
+        if hint.is_finite() && !hint.same_x {
+            hint.slope = (y2 - y1) * (x2 - x1).inverse().unwrap_or_default();
+        } else if hint.is_finite() && hint.same_y {
+            let numerator = x1.square() * F::from(3u64) + a;
+            hint.slope = numerator * y1.double().inverse().unwrap_or_default();
+        }
+        hint
+    }
+
+    fn is_finite(&self) -> bool {
+        !self.lhs_infinite && !self.rhs_infinite
+    }
+
+    fn cancels(&self) -> bool {
+        self.same_x && !self.same_y
+    }
+
+    fn sum_x(&self) -> F {
+        self.slope.square() - self.x1 - self.x2
+    }
+
+    fn sum_y(&self) -> F {
+        self.slope * (self.x1 - self.sum_x()) - self.y1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use acvm::acir::circuit::opcodes::{BlackBoxFuncCall, FunctionInput};
+    use acvm::acir::circuit::{Circuit, Opcode};
+    use acvm::acir::native_types::WitnessMap;
+    use acvm::FieldElement;
+    use ark_ff::Field;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+
+    use super::*;
+    use crate::field::field_from_hex;
+    use crate::test::circuit_with_opcodes;
+    use crate::CurveAcir;
+
+    fn add(lhs: [u32; 3], rhs: [u32; 3]) -> Opcode<FieldElement> {
+        let input = |index| FunctionInput {
+            witness: Witness(index),
+            num_bits: 254,
+        };
+        Opcode::BlackBoxFuncCall(BlackBoxFuncCall::EmbeddedCurveAdd {
+            input1_x: input(lhs[0]),
+            input1_y: input(lhs[1]),
+            input1_infinite: input(lhs[2]),
+            input2_x: input(rhs[0]),
+            input2_y: input(rhs[1]),
+            input2_infinite: input(rhs[2]),
+            outputs: (Witness(6), Witness(7), Witness(8)),
+        })
+    }
+
+    fn is_satisfied(circuit: &Circuit<FieldElement>, values: &[Fr]) -> bool {
+        let mut witness_map = WitnessMap::new();
+        for (index, value) in values.iter().enumerate() {
+            witness_map.insert(Witness(index as u32), FieldElement::from_repr(*value));
+        }
+        let cs = ConstraintSystem::new_ref();
+        CurveAcir::from((circuit, witness_map))
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert_eq!(cs.num_constraints(), 34);
+        cs.is_satisfied().unwrap()
+    }
+
+    #[test]
+    fn doubles_the_generator() {
+        let (a, b) = coefficients::<Fr>().unwrap();
+        // Grumpkin's generator
+        let (x, y) = (
+            Fr::from(1u64),
+            field_from_hex("0x0000000000000002cf135e7506a45d632d270d45f1181294833fc48d823f272c")
+                .unwrap(),
+        );
+        assert_eq!(y.square(), x.square() * x + a * x + b);
+
+        // Doubling off-circuit, along the tangent
+        let slope = (x.square() * Fr::from(3u64) + a) / y.double();
+        let double_x = slope.square() - x.double();
+        let double_y = slope * (x - double_x) - y;
+        assert_eq!(
+            double_x,
+            field_from_hex("0x06ce1b0827aafa85ddeb49cdaa36306d19a74caa311e13d46d8bc688cdbffffe")
+                .unwrap()
+        );
+
+        let (zero, one) = (Fr::from(0u64), Fr::from(1u64));
+        let circuit = circuit_with_opcodes(8, vec![add([0, 1, 2], [3, 4, 5])], &[]);
+        let generator = [x, y, zero];
+        let lower = |rhs: [Fr; 3], output: [Fr; 3]| {
+            is_satisfied(&circuit, &[generator, rhs, output].concat())
+        };
+        assert!(lower(generator, [double_x, double_y, zero]));
+        assert!(!lower(generator, [double_x, -double_y, zero]));
+        assert!(!lower(generator, [zero, zero, one]));
+
+        // Distinct points take the chord: G + 2G == 3G
+        let slope = (double_y - y) / (double_x - x);
+        let triple_x = slope.square() - x - double_x;
+        let triple_y = slope * (x - triple_x) - y;
+        assert!(lower(
+            [double_x, double_y, zero],
+            [triple_x, triple_y, zero]
+        ));
+
+        // Opposite points cancel out, and infinity is the identity
+        assert!(lower([x, -y, zero], [zero, zero, one]));
+        assert!(!lower([x, -y, zero], [x, y, zero]));
+        assert!(lower([zero, zero, one], generator));
+        // Operands off the curve are rejected
+        assert!(!lower([x, y + one, zero], [zero, zero, one]));
+    }
+
+    #[test]
+    fn sums_multiples_of_the_generator() {
+        let (a, _) = coefficients::<Fr>().unwrap();
+        let (zero, one) = (Fr::from(0u64), Fr::from(1u64));
+        let generator = [
+            one,
+            field_from_hex("0x0000000000000002cf135e7506a45d632d270d45f1181294833fc48d823f272c")
+                .unwrap(),
+            zero,
+        ];
+        let double = add_values(generator, generator, a);
+        let triple = add_values(double, generator, a);
+        let quintuple = add_values(triple, double, a);
+
+        // 2 * G + 3 * G, with the scalars' limbs in w6 to w9 and the output in w10 to w12
+        let input = |index| FunctionInput {
+            witness: Witness(index),
+            num_bits: 254,
+        };
+        let msm = Opcode::BlackBoxFuncCall(BlackBoxFuncCall::MultiScalarMul {
+            points: (0..6).map(input).collect(),
+            scalars: (6..10).map(input).collect(),
+            outputs: (Witness(10), Witness(11), Witness(12)),
+        });
+        let circuit = circuit_with_opcodes(12, vec![msm], &[]);
+        let lower = |scalars: [u64; 2], output: [Fr; 3]| {
+            let scalars = scalars.map(|scalar| [Fr::from(scalar), zero]).concat();
+            let values = [&generator[..], &generator, &scalars, &output].concat();
+            let mut witness_map = WitnessMap::new();
+            for (index, value) in values.iter().enumerate() {
+                witness_map.insert(Witness(index as u32), FieldElement::from_repr(*value));
+            }
+            let cs = ConstraintSystem::new_ref();
+            CurveAcir::from((&circuit, witness_map))
+                .generate_constraints(cs.clone())
+                .unwrap();
+            assert_eq!(cs.num_constraints(), 6 + 2 * 18366 + 34);
+            cs.is_satisfied().unwrap()
+        };
+        assert!(lower([2, 3], quintuple));
+        assert!(!lower([2, 3], triple));
+        // A zero scalar contributes the point at infinity
+        assert!(lower([0, 5], quintuple));
+        assert!(lower([0, 0], [zero, zero, one]));
+        assert!(!lower([0, 0], generator));
+    }
+}
@@ -0,0 +1,183 @@
+use ark_ff::{BigInteger, PrimeField};
+use thiserror::Error;
+
+// Parsing of field elements at the boundary where values enter from bytes or hex.
+//
+// `from_be_bytes_mod_order` and acvm's `from_hex` silently reduce their input modulo the field
+// prime, so `x` and `x + p` parse to the same element. A public input which admits several
+// encodings lets a prover present the same proof under different-looking statements, so these
+// helpers reject any encoding of a value not smaller than the modulus.
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FieldParseError {
+    #[error("Error: `{0}` is not a hex string")]
+    InvalidHex(String),
+    #[error("Error: value is not smaller than the field modulus")]
+    NonCanonical,
+}
+
+/// A field element was converted between fields with different moduli, see [`crate::try_from_fe`].
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("Error: field element belongs to a field with a different modulus")]
+pub struct FieldMismatch;
+
+/// A field element is not smaller than the modulus of the native field, see
+/// [`crate::try_into_native_field`].
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("Error: field element is not smaller than the native field modulus")]
+pub struct FieldConversionError;
+
+/// Parses big-endian `bytes` as a field element, rejecting values not smaller than the modulus.
+///
+/// Leading zero bytes are allowed, so the input may be shorter or longer than a field element.
+pub fn field_from_be_bytes<F: PrimeField>(bytes: &[u8]) -> Result<F, FieldParseError> {
+    let strip = |bytes: &[u8]| {
+        let start = bytes
+            .iter()
+            .position(|byte| *byte != 0)
+            .unwrap_or(bytes.len());
+        bytes[start..].to_vec()
+    };
+    let bytes = strip(bytes);
+    let value = F::from_be_bytes_mod_order(&bytes);
+    if strip(&value.into_bigint().to_bytes_be()) != bytes {
+        return Err(FieldParseError::NonCanonical);
+    }
+    Ok(value)
+}
+
+/// Parses a big-endian hex string, with or without a `0x` prefix, as a field element, rejecting
+/// values not smaller than the modulus.
+pub fn field_from_hex<F: PrimeField>(hex: &str) -> Result<F, FieldParseError> {
+    let invalid = || FieldParseError::InvalidHex(hex.to_string());
+    let digits = hex.strip_prefix("0x").unwrap_or(hex);
+    if digits.is_empty() || !digits.is_ascii() {
+        return Err(invalid());
+    }
+    let padded = if digits.len() % 2 == 1 {
+        format!("0{digits}")
+    } else {
+        digits.to_string()
+    };
+    let bytes = (0..padded.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&padded[i..i + 2], 16).map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    field_from_be_bytes(&bytes)
+}
+
+/// Parses public inputs given as hex strings, in the order the verifier expects them.
+pub fn public_inputs_from_hex<F: PrimeField>(
+    public_inputs: &[&str],
+) -> Result<Vec<F>, FieldParseError> {
+    public_inputs
+        .iter()
+        .map(|public_input| field_from_hex(public_input))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Fr;
+
+    #[test]
+    fn rejects_non_canonical_public_inputs() {
+        let modulus = Fr::MODULUS.to_bytes_be();
+        let mut below_modulus = Fr::MODULUS;
+        below_modulus.sub_with_borrow(&1u64.into());
+
+        assert_eq!(
+            field_from_be_bytes::<Fr>(&modulus),
+            Err(FieldParseError::NonCanonical)
+        );
+        assert_eq!(
+            field_from_be_bytes::<Fr>(&below_modulus.to_bytes_be()),
+            Ok(-Fr::from(1u64))
+        );
+        assert_eq!(field_from_hex::<Fr>("0x0f"), Ok(Fr::from(15u64)));
+        assert!(matches!(
+            field_from_hex::<Fr>("0xzz"),
+            Err(FieldParseError::InvalidHex(_))
+        ));
+
+        // 2 + p encodes the same element as 2
+        let mut aliased = Fr::MODULUS;
+        aliased.add_with_carry(&2u64.into());
+        let aliased = format!(
+            "0x{}",
+            aliased
+                .to_bytes_be()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>()
+        );
+        assert_eq!(
+            public_inputs_from_hex::<Fr>(&["0x02", "0x03"]),
+            Ok(vec![Fr::from(2u64), Fr::from(3u64)])
+        );
+        assert_eq!(
+            public_inputs_from_hex::<Fr>(&["0x02", &aliased]),
+            Err(FieldParseError::NonCanonical)
+        );
+    }
+
+    #[test]
+    fn rejects_elements_of_another_field() {
+        use acvm::acir::acir_field::GenericFieldElement;
+        use acvm::AcirField;
+        use ark_bls12_381::Fq;
+
+        use crate::try_from_fe;
+
+        // -1 in BLS12-381's base field exceeds the scalar field modulus of either curve
+        let minus_one = -GenericFieldElement::<Fq>::one();
+        assert_eq!(try_from_fe::<Fq, Fr>(minus_one), Err(FieldMismatch));
+
+        let minus_one = -GenericFieldElement::<Fr>::one();
+        assert_eq!(try_from_fe::<Fr, Fr>(minus_one), Ok(-Fr::from(1u64)));
+    }
+
+    #[test]
+    fn converts_elements_in_the_native_range() {
+        use acvm::acir::acir_field::GenericFieldElement;
+        use ark_bls12_381::Fq;
+
+        use crate::try_into_native_field;
+
+        // BLS12-381's base field is larger than the scalar field of either curve, so it holds
+        // the native modulus and the values around it
+        let native = |value: Fr| GenericFieldElement::<Fr>::from_repr(value);
+        let wide =
+            |bytes: &[u8]| GenericFieldElement::from_repr(Fq::from_be_bytes_mod_order(bytes));
+        let mut below_modulus = Fr::MODULUS;
+        below_modulus.sub_with_borrow(&1u64.into());
+
+        assert_eq!(
+            try_into_native_field(native(Fr::from(0u64))),
+            Ok(Fr::from(0u64))
+        );
+        assert_eq!(
+            try_into_native_field(native(Fr::from(1u64))),
+            Ok(Fr::from(1u64))
+        );
+        assert_eq!(
+            try_into_native_field(native(-Fr::from(1u64))),
+            Ok(-Fr::from(1u64))
+        );
+        assert_eq!(try_into_native_field(wide(&[])), Ok(Fr::from(0u64)));
+        assert_eq!(try_into_native_field(wide(&[1])), Ok(Fr::from(1u64)));
+        assert_eq!(
+            try_into_native_field(wide(&below_modulus.to_bytes_be())),
+            Ok(-Fr::from(1u64))
+        );
+        assert_eq!(
+            try_into_native_field(wide(&Fr::MODULUS.to_bytes_be())),
+            Err(FieldConversionError)
+        );
+        assert_eq!(
+            try_into_native_field(-GenericFieldElement::<Fq>::from_repr(Fq::from(1u64))),
+            Err(FieldConversionError)
+        );
+    }
+}
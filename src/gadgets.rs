@@ -0,0 +1,553 @@
+use ark_ff::{BigInteger, PrimeField};
+use ark_relations::{
+    lc,
+    r1cs::{ConstraintSystemRef, LinearCombination, SynthesisError, Variable},
+};
+
+// Constraint gadgets shared by the lowering of black box functions and integer operations.
+//
+// The gadgets operate directly on arkworks `Variable`s. Values are read back from the
+// constraint system, so in setup mode (where nothing is assigned) only the shape of the
+// constraints is produced.
+
+/// Allocates the `num_bits` little-endian bits of `value` as witnesses constrained to be boolean.
+///
+/// The bits are not tied to `value`, see [`enforce_bit_decomposition`] for that.
+pub fn alloc_bits<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    value: Option<F>,
+    num_bits: u32,
+) -> Result<Vec<Variable>, SynthesisError> {
+    let bits = value.map(|value| value.into_bigint().to_bits_le());
+
+    (0..num_bits as usize)
+        .map(|i| {
+            let bit = cs.new_witness_variable(|| {
+                let bits = bits.as_ref().ok_or(SynthesisError::AssignmentMissing)?;
+                Ok(F::from(bits.get(i).copied().unwrap_or(false)))
+            })?;
+            // bit * bit == bit
+            cs.enforce_constraint(lc!() + bit, lc!() + bit, lc!() + bit)?;
+            Ok(bit)
+        })
+        .collect()
+}
+
+/// Returns the linear combination `sum(bits[i] * 2^i)`.
+pub fn pack_bits<F: PrimeField>(bits: &[Variable]) -> LinearCombination<F> {
+    let mut packed = lc!();
+    let mut coeff = F::one();
+    for bit in bits {
+        packed += (coeff, *bit);
+        coeff.double_in_place();
+    }
+    packed
+}
+
+/// Decomposes `value` into `num_bits` little-endian boolean witnesses and enforces that they
+/// recompose to `value`, which range-constrains `value` to `num_bits` bits.
+pub fn enforce_bit_decomposition<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    value: Variable,
+    num_bits: u32,
+) -> Result<Vec<Variable>, SynthesisError> {
+    let bits = alloc_bits(cs, cs.assigned_value(value), num_bits)?;
+    cs.enforce_constraint(lc!() + Variable::One, pack_bits(&bits), lc!() + value)?;
+    Ok(bits)
+}
+
+/// Splits the `value_bits`-bit `value` into `(low, high)` such that
+/// `value == low + high * 2^num_bits`, with `low` range-constrained to `num_bits` bits and
+/// `high` to the remaining `value_bits - num_bits` bits.
+///
+/// As `value_bits` is below the field's bit size, the recomposition cannot wrap around the
+/// field modulus, so `low` is bound to the low bits of `value`. This also range-constrains
+/// `value` to `value_bits` bits.
+pub fn reduce_to_bits<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    value: Variable,
+    value_bits: u32,
+    num_bits: u32,
+) -> Result<(Variable, Variable), SynthesisError> {
+    assert!(
+        value_bits < F::MODULUS_BIT_SIZE,
+        "value must be narrower than the field"
+    );
+    assert!(
+        num_bits <= value_bits,
+        "cannot keep more bits than the value has"
+    );
+    let shift = F::from(2u64).pow([u64::from(num_bits)]);
+    let value_val = cs.assigned_value(value);
+    let low_val = value_val.map(|value| {
+        let bits = value.into_bigint().to_bits_le();
+        bits.iter()
+            .take(num_bits as usize)
+            .rev()
+            .fold(F::zero(), |acc, bit| acc.double() + F::from(*bit))
+    });
+    let high_val = value_val
+        .zip(low_val)
+        .map(|(value, low)| (value - low) * shift.inverse().unwrap());
+
+    let bits = alloc_bits(cs, value_val, num_bits)?;
+    let low = cs.new_witness_variable(|| low_val.ok_or(SynthesisError::AssignmentMissing))?;
+    cs.enforce_constraint(lc!() + Variable::One, pack_bits(&bits), lc!() + low)?;
+
+    let high = cs.new_witness_variable(|| high_val.ok_or(SynthesisError::AssignmentMissing))?;
+    enforce_bit_decomposition(cs, high, value_bits - num_bits)?;
+    cs.enforce_constraint(
+        lc!() + Variable::One,
+        lc!() + low + (shift, high),
+        lc!() + value,
+    )?;
+
+    Ok((low, high))
+}
+
+/// The bit width of the limbs [`mul_mod`] and [`modexp`] operate on.
+#[cfg(feature = "gadget-bigint")]
+pub const LIMB_BITS: u32 = 64;
+
+/// Constrains and returns `lhs * rhs mod modulus` for integers given as little-endian
+/// [`LIMB_BITS`]-bit limbs, so that RSA-sized operands are split over as many limbs as they need.
+///
+/// The product is checked as the integer identity `lhs * rhs == quotient * modulus + remainder`
+/// on the limbs, the schoolbook products of the limbs being allocated and each column carried
+/// into the next, as in the lowering of the BigInt black box functions. The carries are
+/// range-constrained so that no column wraps around the field modulus. The quotient has as many
+/// limbs as both operands, as they need not be reduced, and the remainder as many as the
+/// modulus, with `remainder < modulus`. A zero `modulus` is therefore unsatisfiable.
+///
+/// The limbs of `lhs`, `rhs` and `modulus` must already be range-constrained to [`LIMB_BITS`]
+/// bits by the caller.
+#[cfg(feature = "gadget-bigint")]
+pub fn mul_mod<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    lhs: &[Variable],
+    rhs: &[Variable],
+    modulus: &[Variable],
+) -> Result<Vec<Variable>, SynthesisError> {
+    use num_bigint::BigUint;
+
+    let values = limbs::value(cs, lhs)
+        .zip(limbs::value(cs, rhs))
+        .zip(limbs::value(cs, modulus));
+    let division = values.map(|((lhs, rhs), modulus)| {
+        let product = lhs * rhs;
+        let modulus = modulus.max(BigUint::from(1u8));
+        (&product / &modulus, product % modulus)
+    });
+    let quotient = limbs::alloc(
+        cs,
+        division.as_ref().map(|(quotient, _)| quotient),
+        lhs.len() + rhs.len(),
+    )?;
+    let remainder = limbs::alloc(
+        cs,
+        division.as_ref().map(|(_, remainder)| remainder),
+        modulus.len(),
+    )?;
+
+    // lhs * rhs - quotient * modulus - remainder == 0
+    let mut columns = limbs::Columns::new(lhs.len() + rhs.len() + modulus.len() - 1);
+    columns.add_product(cs, lhs, rhs, 1)?;
+    columns.add_product(cs, &quotient, modulus, -1)?;
+    columns.add_limbs(cs, &remainder, -1);
+    columns.enforce_zero(cs)?;
+
+    limbs::enforce_less_than(cs, &remainder, modulus)?;
+    Ok(remainder)
+}
+
+/// Constrains and returns `base^exponent mod modulus` by square-and-multiply, where
+/// `exponent_bits` are the little-endian bits of the exponent and the result has as many limbs
+/// as `modulus`.
+///
+/// The exponent bits must already be constrained to be boolean, e.g. by
+/// [`enforce_bit_decomposition`]. The limbs of `base` and `modulus` must be range-constrained to
+/// [`LIMB_BITS`] bits, see [`mul_mod`]; `base` does not need to be reduced.
+#[cfg(feature = "gadget-bigint")]
+pub fn modexp<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    base: &[Variable],
+    exponent_bits: &[Variable],
+    modulus: &[Variable],
+) -> Result<Vec<Variable>, SynthesisError> {
+    let one = [Variable::One];
+    let base = mul_mod(cs, base, &one, modulus)?;
+    let mut acc = mul_mod(cs, &one, &one, modulus)?;
+
+    for bit in exponent_bits.iter().rev() {
+        let squared = mul_mod(cs, &acc, &acc, modulus)?;
+        let multiplied = mul_mod(cs, &squared, &base, modulus)?;
+
+        // acc = bit ? multiplied : squared, limb by limb
+        acc = squared
+            .iter()
+            .zip(&multiplied)
+            .map(|(squared, multiplied)| {
+                let selected = cs.new_witness_variable(|| {
+                    let bit = cs.assigned_value(*bit);
+                    let squared = cs.assigned_value(*squared);
+                    let multiplied = cs.assigned_value(*multiplied);
+                    bit.zip(squared)
+                        .zip(multiplied)
+                        .map(|((bit, squared), multiplied)| squared + bit * (multiplied - squared))
+                        .ok_or(SynthesisError::AssignmentMissing)
+                })?;
+                cs.enforce_constraint(
+                    lc!() + *bit,
+                    lc!() + *multiplied - *squared,
+                    lc!() + selected - *squared,
+                )?;
+                Ok(selected)
+            })
+            .collect::<Result<_, SynthesisError>>()?;
+    }
+
+    Ok(acc)
+}
+
+/// Limb arithmetic for [`mul_mod`].
+#[cfg(feature = "gadget-bigint")]
+mod limbs {
+    use ark_ff::{BigInteger, PrimeField};
+    use ark_relations::{
+        lc,
+        r1cs::{ConstraintSystemRef, LinearCombination, SynthesisError, Variable},
+    };
+    use num_bigint::{BigInt, BigUint, Sign};
+
+    use super::{enforce_bit_decomposition, LIMB_BITS};
+
+    fn to_field<F: PrimeField>(value: &BigInt) -> F {
+        let magnitude = F::from_le_bytes_mod_order(&value.magnitude().to_bytes_le());
+        match value.sign() {
+            Sign::Minus => -magnitude,
+            _ => magnitude,
+        }
+    }
+
+    fn limb_values<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        limbs: &[Variable],
+    ) -> Option<Vec<BigUint>> {
+        limbs
+            .iter()
+            .map(|limb| {
+                let limb = cs.assigned_value(*limb)?;
+                Some(BigUint::from_bytes_le(&limb.into_bigint().to_bytes_le()))
+            })
+            .collect()
+    }
+
+    /// The integer the little-endian `limbs` stand for, unless in setup mode.
+    pub(super) fn value<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        limbs: &[Variable],
+    ) -> Option<BigUint> {
+        let limbs = limb_values(cs, limbs)?;
+        Some(
+            limbs
+                .into_iter()
+                .rev()
+                .fold(BigUint::default(), |value, limb| {
+                    (value << LIMB_BITS) + limb
+                }),
+        )
+    }
+
+    /// Allocates `value` as `num_limbs` limbs range-constrained to [`LIMB_BITS`] bits.
+    pub(super) fn alloc<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        value: Option<&BigUint>,
+        num_limbs: usize,
+    ) -> Result<Vec<Variable>, SynthesisError> {
+        let mask = (BigUint::from(1u8) << LIMB_BITS) - 1u8;
+        (0..num_limbs)
+            .map(|i| {
+                let limb = cs.new_witness_variable(|| {
+                    let value = value.ok_or(SynthesisError::AssignmentMissing)?;
+                    let limb = (value >> (LIMB_BITS as usize * i)) & &mask;
+                    Ok(F::from_le_bytes_mod_order(&limb.to_bytes_le()))
+                })?;
+                enforce_bit_decomposition(cs, limb, LIMB_BITS)?;
+                Ok(limb)
+            })
+            .collect()
+    }
+
+    /// Enforces `lhs < modulus` by recomputing `lhs + (modulus - lhs - 1) + 1 == modulus` limb
+    /// by limb with boolean carries, the difference being range-constrained like the limbs.
+    pub(super) fn enforce_less_than<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        lhs: &[Variable],
+        modulus: &[Variable],
+    ) -> Result<(), SynthesisError> {
+        let values = value(cs, lhs).zip(value(cs, modulus));
+        let difference = values.map(|(lhs, modulus)| match modulus > lhs {
+            true => modulus - lhs - 1u8,
+            false => BigUint::default(),
+        });
+        let difference = alloc(cs, difference.as_ref(), modulus.len())?;
+        let limb_values = limb_values(cs, lhs).zip(limb_values(cs, &difference));
+
+        let shift = F::from(2u64).pow([LIMB_BITS as u64]);
+        let mut carry = lc!() + Variable::One;
+        let mut carry_value = Some(BigUint::from(1u8));
+        for (i, modulus_limb) in modulus.iter().enumerate() {
+            let sum = carry + lhs[i] + difference[i];
+            if i + 1 == modulus.len() {
+                cs.enforce_constraint(lc!() + Variable::One, sum, lc!() + *modulus_limb)?;
+                break;
+            }
+            carry_value = carry_value
+                .zip(limb_values.as_ref())
+                .map(|(carry, (lhs, difference))| (carry + &lhs[i] + &difference[i]) >> LIMB_BITS);
+            let next = cs.new_witness_variable(|| {
+                carry_value
+                    .as_ref()
+                    .map(|carry| to_field(&carry.clone().into()))
+                    .ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            cs.enforce_constraint(lc!() + next, lc!() + next, lc!() + next)?;
+            cs.enforce_constraint(
+                lc!() + Variable::One,
+                sum,
+                lc!() + *modulus_limb + (shift, next),
+            )?;
+            carry = lc!() + next;
+        }
+        Ok(())
+    }
+
+    /// The columns of an identity between limb polynomials, with their values unless in setup
+    /// mode.
+    pub(super) struct Columns<F: PrimeField> {
+        lcs: Vec<LinearCombination<F>>,
+        values: Option<Vec<BigInt>>,
+    }
+
+    impl<F: PrimeField> Columns<F> {
+        pub(super) fn new(len: usize) -> Self {
+            Columns {
+                lcs: vec![lc!(); len],
+                values: Some(vec![BigInt::default(); len]),
+            }
+        }
+
+        fn add(&mut self, k: usize, sign: i64, lc: LinearCombination<F>, value: Option<BigInt>) {
+            let coeff = to_field::<F>(&BigInt::from(sign));
+            self.lcs[k] = std::mem::replace(&mut self.lcs[k], lc!()) + (coeff, &lc);
+            self.values = self.values.take().zip(value).map(|(mut values, value)| {
+                values[k] += value * sign;
+                values
+            });
+        }
+
+        /// Adds `sign * lhs * rhs`, allocating the product of each pair of limbs.
+        pub(super) fn add_product(
+            &mut self,
+            cs: &ConstraintSystemRef<F>,
+            lhs: &[Variable],
+            rhs: &[Variable],
+            sign: i64,
+        ) -> Result<(), SynthesisError> {
+            let values = limb_values(cs, lhs).zip(limb_values(cs, rhs));
+            for (i, lhs_limb) in lhs.iter().enumerate() {
+                for (j, rhs_limb) in rhs.iter().enumerate() {
+                    let value = values
+                        .as_ref()
+                        .map(|(lhs, rhs)| BigInt::from(&lhs[i] * &rhs[j]));
+                    let product = cs.new_witness_variable(|| {
+                        value
+                            .as_ref()
+                            .map(to_field)
+                            .ok_or(SynthesisError::AssignmentMissing)
+                    })?;
+                    cs.enforce_constraint(lc!() + *lhs_limb, lc!() + *rhs_limb, lc!() + product)?;
+                    self.add(i + j, sign, lc!() + product, value);
+                }
+            }
+            Ok(())
+        }
+
+        /// Adds `sign * limbs`.
+        pub(super) fn add_limbs(
+            &mut self,
+            cs: &ConstraintSystemRef<F>,
+            limbs: &[Variable],
+            sign: i64,
+        ) {
+            let values = limb_values(cs, limbs);
+            for (i, limb) in limbs.iter().enumerate() {
+                let value = values
+                    .as_ref()
+                    .map(|values| BigInt::from(values[i].clone()));
+                self.add(i, sign, lc!() + *limb, value);
+            }
+        }
+
+        /// Enforces that the columns sum to zero as an integer: each column plus the incoming
+        /// carry must be a multiple of `2^64`, with the outgoing carry as the quotient, and the
+        /// last column must cancel the last carry.
+        pub(super) fn enforce_zero(
+            self,
+            cs: &ConstraintSystemRef<F>,
+        ) -> Result<(), SynthesisError> {
+            // A column sums at most one more product of two limbs than there are columns, so a
+            // carry is below twice that many times 2^64, offset to be non-negative
+            let max_terms = self.lcs.len() + 1;
+            let carry_bits = LIMB_BITS + (usize::BITS - max_terms.leading_zeros()) + 2;
+            let radix = F::from(2u64).pow([LIMB_BITS as u64]);
+            let carry_offset = BigInt::from(1u8) << (carry_bits - 1);
+            let mut carry = lc!();
+            let mut carry_value = Some(BigInt::default());
+            for (k, column) in self.lcs.iter().enumerate() {
+                let sum = carry.clone() + column;
+                if k + 1 == self.lcs.len() {
+                    cs.enforce_constraint(lc!() + Variable::One, sum, lc!())?;
+                    break;
+                }
+                carry_value = carry_value
+                    .zip(self.values.as_ref())
+                    .map(|(carry, values)| (carry + &values[k]) >> LIMB_BITS);
+                let shifted = cs.new_witness_variable(|| {
+                    carry_value
+                        .as_ref()
+                        .map(|carry| to_field(&(carry + &carry_offset)))
+                        .ok_or(SynthesisError::AssignmentMissing)
+                })?;
+                enforce_bit_decomposition(cs, shifted, carry_bits)?;
+                carry = lc!() + shifted - (to_field::<F>(&carry_offset), Variable::One);
+                cs.enforce_constraint(lc!() + Variable::One, sum, carry.clone() * radix)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ark_ff::Field;
+    use ark_relations::r1cs::{ConstraintSystem, ConstraintSystemRef};
+
+    use super::*;
+    use crate::Fr;
+
+    #[test]
+    fn reduce_to_bits_splits_low_and_high_parts() {
+        let cs: ConstraintSystemRef<Fr> = ConstraintSystem::new_ref();
+        let value = cs.new_witness_variable(|| Ok(Fr::from(0x1234u64))).unwrap();
+
+        let (low, high) = reduce_to_bits(&cs, value, 16, 8).unwrap();
+        assert_eq!(cs.assigned_value(low), Some(Fr::from(0x34u64)));
+        assert_eq!(cs.assigned_value(high), Some(Fr::from(0x12u64)));
+        // 16 booleanity checks, the packing of both parts and the recomposition
+        assert_eq!(cs.num_constraints(), 19);
+        assert!(cs.is_satisfied().unwrap());
+
+        let set = |cs: &ConstraintSystemRef<Fr>, var: Variable, value: Fr| match var {
+            Variable::Witness(index) => cs.borrow_mut().unwrap().witness_assignment[index] = value,
+            _ => unreachable!(),
+        };
+
+        // a low part which does not match its bits is rejected
+        let cs: ConstraintSystemRef<Fr> = ConstraintSystem::new_ref();
+        let value = cs.new_witness_variable(|| Ok(Fr::from(0x1234u64))).unwrap();
+        let (low, _) = reduce_to_bits(&cs, value, 16, 8).unwrap();
+        set(&cs, low, Fr::from(0x35u64));
+        assert!(!cs.is_satisfied().unwrap());
+
+        // so is another in-range low part, with its bits and the high part which recomposes to
+        // the value adjusted to match
+        let cs: ConstraintSystemRef<Fr> = ConstraintSystem::new_ref();
+        let value = cs.new_witness_variable(|| Ok(Fr::from(0x1234u64))).unwrap();
+        let (low, high) = reduce_to_bits(&cs, value, 16, 8).unwrap();
+        let low_bits = (0..8).map(|i| Variable::Witness(i + 1));
+        for (i, bit) in low_bits.enumerate() {
+            set(&cs, bit, Fr::from((0x35u64 >> i) & 1));
+        }
+        set(&cs, low, Fr::from(0x35u64));
+        let shift_inv = Fr::from(0x100u64).inverse().unwrap();
+        set(
+            &cs,
+            high,
+            (Fr::from(0x1234u64) - Fr::from(0x35u64)) * shift_inv,
+        );
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    #[cfg(feature = "gadget-bigint")]
+    #[test]
+    fn modexp_matches_native_result() {
+        let cs: ConstraintSystemRef<Fr> = ConstraintSystem::new_ref();
+        let witness = |value: u64| cs.new_witness_variable(|| Ok(Fr::from(value))).unwrap();
+        let (base, exponent, modulus) = (witness(3), witness(5), witness(7));
+        let exponent_bits = enforce_bit_decomposition(&cs, exponent, 3).unwrap();
+
+        // 3^5 = 243 = 34 * 7 + 5
+        let result = modexp(&cs, &[base], &exponent_bits, &[modulus]).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(cs.assigned_value(result[0]), Some(Fr::from(5u64)));
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[cfg(feature = "gadget-bigint")]
+    #[test]
+    fn modexp_over_several_limbs() {
+        use num_bigint::BigUint;
+
+        let cs: ConstraintSystemRef<Fr> = ConstraintSystem::new_ref();
+        let limbs = |value: &BigUint| {
+            let limbs = value.to_u64_digits();
+            (0..2)
+                .map(|i| {
+                    let limb = Fr::from(limbs.get(i).copied().unwrap_or(0));
+                    let limb = cs.new_witness_variable(|| Ok(limb)).unwrap();
+                    enforce_bit_decomposition(&cs, limb, LIMB_BITS).unwrap();
+                    limb
+                })
+                .collect::<Vec<_>>()
+        };
+        // the Mersenne prime 2^127 - 1, and a base above it
+        let modulus = (BigUint::from(1u8) << 127u32) - 1u8;
+        let base = (BigUint::from(0xfedc_ba98_7654_3210u64) << 64u32) + 0x0123_4567u64;
+        let exponent = 65537u64;
+
+        let exponent_var = cs.new_witness_variable(|| Ok(Fr::from(exponent))).unwrap();
+        let exponent_bits = enforce_bit_decomposition(&cs, exponent_var, 17).unwrap();
+        let result = modexp(&cs, &limbs(&base), &exponent_bits, &limbs(&modulus)).unwrap();
+
+        let expected = base
+            .modpow(&BigUint::from(exponent), &modulus)
+            .to_u64_digits();
+        assert_eq!(result.len(), 2);
+        for (i, limb) in result.iter().enumerate() {
+            let expected = expected.get(i).copied().unwrap_or(0);
+            assert_eq!(cs.assigned_value(*limb), Some(Fr::from(expected)));
+        }
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[cfg(feature = "gadget-bigint")]
+    #[test]
+    fn mul_mod_accepts_unreduced_operands() {
+        let cs: ConstraintSystemRef<Fr> = ConstraintSystem::new_ref();
+        let witness = |value: u64| cs.new_witness_variable(|| Ok(Fr::from(value))).unwrap();
+        let (lhs, rhs, modulus) = (witness(u64::MAX), witness(u64::MAX), witness(2));
+
+        // (2^64 - 1)^2 is odd, with a quotient over two limbs
+        let result = mul_mod(&cs, &[lhs], &[rhs], &[modulus]).unwrap();
+        assert_eq!(cs.assigned_value(result[0]), Some(Fr::from(1u64)));
+        assert!(cs.is_satisfied().unwrap());
+
+        // a zero modulus leaves no remainder below it
+        let cs: ConstraintSystemRef<Fr> = ConstraintSystem::new_ref();
+        let witness = |value: u64| cs.new_witness_variable(|| Ok(Fr::from(value))).unwrap();
+        let (lhs, rhs, modulus) = (witness(3), witness(3), witness(0));
+        mul_mod(&cs, &[lhs], &[rhs], &[modulus]).unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+}
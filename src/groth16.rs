@@ -0,0 +1,871 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use acvm::acir::circuit::OpcodeLocation;
+use acvm::acir::native_types::{Witness, WitnessMap};
+use acvm::FieldElement;
+use ark_ec::{pairing::Pairing, CurveGroup, VariableBaseMSM};
+use ark_ff::{PrimeField, Zero};
+use ark_groth16::r1cs_to_qap::{LibsnarkReduction, R1CSToQAP};
+use ark_groth16::{Groth16, Proof, ProvingKey, VerifyingKey};
+use ark_poly::GeneralEvaluationDomain;
+use ark_relations::r1cs::{
+    ConstraintMatrices, ConstraintSynthesizer, ConstraintSystem, OptimizationGoal, SynthesisError,
+    SynthesisMode,
+};
+use ark_serialize::CanonicalSerialize;
+use ark_snark::SNARK;
+use ark_std::rand::{CryptoRng, RngCore};
+use ark_std::UniformRand;
+use noirc_abi::{input_parser::InputValue, Abi, AbiVisibility};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::abi::{flatten_input_value, param_witnesses};
+use crate::concrete_cfg::{from_fe, Curve, CurveAcir, Fr};
+
+// Groth16 proving and verification specialised to the curve selected in `concrete_cfg`.
+//
+// The circuit passed to `setup` only needs to have the right shape, its values are ignored.
+// The circuit passed to `prove` must carry the solved witness values.
+
+pub fn setup<R: RngCore + CryptoRng>(
+    circuit: &CurveAcir,
+    rng: &mut R,
+) -> Result<(ProvingKey<Curve>, VerifyingKey<Curve>), SynthesisError> {
+    Groth16::<Curve>::circuit_specific_setup(circuit.clone(), rng)
+}
+
+#[derive(Debug, Error)]
+pub enum ProveError {
+    /// A gate lowered from an opcode carrying an assertion message does not hold.
+    #[error("Error: assertion failed: '{message}'")]
+    AssertionFailed {
+        message: String,
+        source_location: Option<OpcodeLocation>,
+    },
+    #[error("Error: expected {expected} public inputs, found {found}")]
+    PublicInputCountMismatch { expected: usize, found: usize },
+    #[error("Error: the witness map assigns public input {} a different value than the public inputs", .0.0)]
+    PublicInputMismatch(Witness),
+    #[error("Error: could not generate proof: {0}")]
+    Synthesis(SynthesisError),
+}
+
+impl From<SynthesisError> for ProveError {
+    fn from(error: SynthesisError) -> Self {
+        ProveError::Synthesis(error)
+    }
+}
+
+// Fails if a gate does not hold, with the message of the first unsatisfied gate's assertion if
+// it has one.
+fn check_assertions(circuit: &CurveAcir) -> Result<(), ProveError> {
+    let Err(failure) = circuit.check_satisfied() else {
+        return Ok(());
+    };
+    match failure.message {
+        Some(message) => Err(ProveError::AssertionFailed {
+            message,
+            source_location: failure.source_opcode.map(OpcodeLocation::Acir),
+        }),
+        None => Err(ProveError::Synthesis(SynthesisError::Unsatisfiable)),
+    }
+}
+
+/// Proves `circuit`, first checking that its gates hold for the circuit's values.
+///
+/// A failing gate lowered from an opcode with an assertion message is reported as
+/// [`ProveError::AssertionFailed`], any other as [`SynthesisError::Unsatisfiable`].
+pub fn prove<R: RngCore + CryptoRng>(
+    pk: &ProvingKey<Curve>,
+    circuit: &CurveAcir,
+    rng: &mut R,
+) -> Result<Proof<Curve>, ProveError> {
+    check_assertions(circuit)?;
+    Ok(Groth16::<Curve>::prove(pk, circuit.clone(), rng)?)
+}
+
+/// Proves `circuit` with explicitly separated public and private values, ignoring the values
+/// the circuit was lowered with.
+///
+/// `public_inputs` are assigned to the public witnesses in ascending `Witness` order, the
+/// same order [`verify`] expects them in. Every other witness takes its value from
+/// `private_witness`, or zero if it has none. `private_witness` may also hold the public
+/// witnesses, in which case their values must agree with `public_inputs`.
+pub fn prove_with_inputs<R: RngCore + CryptoRng>(
+    pk: &ProvingKey<Curve>,
+    circuit: &CurveAcir,
+    public_inputs: &[Fr],
+    private_witness: &WitnessMap<FieldElement>,
+    rng: &mut R,
+) -> Result<Proof<Curve>, ProveError> {
+    let public_witnesses = &circuit.public_inputs.0;
+    if public_witnesses.len() != public_inputs.len() {
+        return Err(ProveError::PublicInputCountMismatch {
+            expected: public_witnesses.len(),
+            found: public_inputs.len(),
+        });
+    }
+
+    let mut values: BTreeMap<Witness, Fr> = circuit
+        .values
+        .keys()
+        .map(|witness| {
+            let value = private_witness.get(witness).copied().map(from_fe);
+            (*witness, value.unwrap_or_default())
+        })
+        .collect();
+    for (witness, value) in public_witnesses.iter().zip(public_inputs) {
+        if private_witness
+            .get(witness)
+            .map_or(false, |private| from_fe(*private) != *value)
+        {
+            return Err(ProveError::PublicInputMismatch(*witness));
+        }
+        values.insert(*witness, *value);
+    }
+
+    let mut circuit = circuit.clone();
+    circuit.values = values;
+    prove(pk, &circuit, rng)
+}
+
+/// A step of [`prove_with_progress`], reported once it has completed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProvingPhase {
+    /// The constraint system and its assignment have been generated.
+    Synthesis,
+    /// The R1CS assignment has been reduced to the coefficients of the QAP quotient `h`.
+    QapReduction,
+    /// The multi-scalar multiplications over `h` and the auxiliary witnesses, for `C`.
+    MsmC,
+    /// The multi-scalar multiplication computing `A`.
+    MsmA,
+    /// The multi-scalar multiplications computing `B` in G1 and G2.
+    MsmB,
+}
+
+/// Proves like [`prove`], calling `progress` as each [`ProvingPhase`] completes.
+///
+/// arkworks does not expose its prover's intermediate steps, so this performs the same
+/// computation step by step. For the same `rng` the proof is identical to the one of [`prove`].
+pub fn prove_with_progress<R: RngCore + CryptoRng>(
+    pk: &ProvingKey<Curve>,
+    circuit: &CurveAcir,
+    rng: &mut R,
+    mut progress: impl FnMut(ProvingPhase),
+) -> Result<Proof<Curve>, ProveError> {
+    check_assertions(circuit)?;
+    type G1 = <Curve as Pairing>::G1;
+    type G2 = <Curve as Pairing>::G2;
+
+    let r = Fr::rand(rng);
+    let s = Fr::rand(rng);
+
+    let cs = ConstraintSystem::new_ref();
+    cs.set_optimization_goal(OptimizationGoal::Constraints);
+    circuit.clone().generate_constraints(cs.clone())?;
+    cs.finalize();
+    let matrices = cs
+        .to_matrices()
+        .expect("matrices are constructed in prove mode");
+    let cs = cs.borrow().unwrap();
+    let full_assignment = [
+        cs.instance_assignment.as_slice(),
+        cs.witness_assignment.as_slice(),
+    ]
+    .concat();
+    progress(ProvingPhase::Synthesis);
+
+    let h = LibsnarkReduction::witness_map_from_matrices::<Fr, GeneralEvaluationDomain<Fr>>(
+        &matrices,
+        cs.num_instance_variables,
+        cs.num_constraints,
+        &full_assignment,
+    )?;
+    progress(ProvingPhase::QapReduction);
+
+    let h: Vec<_> = h.iter().map(|value| value.into_bigint()).collect();
+    let aux: Vec<_> = cs
+        .witness_assignment
+        .iter()
+        .map(|value| value.into_bigint())
+        .collect();
+    let h_acc = G1::msm_bigint(&pk.h_query, &h);
+    let l_aux_acc = G1::msm_bigint(&pk.l_query, &aux);
+    let r_s_delta_g1 = pk.delta_g1 * (r * s);
+    progress(ProvingPhase::MsmC);
+
+    // The assignment without the constant `1`, which `query[0]` accounts for
+    let assignment: Vec<_> = full_assignment[1..]
+        .iter()
+        .map(|value| value.into_bigint())
+        .collect();
+    let g_a = pk.delta_g1 * r
+        + pk.a_query[0]
+        + G1::msm_bigint(&pk.a_query[1..], &assignment)
+        + pk.vk.alpha_g1;
+    progress(ProvingPhase::MsmA);
+
+    let g1_b = if r.is_zero() {
+        G1::zero()
+    } else {
+        pk.delta_g1 * s
+            + pk.b_g1_query[0]
+            + G1::msm_bigint(&pk.b_g1_query[1..], &assignment)
+            + pk.beta_g1
+    };
+    let g2_b = pk.vk.delta_g2 * s
+        + pk.b_g2_query[0]
+        + G2::msm_bigint(&pk.b_g2_query[1..], &assignment)
+        + pk.vk.beta_g2;
+    progress(ProvingPhase::MsmB);
+
+    let g_c = g_a * s + g1_b * r - r_s_delta_g1 + l_aux_acc + h_acc;
+    Ok(Proof {
+        a: g_a.into_affine(),
+        b: g2_b.into_affine(),
+        c: g_c.into_affine(),
+    })
+}
+
+/// A proving key together with the constraint matrices of the circuit it was generated for.
+///
+/// [`prove`] re-synthesizes the full constraint system, matrices included, on every call. The
+/// matrices only depend on the structure of the circuit, so [`prove_prepared`] reuses the ones
+/// computed once by [`prepare_pk`] and only synthesizes the witness assignment.
+pub struct PreparedProvingKey {
+    pub pk: ProvingKey<Curve>,
+    matrices: ConstraintMatrices<Fr>,
+}
+
+pub fn prepare_pk(
+    pk: ProvingKey<Curve>,
+    circuit: &CurveAcir,
+) -> Result<PreparedProvingKey, SynthesisError> {
+    let cs = ConstraintSystem::new_ref();
+    cs.set_optimization_goal(OptimizationGoal::Constraints);
+    cs.set_mode(SynthesisMode::Setup);
+    circuit.clone().generate_constraints(cs.clone())?;
+    cs.finalize();
+
+    let matrices = cs
+        .to_matrices()
+        .expect("matrices are constructed in setup mode");
+    Ok(PreparedProvingKey { pk, matrices })
+}
+
+/// Proves `circuit` with a key from [`prepare_pk`], which must have been prepared for a circuit
+/// of the same structure.
+pub fn prove_prepared<R: RngCore + CryptoRng>(
+    ppk: &PreparedProvingKey,
+    circuit: &CurveAcir,
+    rng: &mut R,
+) -> Result<Proof<Curve>, ProveError> {
+    check_assertions(circuit)?;
+    let cs = ConstraintSystem::new_ref();
+    cs.set_optimization_goal(OptimizationGoal::Constraints);
+    cs.set_mode(SynthesisMode::Prove {
+        construct_matrices: false,
+    });
+    circuit.clone().generate_constraints(cs.clone())?;
+
+    let cs = cs.borrow().unwrap();
+    let full_assignment = [
+        cs.instance_assignment.as_slice(),
+        cs.witness_assignment.as_slice(),
+    ]
+    .concat();
+
+    Ok(prove_from_assignment(ppk, &full_assignment, rng)?)
+}
+
+/// Proves with an already computed full assignment `[1, public inputs.., witnesses..]`, such as
+/// one read from a snarkjs witness file with [`crate::wtns::read_wtns`].
+pub fn prove_from_assignment<R: RngCore + CryptoRng>(
+    ppk: &PreparedProvingKey,
+    full_assignment: &[Fr],
+    rng: &mut R,
+) -> Result<Proof<Curve>, SynthesisError> {
+    let num_variables = ppk.matrices.num_instance_variables + ppk.matrices.num_witness_variables;
+    if full_assignment.len() != num_variables {
+        return Err(SynthesisError::Unsatisfiable);
+    }
+
+    let r = Fr::rand(rng);
+    let s = Fr::rand(rng);
+    Groth16::<Curve>::create_proof_with_reduction_and_matrices(
+        &ppk.pk,
+        r,
+        s,
+        &ppk.matrices,
+        ppk.matrices.num_instance_variables,
+        ppk.matrices.num_constraints,
+        full_assignment,
+    )
+}
+
+/// Verifies `proof` against the public inputs, given in ascending `Witness` order.
+pub fn verify(
+    vk: &VerifyingKey<Curve>,
+    public_inputs: &[Fr],
+    proof: &Proof<Curve>,
+) -> Result<bool, SynthesisError> {
+    Groth16::<Curve>::verify(vk, public_inputs, proof)
+}
+
+#[derive(Debug, Error)]
+pub enum VerifyError {
+    #[error("Error: verifying key does not match the expected hash")]
+    VkMismatch,
+    #[error("Error: proof is invalid")]
+    InvalidProof,
+    #[error("Error: public outputs do not match the expected value")]
+    OutputMismatch,
+    #[error("Error: could not verify proof: {0}")]
+    Synthesis(SynthesisError),
+}
+
+/// Returns the SHA-256 hash of the compressed serialization of `vk`.
+pub fn vk_hash(vk: &VerifyingKey<Curve>) -> [u8; 32] {
+    let mut bytes = Vec::new();
+    vk.serialize_compressed(&mut bytes)
+        .expect("serializing into a Vec is infallible");
+    Sha256::digest(&bytes).into()
+}
+
+/// Verifies `proof` like [`verify`], after checking that `vk` hashes to `expected_hash`.
+///
+/// A key with a different hash is rejected with [`VerifyError::VkMismatch`] before any
+/// pairing is computed.
+pub fn verify_with_expected_vk_hash(
+    vk: &VerifyingKey<Curve>,
+    expected_hash: &[u8; 32],
+    public_inputs: &[Fr],
+    proof: &Proof<Curve>,
+) -> Result<bool, VerifyError> {
+    if vk_hash(vk) != *expected_hash {
+        return Err(VerifyError::VkMismatch);
+    }
+    verify(vk, public_inputs, proof).map_err(VerifyError::Synthesis)
+}
+
+/// Verifies `proof`, then checks that the program's return value, as found in the public inputs,
+/// equals `expected`.
+///
+/// The public inputs are the ABI's public parameters and the return value in ascending
+/// `Witness` order. Noir allocates the return value to fresh witnesses after the parameters, so
+/// its fields are looked up after those of the public parameters. A return value which aliases
+/// a parameter, as in `fn main(x: pub Field) -> pub Field { x }`, is only stored once among the
+/// public inputs: use [`verify_and_assert_circuit_outputs`] to locate it. A program without a
+/// return value never matches.
+pub fn verify_and_assert_outputs(
+    vk: &VerifyingKey<Curve>,
+    abi: &Abi,
+    public_inputs: &[Fr],
+    proof: &Proof<Curve>,
+    expected: &InputValue,
+) -> Result<(), VerifyError> {
+    let param_witnesses = param_witnesses(abi);
+    let first_return = param_witnesses
+        .values()
+        .flatten()
+        .map(|witness| witness.0 + 1)
+        .max()
+        .unwrap_or(0);
+    let num_returns = abi
+        .return_type
+        .as_ref()
+        .map_or(0, |return_type| return_type.abi_type.field_count());
+    let return_witnesses: Vec<Witness> = (first_return..first_return + num_returns)
+        .map(Witness)
+        .collect();
+    let public_witnesses = abi
+        .parameters
+        .iter()
+        .filter(|param| param.visibility == AbiVisibility::Public)
+        .flat_map(|param| param_witnesses[&param.name].iter().copied())
+        .chain(return_witnesses.iter().copied())
+        .collect();
+    assert_outputs(
+        vk,
+        abi,
+        &public_witnesses,
+        &return_witnesses,
+        public_inputs,
+        proof,
+        expected,
+    )
+}
+
+/// Verifies `proof` like [`verify_and_assert_outputs`], locating each field of the return value
+/// by the rank of its witness among the public witnesses of `circuit`, so that a return value
+/// may alias a parameter.
+///
+/// The return witnesses are taken in ascending `Witness` order.
+pub fn verify_and_assert_circuit_outputs(
+    vk: &VerifyingKey<Curve>,
+    circuit: &CurveAcir,
+    abi: &Abi,
+    public_inputs: &[Fr],
+    proof: &Proof<Curve>,
+    expected: &InputValue,
+) -> Result<(), VerifyError> {
+    let return_witnesses: Vec<Witness> = circuit.return_values.iter().copied().collect();
+    assert_outputs(
+        vk,
+        abi,
+        &circuit.public_inputs.0,
+        &return_witnesses,
+        public_inputs,
+        proof,
+        expected,
+    )
+}
+
+// Verifies `proof`, then checks each field of `expected` against the public input at the rank
+// of its return witness among `public_witnesses`.
+fn assert_outputs(
+    vk: &VerifyingKey<Curve>,
+    abi: &Abi,
+    public_witnesses: &BTreeSet<Witness>,
+    return_witnesses: &[Witness],
+    public_inputs: &[Fr],
+    proof: &Proof<Curve>,
+    expected: &InputValue,
+) -> Result<(), VerifyError> {
+    if !verify(vk, public_inputs, proof).map_err(VerifyError::Synthesis)? {
+        return Err(VerifyError::InvalidProof);
+    }
+
+    let return_type = abi
+        .return_type
+        .as_ref()
+        .ok_or(VerifyError::OutputMismatch)?;
+    let expected =
+        flatten_input_value(&return_type.abi_type, expected).ok_or(VerifyError::OutputMismatch)?;
+    if expected.len() != return_witnesses.len() || public_inputs.len() != public_witnesses.len() {
+        return Err(VerifyError::OutputMismatch);
+    }
+
+    for (witness, expected) in return_witnesses.iter().zip(expected) {
+        let position = public_witnesses.range(..witness).count();
+        if public_inputs.get(position) != Some(&from_fe(expected)) {
+            return Err(VerifyError::OutputMismatch);
+        }
+    }
+    Ok(())
+}
+
+/// Verifies a proof of a circuit bound to a domain separator with
+/// [`AcirCircuit::with_domain`](crate::bridge::AcirCircuit::with_domain).
+///
+/// The domain occupies the first public inputs, so it is prepended to `public_inputs`.
+pub fn verify_with_domain(
+    vk: &VerifyingKey<Curve>,
+    domain: &[Fr],
+    public_inputs: &[Fr],
+    proof: &Proof<Curve>,
+) -> Result<bool, SynthesisError> {
+    let inputs: Vec<Fr> = domain.iter().chain(public_inputs).copied().collect();
+    verify(vk, &inputs, proof)
+}
+
+/// Returns the number of pairings (Miller loops) needed to verify `num_proofs` proofs at once
+/// with randomized batch verification.
+///
+/// A single proof needs three pairings, `e(A, B)`, `e(IC, gamma)` and `e(C, delta)`, against
+/// the precomputed `e(alpha, beta)`. Batching scales each proof's equation by a random factor
+/// and multiplies them together: the `e(A_i, B_i)` pairings remain, but the `gamma` and `delta`
+/// terms fold into one pairing each. Verifying `n` proofs therefore takes `n + 2` pairings rather
+/// than `3n`.
+///
+/// This crate does not implement batch verification itself, so the figure is the theoretical
+/// cost of the standard randomized batching scheme, meant for estimating what a verifier such
+/// as an on-chain relayer would spend. `instance_len` is part of the signature so callers can
+/// describe a batch completely, but it does not change the result: the number of public inputs
+/// only affects the size of the multi-scalar multiplications computing `IC`, not the number of
+/// pairings.
+pub fn batch_verify_pairing_count(num_proofs: usize, _instance_len: usize) -> usize {
+    if num_proofs == 0 {
+        return 0;
+    }
+    num_proofs + 2
+}
+
+/// Verifies each `(public_inputs, proof)` pair independently on the rayon thread pool.
+///
+/// Unlike batch verification, which only reports whether the whole batch is valid, this returns
+/// one result per proof. Proofs whose public inputs are malformed are reported as invalid.
+#[cfg(feature = "parallel")]
+pub fn verify_parallel(vk: &VerifyingKey<Curve>, items: &[(Vec<Fr>, Proof<Curve>)]) -> Vec<bool> {
+    use rayon::prelude::*;
+
+    let pvk = Groth16::<Curve>::process_vk(vk).expect("preparing a verifying key is infallible");
+    items
+        .par_iter()
+        .map(|(public_inputs, proof)| {
+            Groth16::<Curve>::verify_with_processed_vk(&pvk, public_inputs, proof).unwrap_or(false)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+
+    use acvm::acir::circuit::{AssertionPayload, Opcode, PublicInputs};
+    use acvm::acir::native_types::{Expression, Witness};
+    use acvm::blackbox_solver::StubbedBlackBoxSolver;
+    use acvm::pwg::{ACVMStatus, ACVM};
+    use acvm::{AcirField, FieldElement};
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+    use noirc_abi::{AbiParameter, AbiReturnType, AbiType};
+
+    use super::*;
+    use crate::read_program_from_file;
+    use crate::test::{circuit_with_opcodes, test_circuit, test_circuit_witness_map, witness_map};
+
+    #[test]
+    fn proves_compiled_test_circuit() {
+        let circuit_path = env::current_dir()
+            .unwrap()
+            .join("src/artifacts/test_circuit");
+        let program = read_program_from_file::<Fr, _>(circuit_path).unwrap();
+        let circuit = &program.functions[0];
+
+        let initial_witness = witness_map(&[(0, 2), (1, 3), (2, 4), (3, 5)]);
+        let mut acvm = ACVM::new(
+            &StubbedBlackBoxSolver,
+            &circuit.opcodes,
+            initial_witness,
+            &[],
+            &[],
+        );
+        assert_eq!(acvm.solve(), ACVMStatus::Solved);
+        let witness = acvm.finalize();
+        let public_inputs: Vec<Fr> = circuit
+            .public_inputs()
+            .indices()
+            .into_iter()
+            .map(|index| from_fe(witness[&Witness(index)]))
+            .collect();
+
+        let circuit = CurveAcir::from((circuit, witness));
+        let mut rng = StdRng::seed_from_u64(0);
+        let (pk, vk) = setup(&circuit, &mut rng).unwrap();
+        let proof = prove(&pk, &circuit, &mut rng).unwrap();
+        assert!(verify(&vk, &public_inputs, &proof).unwrap());
+
+        let mut tampered = public_inputs;
+        tampered[0] += Fr::from(1u64);
+        assert!(!verify(&vk, &tampered, &proof).unwrap());
+    }
+
+    #[test]
+    fn proof_does_not_verify_in_another_domain() {
+        // assert w1 == w2 with w1 public
+        let circuit = circuit_with_opcodes(
+            2,
+            vec![Opcode::AssertZero(Expression {
+                mul_terms: vec![],
+                linear_combinations: vec![
+                    (FieldElement::one(), Witness(1)),
+                    (-FieldElement::one(), Witness(2)),
+                ],
+                q_c: FieldElement::zero(),
+            })],
+            &[1],
+        );
+        let circuit = CurveAcir::from((&circuit, witness_map(&[(1, 6), (2, 6)])));
+        let in_domain = |domain: u64| circuit.clone().with_domain(vec![Fr::from(domain)]);
+
+        // The keys depend on the length of the domain only
+        let mut rng = StdRng::seed_from_u64(0);
+        let (pk, vk) = setup(&in_domain(0), &mut rng).unwrap();
+        let public_inputs = [Fr::from(6u64)];
+        for domain in [1, 2] {
+            let proof = prove(&pk, &in_domain(domain), &mut rng).unwrap();
+            for other in [1, 2] {
+                assert_eq!(
+                    verify_with_domain(&vk, &[Fr::from(other)], &public_inputs, &proof).unwrap(),
+                    domain == other
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn verify_parallel_reports_each_proof() {
+        let circuit = circuit_with_opcodes(
+            2,
+            vec![Opcode::AssertZero(Expression {
+                mul_terms: vec![],
+                linear_combinations: vec![
+                    (FieldElement::one(), Witness(1)),
+                    (-FieldElement::one(), Witness(2)),
+                ],
+                q_c: FieldElement::zero(),
+            })],
+            &[1],
+        );
+        let mut rng = StdRng::seed_from_u64(0);
+        let (pk, vk) = setup(&CurveAcir::from(&circuit), &mut rng).unwrap();
+
+        let mut prove_equal = |value: i128| {
+            let values = witness_map(&[(1, value), (2, value)]);
+            prove(&pk, &CurveAcir::from((&circuit, values)), &mut rng).unwrap()
+        };
+        let items = vec![
+            (vec![Fr::from(6u64)], prove_equal(6)),
+            (vec![Fr::from(7u64)], prove_equal(6)),
+            (vec![Fr::from(8u64)], prove_equal(8)),
+            (vec![], prove_equal(8)),
+        ];
+
+        assert_eq!(verify_parallel(&vk, &items), vec![true, false, true, false]);
+    }
+
+    #[test]
+    fn prove_prepared_matches_prove() {
+        let circuit = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        let public_inputs: Vec<Fr> = [2u64, 3, 8, 15].into_iter().map(Fr::from).collect();
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let (pk, vk) = setup(&circuit, &mut rng).unwrap();
+        let proof = prove(&pk, &circuit, &mut rng).unwrap();
+        assert!(verify(&vk, &public_inputs, &proof).unwrap());
+
+        let ppk = prepare_pk(pk, &circuit).unwrap();
+        for _ in 0..2 {
+            let proof = prove_prepared(&ppk, &circuit, &mut rng).unwrap();
+            assert!(verify(&vk, &public_inputs, &proof).unwrap());
+        }
+    }
+
+    #[test]
+    fn verify_checks_vk_hash_first() {
+        let circuit = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        let public_inputs: Vec<Fr> = [2u64, 3, 8, 15].into_iter().map(Fr::from).collect();
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let (pk, vk) = setup(&circuit, &mut rng).unwrap();
+        let proof = prove(&pk, &circuit, &mut rng).unwrap();
+
+        let hash = vk_hash(&vk);
+        assert!(verify_with_expected_vk_hash(&vk, &hash, &public_inputs, &proof).unwrap());
+
+        let (_, other_vk) = setup(&circuit, &mut rng).unwrap();
+        assert!(matches!(
+            verify_with_expected_vk_hash(&other_vk, &hash, &public_inputs, &proof),
+            Err(VerifyError::VkMismatch)
+        ));
+    }
+
+    #[test]
+    fn batching_saves_pairings() {
+        let single = batch_verify_pairing_count(1, 4);
+        assert_eq!(single, 3);
+        assert!(batch_verify_pairing_count(10, 4) < 10 * single);
+    }
+
+    #[test]
+    fn progress_reports_each_phase_in_order() {
+        let circuit = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        let public_inputs: Vec<Fr> = [2u64, 3, 8, 15].into_iter().map(Fr::from).collect();
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let (pk, vk) = setup(&circuit, &mut rng).unwrap();
+
+        let mut phases = Vec::new();
+        let proof =
+            prove_with_progress(&pk, &circuit, &mut rng, |phase| phases.push(phase)).unwrap();
+        assert!(verify(&vk, &public_inputs, &proof).unwrap());
+        assert_eq!(
+            phases,
+            vec![
+                ProvingPhase::Synthesis,
+                ProvingPhase::QapReduction,
+                ProvingPhase::MsmC,
+                ProvingPhase::MsmA,
+                ProvingPhase::MsmB,
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_assertion_reports_its_message() {
+        // assert(w1 == w2, "values differ")
+        let mut circuit = circuit_with_opcodes(
+            2,
+            vec![Opcode::AssertZero(Expression {
+                mul_terms: vec![],
+                linear_combinations: vec![
+                    (FieldElement::one(), Witness(1)),
+                    (-FieldElement::one(), Witness(2)),
+                ],
+                q_c: FieldElement::zero(),
+            })],
+            &[1],
+        );
+        circuit.assert_messages = vec![(
+            OpcodeLocation::Acir(0),
+            AssertionPayload::StaticString("values differ".to_owned()),
+        )];
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let (pk, _) = setup(&CurveAcir::from(&circuit), &mut rng).unwrap();
+        let values = witness_map(&[(1, 6), (2, 7)]);
+        let error = prove(&pk, &CurveAcir::from((&circuit, values)), &mut rng).unwrap_err();
+
+        assert!(matches!(
+            error,
+            ProveError::AssertionFailed {
+                source_location: Some(OpcodeLocation::Acir(0)),
+                ..
+            }
+        ));
+        assert!(error.to_string().contains("values differ"));
+    }
+
+    #[test]
+    fn prove_with_separated_inputs() {
+        let structure = CurveAcir::from(&test_circuit());
+        let public_inputs: Vec<Fr> = [2u64, 3, 8, 15].into_iter().map(Fr::from).collect();
+        let private_witness = witness_map(&[(2, 4), (3, 5)]);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let (pk, vk) = setup(&structure, &mut rng).unwrap();
+        let proof =
+            prove_with_inputs(&pk, &structure, &public_inputs, &private_witness, &mut rng).unwrap();
+        assert!(verify(&vk, &public_inputs, &proof).unwrap());
+
+        assert!(matches!(
+            prove_with_inputs(
+                &pk,
+                &structure,
+                &public_inputs[..3],
+                &private_witness,
+                &mut rng
+            ),
+            Err(ProveError::PublicInputCountMismatch {
+                expected: 4,
+                found: 3
+            })
+        ));
+        let conflicting = witness_map(&[(0, 7), (2, 4), (3, 5)]);
+        assert!(matches!(
+            prove_with_inputs(&pk, &structure, &public_inputs, &conflicting, &mut rng),
+            Err(ProveError::PublicInputMismatch(Witness(0)))
+        ));
+    }
+
+    #[test]
+    fn verify_checks_decoded_outputs() {
+        let param = |name: &str, visibility| AbiParameter {
+            name: name.to_owned(),
+            typ: AbiType::Field,
+            visibility,
+        };
+        let abi = Abi {
+            parameters: vec![
+                param("x", AbiVisibility::Public),
+                param("y", AbiVisibility::Public),
+                param("a", AbiVisibility::Private),
+                param("b", AbiVisibility::Private),
+            ],
+            return_type: Some(AbiReturnType {
+                abi_type: AbiType::Array {
+                    length: 2,
+                    typ: Box::new(AbiType::Field),
+                },
+                visibility: AbiVisibility::Public,
+            }),
+            error_types: Default::default(),
+        };
+
+        let circuit = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        let public_inputs: Vec<Fr> = [2u64, 3, 8, 15].into_iter().map(Fr::from).collect();
+        let mut rng = StdRng::seed_from_u64(0);
+        let (pk, vk) = setup(&circuit, &mut rng).unwrap();
+        let proof = prove(&pk, &circuit, &mut rng).unwrap();
+
+        let outputs = |values: [i128; 2]| {
+            InputValue::Vec(
+                values
+                    .into_iter()
+                    .map(|value| InputValue::Field(FieldElement::from(value)))
+                    .collect(),
+            )
+        };
+        let verify_outputs =
+            |values| verify_and_assert_outputs(&vk, &abi, &public_inputs, &proof, &outputs(values));
+        assert!(verify_outputs([8, 15]).is_ok());
+        assert!(matches!(
+            verify_outputs([8, 16]),
+            Err(VerifyError::OutputMismatch)
+        ));
+        // a single output cannot fill the two return fields
+        let single = InputValue::Vec(vec![InputValue::Field(FieldElement::from(8_i128))]);
+        assert!(matches!(
+            verify_and_assert_outputs(&vk, &abi, &public_inputs, &proof, &single),
+            Err(VerifyError::OutputMismatch)
+        ));
+    }
+
+    #[test]
+    fn verify_checks_outputs_aliasing_inputs() {
+        // fn main(x: pub Field, y: pub Field) -> pub Field { assert(x * y == 6); x }
+        let mut circuit = circuit_with_opcodes(
+            1,
+            vec![Opcode::AssertZero(Expression {
+                mul_terms: vec![(FieldElement::one(), Witness(0), Witness(1))],
+                linear_combinations: vec![],
+                q_c: -FieldElement::from(6_i128),
+            })],
+            &[0, 1],
+        );
+        circuit.return_values = PublicInputs(BTreeSet::from([Witness(0)]));
+        let param = |name: &str| AbiParameter {
+            name: name.to_owned(),
+            typ: AbiType::Field,
+            visibility: AbiVisibility::Public,
+        };
+        let abi = Abi {
+            parameters: vec![param("x"), param("y")],
+            return_type: Some(AbiReturnType {
+                abi_type: AbiType::Field,
+                visibility: AbiVisibility::Public,
+            }),
+            error_types: Default::default(),
+        };
+
+        let circuit = CurveAcir::from((&circuit, witness_map(&[(0, 2), (1, 3)])));
+        let public_inputs = [Fr::from(2u64), Fr::from(3u64)];
+        let mut rng = StdRng::seed_from_u64(0);
+        let (pk, vk) = setup(&circuit, &mut rng).unwrap();
+        let proof = prove(&pk, &circuit, &mut rng).unwrap();
+
+        let verify_output = |value: i128| {
+            let expected = InputValue::Field(FieldElement::from(value));
+            verify_and_assert_circuit_outputs(
+                &vk,
+                &circuit,
+                &abi,
+                &public_inputs,
+                &proof,
+                &expected,
+            )
+        };
+        // the return value is x, not the last public input
+        assert!(verify_output(2).is_ok());
+        assert!(matches!(verify_output(3), Err(VerifyError::OutputMismatch)));
+        // the ABI alone cannot tell that x is returned
+        let expected = InputValue::Field(FieldElement::from(2_i128));
+        assert!(matches!(
+            verify_and_assert_outputs(&vk, &abi, &public_inputs, &proof, &expected),
+            Err(VerifyError::OutputMismatch)
+        ));
+    }
+}
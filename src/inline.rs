@@ -0,0 +1,691 @@
+use acvm::acir::acir_field::GenericFieldElement;
+use acvm::acir::circuit::brillig::{BrilligInputs, BrilligOutputs};
+use acvm::acir::circuit::opcodes::{BlackBoxFuncCall, BlockId, FunctionInput, MemOp};
+use acvm::acir::circuit::{Circuit, Opcode, Program};
+use acvm::acir::native_types::{Expression, Witness, WitnessMap};
+use acvm::AcirField;
+use ark_ff::PrimeField;
+use thiserror::Error;
+
+use crate::bridge::AcirCircuit;
+use crate::UnsupportedOpcodeError;
+
+// Inlining of `Opcode::Call` to flatten a multi-function program into a single circuit.
+//
+// Each call is replaced by the opcodes of the called function, with its witnesses and memory
+// blocks moved past those of the caller, plus gates linking the call's inputs to the callee's
+// parameters (its witnesses `0..inputs.len()`, as the ACVM assigns them) and the callee's return
+// values to the call's outputs. Every call gets fresh witnesses, so a function called twice is
+// inlined twice. Witnesses are handed out from a single counter, so a call nested in an inlined
+// function lands past everything inlined before it, whatever its depth. Brillig calls are kept,
+// with their witnesses moved, and still refer to the program's unconstrained functions by id.
+//
+// The flattened circuit has no calls left, so the ACVM solves it in a single pass.
+
+/// Flattens `program` into a single circuit, starting at the function with index `entry`, by
+/// inlining every `Opcode::Call`.
+///
+/// The parameters and return values are those of the entry function. Fails if `entry` is not a
+/// function of `program`, and on recursive calls, calls to unknown functions or with a
+/// predicate, and opcodes which cannot be moved to other witnesses, naming the opcode by its
+/// index in its own function.
+pub fn inline_calls<F: AcirField>(
+    program: &Program<F>,
+    entry: usize,
+) -> Result<Circuit<F>, InlineError> {
+    let main = program
+        .functions
+        .get(entry)
+        .ok_or(InlineError::UnknownFunction(entry))?;
+    let mut inliner = Inliner {
+        functions: &program.functions,
+        next_witness: main.current_witness_index + 1,
+        next_block: next_block_id(main),
+        next_bigint: next_bigint_id(main),
+        call_stack: Vec::new(),
+        opcodes: Vec::new(),
+    };
+    inliner.inline(entry, 0, 0, 0)?;
+
+    Ok(Circuit {
+        current_witness_index: inliner.next_witness - 1,
+        opcodes: inliner.opcodes,
+        // The messages refer to the opcode indices before inlining
+        assert_messages: Vec::new(),
+        ..main.clone()
+    })
+}
+
+/// Why [`inline_calls`] could not flatten a program.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum InlineError {
+    #[error("Error: the program has no function with index {0}")]
+    UnknownFunction(usize),
+    #[error(transparent)]
+    Unsupported(#[from] UnsupportedOpcodeError),
+}
+
+impl<F: PrimeField> AcirCircuit<F> {
+    /// Lowers the function with index `entry` of `program`, inlining the functions it calls.
+    ///
+    /// `witness_map` must be a solution of the flattened circuit, see [`inline_calls`].
+    pub fn from_program(
+        program: &Program<GenericFieldElement<F>>,
+        entry: usize,
+        witness_map: WitnessMap<GenericFieldElement<F>>,
+    ) -> Result<AcirCircuit<F>, InlineError> {
+        let circuit = inline_calls(program, entry)?;
+        Ok(AcirCircuit::from((&circuit, witness_map)))
+    }
+}
+
+struct Inliner<'a, F> {
+    functions: &'a [Circuit<F>],
+    next_witness: u32,
+    next_block: u32,
+    next_bigint: u32,
+    // The functions being inlined, to detect recursion
+    call_stack: Vec<usize>,
+    opcodes: Vec<Opcode<F>>,
+}
+
+impl<F: AcirField> Inliner<'_, F> {
+    // Appends the opcodes of `function`, with its witnesses, blocks and BigInt ids moved by the
+    // offsets.
+    fn inline(
+        &mut self,
+        function: usize,
+        witness_offset: u32,
+        block_offset: u32,
+        bigint_offset: u32,
+    ) -> Result<(), UnsupportedOpcodeError> {
+        self.call_stack.push(function);
+        let functions = self.functions;
+        let witness = |witness: &Witness| Witness(witness.0 + witness_offset);
+        let block = |block_id: &BlockId| BlockId(block_id.0 + block_offset);
+        let bigint = |id: &u32| id + bigint_offset;
+        let expression = |expr: &Expression<F>| Expression {
+            mul_terms: expr
+                .mul_terms
+                .iter()
+                .map(|(coeff, lhs, rhs)| (*coeff, witness(lhs), witness(rhs)))
+                .collect(),
+            linear_combinations: expr
+                .linear_combinations
+                .iter()
+                .map(|(coeff, term)| (*coeff, witness(term)))
+                .collect(),
+            q_c: expr.q_c,
+        };
+        let input = |input: &FunctionInput| FunctionInput {
+            witness: witness(&input.witness),
+            num_bits: input.num_bits,
+        };
+        let unsupported = |index: usize, opcode: &Opcode<F>| {
+            let debug = format!("{opcode:?}");
+            let name = debug.split(|c: char| !c.is_alphanumeric()).next();
+            UnsupportedOpcodeError {
+                index,
+                opcode: name.unwrap_or_default().to_string(),
+            }
+        };
+
+        for (index, opcode) in functions[function].opcodes.iter().enumerate() {
+            let inlined = match opcode {
+                Opcode::AssertZero(expr) => Opcode::AssertZero(expression(expr)),
+                Opcode::MemoryInit {
+                    block_id,
+                    init,
+                    block_type,
+                } => Opcode::MemoryInit {
+                    block_id: block(block_id),
+                    init: init.iter().map(witness).collect(),
+                    block_type: *block_type,
+                },
+                Opcode::MemoryOp {
+                    block_id,
+                    op,
+                    predicate,
+                } => Opcode::MemoryOp {
+                    block_id: block(block_id),
+                    op: MemOp {
+                        operation: expression(&op.operation),
+                        index: expression(&op.index),
+                        value: expression(&op.value),
+                    },
+                    predicate: predicate.as_ref().map(expression),
+                },
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::RANGE { input: range }) => {
+                    Opcode::BlackBoxFuncCall(BlackBoxFuncCall::RANGE {
+                        input: input(range),
+                    })
+                }
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::AND { lhs, rhs, output }) => {
+                    Opcode::BlackBoxFuncCall(BlackBoxFuncCall::AND {
+                        lhs: input(lhs),
+                        rhs: input(rhs),
+                        output: witness(output),
+                    })
+                }
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::XOR { lhs, rhs, output }) => {
+                    Opcode::BlackBoxFuncCall(BlackBoxFuncCall::XOR {
+                        lhs: input(lhs),
+                        rhs: input(rhs),
+                        output: witness(output),
+                    })
+                }
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Poseidon2Permutation {
+                    inputs,
+                    outputs,
+                    len,
+                }) => Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Poseidon2Permutation {
+                    inputs: inputs.iter().map(input).collect(),
+                    outputs: outputs.iter().map(witness).collect(),
+                    len: *len,
+                }),
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Keccakf1600 { inputs, outputs }) => {
+                    Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Keccakf1600 {
+                        inputs: Box::new(std::array::from_fn(|lane| input(&inputs[lane]))),
+                        outputs: Box::new(std::array::from_fn(|lane| witness(&outputs[lane]))),
+                    })
+                }
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Sha256Compression {
+                    inputs,
+                    hash_values,
+                    outputs,
+                }) => Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Sha256Compression {
+                    inputs: Box::new(std::array::from_fn(|word| input(&inputs[word]))),
+                    hash_values: Box::new(std::array::from_fn(|word| input(&hash_values[word]))),
+                    outputs: Box::new(std::array::from_fn(|word| witness(&outputs[word]))),
+                }),
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Blake2s { inputs, outputs }) => {
+                    Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Blake2s {
+                        inputs: inputs.iter().map(input).collect(),
+                        outputs: Box::new(std::array::from_fn(|byte| witness(&outputs[byte]))),
+                    })
+                }
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::PedersenHash {
+                    inputs,
+                    domain_separator,
+                    output,
+                }) => Opcode::BlackBoxFuncCall(BlackBoxFuncCall::PedersenHash {
+                    inputs: inputs.iter().map(input).collect(),
+                    domain_separator: *domain_separator,
+                    output: witness(output),
+                }),
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::SchnorrVerify {
+                    public_key_x,
+                    public_key_y,
+                    signature,
+                    message,
+                    output,
+                }) => Opcode::BlackBoxFuncCall(BlackBoxFuncCall::SchnorrVerify {
+                    public_key_x: input(public_key_x),
+                    public_key_y: input(public_key_y),
+                    signature: Box::new(std::array::from_fn(|byte| input(&signature[byte]))),
+                    message: message.iter().map(input).collect(),
+                    output: witness(output),
+                }),
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::AES128Encrypt {
+                    inputs,
+                    iv,
+                    key,
+                    outputs,
+                }) => Opcode::BlackBoxFuncCall(BlackBoxFuncCall::AES128Encrypt {
+                    inputs: inputs.iter().map(input).collect(),
+                    iv: Box::new(std::array::from_fn(|byte| input(&iv[byte]))),
+                    key: Box::new(std::array::from_fn(|byte| input(&key[byte]))),
+                    outputs: outputs.iter().map(witness).collect(),
+                }),
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntFromLeBytes {
+                    inputs,
+                    modulus,
+                    output,
+                }) => Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntFromLeBytes {
+                    inputs: inputs.iter().map(input).collect(),
+                    modulus: modulus.clone(),
+                    output: bigint(output),
+                }),
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntAdd { lhs, rhs, output }) => {
+                    Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntAdd {
+                        lhs: bigint(lhs),
+                        rhs: bigint(rhs),
+                        output: bigint(output),
+                    })
+                }
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntSub { lhs, rhs, output }) => {
+                    Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntSub {
+                        lhs: bigint(lhs),
+                        rhs: bigint(rhs),
+                        output: bigint(output),
+                    })
+                }
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntMul { lhs, rhs, output }) => {
+                    Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntMul {
+                        lhs: bigint(lhs),
+                        rhs: bigint(rhs),
+                        output: bigint(output),
+                    })
+                }
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntDiv { lhs, rhs, output }) => {
+                    Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntDiv {
+                        lhs: bigint(lhs),
+                        rhs: bigint(rhs),
+                        output: bigint(output),
+                    })
+                }
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntToLeBytes { input, outputs }) => {
+                    Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntToLeBytes {
+                        input: bigint(input),
+                        outputs: outputs.iter().map(witness).collect(),
+                    })
+                }
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::EcdsaSecp256k1 {
+                    public_key_x,
+                    public_key_y,
+                    signature,
+                    hashed_message,
+                    output,
+                }) => Opcode::BlackBoxFuncCall(BlackBoxFuncCall::EcdsaSecp256k1 {
+                    public_key_x: Box::new(std::array::from_fn(|byte| input(&public_key_x[byte]))),
+                    public_key_y: Box::new(std::array::from_fn(|byte| input(&public_key_y[byte]))),
+                    signature: Box::new(std::array::from_fn(|byte| input(&signature[byte]))),
+                    hashed_message: Box::new(std::array::from_fn(|byte| {
+                        input(&hashed_message[byte])
+                    })),
+                    output: witness(output),
+                }),
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::EmbeddedCurveAdd {
+                    input1_x,
+                    input1_y,
+                    input1_infinite,
+                    input2_x,
+                    input2_y,
+                    input2_infinite,
+                    outputs,
+                }) => Opcode::BlackBoxFuncCall(BlackBoxFuncCall::EmbeddedCurveAdd {
+                    input1_x: input(input1_x),
+                    input1_y: input(input1_y),
+                    input1_infinite: input(input1_infinite),
+                    input2_x: input(input2_x),
+                    input2_y: input(input2_y),
+                    input2_infinite: input(input2_infinite),
+                    outputs: (
+                        witness(&outputs.0),
+                        witness(&outputs.1),
+                        witness(&outputs.2),
+                    ),
+                }),
+                Opcode::BlackBoxFuncCall(BlackBoxFuncCall::MultiScalarMul {
+                    points,
+                    scalars,
+                    outputs,
+                }) => Opcode::BlackBoxFuncCall(BlackBoxFuncCall::MultiScalarMul {
+                    points: points.iter().map(input).collect(),
+                    scalars: scalars.iter().map(input).collect(),
+                    outputs: (
+                        witness(&outputs.0),
+                        witness(&outputs.1),
+                        witness(&outputs.2),
+                    ),
+                }),
+                Opcode::Call {
+                    id,
+                    inputs,
+                    outputs,
+                    predicate,
+                } => {
+                    let callee = *id as usize;
+                    let unconditional = predicate
+                        .as_ref()
+                        .map_or(true, |predicate| predicate.to_const() == Some(F::one()));
+                    if !unconditional
+                        || callee >= functions.len()
+                        || self.call_stack.contains(&callee)
+                    {
+                        return Err(unsupported(index, opcode));
+                    }
+
+                    let callee_offset = self.next_witness;
+                    let callee_block_offset = self.next_block;
+                    let callee_bigint_offset = self.next_bigint;
+                    self.next_witness += functions[callee].current_witness_index + 1;
+                    self.next_block += next_block_id(&functions[callee]);
+                    self.next_bigint += next_bigint_id(&functions[callee]);
+
+                    // The ACVM assigns the call's inputs to the callee's first witnesses
+                    for (parameter, input) in inputs.iter().enumerate() {
+                        let parameter = Witness(parameter as u32 + callee_offset);
+                        self.opcodes.push(equal(witness(input), parameter));
+                    }
+                    self.inline(
+                        callee,
+                        callee_offset,
+                        callee_block_offset,
+                        callee_bigint_offset,
+                    )?;
+                    let return_values = functions[callee].return_values.0.iter();
+                    for (output, return_value) in outputs.iter().zip(return_values) {
+                        let return_value = Witness(return_value.0 + callee_offset);
+                        self.opcodes.push(equal(witness(output), return_value));
+                    }
+                    continue;
+                }
+                Opcode::BrilligCall {
+                    id,
+                    inputs,
+                    outputs,
+                    predicate,
+                } => Opcode::BrilligCall {
+                    id: *id,
+                    inputs: inputs
+                        .iter()
+                        .map(|brillig_input| match brillig_input {
+                            BrilligInputs::Single(expr) => BrilligInputs::Single(expression(expr)),
+                            BrilligInputs::Array(exprs) => {
+                                BrilligInputs::Array(exprs.iter().map(expression).collect())
+                            }
+                            BrilligInputs::MemoryArray(block_id) => {
+                                BrilligInputs::MemoryArray(block(block_id))
+                            }
+                        })
+                        .collect(),
+                    outputs: outputs
+                        .iter()
+                        .map(|output| match output {
+                            BrilligOutputs::Simple(output) => {
+                                BrilligOutputs::Simple(witness(output))
+                            }
+                            BrilligOutputs::Array(outputs) => {
+                                BrilligOutputs::Array(outputs.iter().map(witness).collect())
+                            }
+                        })
+                        .collect(),
+                    predicate: predicate.as_ref().map(expression),
+                },
+                _ => return Err(unsupported(index, opcode)),
+            };
+            self.opcodes.push(inlined);
+        }
+
+        self.call_stack.pop();
+        Ok(())
+    }
+}
+
+// lhs - rhs == 0
+fn equal<F: AcirField>(lhs: Witness, rhs: Witness) -> Opcode<F> {
+    Opcode::AssertZero(Expression {
+        mul_terms: Vec::new(),
+        linear_combinations: vec![(F::one(), lhs), (-F::one(), rhs)],
+        q_c: F::zero(),
+    })
+}
+
+// One past the largest block id `circuit` initializes
+fn next_block_id<F>(circuit: &Circuit<F>) -> u32 {
+    circuit
+        .opcodes
+        .iter()
+        .filter_map(|opcode| match opcode {
+            Opcode::MemoryInit { block_id, .. } => Some(block_id.0 + 1),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+// One past the largest BigInt id `circuit` creates
+fn next_bigint_id<F>(circuit: &Circuit<F>) -> u32 {
+    circuit
+        .opcodes
+        .iter()
+        .filter_map(|opcode| match opcode {
+            Opcode::BlackBoxFuncCall(
+                BlackBoxFuncCall::BigIntFromLeBytes { output, .. }
+                | BlackBoxFuncCall::BigIntAdd { output, .. }
+                | BlackBoxFuncCall::BigIntSub { output, .. }
+                | BlackBoxFuncCall::BigIntMul { output, .. }
+                | BlackBoxFuncCall::BigIntDiv { output, .. },
+            ) => Some(output + 1),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use acvm::acir::circuit::PublicInputs;
+    use acvm::blackbox_solver::StubbedBlackBoxSolver;
+    use acvm::pwg::{ACVMStatus, ACVM};
+    use acvm::FieldElement;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+
+    use super::*;
+    use crate::test::{circuit_with_opcodes, witness_map};
+    use crate::CurveAcir;
+
+    #[test]
+    fn inlines_called_functions() {
+        let one = FieldElement::one();
+        // main(w0) -> w2: w1 = square(w0), w2 = w1 + 1
+        let mut main = circuit_with_opcodes(
+            2,
+            vec![
+                Opcode::Call {
+                    id: 1,
+                    inputs: vec![Witness(0)],
+                    outputs: vec![Witness(1)],
+                    predicate: None,
+                },
+                Opcode::AssertZero(Expression {
+                    mul_terms: vec![],
+                    linear_combinations: vec![(one, Witness(1)), (-one, Witness(2))],
+                    q_c: one,
+                }),
+            ],
+            &[0],
+        );
+        main.return_values = PublicInputs(BTreeSet::from([Witness(2)]));
+        // square(w0) -> w1: w1 = w0 * w0
+        let mut square = circuit_with_opcodes(
+            1,
+            vec![Opcode::AssertZero(Expression {
+                mul_terms: vec![(one, Witness(0), Witness(0))],
+                linear_combinations: vec![(-one, Witness(1))],
+                q_c: FieldElement::zero(),
+            })],
+            &[],
+        );
+        square.private_parameters = BTreeSet::from([Witness(0)]);
+        square.return_values = PublicInputs(BTreeSet::from([Witness(1)]));
+        let program = Program {
+            functions: vec![main, square],
+            unconstrained_functions: Vec::new(),
+        };
+
+        let circuit = inline_calls(&program, 0).unwrap();
+        // the linked input, the callee's gate, the linked output and main's gate
+        assert_eq!(circuit.opcodes.len(), 4);
+        let mut acvm = ACVM::new(
+            &StubbedBlackBoxSolver,
+            &circuit.opcodes,
+            witness_map(&[(0, 3)]),
+            &[],
+            &[],
+        );
+        assert_eq!(acvm.solve(), ACVMStatus::Solved);
+        let witness = acvm.finalize();
+        assert_eq!(witness[&Witness(2)], FieldElement::from(10_i128));
+
+        let is_satisfied = |witness| {
+            let cs = ConstraintSystem::new_ref();
+            CurveAcir::from_program(&program, 0, witness)
+                .unwrap()
+                .generate_constraints(cs.clone())
+                .unwrap();
+            cs.is_satisfied().unwrap()
+        };
+        assert!(is_satisfied(witness.clone()));
+
+        // the callee's return value, w4 after inlining, must be the square
+        let mut tampered = witness;
+        tampered.insert(Witness(4), FieldElement::from(8_i128));
+        tampered.insert(Witness(1), FieldElement::from(8_i128));
+        tampered.insert(Witness(2), FieldElement::from(9_i128));
+        assert!(!is_satisfied(tampered));
+    }
+
+    #[test]
+    fn keeps_brillig_calls_of_callees() {
+        use crate::test::{brillig_inverse_circuit, inverse_brillig};
+
+        let one = FieldElement::one();
+        // main(w0) -> w2: w1 = inverse(w0), w2 = w1 + 1
+        let mut main = circuit_with_opcodes(
+            2,
+            vec![
+                Opcode::Call {
+                    id: 1,
+                    inputs: vec![Witness(0)],
+                    outputs: vec![Witness(1)],
+                    predicate: None,
+                },
+                Opcode::AssertZero(Expression {
+                    mul_terms: vec![],
+                    linear_combinations: vec![(one, Witness(1)), (-one, Witness(2))],
+                    q_c: one,
+                }),
+            ],
+            &[0],
+        );
+        main.return_values = PublicInputs(BTreeSet::from([Witness(2)]));
+        let program = Program {
+            functions: vec![main, brillig_inverse_circuit()],
+            unconstrained_functions: vec![inverse_brillig()],
+        };
+
+        // the callee's w0 and w1 are moved to w3 and w4
+        let circuit = inline_calls(&program, 0).unwrap();
+        assert!(circuit.opcodes.contains(&Opcode::BrilligCall {
+            id: 0,
+            inputs: vec![BrilligInputs::Single(Expression::from(Witness(3)))],
+            outputs: vec![BrilligOutputs::Simple(Witness(4))],
+            predicate: None,
+        }));
+        let mut acvm = ACVM::new(
+            &StubbedBlackBoxSolver,
+            &circuit.opcodes,
+            witness_map(&[(0, 4)]),
+            &program.unconstrained_functions,
+            &[],
+        );
+        assert_eq!(acvm.solve(), ACVMStatus::Solved);
+        let witness = acvm.finalize();
+        let inverse = FieldElement::from(4_i128).inverse();
+        assert_eq!(witness[&Witness(2)], inverse + one);
+
+        let cs = ConstraintSystem::new_ref();
+        CurveAcir::from_program(&program, 0, witness)
+            .unwrap()
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        assert_eq!(
+            inline_calls(&program, 2).unwrap_err(),
+            InlineError::UnknownFunction(2)
+        );
+    }
+
+    #[test]
+    fn offsets_nested_calls_cumulatively() {
+        let one = FieldElement::one();
+        let call = |id, input: u32, output: u32| Opcode::Call {
+            id,
+            inputs: vec![Witness(input)],
+            outputs: vec![Witness(output)],
+            predicate: None,
+        };
+        // lhs + rhs + constant == out
+        let add = |lhs: u32, rhs: Option<u32>, constant: FieldElement, out: u32| {
+            let mut linear_combinations = vec![(one, Witness(lhs)), (-one, Witness(out))];
+            linear_combinations.extend(rhs.map(|rhs| (one, Witness(rhs))));
+            Opcode::AssertZero(Expression {
+                mul_terms: vec![],
+                linear_combinations,
+                q_c: constant,
+            })
+        };
+        let function = |current_witness_index, opcodes, return_value| {
+            let mut circuit = circuit_with_opcodes(current_witness_index, opcodes, &[]);
+            circuit.private_parameters = BTreeSet::from([Witness(0)]);
+            circuit.return_values = PublicInputs(BTreeSet::from([Witness(return_value)]));
+            circuit
+        };
+
+        // main(w0) -> w3: w1 = f(w0), w2 = f(w1), w3 = w2 + 1
+        let mut main = circuit_with_opcodes(
+            3,
+            vec![call(1, 0, 1), call(1, 1, 2), add(2, None, one, 3)],
+            &[0],
+        );
+        main.return_values = PublicInputs(BTreeSet::from([Witness(3)]));
+        // f(w0) -> w2: w1 = g(w0), w2 = w1 + w0
+        let f = function(
+            2,
+            vec![call(2, 0, 1), add(1, Some(0), FieldElement::zero(), 2)],
+            2,
+        );
+        // g(w0) -> w1: w1 = w0 * w0
+        let g = function(
+            1,
+            vec![Opcode::AssertZero(Expression {
+                mul_terms: vec![(one, Witness(0), Witness(0))],
+                linear_combinations: vec![(-one, Witness(1))],
+                q_c: FieldElement::zero(),
+            })],
+            1,
+        );
+        let program = Program {
+            functions: vec![main, f, g],
+            unconstrained_functions: Vec::new(),
+        };
+
+        // main's w0..w3, then f at w4..w6 calling g at w7..w8, then f at w9..w11 calling g at
+        // w12..w13
+        let circuit = inline_calls(&program, 0).unwrap();
+        assert_eq!(circuit.current_witness_index, 13);
+        let mut acvm = ACVM::new(
+            &StubbedBlackBoxSolver,
+            &circuit.opcodes,
+            witness_map(&[(0, 3)]),
+            &[],
+            &[],
+        );
+        assert_eq!(acvm.solve(), ACVMStatus::Solved);
+        let witness = acvm.finalize();
+        // f(3) = 9 + 3, f(12) = 144 + 12
+        assert_eq!(witness[&Witness(1)], FieldElement::from(12_i128));
+        assert_eq!(witness[&Witness(3)], FieldElement::from(157_i128));
+        assert_eq!(witness[&Witness(13)], FieldElement::from(144_i128));
+
+        let is_satisfied = |witness| {
+            let cs = ConstraintSystem::new_ref();
+            CurveAcir::from_program(&program, 0, witness)
+                .unwrap()
+                .generate_constraints(cs.clone())
+                .unwrap();
+            cs.is_satisfied().unwrap()
+        };
+        assert!(is_satisfied(witness.clone()));
+
+        // the return value of the innermost call of the second call to f
+        let mut tampered = witness;
+        tampered.insert(Witness(13), FieldElement::from(145_i128));
+        assert!(!is_satisfied(tampered));
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::BTreeMap;
+
+use acvm::acir::native_types::Witness;
+use ark_ff::PrimeField;
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError, Variable};
+
+use crate::R1csSize;
+
+// Lowering of `BlackBoxFuncCall::Keccakf1600` to R1CS.
+//
+// Each of the 25 input lanes is decomposed into 64 little-endian bits, which also
+// range-constrains it, and the 24 rounds are applied to the bits. The rotations and the
+// permutation of the lanes only rewire bits, and XORing a round constant negates bits, so
+// neither costs constraints. Every XOR of two bits costs one constraint, and every `!b & c` of
+// chi one more; a permutation lowers to roughly 150k constraints. The output lanes must
+// recompose from the final bits.
+//
+// The constraints are only generated with the `keccak` feature, as they dwarf most circuits.
+
+/// A single `BlackBoxFuncCall::Keccakf1600`, whose 25 lanes are indexed by `x + 5 * y`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct KeccakGate {
+    pub(crate) inputs: Vec<Witness>,
+    pub(crate) outputs: Vec<Witness>,
+    pub(crate) source_opcode: usize,
+}
+
+impl KeccakGate {
+    pub(crate) fn witnesses(&self) -> impl Iterator<Item = Witness> + '_ {
+        self.inputs.iter().chain(&self.outputs).copied()
+    }
+}
+
+/// Counts the constraints and witnesses [`enforce_keccak_gates`] allocates, none without the
+/// `keccak` feature.
+#[cfg_attr(not(feature = "keccak"), allow(unused_variables))]
+pub(crate) fn r1cs_size(gates: &[KeccakGate]) -> R1csSize {
+    #[cfg(feature = "keccak")]
+    return permutation::r1cs_size(gates);
+
+    #[cfg(not(feature = "keccak"))]
+    R1csSize::default()
+}
+
+/// Enforces every permutation. Without the `keccak` feature this fails with
+/// `SynthesisError::Unsatisfiable` if there are any.
+#[cfg_attr(not(feature = "keccak"), allow(unused_variables))]
+pub(crate) fn enforce_keccak_gates<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    gates: &[KeccakGate],
+    variables: &BTreeMap<Witness, Variable>,
+) -> Result<(), SynthesisError> {
+    #[cfg(feature = "keccak")]
+    return permutation::enforce_keccak_gates(cs, gates, variables);
+
+    #[cfg(not(feature = "keccak"))]
+    match gates.is_empty() {
+        true => Ok(()),
+        false => Err(SynthesisError::Unsatisfiable),
+    }
+}
+
+#[cfg(feature = "keccak")]
+mod permutation {
+    use std::collections::BTreeMap;
+
+    use acvm::acir::native_types::Witness;
+    use ark_ff::PrimeField;
+    use ark_relations::{
+        lc,
+        r1cs::{ConstraintSystemRef, LinearCombination, SynthesisError, Variable},
+    };
+
+    use super::KeccakGate;
+    use crate::gadgets::enforce_bit_decomposition;
+    use crate::R1csSize;
+
+    pub(super) const LANE_BITS: u32 = 64;
+
+    /// The rotation of each lane in rho, indexed by `x + 5 * y`.
+    pub(super) const ROTATIONS: [u32; 25] = [
+        0, 1, 62, 28, 27, 36, 44, 6, 55, 20, 3, 10, 43, 25, 39, 41, 45, 15, 21, 8, 18, 2, 61, 56,
+        14,
+    ];
+
+    /// The constants XORed into the first lane in iota, one per round.
+    pub(super) const ROUND_CONSTANTS: [u64; 24] = [
+        0x0000000000000001,
+        0x0000000000008082,
+        0x800000000000808A,
+        0x8000000080008000,
+        0x000000000000808B,
+        0x0000000080000001,
+        0x8000000080008081,
+        0x8000000000008009,
+        0x000000000000008A,
+        0x0000000000000088,
+        0x0000000080008009,
+        0x000000008000000A,
+        0x000000008000808B,
+        0x800000000000008B,
+        0x8000000000008089,
+        0x8000000000008003,
+        0x8000000000008002,
+        0x8000000000000080,
+        0x000000000000800A,
+        0x800000008000000A,
+        0x8000000080008081,
+        0x8000000000008080,
+        0x0000000080000001,
+        0x8000000080008008,
+    ];
+
+    /// A bit of the state, with its value unless in setup mode.
+    #[derive(Clone)]
+    struct Bit<F: PrimeField> {
+        lc: LinearCombination<F>,
+        value: Option<bool>,
+    }
+
+    pub(super) fn r1cs_size(gates: &[KeccakGate]) -> R1csSize {
+        let lane_bits = LANE_BITS as usize;
+        // Theta XORs 4 lanes into each of the 5 columns, 5 pairs of columns and the result
+        // into each of the 25 lanes, then chi masks and XORs each bit of the 25 lanes
+        let per_round = (5 * 4 + 5 + 25 + 2 * 25) * lane_bits;
+        let per_permutation = ROUND_CONSTANTS.len() * per_round;
+        R1csSize {
+            // The booleanity and recomposition of the input bits, and the recomposition of
+            // the output lanes
+            constraints: gates.len() * (25 * (lane_bits + 1) + per_permutation + 25),
+            witnesses: gates.len() * (25 * lane_bits + per_permutation),
+            public_inputs: 0,
+        }
+    }
+
+    pub(super) fn enforce_keccak_gates<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        gates: &[KeccakGate],
+        variables: &BTreeMap<Witness, Variable>,
+    ) -> Result<(), SynthesisError> {
+        for gate in gates {
+            let mut lanes = gate
+                .inputs
+                .iter()
+                .map(|input| {
+                    let bits = enforce_bit_decomposition(cs, variables[input], LANE_BITS)?;
+                    Ok(bits
+                        .into_iter()
+                        .map(|bit| Bit {
+                            lc: lc!() + bit,
+                            value: cs.assigned_value(bit).map(|value| value.is_one()),
+                        })
+                        .collect::<Vec<_>>())
+                })
+                .collect::<Result<Vec<_>, SynthesisError>>()?;
+
+            for round_constant in ROUND_CONSTANTS {
+                lanes = enforce_round(cs, lanes, round_constant)?;
+            }
+
+            for (lane, output) in lanes.iter().zip(&gate.outputs) {
+                let mut packed = lc!();
+                let mut coeff = F::one();
+                for bit in lane {
+                    packed = packed + (coeff, &bit.lc);
+                    coeff.double_in_place();
+                }
+                cs.enforce_constraint(lc!() + Variable::One, packed, lc!() + variables[output])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn enforce_round<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        lanes: Vec<Vec<Bit<F>>>,
+        round_constant: u64,
+    ) -> Result<Vec<Vec<Bit<F>>>, SynthesisError> {
+        let xor_lanes = |lhs: &[Bit<F>], rhs: &[Bit<F>]| {
+            lhs.iter()
+                .zip(rhs)
+                .map(|(lhs, rhs)| xor(cs, lhs, rhs))
+                .collect::<Result<Vec<_>, SynthesisError>>()
+        };
+
+        // theta
+        let mut columns = Vec::new();
+        for x in 0..5 {
+            let mut column = lanes[x].clone();
+            for y in 1..5 {
+                column = xor_lanes(&column, &lanes[x + 5 * y])?;
+            }
+            columns.push(column);
+        }
+        let mut theta = Vec::new();
+        for x in 0..5 {
+            theta.push(xor_lanes(
+                &columns[(x + 4) % 5],
+                &rotate(&columns[(x + 1) % 5], 1),
+            )?);
+        }
+        let mut lanes = lanes
+            .iter()
+            .enumerate()
+            .map(|(index, lane)| xor_lanes(lane, &theta[index % 5]))
+            .collect::<Result<Vec<_>, SynthesisError>>()?;
+
+        // rho and pi
+        let mut permuted = lanes.clone();
+        for x in 0..5 {
+            for y in 0..5 {
+                permuted[y + 5 * ((2 * x + 3 * y) % 5)] =
+                    rotate(&lanes[x + 5 * y], ROTATIONS[x + 5 * y]);
+            }
+        }
+
+        // chi
+        for x in 0..5 {
+            for y in 0..5 {
+                let lhs = &permuted[x + 5 * y];
+                let negated = &permuted[(x + 1) % 5 + 5 * y];
+                let rhs = &permuted[(x + 2) % 5 + 5 * y];
+                lanes[x + 5 * y] = (0..LANE_BITS as usize)
+                    .map(|i| {
+                        let masked = and_not(cs, &negated[i], &rhs[i])?;
+                        xor(cs, &lhs[i], &masked)
+                    })
+                    .collect::<Result<Vec<_>, SynthesisError>>()?;
+            }
+        }
+
+        // iota
+        for (i, bit) in lanes[0].iter_mut().enumerate() {
+            if round_constant >> i & 1 == 1 {
+                *bit = Bit {
+                    lc: lc!() + Variable::One - &bit.lc,
+                    value: bit.value.map(|value| !value),
+                };
+            }
+        }
+        Ok(lanes)
+    }
+
+    /// Rotates a lane towards its most significant bit.
+    fn rotate<F: PrimeField>(lane: &[Bit<F>], amount: u32) -> Vec<Bit<F>> {
+        let mut rotated = lane.to_vec();
+        rotated.rotate_right(amount as usize);
+        rotated
+    }
+
+    fn xor<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        lhs: &Bit<F>,
+        rhs: &Bit<F>,
+    ) -> Result<Bit<F>, SynthesisError> {
+        let value = lhs.value.zip(rhs.value).map(|(lhs, rhs)| lhs ^ rhs);
+        let output = cs
+            .new_witness_variable(|| value.map(F::from).ok_or(SynthesisError::AssignmentMissing))?;
+        // 2 * lhs * rhs == lhs + rhs - output
+        cs.enforce_constraint(
+            lhs.lc.clone() * F::from(2u64),
+            rhs.lc.clone(),
+            lhs.lc.clone() + &rhs.lc - output,
+        )?;
+        Ok(Bit {
+            lc: lc!() + output,
+            value,
+        })
+    }
+
+    /// Returns `!negated & rhs`.
+    fn and_not<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        negated: &Bit<F>,
+        rhs: &Bit<F>,
+    ) -> Result<Bit<F>, SynthesisError> {
+        let value = negated
+            .value
+            .zip(rhs.value)
+            .map(|(negated, rhs)| !negated & rhs);
+        let output = cs
+            .new_witness_variable(|| value.map(F::from).ok_or(SynthesisError::AssignmentMissing))?;
+        // (1 - negated) * rhs == output
+        cs.enforce_constraint(
+            lc!() + Variable::One - &negated.lc,
+            rhs.lc.clone(),
+            lc!() + output,
+        )?;
+        Ok(Bit {
+            lc: lc!() + output,
+            value,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "keccak"))]
+mod test {
+    use acvm::acir::circuit::opcodes::{BlackBoxFuncCall, FunctionInput};
+    use acvm::acir::circuit::Opcode;
+    use acvm::acir::native_types::WitnessMap;
+    use acvm::FieldElement;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+
+    use super::permutation::{ROTATIONS, ROUND_CONSTANTS};
+    use super::*;
+    use crate::test::circuit_with_opcodes;
+    use crate::CurveAcir;
+
+    // The reference permutation, on lanes indexed by `x + 5 * y`
+    fn keccakf1600(mut lanes: [u64; 25]) -> [u64; 25] {
+        for round_constant in ROUND_CONSTANTS {
+            let columns: Vec<u64> = (0..5)
+                .map(|x| (0..5).fold(0, |acc, y| acc ^ lanes[x + 5 * y]))
+                .collect();
+            for (index, lane) in lanes.iter_mut().enumerate() {
+                let x = index % 5;
+                *lane ^= columns[(x + 4) % 5] ^ columns[(x + 1) % 5].rotate_left(1);
+            }
+            let mut permuted = [0; 25];
+            for x in 0..5 {
+                for y in 0..5 {
+                    permuted[y + 5 * ((2 * x + 3 * y) % 5)] =
+                        lanes[x + 5 * y].rotate_left(ROTATIONS[x + 5 * y]);
+                }
+            }
+            for x in 0..5 {
+                for y in 0..5 {
+                    lanes[x + 5 * y] = permuted[x + 5 * y]
+                        ^ (!permuted[(x + 1) % 5 + 5 * y] & permuted[(x + 2) % 5 + 5 * y]);
+                }
+            }
+            lanes[0] ^= round_constant;
+        }
+        lanes
+    }
+
+    #[test]
+    fn hashes_the_empty_input() {
+        // The padded empty message: a single block of the 136 byte rate
+        let mut inputs = [0u64; 25];
+        inputs[0] = 0x01;
+        inputs[16] = 0x80 << 56;
+        let outputs = keccakf1600(inputs);
+        // keccak256("") = c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470, as
+        // little-endian lanes
+        assert_eq!(
+            outputs[..4],
+            [
+                0x3c23f7860146d2c5,
+                0xc003c7dcb27d7e92,
+                0x3b2782ca53b600e5,
+                0x70a4855d04d8fa7b
+            ]
+        );
+
+        let permutation = Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Keccakf1600 {
+            inputs: Box::new(std::array::from_fn(|index| FunctionInput {
+                witness: Witness(index as u32),
+                num_bits: 64,
+            })),
+            outputs: Box::new(std::array::from_fn(|index| Witness(25 + index as u32))),
+        });
+        let circuit = circuit_with_opcodes(49, vec![permutation], &[]);
+        let lower = |outputs: &[u64; 25]| {
+            let mut witness_map = WitnessMap::new();
+            for (index, lane) in inputs.iter().chain(outputs).enumerate() {
+                witness_map.insert(Witness(index as u32), FieldElement::from(*lane as u128));
+            }
+            CurveAcir::from((&circuit, witness_map))
+        };
+        let is_satisfied = |circuit: CurveAcir| {
+            let cs = ConstraintSystem::new_ref();
+            circuit.generate_constraints(cs.clone()).unwrap();
+            cs.is_satisfied().unwrap()
+        };
+        assert!(is_satisfied(lower(&outputs)));
+
+        let mut tampered = outputs;
+        tampered[0] ^= 1;
+        assert!(!is_satisfied(lower(&tampered)));
+    }
+}
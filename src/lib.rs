@@ -6,7 +6,8 @@
 use acvm::{
     acir::{
         acir_field::GenericFieldElement,
-        circuit::{Circuit, Opcode, Program},
+        circuit::{opcodes::BlackBoxFuncCall, Circuit, Opcode, OpcodeLocation, Program},
+        native_types::{WitnessMap, WitnessStack},
     },
     FieldElement,
 };
@@ -15,19 +16,50 @@ use noirc_artifacts::program::ProgramArtifact;
 use noirc_driver::CompiledProgram;
 use std::{
     collections::BTreeMap,
+    fs::File,
+    io::{BufReader, Read},
+    ops::AddAssign,
     path::{Path, PathBuf},
 };
 
+pub mod abi;
+mod aes;
+mod bigint;
+mod bitwise;
+mod blake2s;
 pub mod bridge;
+pub mod bundle;
+pub mod cache;
 mod concrete_cfg;
+mod dot;
+mod ecdsa;
+mod embedded_curve;
+pub mod field;
+pub mod gadgets;
+pub mod groth16;
+pub mod inline;
+mod keccak;
+mod memory;
+pub mod optimize;
+mod pedersen;
+pub mod poseidon2;
+pub mod prover;
+pub mod r1cs;
+mod schnorr;
 mod serializer;
+mod sha256;
+pub mod test_vector;
+pub mod witness;
+pub mod wtns;
 use fm::FileId;
 use serde::{Deserialize, Serialize};
 
-pub use concrete_cfg::{from_fe, Curve, CurveAcir, Fr};
+#[cfg(feature = "bn254")]
+pub use concrete_cfg::GrumpkinFr;
+pub use concrete_cfg::{from_fe, try_from_fe, try_into_native_field, Curve, CurveAcir, Fr};
 use noirc_abi::Abi;
 use noirc_driver::DebugFile;
-use noirc_errors::debug_info::ProgramDebugInfo;
+use noirc_errors::{debug_info::ProgramDebugInfo, Span};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -36,7 +68,19 @@ pub enum FilesystemError {
     PathNotValid(PathBuf),
     #[error("Error: could not deserialize build program: {0}")]
     ProgramSerializationError(String),
+    #[error(
+        "Error: program was compiled with noir {found}, but this backend supports noir {supported}"
+    )]
+    UnsupportedNoirVersion { found: String, supported: String },
+    #[error("Error: could not deserialize witness file: {0}")]
+    WitnessSerializationError(String),
+    #[error("Error: could not read file: {0}")]
+    Io(#[from] std::io::Error),
 }
+
+/// The `noir_version`s whose ACIR this backend can lower, from the first supported version
+/// (inclusive) to the first unsupported one (exclusive).
+pub const SUPPORTED_NOIR_VERSIONS: (&str, &str) = ("0.30.0", "0.32.0");
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct ProgramArtifactGeneric<F: PrimeField> {
     pub noir_version: String,
@@ -62,36 +106,405 @@ pub struct ProgramArtifactGeneric<F: PrimeField> {
     pub names: Vec<String>,
 }
 
+/// The parts of a program artifact needed to solve and prove the program.
+#[derive(Clone, Debug)]
+pub struct ProgramWithAbi<F: PrimeField> {
+    pub bytecode: Program<GenericFieldElement<F>>,
+    /// Maps the program's named parameters to the witnesses of its main function.
+    pub abi: Abi,
+    /// The names of the program's functions.
+    pub names: Vec<String>,
+}
+
+/// Reads the file at `path`, reporting a missing file as [`FilesystemError::PathNotValid`] and
+/// any other failure with its cause.
+fn read_file(path: PathBuf) -> Result<Vec<u8>, FilesystemError> {
+    match std::fs::read(&path) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            Err(FilesystemError::PathNotValid(path))
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Opens the file at `path` for buffered reading, reporting a missing file like [`read_file`].
+fn open_file(path: PathBuf) -> Result<BufReader<File>, FilesystemError> {
+    match File::open(&path) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            Err(FilesystemError::PathNotValid(path))
+        }
+        result => Ok(BufReader::new(result?)),
+    }
+}
+
+/// Reports a failure of the underlying reader as [`FilesystemError::Io`] and anything else as a
+/// malformed artifact.
+fn artifact_error(err: serde_json::Error) -> FilesystemError {
+    if err.is_io() {
+        FilesystemError::Io(err.into())
+    } else {
+        FilesystemError::ProgramSerializationError(err.to_string())
+    }
+}
+
 pub fn read_program_from_file<F: PrimeField, P: AsRef<Path>>(
     circuit_path: P,
 ) -> Result<Program<GenericFieldElement<F>>, FilesystemError> {
+    read_program_with_abi_from_file(circuit_path).map(|program| program.bytecode)
+}
+
+/// Reads a program artifact from its JSON bytes, e.g. when it was fetched over the network
+/// rather than read from disk.
+pub fn read_program_from_bytes<F: PrimeField>(
+    bytes: &[u8],
+) -> Result<Program<GenericFieldElement<F>>, FilesystemError> {
+    read_program_with_abi_from_bytes(bytes).map(|program| program.bytecode)
+}
+
+/// Reads a program artifact from `reader`, parsing the JSON as it arrives rather than buffering
+/// the whole artifact first.
+///
+/// Wrap unbuffered readers such as a [`File`] in a [`BufReader`].
+pub fn read_program_streaming<F: PrimeField, R: Read>(
+    reader: R,
+) -> Result<Program<GenericFieldElement<F>>, FilesystemError> {
+    read_program_with_abi_streaming(reader).map(|program| program.bytecode)
+}
+
+/// Reads a program artifact like [`read_program_from_file`], keeping its ABI and function names.
+pub fn read_program_with_abi_from_file<F: PrimeField, P: AsRef<Path>>(
+    circuit_path: P,
+) -> Result<ProgramWithAbi<F>, FilesystemError> {
     let file_path = circuit_path.as_ref().with_extension("json");
-    let input_string =
-        std::fs::read(&file_path).map_err(|_| FilesystemError::PathNotValid(file_path))?;
-    let program: ProgramArtifactGeneric<F> = serde_json::from_slice(&input_string)
+    read_program_with_abi_streaming(open_file(file_path)?)
+}
+
+/// Reads a program artifact like [`read_program_from_bytes`], keeping its ABI and function names.
+pub fn read_program_with_abi_from_bytes<F: PrimeField>(
+    bytes: &[u8],
+) -> Result<ProgramWithAbi<F>, FilesystemError> {
+    // Check the version before deserializing the bytecode, whose format may have changed
+    #[derive(Deserialize)]
+    struct VersionedArtifact {
+        noir_version: String,
+    }
+    let artifact: VersionedArtifact = serde_json::from_slice(bytes)
         .map_err(|err| FilesystemError::ProgramSerializationError(err.to_string()))?;
-    Ok(program.bytecode)
+    check_noir_version(&artifact.noir_version)?;
+
+    let program: ProgramArtifactGeneric<F> = serde_json::from_slice(bytes)
+        .map_err(|err| FilesystemError::ProgramSerializationError(err.to_string()))?;
+    Ok(ProgramWithAbi {
+        bytecode: program.bytecode,
+        abi: program.abi,
+        names: program.names,
+    })
+}
+
+/// Reads a program artifact like [`read_program_streaming`], keeping its ABI and function names.
+pub fn read_program_with_abi_streaming<F: PrimeField, R: Read>(
+    reader: R,
+) -> Result<ProgramWithAbi<F>, FilesystemError> {
+    use serde::de::{value::StringDeserializer, IntoDeserializer};
+
+    // The reader can only be consumed once, so the bytecode is kept undecoded until the version
+    // is known to be supported, as its format may have changed. The debug symbols and file map
+    // are skipped without being kept.
+    #[derive(Deserialize)]
+    struct VersionedArtifact {
+        noir_version: String,
+        abi: Option<Abi>,
+        bytecode: Option<String>,
+        names: Option<Vec<String>>,
+    }
+    let artifact: VersionedArtifact = serde_json::from_reader(reader).map_err(artifact_error)?;
+    check_noir_version(&artifact.noir_version)?;
+
+    let missing = |field: &str| {
+        FilesystemError::ProgramSerializationError(format!("missing field `{field}`"))
+    };
+    let bytecode: StringDeserializer<serde_json::Error> = artifact
+        .bytecode
+        .ok_or_else(|| missing("bytecode"))?
+        .into_deserializer();
+    Ok(ProgramWithAbi {
+        bytecode: Program::deserialize_program_base64(bytecode).map_err(artifact_error)?,
+        abi: artifact.abi.ok_or_else(|| missing("abi"))?,
+        names: artifact.names.ok_or_else(|| missing("names"))?,
+    })
+}
+
+/// Returns the debug symbols of `artifact`.
+///
+/// Artifacts store them as gzipped base64 JSON, which is decoded when the artifact is
+/// deserialized.
+pub fn decode_debug_symbols<F: PrimeField>(
+    artifact: &ProgramArtifactGeneric<F>,
+) -> &ProgramDebugInfo {
+    &artifact.debug_symbols
 }
 
-pub fn compute_num_opcodes(acir: &Circuit<FieldElement>) -> u32 {
-    let mut num_opcodes = acir.opcodes.len();
+/// Maps the opcode `opcode_index` of the program's main function to the Noir source it was
+/// compiled from, as the file and the span within it of the innermost call.
+///
+/// Pass [`bridge::ConstraintFailure::source_opcode`] to locate a gate which
+/// [`bridge::AcirCircuit::check_satisfied`] reports. Returns `None` if the opcode has no debug
+/// location or its file is missing from the artifact's `file_map`.
+pub fn source_location_for_gate<F: PrimeField>(
+    artifact: &ProgramArtifactGeneric<F>,
+    opcode_index: usize,
+) -> Option<(FileId, Span)> {
+    let debug_info = decode_debug_symbols(artifact).debug_infos.first()?;
+    let call_stack = debug_info
+        .locations
+        .get(&OpcodeLocation::Acir(opcode_index))?;
+    let location = call_stack.last()?;
+    artifact
+        .file_map
+        .contains_key(&location.file)
+        .then_some((location.file, location.span))
+}
+
+/// Reads the witness of the main function from a gzipped witness stack, as written by
+/// `nargo execute`.
+pub fn read_witness_from_file<F: PrimeField, P: AsRef<Path>>(
+    witness_path: P,
+) -> Result<WitnessMap<GenericFieldElement<F>>, FilesystemError> {
+    let file_path = witness_path.as_ref().with_extension("gz");
+    let input_bytes = read_file(file_path)?;
+
+    let mut witness_stack = WitnessStack::try_from(input_bytes.as_slice())
+        .map_err(|err| FilesystemError::WitnessSerializationError(err.to_string()))?;
+    // The main function is solved last, so its witness is at the top of the stack
+    witness_stack
+        .pop()
+        .map(|item| item.witness)
+        .ok_or_else(|| FilesystemError::WitnessSerializationError("empty witness stack".into()))
+}
+
+/// Checks that `noir_version` (e.g. `0.31.0+<commit>`) lies within [`SUPPORTED_NOIR_VERSIONS`].
+pub fn check_noir_version(noir_version: &str) -> Result<(), FilesystemError> {
+    fn parse(version: &str) -> Option<(u64, u64, u64)> {
+        let release = version.split('+').next()?;
+        let mut parts = release.split('.').map(|part| part.parse().ok());
+        let version = (parts.next()??, parts.next()??, parts.next()??);
+        parts.next().is_none().then_some(version)
+    }
+
+    let (min, max) = SUPPORTED_NOIR_VERSIONS;
+    let supported = parse(noir_version).map_or(false, |version| {
+        parse(min) <= Some(version) && Some(version) < parse(max)
+    });
+    if supported {
+        Ok(())
+    } else {
+        Err(FilesystemError::UnsupportedNoirVersion {
+            found: noir_version.to_string(),
+            supported: format!(">={min}, <{max}"),
+        })
+    }
+}
+
+/// Degree added on top of the constraint count to leave room for zero-knowledge blinding.
+pub const SRS_BLINDING_DEGREE: usize = 2;
 
-    for opcode in acir.opcodes.iter() {
-        match opcode {
+/// Returns the minimum degree a universal SRS (as used by Marlin or PLONK) must support for
+/// `circuit`: its constraint count plus [`SRS_BLINDING_DEGREE`], rounded up to the next power of
+/// two.
+///
+/// The constraints are counted with [`estimate_r1cs_size`], so the circuit needs no values.
+pub fn required_srs_degree(circuit: &CurveAcir) -> usize {
+    (estimate_r1cs_size(circuit).constraints + SRS_BLINDING_DEGREE).next_power_of_two()
+}
+
+/// The size of the R1CS a circuit synthesizes to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct R1csSize {
+    pub constraints: usize,
+    /// The witness variables, including the auxiliary ones the lowering allocates.
+    pub witnesses: usize,
+    /// The instance variables, excluding the constant one.
+    pub public_inputs: usize,
+}
+
+impl AddAssign for R1csSize {
+    fn add_assign(&mut self, other: Self) {
+        self.constraints += other.constraints;
+        self.witnesses += other.witnesses;
+        self.public_inputs += other.public_inputs;
+    }
+}
+
+/// Counts the constraints and variables `circuit` synthesizes to, without building the
+/// constraint system.
+pub fn estimate_r1cs_size(circuit: &CurveAcir) -> R1csSize {
+    circuit.r1cs_size()
+}
+
+/// An opcode which the backend cannot lower.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("Error: opcode {index} ({opcode}) is not supported")]
+pub struct UnsupportedOpcodeError {
+    /// The position of the opcode in the circuit.
+    pub index: usize,
+    /// The name of the opcode's variant, e.g. `BlackBoxFuncCall`.
+    pub opcode: String,
+}
+
+/// Why [`compute_num_opcodes`] could not count a circuit's constraints.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum OpcodeCountError {
+    #[error(transparent)]
+    Unsupported(#[from] UnsupportedOpcodeError),
+    #[error("Error: the circuit lowers to more than {} constraints", u32::MAX)]
+    Overflow,
+}
+
+/// Counts the constraints `circuit` lowers to, failing if they do not fit a `u32`.
+///
+/// Memory opcodes are counted once each, regardless of the selectors a dynamic index needs.
+pub fn compute_num_opcodes(acir: &Circuit<FieldElement>) -> Result<u32, OpcodeCountError> {
+    let mut num_opcodes =
+        u32::try_from(acir.opcodes.len()).map_err(|_| OpcodeCountError::Overflow)?;
+
+    for (index, opcode) in acir.opcodes.iter().enumerate() {
+        let count = match opcode {
             Opcode::AssertZero(arith) => {
                 // Each multiplication term adds an extra constraint
                 // plus one for the linear combination gate.
-                num_opcodes += arith.num_mul_terms() + 1;
+                arith.num_mul_terms() + 1
             }
-            Opcode::Directive(_) => (),
-            _ => unreachable!(
-                "currently we do not support non-arithmetic opcodes {:?}",
-                opcode
-            ),
-        }
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::RANGE { input }) => {
+                // One booleanity constraint per bit plus the recomposition, or just the
+                // booleanity of a single bit.
+                match input.num_bits {
+                    1 => 0,
+                    num_bits => num_bits as usize,
+                }
+            }
+            Opcode::BlackBoxFuncCall(
+                BlackBoxFuncCall::AND { lhs, .. } | BlackBoxFuncCall::XOR { lhs, .. },
+            ) => {
+                // Both operands are decomposed, then one constraint per output bit plus the
+                // recomposition of the output.
+                3 * lhs.num_bits as usize + 2
+            }
+            #[cfg(feature = "keccak")]
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Keccakf1600 { .. }) => {
+                // The 25 input lanes are decomposed into 64 bits each, every round costs 6400
+                // XOR and chi constraints, and the 25 output lanes are recomposed.
+                25 * 65 + 24 * 6400 + 25
+            }
+            #[cfg(feature = "sha256")]
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Sha256Compression { .. }) => {
+                // The message schedule and the 64 rounds on the bits of the words, see the
+                // `sha256` module.
+                27216
+            }
+            #[cfg(feature = "blake2s")]
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Blake2s { inputs, outputs }) => {
+                // The decomposition of the message bytes, 10 rounds of mixing per 64-byte block
+                // and the recomposition of the digest, see the `blake2s` module.
+                let num_blocks = ((inputs.len() + 63) / 64).max(1);
+                9 * inputs.len() + 21664 * num_blocks - 384 + outputs.len()
+            }
+            #[cfg(feature = "pedersen")]
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::PedersenHash { inputs, .. }) => {
+                // Per input the decomposition into bits below the modulus, then a selection and
+                // an addition per bit, see the `pedersen` module.
+                4 + 9906 * inputs.len()
+            }
+            #[cfg(feature = "schnorr")]
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::SchnorrVerify { message, .. }) => {
+                // The two scalar multiplications and the Pedersen hash, then BLAKE2s over the
+                // hash and the message, see the `schnorr` module.
+                let num_blocks = (message.len() + 32 + 63) / 64;
+                58442 + 9 * message.len() + 21664 * num_blocks - 384
+            }
+            #[cfg(feature = "aes")]
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::AES128Encrypt {
+                inputs, outputs, ..
+            }) => {
+                // The byte decompositions, the key schedule, and per block the chaining and the
+                // 10 rounds on the bits of the state, see the `aes` module.
+                let num_blocks = outputs.len() / 16;
+                let padding = outputs.len().saturating_sub(inputs.len());
+                2968 + 9 * inputs.len() + outputs.len() + 11888 * num_blocks - 8 * padding
+            }
+            #[cfg(feature = "bigint")]
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntFromLeBytes { inputs, .. }) => {
+                // The decomposition of the bytes into bits.
+                9 * inputs.len()
+            }
+            #[cfg(feature = "bigint")]
+            Opcode::BlackBoxFuncCall(
+                BlackBoxFuncCall::BigIntAdd { .. } | BlackBoxFuncCall::BigIntSub { .. },
+            ) => {
+                // The reduced result and the identity with its quotient and carries, see the
+                // `bigint` module.
+                1768
+            }
+            #[cfg(feature = "bigint")]
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntMul { .. }) => {
+                // As for an addition, plus the products of the limbs.
+                1784
+            }
+            #[cfg(feature = "bigint")]
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntDiv { .. }) => {
+                // The reduced result and divisor, whether the divisor is zero, and multiplying
+                // the result back.
+                3562
+            }
+            #[cfg(feature = "bigint")]
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntToLeBytes { outputs, .. }) => {
+                // The decomposition of the bytes into bits, and the recomposition of the limbs.
+                9 * outputs.len() + 4
+            }
+            #[cfg(feature = "ecdsa")]
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::EcdsaSecp256k1 { .. }) => {
+                // The byte decompositions, the emulated field arithmetic and the 256 steps of
+                // the scalar multiplication, see the `ecdsa` module.
+                1536072
+            }
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::EmbeddedCurveAdd { .. }) => {
+                // On-curve checks of both operands, the chord and tangent slopes, and the
+                // selection of the output between the operands and their sum.
+                34
+            }
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::MultiScalarMul { points, .. }) => {
+                // Per point the decomposition of its 256-bit scalar and 255 doublings,
+                // additions and selections, then the sum of the products, see the
+                // `embedded_curve` module.
+                let num_terms = points.len() / 3;
+                6 + 18366 * num_terms + 34 * num_terms.saturating_sub(1)
+            }
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Poseidon2Permutation { inputs, .. }) => {
+                // Three constraints per `x^5` S-box of Noir's 8 full and 56 partial rounds, plus
+                // one per output.
+                3 * (8 * inputs.len() + 56) + inputs.len()
+            }
+            // Brillig calls are unconstrained: only the gates constraining their outputs count
+            Opcode::Directive(_)
+            | Opcode::MemoryInit { .. }
+            | Opcode::MemoryOp { .. }
+            | Opcode::BrilligCall { .. } => 0,
+            _ => {
+                // The variant name is the Debug output up to its fields
+                let debug = format!("{opcode:?}");
+                let name = debug.split(|c: char| !c.is_alphanumeric()).next();
+                return Err(OpcodeCountError::Unsupported(UnsupportedOpcodeError {
+                    index,
+                    opcode: name.unwrap_or_default().to_string(),
+                }));
+            }
+        };
+        num_opcodes = u32::try_from(count)
+            .ok()
+            .and_then(|count| num_opcodes.checked_add(count))
+            .ok_or(OpcodeCountError::Overflow)?;
     }
 
-    num_opcodes as u32
+    Ok(num_opcodes)
 }
 
 #[cfg(test)]
@@ -105,10 +518,153 @@ mod test {
     use std::env;
 
     use super::*;
+    use crate::bridge::{AcirArithGate, AcirCircuit, CommitmentLayout};
+    use acvm::acir::brillig::{BinaryFieldOp, MemoryAddress, Opcode as BrilligOpcode};
+    use acvm::acir::circuit::brillig::{BrilligBytecode, BrilligInputs, BrilligOutputs};
     use acvm::acir::circuit::{ExpressionWidth, Opcode, PublicInputs};
     use acvm::acir::native_types::{Expression, Witness, WitnessMap};
     use acvm::FieldElement;
 
+    pub(crate) fn circuit_with_opcodes(
+        current_witness_index: u32,
+        opcodes: Vec<Opcode<FieldElement>>,
+        public_parameters: &[u32],
+    ) -> Circuit<FieldElement> {
+        Circuit {
+            expression_width: ExpressionWidth::Unbounded,
+            recursive: false,
+            current_witness_index,
+            opcodes,
+            public_parameters: PublicInputs(
+                public_parameters.iter().copied().map(Witness).collect(),
+            ),
+            return_values: PublicInputs(BTreeSet::new()),
+            private_parameters: BTreeSet::new(),
+            assert_messages: Vec::new(),
+        }
+    }
+
+    /// Mirrors `artifacts/test_circuit.nr`: public inputs `w0, w1`, private inputs `w2, w3` and
+    /// the returned products `w4 = w0 * w2`, `w5 = w1 * w3`.
+    pub(crate) fn test_circuit() -> Circuit<FieldElement> {
+        let product = |lhs: u32, rhs: u32, out: u32| {
+            Opcode::AssertZero(Expression {
+                mul_terms: vec![(FieldElement::one(), Witness(lhs), Witness(rhs))],
+                linear_combinations: vec![(-FieldElement::one(), Witness(out))],
+                q_c: FieldElement::zero(),
+            })
+        };
+        let mut circuit =
+            circuit_with_opcodes(5, vec![product(0, 2, 4), product(1, 3, 5)], &[0, 1]);
+        circuit.private_parameters = BTreeSet::from([Witness(2), Witness(3)]);
+        circuit.return_values = PublicInputs(BTreeSet::from([Witness(4), Witness(5)]));
+        circuit
+    }
+
+    /// A solved witness for [`test_circuit`] with inputs `[2, 3]` and `[4, 5]`.
+    pub(crate) fn test_circuit_witness_map() -> WitnessMap<FieldElement> {
+        witness_map(&[(0, 2), (1, 3), (2, 4), (3, 5), (4, 8), (5, 15)])
+    }
+
+    pub(crate) fn witness_map(values: &[(u32, i128)]) -> WitnessMap<FieldElement> {
+        let mut witness_map = WitnessMap::new();
+        for (index, value) in values {
+            witness_map.insert(Witness(*index), FieldElement::from(*value));
+        }
+        witness_map
+    }
+
+    /// Unconstrained bytecode returning the inverse of its single input.
+    pub(crate) fn inverse_brillig() -> BrilligBytecode<FieldElement> {
+        BrilligBytecode {
+            bytecode: vec![
+                BrilligOpcode::CalldataCopy {
+                    destination_address: MemoryAddress(0),
+                    size: 1,
+                    offset: 0,
+                },
+                BrilligOpcode::Const {
+                    destination: MemoryAddress(1),
+                    bit_size: FieldElement::max_num_bits(),
+                    value: FieldElement::one(),
+                },
+                BrilligOpcode::BinaryFieldOp {
+                    destination: MemoryAddress(0),
+                    op: BinaryFieldOp::Div,
+                    lhs: MemoryAddress(1),
+                    rhs: MemoryAddress(0),
+                },
+                BrilligOpcode::Stop {
+                    return_data_offset: 0,
+                    return_data_size: 1,
+                },
+            ],
+        }
+    }
+
+    /// Returns `w1 = 1 / w0` for the private input `w0`, computed by a Brillig call to
+    /// [`inverse_brillig`] and constrained by `w0 * w1 == 1`.
+    pub(crate) fn brillig_inverse_circuit() -> Circuit<FieldElement> {
+        let mut circuit = circuit_with_opcodes(
+            1,
+            vec![
+                Opcode::BrilligCall {
+                    id: 0,
+                    inputs: vec![BrilligInputs::Single(Expression::from(Witness(0)))],
+                    outputs: vec![BrilligOutputs::Simple(Witness(1))],
+                    predicate: None,
+                },
+                Opcode::AssertZero(Expression {
+                    mul_terms: vec![(FieldElement::one(), Witness(0), Witness(1))],
+                    linear_combinations: vec![],
+                    q_c: -FieldElement::one(),
+                }),
+            ],
+            &[],
+        );
+        circuit.private_parameters = BTreeSet::from([Witness(0)]);
+        circuit.return_values = PublicInputs(BTreeSet::from([Witness(1)]));
+        circuit
+    }
+
+    /// An [`AcirCircuit`] with only arithmetic gates, as most lowering tests need.
+    pub(crate) fn circuit_with_gates(
+        gates: Vec<AcirArithGate<Fr>>,
+        public_inputs: &[Witness],
+        values: &[(Witness, u64)],
+    ) -> CurveAcir {
+        AcirCircuit {
+            gates,
+            public_inputs: PublicInputs(public_inputs.iter().copied().collect()),
+            values: values
+                .iter()
+                .map(|(witness, value)| (*witness, Fr::from(*value)))
+                .collect(),
+            layout: CommitmentLayout::default(),
+            domain: Vec::new(),
+            memory_blocks: Default::default(),
+            private_parameters: Default::default(),
+            return_values: Default::default(),
+            memory_ops: Vec::new(),
+            memory_mode: Default::default(),
+            assert_messages: Default::default(),
+            range_checks: Default::default(),
+            bitwise_gates: Vec::new(),
+            poseidon2_gates: Vec::new(),
+            poseidon_config: None,
+            keccak_gates: Vec::new(),
+            embedded_curve_adds: Vec::new(),
+            multi_scalar_muls: Vec::new(),
+            sha256_gates: Vec::new(),
+            ecdsa_secp256k1_gates: Vec::new(),
+            blake2s_gates: Vec::new(),
+            pedersen_hash_gates: Vec::new(),
+            schnorr_verify_gates: Vec::new(),
+            aes128_encrypt_gates: Vec::new(),
+            bigint_gates: Vec::new(),
+        }
+    }
+
     #[test]
     fn simple_equal() {
         let a = Witness(1);
@@ -170,4 +726,817 @@ mod test {
 
         assert!(res.is_ok())
     }
+
+    #[test]
+    fn reads_abi_alongside_bytecode() {
+        let circuit_path = env::current_dir()
+            .unwrap()
+            .join("src/artifacts/test_circuit");
+        let program = read_program_with_abi_from_file::<Fr, _>(circuit_path).unwrap();
+        assert_eq!(program.abi.parameters.len(), 2);
+        assert_eq!(program.names, ["main"]);
+        assert_eq!(
+            abi::param_witnesses(&program.abi)["private_inputs"],
+            [Witness(2), Witness(3)]
+        );
+    }
+
+    #[test]
+    fn reads_program_from_a_stream() {
+        let circuit_path = env::current_dir()
+            .unwrap()
+            .join("src/artifacts/test_circuit.json");
+        let reader = BufReader::new(File::open(&circuit_path).unwrap());
+        let program = read_program_with_abi_streaming::<Fr, _>(reader).unwrap();
+
+        // Parsing the whole artifact from a slice yields the same program
+        let bytes = std::fs::read(&circuit_path).unwrap();
+        let artifact: ProgramArtifactGeneric<Fr> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(program.bytecode, artifact.bytecode);
+        assert_eq!(program.abi, artifact.abi);
+        assert_eq!(program.names, artifact.names);
+
+        let reader = BufReader::new(File::open(&circuit_path).unwrap());
+        assert_eq!(
+            read_program_streaming::<Fr, _>(reader).unwrap(),
+            read_program_from_bytes::<Fr>(&bytes).unwrap()
+        );
+    }
+
+    #[test]
+    fn transform_hook_negates_values() {
+        let one = FieldElement::one();
+        let negate = |_, value: Fr| -value;
+
+        // assert a == b is invariant under negation
+        let symmetric = circuit_with_opcodes(
+            2,
+            vec![Opcode::AssertZero(Expression {
+                mul_terms: vec![],
+                linear_combinations: vec![(one, Witness(1)), (-one, Witness(2))],
+                q_c: FieldElement::zero(),
+            })],
+            &[1],
+        );
+        let values = witness_map(&[(1, 6), (2, 6)]);
+        let cs = ConstraintSystem::new_ref();
+        CurveAcir::from_with_transform((&symmetric, values), negate)
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        // assert a * b == c is not: (-a) * (-b) != -c
+        let asymmetric = circuit_with_opcodes(
+            3,
+            vec![Opcode::AssertZero(Expression {
+                mul_terms: vec![(one, Witness(1), Witness(2))],
+                linear_combinations: vec![(-one, Witness(3))],
+                q_c: FieldElement::zero(),
+            })],
+            &[1],
+        );
+        let values = witness_map(&[(1, 2), (2, 3), (3, 6)]);
+        let cs = ConstraintSystem::new_ref();
+        CurveAcir::from((&asymmetric, values.clone()))
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        let cs = ConstraintSystem::new_ref();
+        CurveAcir::from_with_transform((&asymmetric, values), negate)
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn infers_widths_from_range_opcodes() {
+        use acvm::acir::circuit::opcodes::{BlackBoxFuncCall, FunctionInput};
+
+        let range = |witness: u32, num_bits: u32| {
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::RANGE {
+                input: FunctionInput {
+                    witness: Witness(witness),
+                    num_bits,
+                },
+            })
+        };
+        let mut circuit = test_circuit();
+        circuit
+            .opcodes
+            .extend([range(2, 32), range(4, 64), range(2, 8)]);
+
+        let lowered = CurveAcir::from((&circuit, test_circuit_witness_map()));
+        assert_eq!(
+            lowered.inferred_witness_widths(),
+            BTreeMap::from([(Witness(2), 8), (Witness(4), 64)])
+        );
+    }
+
+    #[test]
+    fn builds_constraints_over_bls12_381() {
+        use ark_bls12_381::Fr as BlsFr;
+        type F = GenericFieldElement<BlsFr>;
+
+        // w0 * w1 == w2
+        let circuit: Circuit<F> = Circuit {
+            expression_width: ExpressionWidth::Unbounded,
+            recursive: false,
+            current_witness_index: 2,
+            opcodes: vec![Opcode::AssertZero(Expression {
+                mul_terms: vec![(F::one(), Witness(0), Witness(1))],
+                linear_combinations: vec![(-F::one(), Witness(2))],
+                q_c: F::zero(),
+            })],
+            public_parameters: PublicInputs(BTreeSet::from([Witness(0)])),
+            return_values: PublicInputs(BTreeSet::new()),
+            private_parameters: BTreeSet::from([Witness(1)]),
+            assert_messages: Vec::new(),
+        };
+        let mut values = WitnessMap::new();
+        for (index, value) in [(0, 3u128), (1, 4), (2, 12)] {
+            values.insert(Witness(index), F::from(value));
+        }
+
+        let cs = ConstraintSystem::<BlsFr>::new_ref();
+        CurveAcir::<BlsFr>::from((&circuit, values))
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "bn254")]
+    fn builds_constraints_over_grumpkin() {
+        type F = GenericFieldElement<GrumpkinFr>;
+
+        // The moduli differ, so -1 must not be reduced modulo BN254's scalar field
+        let minus_one = from_fe(-F::one());
+        assert_eq!(minus_one, -GrumpkinFr::from(1u64));
+        assert_ne!(minus_one.into_bigint(), (-Fr::from(1u64)).into_bigint());
+
+        // w0 * w1 - w2 == 1
+        let circuit: Circuit<F> = Circuit {
+            expression_width: ExpressionWidth::Unbounded,
+            recursive: false,
+            current_witness_index: 2,
+            opcodes: vec![Opcode::AssertZero(Expression {
+                mul_terms: vec![(F::one(), Witness(0), Witness(1))],
+                linear_combinations: vec![(-F::one(), Witness(2))],
+                q_c: -F::one(),
+            })],
+            public_parameters: PublicInputs(BTreeSet::from([Witness(0)])),
+            return_values: PublicInputs(BTreeSet::new()),
+            private_parameters: BTreeSet::from([Witness(1)]),
+            assert_messages: Vec::new(),
+        };
+        let is_satisfied = |output: F| {
+            let mut values = WitnessMap::new();
+            values.insert(Witness(0), F::from(3u128));
+            values.insert(Witness(1), -F::from(4u128));
+            values.insert(Witness(2), output);
+
+            let cs = ConstraintSystem::<GrumpkinFr>::new_ref();
+            CurveAcir::<GrumpkinFr>::from((&circuit, values))
+                .generate_constraints(cs.clone())
+                .unwrap();
+            cs.is_satisfied().unwrap()
+        };
+        assert!(is_satisfied(-F::from(13u128)));
+        assert!(!is_satisfied(F::from(13u128)));
+    }
+
+    #[test]
+    fn num_opcodes_reports_unsupported_opcodes() {
+        use acvm::acir::circuit::opcodes::BlackBoxFuncCall;
+
+        // two opcodes, each with one mul term and the linear combination
+        assert_eq!(compute_num_opcodes(&test_circuit()), Ok(6));
+
+        let mut circuit = test_circuit();
+        circuit
+            .opcodes
+            .push(Opcode::BlackBoxFuncCall(BlackBoxFuncCall::BigIntAdd {
+                lhs: 0,
+                rhs: 1,
+                output: 2,
+            }));
+        #[cfg(not(feature = "bigint"))]
+        assert_eq!(
+            compute_num_opcodes(&circuit),
+            Err(OpcodeCountError::Unsupported(UnsupportedOpcodeError {
+                index: 2,
+                opcode: "BlackBoxFuncCall".to_string(),
+            }))
+        );
+        // one per opcode, the four constraints of the products and those of the addition
+        #[cfg(feature = "bigint")]
+        assert_eq!(compute_num_opcodes(&circuit), Ok(3 + 4 + 1768));
+    }
+
+    #[test]
+    fn num_opcodes_reports_overflow() {
+        use acvm::acir::circuit::opcodes::{BlackBoxFuncCall, FunctionInput};
+
+        let range = |num_bits| {
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::RANGE {
+                input: FunctionInput {
+                    witness: Witness(1),
+                    num_bits,
+                },
+            })
+        };
+        // Each range check counts its bits, and the opcodes themselves are counted too
+        let circuit = circuit_with_opcodes(1, vec![range(u32::MAX - 1)], &[]);
+        assert_eq!(compute_num_opcodes(&circuit), Ok(u32::MAX));
+
+        let circuit = circuit_with_opcodes(1, vec![range(u32::MAX - 1), range(2)], &[]);
+        assert_eq!(
+            compute_num_opcodes(&circuit),
+            Err(OpcodeCountError::Overflow)
+        );
+    }
+
+    #[test]
+    fn range_opcode_rejects_out_of_range_values() {
+        use acvm::acir::circuit::opcodes::{BlackBoxFuncCall, FunctionInput};
+
+        // x as u8
+        let circuit = circuit_with_opcodes(
+            0,
+            vec![Opcode::BlackBoxFuncCall(BlackBoxFuncCall::RANGE {
+                input: FunctionInput {
+                    witness: Witness(0),
+                    num_bits: 8,
+                },
+            })],
+            &[0],
+        );
+
+        for (value, satisfied) in [(200, true), (300, false)] {
+            let cs = ConstraintSystem::new_ref();
+            CurveAcir::from((&circuit, witness_map(&[(0, value)])))
+                .generate_constraints(cs.clone())
+                .unwrap();
+            assert_eq!(cs.is_satisfied().unwrap(), satisfied);
+        }
+    }
+
+    #[test]
+    fn constant_terms_keep_their_sign_next_to_public_inputs() {
+        let (a, b) = (Witness(0), Witness(1));
+
+        // 2 * a - b + 5 == 0 with a public
+        let circuit = circuit_with_opcodes(
+            1,
+            vec![Opcode::AssertZero(Expression {
+                mul_terms: vec![],
+                linear_combinations: vec![
+                    (FieldElement::from(2u128), a),
+                    (-FieldElement::one(), b),
+                ],
+                q_c: FieldElement::from(5u128),
+            })],
+            &[0],
+        );
+
+        // b = 2 * a + 5, while b = 2 * a - 5 would satisfy the gate with the constant negated
+        for (b_value, satisfied) in [(7, true), (-3, false), (8, false)] {
+            let circuit = CurveAcir::from((&circuit, witness_map(&[(0, 1), (1, b_value)])));
+            assert_eq!(circuit.check_satisfied().is_ok(), satisfied);
+
+            let cs = ConstraintSystem::new_ref();
+            circuit.generate_constraints(cs.clone()).unwrap();
+            assert_eq!(cs.num_instance_variables(), 2);
+            assert_eq!(cs.is_satisfied().unwrap(), satisfied);
+        }
+    }
+
+    #[test]
+    fn boolean_range_checks_add_one_constraint_each() {
+        use acvm::acir::circuit::opcodes::{BlackBoxFuncCall, FunctionInput};
+
+        let booleans = |value: i128| {
+            let circuit = circuit_with_opcodes(
+                9,
+                (0..10)
+                    .map(|witness| {
+                        Opcode::BlackBoxFuncCall(BlackBoxFuncCall::RANGE {
+                            input: FunctionInput {
+                                witness: Witness(witness),
+                                num_bits: 1,
+                            },
+                        })
+                    })
+                    .collect(),
+                &[],
+            );
+            let values: Vec<_> = (0..10).map(|witness| (witness, value)).collect();
+            let cs = ConstraintSystem::new_ref();
+            CurveAcir::from((&circuit, witness_map(&values)))
+                .generate_constraints(cs.clone())
+                .unwrap();
+            assert_eq!(compute_num_opcodes(&circuit).unwrap(), 10);
+            cs
+        };
+
+        let cs = booleans(1);
+        assert_eq!(cs.num_constraints(), 10);
+        assert!(cs.is_satisfied().unwrap());
+        assert!(!booleans(2).is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn gate_grouped_layout_orders_witnesses_by_gate() {
+        let one = FieldElement::one();
+        let circuit = circuit_with_opcodes(
+            4,
+            vec![
+                // w1 * w3 == w4
+                Opcode::AssertZero(Expression {
+                    mul_terms: vec![(one, Witness(1), Witness(3))],
+                    linear_combinations: vec![(-one, Witness(4))],
+                    q_c: FieldElement::zero(),
+                }),
+                // w2 == 7
+                Opcode::AssertZero(Expression {
+                    mul_terms: vec![],
+                    linear_combinations: vec![(one, Witness(2))],
+                    q_c: -FieldElement::from(7_i128),
+                }),
+            ],
+            &[1],
+        );
+        let values = witness_map(&[(1, 2), (2, 7), (3, 3), (4, 6)]);
+
+        let cs = ConstraintSystem::new_ref();
+        CurveAcir::from((&circuit, values))
+            .with_layout(bridge::CommitmentLayout::GateGrouped)
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        let cs = cs.borrow().unwrap();
+        let to_fr = |values: &[u64]| values.iter().map(|v| Fr::from(*v)).collect::<Vec<_>>();
+        // the constant one followed by the public input
+        assert_eq!(cs.instance_assignment, to_fr(&[1, 2]));
+        // gate 0 witnesses and product, gate 1 witnesses, then the unused w0
+        assert_eq!(cs.witness_assignment, to_fr(&[3, 6, 6, 7, 0]));
+    }
+
+    #[test]
+    fn checked_lowering_reports_integer_underflow() {
+        use noirc_abi::{AbiParameter, AbiReturnType, AbiType, AbiVisibility, Sign};
+
+        let u8_type = AbiType::Integer {
+            sign: Sign::Unsigned,
+            width: 8,
+        };
+        let param = |name: &str| AbiParameter {
+            name: name.to_string(),
+            typ: u8_type.clone(),
+            visibility: AbiVisibility::Private,
+        };
+        let abi = Abi {
+            parameters: vec![param("x"), param("y")],
+            return_type: Some(AbiReturnType {
+                abi_type: u8_type,
+                visibility: AbiVisibility::Public,
+            }),
+            error_types: BTreeMap::new(),
+        };
+
+        // z = x - y
+        let one = FieldElement::one();
+        let mut circuit = circuit_with_opcodes(
+            2,
+            vec![Opcode::AssertZero(Expression {
+                mul_terms: vec![],
+                linear_combinations: vec![
+                    (one, Witness(0)),
+                    (-one, Witness(1)),
+                    (-one, Witness(2)),
+                ],
+                q_c: FieldElement::zero(),
+            })],
+            &[],
+        );
+        circuit.return_values = PublicInputs(BTreeSet::from([Witness(2)]));
+
+        let values = witness_map(&[(0, 3), (1, 2), (2, 1)]);
+        assert!(CurveAcir::from_checked((&circuit, values), &abi).is_ok());
+
+        let values = witness_map(&[(0, 1), (1, 2), (2, -1)]);
+        let err = CurveAcir::from_checked((&circuit, values), &abi).unwrap_err();
+        assert_eq!(err.witness, Witness(2));
+        assert_eq!(err.gate_index, Some(0));
+        assert_eq!(err.typ.to_string(), "u8");
+    }
+
+    #[test]
+    fn optimizing_removes_implied_linear_gates() {
+        let linear = |terms: &[(i128, u32)]| {
+            Opcode::AssertZero(Expression {
+                mul_terms: vec![],
+                linear_combinations: terms
+                    .iter()
+                    .map(|(coeff, witness)| (FieldElement::from(*coeff), Witness(*witness)))
+                    .collect(),
+                q_c: FieldElement::zero(),
+            })
+        };
+        // w2 == w0 + w1 and w3 == w1 + w2 imply w3 == w0 + 2 * w1 and 2 * w2 == 2 * (w0 + w1)
+        let circuit = circuit_with_opcodes(
+            3,
+            vec![
+                linear(&[(1, 0), (1, 1), (-1, 2)]),
+                linear(&[(1, 1), (1, 2), (-1, 3)]),
+                linear(&[(1, 0), (2, 1), (-1, 3)]),
+                linear(&[(2, 0), (2, 1), (-2, 2)]),
+            ],
+            &[0],
+        );
+
+        let is_satisfied = |w3: i128, optimize: bool| {
+            let witness_map = witness_map(&[(0, 2), (1, 3), (2, 5), (3, w3)]);
+            let circuit = if optimize {
+                CurveAcir::from_optimized(&circuit, witness_map)
+            } else {
+                CurveAcir::from((&circuit, witness_map))
+            };
+            let cs = ConstraintSystem::new_ref();
+            circuit.generate_constraints(cs.clone()).unwrap();
+            (cs.num_constraints(), cs.is_satisfied().unwrap())
+        };
+        assert_eq!(is_satisfied(8, false), (4, true));
+        assert_eq!(is_satisfied(8, true), (2, true));
+        assert_eq!(is_satisfied(9, false), (4, false));
+        assert_eq!(is_satisfied(9, true), (2, false));
+    }
+
+    #[test]
+    fn gates_record_their_source_opcode() {
+        let one = FieldElement::one();
+        let assert_equal = |lhs: u32, rhs: u32| {
+            Opcode::AssertZero(Expression {
+                mul_terms: vec![],
+                linear_combinations: vec![(one, Witness(lhs)), (-one, Witness(rhs))],
+                q_c: FieldElement::zero(),
+            })
+        };
+        let brillig_call = Opcode::BrilligCall {
+            id: 0,
+            inputs: vec![],
+            outputs: vec![],
+            predicate: None,
+        };
+        let circuit = circuit_with_opcodes(
+            3,
+            vec![assert_equal(1, 2), brillig_call, assert_equal(2, 3)],
+            &[],
+        );
+
+        let circuit_acir = CurveAcir::from(&circuit);
+        let sources: Vec<_> = circuit_acir
+            .gates
+            .iter()
+            .map(|gate| gate.source_opcode)
+            .collect();
+        assert_eq!(sources, vec![0, 2]);
+    }
+
+    #[test]
+    fn rejects_unsupported_noir_version() {
+        assert!(check_noir_version("0.30.0").is_ok());
+        assert!(check_noir_version("0.31.0+2b4853e").is_ok());
+        assert!(check_noir_version("0.29.9").is_err());
+        assert!(check_noir_version("0.32.0").is_err());
+        assert!(check_noir_version("not a version").is_err());
+
+        let artifact_path = env::temp_dir().join("arkworks_backend_old_noir_version.json");
+        std::fs::write(&artifact_path, r#"{"noir_version":"0.1.0+0000000"}"#).unwrap();
+        let err = read_program_from_file::<Fr, _>(&artifact_path).unwrap_err();
+        assert!(matches!(
+            err,
+            FilesystemError::UnsupportedNoirVersion { found, .. } if found == "0.1.0+0000000"
+        ));
+    }
+
+    #[test]
+    fn reads_program_from_bytes() {
+        let artifact = ProgramArtifactGeneric::<Fr> {
+            noir_version: SUPPORTED_NOIR_VERSIONS.0.to_string(),
+            hash: 0,
+            abi: Abi {
+                parameters: Vec::new(),
+                return_type: None,
+                error_types: BTreeMap::new(),
+            },
+            bytecode: Program {
+                functions: vec![test_circuit()],
+                unconstrained_functions: Vec::new(),
+            },
+            debug_symbols: ProgramDebugInfo {
+                debug_infos: Vec::new(),
+            },
+            file_map: BTreeMap::new(),
+            names: vec!["main".to_string()],
+        };
+        let bytes = serde_json::to_vec(&artifact).unwrap();
+
+        let program = read_program_from_bytes::<Fr>(&bytes).unwrap();
+        assert_eq!(program, artifact.bytecode);
+        assert!(matches!(
+            read_program_from_bytes::<Fr>(&bytes[1..]),
+            Err(FilesystemError::ProgramSerializationError(_))
+        ));
+    }
+
+    #[test]
+    fn maps_opcodes_to_source_locations() {
+        use noirc_errors::debug_info::DebugInfo;
+        use noirc_errors::Location;
+
+        // main.nr, with `w4 = w0 * w2` lowered from opcode 0 of `test_circuit` inside a call
+        let file = FileId::new(0);
+        let (call, product) = (Span::inclusive(10, 20), Span::inclusive(42, 57));
+        let locations = BTreeMap::from([(
+            OpcodeLocation::Acir(0),
+            vec![Location::new(call, file), Location::new(product, file)],
+        )]);
+        let artifact = ProgramArtifactGeneric::<Fr> {
+            noir_version: SUPPORTED_NOIR_VERSIONS.0.to_string(),
+            hash: 0,
+            abi: Abi {
+                parameters: Vec::new(),
+                return_type: None,
+                error_types: BTreeMap::new(),
+            },
+            bytecode: Program {
+                functions: vec![test_circuit()],
+                unconstrained_functions: Vec::new(),
+            },
+            debug_symbols: ProgramDebugInfo {
+                debug_infos: vec![DebugInfo::new(
+                    locations,
+                    Default::default(),
+                    Default::default(),
+                    Default::default(),
+                )],
+            },
+            file_map: BTreeMap::from([(
+                file,
+                DebugFile {
+                    source: "fn main() {}".to_string(),
+                    path: PathBuf::from("src/main.nr"),
+                },
+            )]),
+            names: vec!["main".to_string()],
+        };
+        // The debug symbols survive the compressed encoding
+        let bytes = serde_json::to_vec(&artifact).unwrap();
+        let artifact: ProgramArtifactGeneric<Fr> = serde_json::from_slice(&bytes).unwrap();
+
+        assert_eq!(
+            source_location_for_gate(&artifact, 0),
+            Some((file, product))
+        );
+        assert_eq!(source_location_for_gate(&artifact, 1), None);
+    }
+
+    #[test]
+    fn lowers_circuit_from_acir_bytes() {
+        let program = Program {
+            functions: vec![test_circuit()],
+            unconstrained_functions: Vec::new(),
+        };
+        let bytes = Program::serialize_program(&program);
+
+        let circuit_acir =
+            CurveAcir::from_acir_bytes(&bytes, Some(test_circuit_witness_map())).unwrap();
+        let cs = ConstraintSystem::new_ref();
+        circuit_acir.generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        // Without a witness map the circuit can still be set up
+        let circuit_acir = CurveAcir::from_acir_bytes(&bytes, None).unwrap();
+        assert_eq!(
+            required_srs_degree(&circuit_acir),
+            required_srs_degree(&CurveAcir::from(&test_circuit()))
+        );
+
+        assert!(matches!(
+            CurveAcir::from_acir_bytes(&bytes[1..], None),
+            Err(FilesystemError::ProgramSerializationError(_))
+        ));
+        let two_functions = Program {
+            functions: vec![test_circuit(), test_circuit()],
+            unconstrained_functions: Vec::new(),
+        };
+        assert!(matches!(
+            CurveAcir::from_acir_bytes(&Program::serialize_program(&two_functions), None),
+            Err(FilesystemError::ProgramSerializationError(_))
+        ));
+    }
+
+    #[test]
+    fn reads_witness_file() {
+        let witness_path = env::temp_dir().join("arkworks_backend_witness.gz");
+        let witness_stack = WitnessStack::from(test_circuit_witness_map());
+        let bytes = Vec::<u8>::try_from(witness_stack).unwrap();
+        std::fs::write(&witness_path, bytes).unwrap();
+
+        let witness_map = read_witness_from_file::<Fr, _>(&witness_path).unwrap();
+        assert_eq!(witness_map, test_circuit_witness_map());
+
+        std::fs::write(&witness_path, b"not gzip").unwrap();
+        assert!(matches!(
+            read_witness_from_file::<Fr, _>(&witness_path),
+            Err(FilesystemError::WitnessSerializationError(_))
+        ));
+        assert!(matches!(
+            read_witness_from_file::<Fr, _>(env::temp_dir().join("arkworks_backend_missing")),
+            Err(FilesystemError::PathNotValid(_))
+        ));
+    }
+
+    #[test]
+    fn read_errors_keep_their_cause() {
+        // A directory exists, but cannot be read as a file
+        let dir = env::temp_dir().join("arkworks_backend_directory");
+        std::fs::create_dir_all(dir.with_extension("json")).unwrap();
+        assert!(matches!(
+            read_program_from_file::<Fr, _>(&dir),
+            Err(FilesystemError::Io(_))
+        ));
+        assert!(matches!(
+            read_program_from_file::<Fr, _>(env::temp_dir().join("arkworks_backend_missing")),
+            Err(FilesystemError::PathNotValid(_))
+        ));
+    }
+
+    #[test]
+    fn unused_witnesses_are_not_allocated() {
+        // w1 * w2 == w8 with w1 public, in a circuit with ten witnesses
+        let circuit = circuit_with_opcodes(
+            9,
+            vec![Opcode::AssertZero(Expression {
+                mul_terms: vec![(FieldElement::one(), Witness(1), Witness(2))],
+                linear_combinations: vec![(-FieldElement::one(), Witness(8))],
+                q_c: FieldElement::zero(),
+            })],
+            &[1],
+        );
+        let circuit_acir = CurveAcir::from((&circuit, witness_map(&[(1, 3), (2, 4), (8, 12)])));
+        assert_eq!(
+            circuit_acir.values.keys().copied().collect::<Vec<_>>(),
+            [Witness(1), Witness(2), Witness(8)]
+        );
+
+        let cs = ConstraintSystem::new_ref();
+        circuit_acir.generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        // w2, w8 and their product, rather than the nine non-public witnesses
+        assert_eq!(cs.num_witness_variables(), 3);
+        assert!(cs.num_witness_variables() < circuit.num_vars() as usize);
+    }
+
+    #[test]
+    fn provider_is_only_queried_for_referenced_witnesses() {
+        use std::cell::RefCell;
+
+        // assert w3 == w7 with w3 public, in a circuit with ten witnesses
+        let circuit = circuit_with_opcodes(
+            9,
+            vec![Opcode::AssertZero(Expression {
+                mul_terms: vec![],
+                linear_combinations: vec![
+                    (FieldElement::one(), Witness(3)),
+                    (-FieldElement::one(), Witness(7)),
+                ],
+                q_c: FieldElement::zero(),
+            })],
+            &[3],
+        );
+
+        let queried = RefCell::new(Vec::new());
+        let circuit_acir = CurveAcir::from_provider(&circuit, |witness| {
+            queried.borrow_mut().push(witness);
+            Some(Fr::from(5u64))
+        });
+        assert_eq!(queried.into_inner(), vec![Witness(3), Witness(7)]);
+
+        let cs = ConstraintSystem::new_ref();
+        circuit_acir.generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        assert_eq!(cs.num_witness_variables(), 1);
+    }
+
+    #[test]
+    fn enforce_blocks_equal_compares_elements() {
+        use acvm::acir::circuit::opcodes::{BlockId, BlockType};
+
+        let memory_init = |block: u32, init: [u32; 2]| Opcode::MemoryInit {
+            block_id: BlockId(block),
+            init: init.into_iter().map(Witness).collect(),
+            block_type: BlockType::Memory,
+        };
+        let circuit =
+            circuit_with_opcodes(4, vec![memory_init(0, [1, 2]), memory_init(1, [3, 4])], &[]);
+
+        let is_satisfied = |values: &[(u32, i128)]| {
+            let mut circuit_acir = CurveAcir::from((&circuit, witness_map(values)));
+            circuit_acir
+                .enforce_blocks_equal(BlockId(0), BlockId(1))
+                .unwrap();
+            let cs = ConstraintSystem::new_ref();
+            circuit_acir.generate_constraints(cs.clone()).unwrap();
+            cs.is_satisfied().unwrap()
+        };
+        assert!(is_satisfied(&[(1, 10), (2, 20), (3, 10), (4, 20)]));
+        assert!(!is_satisfied(&[(1, 10), (2, 20), (3, 10), (4, 21)]));
+
+        let mut circuit_acir = CurveAcir::from(&circuit);
+        assert_eq!(
+            circuit_acir.enforce_blocks_equal(BlockId(0), BlockId(2)),
+            Err(bridge::BridgeError::UnknownBlock(BlockId(2)))
+        );
+    }
+
+    #[test]
+    fn srs_degree_for_test_circuit() {
+        let circuit_acir = CurveAcir::from(&test_circuit());
+        // two products and two gates, plus the blinding degree
+        let degree = required_srs_degree(&circuit_acir);
+        assert!(degree.is_power_of_two());
+        assert_eq!(degree, 8);
+    }
+
+    #[test]
+    fn estimated_r1cs_size_matches_synthesis() {
+        let circuit_acir = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        let cs = ConstraintSystem::new_ref();
+        circuit_acir
+            .clone()
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert_eq!(
+            estimate_r1cs_size(&circuit_acir),
+            R1csSize {
+                constraints: cs.num_constraints(),
+                witnesses: cs.num_witness_variables(),
+                public_inputs: cs.num_instance_variables() - 1,
+            }
+        );
+    }
+
+    #[test]
+    fn negative_signed_public_input_encoding() {
+        use noirc_abi::{AbiParameter, AbiType, AbiVisibility, Sign};
+
+        let abi = Abi {
+            parameters: vec![AbiParameter {
+                name: "x".to_string(),
+                typ: AbiType::Integer {
+                    sign: Sign::Signed,
+                    width: 32,
+                },
+                visibility: AbiVisibility::Public,
+            }],
+            return_type: None,
+            error_types: BTreeMap::new(),
+        };
+        let encoded: Fr = abi::public_input_as_field("x", &abi, -5).unwrap();
+        assert_eq!(encoded, Fr::from((1u64 << 32) - 5));
+        assert!(abi::public_input_as_field::<Fr>("x", &abi, 1 << 31).is_err());
+        assert!(abi::public_input_as_field::<Fr>("y", &abi, 0).is_err());
+
+        // The prover's witness holds the same element the verifier passes as public input
+        let circuit = circuit_with_opcodes(
+            0,
+            vec![Opcode::AssertZero(Expression {
+                mul_terms: vec![],
+                linear_combinations: vec![(FieldElement::one(), Witness(0))],
+                q_c: FieldElement::from(5_i128) - FieldElement::from(1_i128 << 32),
+            })],
+            &[0],
+        );
+        let mut values = WitnessMap::new();
+        values.insert(Witness(0), FieldElement::from_repr(encoded));
+        let cs = ConstraintSystem::new_ref();
+        CurveAcir::from((&circuit, values))
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        assert_eq!(cs.borrow().unwrap().instance_assignment[1], encoded);
+    }
+
+    #[test]
+    fn identifies_private_parameters() {
+        let circuit = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        assert_eq!(
+            circuit.private_parameter_witnesses(),
+            &BTreeSet::from([Witness(2), Witness(3)])
+        );
+    }
 }
@@ -0,0 +1,609 @@
+use std::collections::BTreeMap;
+
+use acvm::acir::circuit::opcodes::BlockId;
+use acvm::acir::native_types::Witness;
+use ark_ff::Field;
+use ark_relations::{
+    lc,
+    r1cs::{ConstraintSystemRef, LinearCombination, SynthesisError, Variable},
+};
+
+use crate::bridge::{AcirArithGate, BridgeError, MemoryMode};
+use crate::R1csSize;
+
+// Lowering of ACIR memory (`Opcode::MemoryInit` and `Opcode::MemoryOp`) to R1CS.
+//
+// Each block is simulated as a list of cells, one linear combination per cell. A block starts
+// out as the variables of the witnesses it is initialized with, which may be computed by other
+// opcodes rather than constants, and every write replaces the cells it may touch:
+// - accesses at a constant index read or replace that cell directly
+// - accesses at a dynamic index select the cell with a one-hot vector of boolean selectors,
+//   constrained to sum to one and to recompose to the index
+//
+// A write at a constant index past the end of a block grows it, leaving the cells in between
+// uninitialized. Reading such a cell at a constant index is rejected in `MemoryMode::Strict`
+// and reads zero in `MemoryMode::Lenient`. Uninitialized cells always read as zero through a
+// dynamic index.
+//
+// An access with a predicate `p` only takes effect when `p` is one, as in the ACVM: `p` is
+// constrained to be boolean, a read is constrained to `p * cell`, so a skipped read yields zero,
+// and a write replaces the cell with `cell + p * (value - cell)`. The selectors of a dynamic index
+// sum to `p` rather than one, so a skipped access may have any index. Since whether it is
+// skipped is only known from the witness, `MemoryMode::Strict` only rejects the reads of
+// uninitialized cells which are always executed; the others read zero.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MemoryOperation {
+    Read,
+    Write,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum MemoryIndex<F: Field> {
+    Constant(usize),
+    // The index as a linear expression, in the form of a gate without mul terms
+    Dynamic(AcirArithGate<F>),
+    // A constant index too large for a `usize`, which the ACVM rejects as well
+    OutOfRange,
+}
+
+/// A single `Opcode::MemoryOp`. The value read or written and the predicate are linear
+/// expressions, which ACIR guarantees for memory operands.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct MemoryAccess<F: Field> {
+    pub(crate) block_id: BlockId,
+    pub(crate) operation: MemoryOperation,
+    pub(crate) index: MemoryIndex<F>,
+    pub(crate) value: AcirArithGate<F>,
+    // `None` if the access is always executed
+    pub(crate) predicate: Option<AcirArithGate<F>>,
+    pub(crate) source_opcode: usize,
+}
+
+impl<F: Field> MemoryAccess<F> {
+    /// Iterates over every witness referenced by the index, value and predicate of this access.
+    pub(crate) fn witnesses(&self) -> impl Iterator<Item = Witness> + '_ {
+        self.expressions().flat_map(|expr| expr.witnesses())
+    }
+
+    /// The index, if it is dynamic, the value and the predicate of this access.
+    pub(crate) fn expressions(&self) -> impl Iterator<Item = &AcirArithGate<F>> {
+        let index = match &self.index {
+            MemoryIndex::Dynamic(index) => Some(index),
+            MemoryIndex::Constant(_) | MemoryIndex::OutOfRange => None,
+        };
+        index
+            .into_iter()
+            .chain([&self.value])
+            .chain(self.predicate.as_ref())
+    }
+
+    /// Like [`MemoryAccess::expressions`], mutably.
+    pub(crate) fn expressions_mut(&mut self) -> impl Iterator<Item = &mut AcirArithGate<F>> {
+        let index = match &mut self.index {
+            MemoryIndex::Dynamic(index) => Some(index),
+            MemoryIndex::Constant(_) | MemoryIndex::OutOfRange => None,
+        };
+        index
+            .into_iter()
+            .chain([&mut self.value])
+            .chain(self.predicate.as_mut())
+    }
+}
+
+/// Reports the first access at a constant index too large for a `usize` and, in
+/// `MemoryMode::Strict`, the first read of an uninitialized cell at a constant index which is
+/// always executed.
+pub(crate) fn check_accesses<F: Field>(
+    accesses: &[MemoryAccess<F>],
+    memory_blocks: &BTreeMap<BlockId, Vec<Witness>>,
+    mode: MemoryMode,
+) -> Result<(), BridgeError> {
+    if let Some(access) = accesses
+        .iter()
+        .find(|access| access.index == MemoryIndex::OutOfRange)
+    {
+        return Err(BridgeError::MemoryIndexOutOfRange {
+            block: access.block_id,
+            opcode: access.source_opcode,
+        });
+    }
+    if mode == MemoryMode::Lenient {
+        return Ok(());
+    }
+
+    let mut initialized: BTreeMap<BlockId, Vec<bool>> = memory_blocks
+        .iter()
+        .map(|(block_id, init)| (*block_id, vec![true; init.len()]))
+        .collect();
+    for access in accesses {
+        let MemoryIndex::Constant(index) = access.index else {
+            continue;
+        };
+        let cells = initialized.entry(access.block_id).or_default();
+        match access.operation {
+            MemoryOperation::Read
+                if access.predicate.is_none() && !cells.get(index).copied().unwrap_or(false) =>
+            {
+                return Err(BridgeError::UninitializedRead {
+                    block: access.block_id,
+                    index,
+                });
+            }
+            MemoryOperation::Read => {}
+            MemoryOperation::Write => {
+                if index >= cells.len() {
+                    cells.resize(index + 1, false);
+                }
+                cells[index] = true;
+            }
+        }
+    }
+    Ok(())
+}
+
+// A memory cell: its linear combination and value, or `None` if it is uninitialized
+type Cell<F> = Option<(LinearCombination<F>, F)>;
+
+/// Enforces every memory access, in order, against the blocks' initial witnesses.
+pub(crate) fn enforce_memory_accesses<F: Field>(
+    cs: &ConstraintSystemRef<F>,
+    accesses: &[MemoryAccess<F>],
+    memory_blocks: &BTreeMap<BlockId, Vec<Witness>>,
+    mode: MemoryMode,
+    variables: &BTreeMap<Witness, Variable>,
+    values: &BTreeMap<Witness, F>,
+) -> Result<(), SynthesisError> {
+    let mut blocks: BTreeMap<BlockId, Vec<Cell<F>>> = memory_blocks
+        .iter()
+        .map(|(block_id, init)| {
+            let cells = init
+                .iter()
+                .map(|witness| Some((lc!() + variables[witness], values[witness])))
+                .collect();
+            (*block_id, cells)
+        })
+        .collect();
+
+    for access in accesses {
+        let cells = blocks.entry(access.block_id).or_default();
+        let (value, value_val) = evaluate(&access.value, variables, values);
+        let (predicate, predicate_val) = match &access.predicate {
+            Some(predicate) => {
+                let (predicate, predicate_val) = evaluate(predicate, variables, values);
+                // predicate * (predicate - 1) == 0
+                cs.enforce_constraint(
+                    predicate.clone(),
+                    predicate.clone() - (F::one(), Variable::One),
+                    lc!(),
+                )?;
+                (predicate, predicate_val)
+            }
+            None => (lc!() + Variable::One, F::one()),
+        };
+
+        match (&access.index, access.operation) {
+            (MemoryIndex::OutOfRange, _) => return Err(SynthesisError::Unsatisfiable),
+            (MemoryIndex::Constant(index), MemoryOperation::Read) => {
+                let cell = match cells.get(*index).cloned().flatten() {
+                    Some((cell, _)) => cell,
+                    None if mode == MemoryMode::Lenient || access.predicate.is_some() => lc!(),
+                    None => return Err(SynthesisError::Unsatisfiable),
+                };
+                cs.enforce_constraint(predicate, cell, value)?;
+            }
+            (MemoryIndex::Constant(index), MemoryOperation::Write) => {
+                if *index >= cells.len() {
+                    cells.resize(*index + 1, None);
+                }
+                cells[*index] = match access.predicate {
+                    None => Some((value, value_val)),
+                    Some(_) => {
+                        // cell' == cell + predicate * (value - cell)
+                        let (old, old_val) = cells[*index].take().unwrap_or((lc!(), F::zero()));
+                        let new_val = old_val + predicate_val * (value_val - old_val);
+                        let new = cs.new_witness_variable(|| Ok(new_val))?;
+                        cs.enforce_constraint(predicate, value - &old, lc!() + new - &old)?;
+                        Some((lc!() + new, new_val))
+                    }
+                };
+            }
+            (MemoryIndex::Dynamic(index), operation) => {
+                let (index, index_val) = evaluate(index, variables, values);
+                let selectors = enforce_one_hot(
+                    cs,
+                    cells.len(),
+                    (index, index_val),
+                    (predicate, predicate_val),
+                )?;
+
+                match operation {
+                    MemoryOperation::Read => {
+                        // value == sum(selector_j * cell_j)
+                        let mut selected = lc!();
+                        for (selector, cell) in selectors.iter().zip(cells.iter()) {
+                            let Some((cell, cell_val)) = cell else {
+                                continue;
+                            };
+                            let (selector, selector_val) = *selector;
+                            let product =
+                                cs.new_witness_variable(|| Ok(selector_val * cell_val))?;
+                            cs.enforce_constraint(lc!() + selector, cell.clone(), lc!() + product)?;
+                            selected += (F::one(), product);
+                        }
+                        cs.enforce_constraint(lc!() + Variable::One, value - selected, lc!())?;
+                    }
+                    MemoryOperation::Write => {
+                        // cell_j' == cell_j + selector_j * (value - cell_j)
+                        for (selector, cell) in selectors.iter().zip(cells.iter_mut()) {
+                            let (old, old_val) = cell.take().unwrap_or((lc!(), F::zero()));
+                            let (selector, selector_val) = *selector;
+                            let new_val = old_val + selector_val * (value_val - old_val);
+                            let new = cs.new_witness_variable(|| Ok(new_val))?;
+                            cs.enforce_constraint(
+                                lc!() + selector,
+                                value.clone() - &old,
+                                lc!() + new - &old,
+                            )?;
+                            *cell = Some((lc!() + new, new_val));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Counts the constraints and witnesses [`enforce_memory_accesses`] allocates.
+pub(crate) fn r1cs_size<F: Field>(
+    accesses: &[MemoryAccess<F>],
+    memory_blocks: &BTreeMap<BlockId, Vec<Witness>>,
+) -> R1csSize {
+    // Whether each cell is initialized
+    let mut blocks: BTreeMap<BlockId, Vec<bool>> = memory_blocks
+        .iter()
+        .map(|(block_id, init)| (*block_id, vec![true; init.len()]))
+        .collect();
+    let mut size = R1csSize::default();
+
+    for access in accesses {
+        let cells = blocks.entry(access.block_id).or_default();
+        if access.predicate.is_some() {
+            // The booleanity of the predicate
+            size.constraints += 1;
+        }
+        match (&access.index, access.operation) {
+            (MemoryIndex::OutOfRange, _) => {}
+            (MemoryIndex::Constant(_), MemoryOperation::Read) => size.constraints += 1,
+            (MemoryIndex::Constant(index), MemoryOperation::Write) => {
+                if *index >= cells.len() {
+                    cells.resize(*index + 1, false);
+                }
+                cells[*index] = true;
+                if access.predicate.is_some() {
+                    // The new value of the cell
+                    size.constraints += 1;
+                    size.witnesses += 1;
+                }
+            }
+            (MemoryIndex::Dynamic(_), operation) => {
+                // The selectors, their sum and their recomposition to the index
+                size.constraints += cells.len() + 2;
+                size.witnesses += cells.len();
+                // A product per initialized cell read, or a new value per cell written
+                let num_cells = match operation {
+                    MemoryOperation::Read => {
+                        size.constraints += 1;
+                        cells.iter().filter(|initialized| **initialized).count()
+                    }
+                    MemoryOperation::Write => {
+                        cells.fill(true);
+                        cells.len()
+                    }
+                };
+                size.constraints += num_cells;
+                size.witnesses += num_cells;
+            }
+        }
+    }
+    size
+}
+
+// Allocates one boolean selector per cell, exactly one of which is set if the boolean
+// `predicate` is: the one at `index`.
+fn enforce_one_hot<F: Field>(
+    cs: &ConstraintSystemRef<F>,
+    len: usize,
+    (index, index_val): (LinearCombination<F>, F),
+    (predicate, predicate_val): (LinearCombination<F>, F),
+) -> Result<Vec<(Variable, F)>, SynthesisError> {
+    let mut selectors = Vec::with_capacity(len);
+    let mut sum = lc!();
+    let mut position = lc!();
+    for j in 0..len {
+        let selector_val = if !predicate_val.is_zero() && F::from(j as u64) == index_val {
+            F::one()
+        } else {
+            F::zero()
+        };
+        let selector = cs.new_witness_variable(|| Ok(selector_val))?;
+        // selector * selector == selector
+        cs.enforce_constraint(lc!() + selector, lc!() + selector, lc!() + selector)?;
+        sum += (F::one(), selector);
+        position += (F::from(j as u64), selector);
+        selectors.push((selector, selector_val));
+    }
+
+    cs.enforce_constraint(lc!() + Variable::One, sum, predicate.clone())?;
+    cs.enforce_constraint(predicate, index, position)?;
+    Ok(selectors)
+}
+
+// Returns the linear combination of a linear expression and its value.
+fn evaluate<F: Field>(
+    expr: &AcirArithGate<F>,
+    variables: &BTreeMap<Witness, Variable>,
+    values: &BTreeMap<Witness, F>,
+) -> (LinearCombination<F>, F) {
+    debug_assert!(expr.mul_terms.is_empty(), "memory operands are linear");
+    let mut combination = lc!() + (expr.constant_term, Variable::One);
+    let mut value = expr.constant_term;
+    for (coeff, witness) in &expr.add_terms {
+        combination += (*coeff, variables[witness]);
+        value += *coeff * values[witness];
+    }
+    (combination, value)
+}
+
+#[cfg(test)]
+mod test {
+    use acvm::acir::circuit::opcodes::{BlockType, MemOp};
+    use acvm::acir::circuit::Opcode;
+    use acvm::acir::native_types::Expression;
+    use acvm::{AcirField, FieldElement};
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+
+    use super::*;
+    use crate::test::{circuit_with_opcodes, witness_map};
+    use crate::CurveAcir;
+
+    #[test]
+    fn reading_an_uninitialized_cell() {
+        // b = [w0, w1]; b[3] = w2; w3 = b[2]
+        let block_id = BlockId(0);
+        let constant = |index: i128| Expression::from_field(FieldElement::from(index));
+        let circuit = circuit_with_opcodes(
+            3,
+            vec![
+                Opcode::MemoryInit {
+                    block_id,
+                    init: vec![Witness(0), Witness(1)],
+                    block_type: BlockType::Memory,
+                },
+                Opcode::MemoryOp {
+                    block_id,
+                    op: MemOp::write_to_mem_index(constant(3), Witness(2).into()),
+                    predicate: None,
+                },
+                Opcode::MemoryOp {
+                    block_id,
+                    op: MemOp::read_at_mem_index(constant(2), Witness(3)),
+                    predicate: None,
+                },
+            ],
+            &[],
+        );
+        let lower = |read: i128| {
+            CurveAcir::from((&circuit, witness_map(&[(0, 1), (1, 2), (2, 3), (3, read)])))
+        };
+
+        let strict = lower(0);
+        assert_eq!(
+            strict.check_memory(),
+            Err(BridgeError::UninitializedRead {
+                block: block_id,
+                index: 2
+            })
+        );
+        let cs = ConstraintSystem::new_ref();
+        assert!(strict.generate_constraints(cs).is_err());
+
+        let is_satisfied = |read: i128| {
+            let circuit = lower(read).with_memory_mode(MemoryMode::Lenient);
+            assert_eq!(circuit.check_memory(), Ok(()));
+            let cs = ConstraintSystem::new_ref();
+            circuit.generate_constraints(cs.clone()).unwrap();
+            cs.is_satisfied().unwrap()
+        };
+        assert!(is_satisfied(0));
+        assert!(!is_satisfied(1));
+    }
+
+    #[test]
+    fn reads_computed_initial_values() {
+        // w2 = w0 * w1, w3 = w0 + 1; b = [w2, w3]; w5 = b[w4]
+        let block_id = BlockId(0);
+        let circuit = circuit_with_opcodes(
+            5,
+            vec![
+                Opcode::AssertZero(Expression {
+                    mul_terms: vec![(FieldElement::one(), Witness(0), Witness(1))],
+                    linear_combinations: vec![(-FieldElement::one(), Witness(2))],
+                    q_c: FieldElement::zero(),
+                }),
+                Opcode::AssertZero(Expression {
+                    mul_terms: vec![],
+                    linear_combinations: vec![
+                        (FieldElement::one(), Witness(0)),
+                        (-FieldElement::one(), Witness(3)),
+                    ],
+                    q_c: FieldElement::one(),
+                }),
+                Opcode::MemoryInit {
+                    block_id,
+                    init: vec![Witness(2), Witness(3)],
+                    block_type: BlockType::Memory,
+                },
+                Opcode::MemoryOp {
+                    block_id,
+                    op: MemOp::read_at_mem_index(Witness(4).into(), Witness(5)),
+                    predicate: None,
+                },
+            ],
+            &[],
+        );
+        let is_satisfied = |index: i128, read: i128| {
+            let values = witness_map(&[(0, 3), (1, 4), (2, 12), (3, 4), (4, index), (5, read)]);
+            let cs = ConstraintSystem::new_ref();
+            CurveAcir::from((&circuit, values))
+                .generate_constraints(cs.clone())
+                .unwrap();
+            cs.is_satisfied().unwrap()
+        };
+
+        assert!(is_satisfied(0, 12));
+        assert!(is_satisfied(1, 4));
+        assert!(!is_satisfied(0, 4));
+        assert!(!is_satisfied(1, 12));
+    }
+
+    #[test]
+    fn reads_at_a_private_index() {
+        // arr = [w0, w1, w2]; w4 = arr[w3] with w3 a private parameter
+        let block_id = BlockId(0);
+        let mut circuit = circuit_with_opcodes(
+            4,
+            vec![
+                Opcode::MemoryInit {
+                    block_id,
+                    init: vec![Witness(0), Witness(1), Witness(2)],
+                    block_type: BlockType::Memory,
+                },
+                Opcode::MemoryOp {
+                    block_id,
+                    op: MemOp::read_at_mem_index(Witness(3).into(), Witness(4)),
+                    predicate: None,
+                },
+            ],
+            &[0, 1, 2],
+        );
+        circuit.private_parameters = [Witness(3)].into();
+        let is_satisfied = |index: i128, read: i128| {
+            let values = witness_map(&[(0, 10), (1, 20), (2, 30), (3, index), (4, read)]);
+            let cs = ConstraintSystem::new_ref();
+            CurveAcir::from((&circuit, values))
+                .generate_constraints(cs.clone())
+                .unwrap();
+            cs.is_satisfied().unwrap()
+        };
+
+        assert!(is_satisfied(2, 30));
+        // an incorrect claimed value
+        assert!(!is_satisfied(2, 20));
+        // an index past the end of the array
+        assert!(!is_satisfied(3, 0));
+    }
+
+    #[test]
+    fn skips_accesses_with_a_false_predicate() {
+        // b = [w0, w1]; if w2 { b[1] = w3; w5 = b[w4] }; w6 = b[1]
+        let block_id = BlockId(0);
+        let predicate = Some(Expression::from(Witness(2)));
+        let circuit = circuit_with_opcodes(
+            6,
+            vec![
+                Opcode::MemoryInit {
+                    block_id,
+                    init: vec![Witness(0), Witness(1)],
+                    block_type: BlockType::Memory,
+                },
+                Opcode::MemoryOp {
+                    block_id,
+                    op: MemOp::write_to_mem_index(
+                        Expression::from_field(FieldElement::one()),
+                        Witness(3).into(),
+                    ),
+                    predicate: predicate.clone(),
+                },
+                Opcode::MemoryOp {
+                    block_id,
+                    op: MemOp::read_at_mem_index(Witness(4).into(), Witness(5)),
+                    predicate,
+                },
+                Opcode::MemoryOp {
+                    block_id,
+                    op: MemOp::read_at_mem_index(
+                        Expression::from_field(FieldElement::one()),
+                        Witness(6),
+                    ),
+                    predicate: None,
+                },
+            ],
+            &[],
+        );
+        let is_satisfied = |predicate: i128, index: i128, read: i128, last: i128| {
+            let values = witness_map(&[
+                (0, 10),
+                (1, 20),
+                (2, predicate),
+                (3, 30),
+                (4, index),
+                (5, read),
+                (6, last),
+            ]);
+            let circuit = CurveAcir::from((&circuit, values));
+            let size = r1cs_size(&circuit.memory_ops, &circuit.memory_blocks);
+            let cs = ConstraintSystem::new_ref();
+            circuit.generate_constraints(cs.clone()).unwrap();
+            assert_eq!(cs.num_constraints(), size.constraints);
+            assert_eq!(cs.num_witness_variables(), 7 + size.witnesses);
+            cs.is_satisfied().unwrap()
+        };
+
+        assert!(is_satisfied(1, 1, 30, 30));
+        assert!(is_satisfied(1, 0, 10, 30));
+        // a skipped access may have any index, and a skipped read yields zero
+        assert!(is_satisfied(0, 7, 0, 20));
+        assert!(!is_satisfied(0, 1, 20, 20));
+        assert!(!is_satisfied(0, 7, 0, 30));
+        // the predicate is boolean
+        assert!(!is_satisfied(2, 1, 30, 30));
+    }
+
+    #[test]
+    fn rejects_an_index_too_large_for_usize() {
+        // b = [w0]; w1 = b[2^64]
+        let block_id = BlockId(0);
+        let circuit = circuit_with_opcodes(
+            1,
+            vec![
+                Opcode::MemoryInit {
+                    block_id,
+                    init: vec![Witness(0)],
+                    block_type: BlockType::Memory,
+                },
+                Opcode::MemoryOp {
+                    block_id,
+                    op: MemOp::read_at_mem_index(
+                        Expression::from_field(FieldElement::from(1_u128 << 64)),
+                        Witness(1),
+                    ),
+                    predicate: None,
+                },
+            ],
+            &[],
+        );
+        let circuit = CurveAcir::from((&circuit, witness_map(&[(0, 1), (1, 1)])));
+        assert_eq!(
+            circuit.check_memory(),
+            Err(BridgeError::MemoryIndexOutOfRange {
+                block: block_id,
+                opcode: 1
+            })
+        );
+        let cs = ConstraintSystem::new_ref();
+        assert!(circuit.generate_constraints(cs).is_err());
+    }
+}
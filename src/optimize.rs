@@ -0,0 +1,304 @@
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet};
+
+use acvm::acir::native_types::Witness;
+use ark_ff::{Field, PrimeField};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem, SynthesisError, SynthesisMode};
+
+use crate::bridge::{AcirArithGate, AcirCircuit};
+
+// Optimization passes over lowered circuits, and a pipeline applying them in a chosen order.
+//
+// Every pass rewrites the gates into an equivalent system: an assignment satisfies the optimized
+// circuit exactly when it satisfies the original one, so witnesses solved for the original
+// circuit still prove the optimized one. The public inputs are never changed.
+
+/// A rewrite of the gates of an [`AcirCircuit`].
+///
+/// There is no pass splitting gates with many terms into gates of bounded arity: the lowering
+/// already allocates one product witness per mul term and enforces the rest of a gate as a
+/// single linear constraint of any length, so a split would only add constraints.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptimizationPass {
+    /// Removes gates which are structurally equal to an earlier gate.
+    Deduplicate,
+    /// Substitutes the value of witnesses fixed by a gate `c * w + k = 0` into the other gates.
+    PropagateConstants,
+    /// See [`AcirCircuit::collapse_public_aliases`].
+    CollapsePublicAliases,
+    /// See [`AcirCircuit::eliminate_linear_dependencies`].
+    EliminateLinearDependencies,
+}
+
+impl OptimizationPass {
+    pub fn apply<F: Field>(self, circuit: AcirCircuit<F>) -> AcirCircuit<F> {
+        match self {
+            OptimizationPass::Deduplicate => circuit.deduplicate_gates(),
+            OptimizationPass::PropagateConstants => circuit.propagate_constants(),
+            OptimizationPass::CollapsePublicAliases => circuit.collapse_public_aliases(),
+            OptimizationPass::EliminateLinearDependencies => {
+                circuit.eliminate_linear_dependencies()
+            }
+        }
+    }
+}
+
+/// The number of constraints before and after a pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PassReport {
+    pub pass: OptimizationPass,
+    pub constraints_before: usize,
+    pub constraints_after: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OptimizationReport {
+    /// The passes in the order they were applied.
+    pub passes: Vec<PassReport>,
+}
+
+impl OptimizationReport {
+    /// The number of constraints before the first pass, if any pass was applied.
+    pub fn constraints_before(&self) -> Option<usize> {
+        self.passes.first().map(|pass| pass.constraints_before)
+    }
+
+    /// The number of constraints after the last pass, if any pass was applied.
+    pub fn constraints_after(&self) -> Option<usize> {
+        self.passes.last().map(|pass| pass.constraints_after)
+    }
+}
+
+/// A sequence of [`OptimizationPass`]es, applied in the order they were added.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OptimizationPipeline {
+    passes: Vec<OptimizationPass>,
+}
+
+impl OptimizationPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_pass(mut self, pass: OptimizationPass) -> Self {
+        self.passes.push(pass);
+        self
+    }
+
+    /// Applies the passes to `circuit`, counting the constraints it synthesizes to around each
+    /// pass.
+    pub fn run<F: PrimeField>(
+        &self,
+        mut circuit: AcirCircuit<F>,
+    ) -> Result<(AcirCircuit<F>, OptimizationReport), SynthesisError> {
+        let mut report = OptimizationReport::default();
+        let mut constraints = count_constraints(&circuit)?;
+        for pass in &self.passes {
+            circuit = pass.apply(circuit);
+            let constraints_after = count_constraints(&circuit)?;
+            report.passes.push(PassReport {
+                pass: *pass,
+                constraints_before: constraints,
+                constraints_after,
+            });
+            constraints = constraints_after;
+        }
+        Ok((circuit, report))
+    }
+}
+
+fn count_constraints<F: PrimeField>(circuit: &AcirCircuit<F>) -> Result<usize, SynthesisError> {
+    let cs = ConstraintSystem::new_ref();
+    cs.set_mode(SynthesisMode::Setup);
+    circuit.clone().generate_constraints(cs.clone())?;
+    Ok(cs.num_constraints())
+}
+
+impl<F: Field> AcirCircuit<F> {
+    /// Removes every gate whose canonical form equals that of an earlier gate.
+    pub fn deduplicate_gates(mut self) -> Self {
+        let mut seen = BTreeSet::new();
+        self.gates.retain(|gate| {
+            let mut canonical = gate.clone();
+            canonical.canonicalize();
+            let key = (
+                canonical.mul_terms,
+                canonical.add_terms,
+                canonical.constant_term,
+            );
+            seen.insert(key)
+        });
+        self
+    }
+
+    /// Finds the witnesses a gate `c * w + k = 0` fixes to `-k / c` and substitutes their value
+    /// into every other gate, until no more witnesses are fixed.
+    ///
+    /// The gates fixing a witness are kept, so the witness remains constrained. Other gates
+    /// which become trivially satisfied are removed.
+    pub fn propagate_constants(mut self) -> Self {
+        let mut constants: BTreeMap<Witness, (F, usize)> = BTreeMap::new();
+        loop {
+            let mut found = false;
+            for (index, gate) in self.gates.iter().enumerate() {
+                if let Some((witness, value)) = gate.as_constant() {
+                    if let Entry::Vacant(entry) = constants.entry(witness) {
+                        entry.insert((value, index));
+                        found = true;
+                    }
+                }
+            }
+            if !found {
+                break;
+            }
+
+            for (index, gate) in self.gates.iter_mut().enumerate() {
+                let defines = |witness: &Witness| {
+                    constants
+                        .get(witness)
+                        .map_or(false, |(_, definition)| *definition == index)
+                };
+                if gate.witnesses().any(|witness| defines(&witness)) {
+                    continue;
+                }
+                let value = |witness: &Witness| constants.get(witness).map(|(value, _)| *value);
+                gate.substitute(value);
+            }
+        }
+
+        self.gates.retain(|gate| {
+            !(gate.mul_terms.is_empty()
+                && gate.add_terms.is_empty()
+                && gate.constant_term.is_zero())
+        });
+        self
+    }
+
+    /// Removes every linear gate which is a linear combination of the earlier linear gates, as
+    /// found by Gaussian elimination.
+    ///
+    /// The remaining linear gates are independent, so each of them is needed to fix the same
+    /// solutions. A combination contradicting the earlier gates, which no assignment satisfies,
+    /// is kept.
+    pub fn eliminate_linear_dependencies(mut self) -> Self {
+        // The rows of the reduced system, keyed on their highest witness, whose coefficient is
+        // one, along with their constant term
+        let mut rows: BTreeMap<Witness, (BTreeMap<Witness, F>, F)> = BTreeMap::new();
+        self.gates.retain(|gate| {
+            if !gate.mul_terms.is_empty() {
+                return true;
+            }
+            let mut terms: BTreeMap<Witness, F> = BTreeMap::new();
+            for (coeff, witness) in &gate.add_terms {
+                *terms.entry(*witness).or_insert_with(F::zero) += coeff;
+            }
+            terms.retain(|_, coeff| !coeff.is_zero());
+            let mut constant = gate.constant_term;
+
+            // Cancel the highest witness with the row it leads, which only has lower witnesses
+            while let Some((&witness, &coeff)) = terms.last_key_value() {
+                let Some((row, row_constant)) = rows.get(&witness) else {
+                    let inverse = coeff.inverse().expect("the coefficient is non-zero");
+                    terms.values_mut().for_each(|coeff| *coeff *= inverse);
+                    rows.insert(witness, (terms, constant * inverse));
+                    return true;
+                };
+                for (row_witness, row_coeff) in row {
+                    let term = terms.entry(*row_witness).or_insert_with(F::zero);
+                    *term -= coeff * row_coeff;
+                    if term.is_zero() {
+                        terms.remove(row_witness);
+                    }
+                }
+                constant -= coeff * row_constant;
+            }
+            !constant.is_zero()
+        });
+        self
+    }
+}
+
+impl<F: Field> AcirArithGate<F> {
+    /// Returns the witness and its value if the gate has the form `c * w + k = 0`.
+    fn as_constant(&self) -> Option<(Witness, F)> {
+        let mut canonical = self.clone();
+        canonical.canonicalize();
+        match (
+            canonical.mul_terms.as_slice(),
+            canonical.add_terms.as_slice(),
+        ) {
+            ([], [(coeff, witness)]) => Some((*witness, -canonical.constant_term / coeff)),
+            _ => None,
+        }
+    }
+
+    /// Replaces every witness `value` knows the value of by that value, then canonicalizes.
+    fn substitute(&mut self, value: impl Fn(&Witness) -> Option<F>) {
+        let mut add_terms = Vec::new();
+        for (coeff, lhs, rhs) in std::mem::take(&mut self.mul_terms) {
+            match (value(&lhs), value(&rhs)) {
+                (Some(lhs), Some(rhs)) => self.constant_term += coeff * lhs * rhs,
+                (Some(lhs), None) => add_terms.push((coeff * lhs, rhs)),
+                (None, Some(rhs)) => add_terms.push((coeff * rhs, lhs)),
+                (None, None) => self.mul_terms.push((coeff, lhs, rhs)),
+            }
+        }
+        for (coeff, witness) in std::mem::take(&mut self.add_terms) {
+            match value(&witness) {
+                Some(value) => self.constant_term += coeff * value,
+                None => add_terms.push((coeff, witness)),
+            }
+        }
+        self.add_terms = add_terms;
+        self.canonicalize();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::groth16::{prove, setup, verify};
+    use crate::test::circuit_with_gates;
+    use crate::Fr;
+
+    #[test]
+    fn pipeline_shrinks_and_preserves_proofs() {
+        let fr = |value: i64| Fr::from(value);
+        let gate = |mul_terms, add_terms, constant_term| AcirArithGate {
+            mul_terms,
+            add_terms,
+            constant_term,
+            source_opcode: 0,
+        };
+        let (x, k, y) = (Witness(0), Witness(1), Witness(2));
+        let product = || gate(vec![(fr(1), x, k)], vec![(fr(-1), y)], fr(0));
+        // k == 3, k * k == 9, x * k == y, stated twice
+        let circuit = circuit_with_gates(
+            vec![
+                gate(vec![], vec![(fr(1), k)], fr(-3)),
+                gate(vec![(fr(1), k, k)], vec![], fr(-9)),
+                product(),
+                product(),
+            ],
+            &[x],
+            &[(x, 5), (k, 3), (y, 15)],
+        );
+
+        let (optimized, report) = OptimizationPipeline::new()
+            .with_pass(OptimizationPass::Deduplicate)
+            .with_pass(OptimizationPass::PropagateConstants)
+            .run(circuit)
+            .unwrap();
+        assert_eq!(report.passes.len(), 2);
+        for pass in &report.passes {
+            assert!(pass.constraints_after < pass.constraints_before);
+        }
+        assert_eq!(optimized.gates.len(), 2);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let (pk, vk) = setup(&optimized, &mut rng).unwrap();
+        let proof = prove(&pk, &optimized, &mut rng).unwrap();
+        assert!(verify(&vk, &[fr(5)], &proof).unwrap());
+    }
+}
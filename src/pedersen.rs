@@ -0,0 +1,399 @@
+use std::collections::BTreeMap;
+
+use acvm::acir::native_types::Witness;
+use ark_ff::PrimeField;
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError, Variable};
+
+use crate::R1csSize;
+
+// Lowering of `BlackBoxFuncCall::PedersenHash` to R1CS.
+//
+// Following barretenberg, the hash of `n` inputs is the `x` coordinate of
+// `n * H + sum(inputs[i] * G[domain_separator + i])` on the embedded curve. The generators are
+// derived by hashing to the curve: a generator's preimage is the BLAKE3 hash of its domain
+// (`pedersen_hash_length` for `H`, `DEFAULT_DOMAIN_SEPARATOR` for the `G`s) followed by its
+// big-endian index and zero padding to 64 bytes. Appending an attempt counter and a `0` or `1`
+// byte gives two BLAKE3 hashes, the high and low halves of a 512-bit big-endian integer which
+// reduces to the `x` coordinate, and the top bit of the high half gives the parity of `y`. The
+// counter is incremented until `x` is on the curve.
+//
+// The generators are constants, so each input is decomposed into bits, constrained to lie below
+// the field modulus so that the decomposition is unique, and each bit selects between a constant
+// multiple `2^j * G` and the point at infinity. The selections are summed with the addition of
+// the `embedded_curve` module, starting from the constant `n * H`.
+//
+// The constraints are only generated with the `pedersen` feature, which also pulls in BLAKE3.
+
+/// A single `BlackBoxFuncCall::PedersenHash`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PedersenHashGate {
+    pub(crate) inputs: Vec<Witness>,
+    /// The index of the generator of the first input.
+    pub(crate) domain_separator: u32,
+    pub(crate) output: Witness,
+    pub(crate) source_opcode: usize,
+}
+
+impl PedersenHashGate {
+    pub(crate) fn witnesses(&self) -> impl Iterator<Item = Witness> + '_ {
+        self.inputs
+            .iter()
+            .chain(std::iter::once(&self.output))
+            .copied()
+    }
+
+    pub(crate) fn witnesses_mut(&mut self) -> impl Iterator<Item = &mut Witness> {
+        self.inputs
+            .iter_mut()
+            .chain(std::iter::once(&mut self.output))
+    }
+}
+
+/// Counts the constraints and witnesses [`enforce_pedersen_hash_gates`] allocates, none without
+/// the `pedersen` feature.
+#[cfg_attr(not(feature = "pedersen"), allow(unused_variables))]
+pub(crate) fn r1cs_size(gates: &[PedersenHashGate]) -> R1csSize {
+    #[cfg(feature = "pedersen")]
+    return hash::r1cs_size(gates);
+
+    #[cfg(not(feature = "pedersen"))]
+    R1csSize::default()
+}
+
+/// Enforces every hash. Fails with `SynthesisError::Unsatisfiable` if there are any but the
+/// field has no embedded curve, or the `pedersen` feature is disabled.
+#[cfg_attr(not(feature = "pedersen"), allow(unused_variables))]
+pub(crate) fn enforce_pedersen_hash_gates<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    gates: &[PedersenHashGate],
+    variables: &BTreeMap<Witness, Variable>,
+) -> Result<(), SynthesisError> {
+    #[cfg(feature = "pedersen")]
+    return hash::enforce_pedersen_hash_gates(cs, gates, variables);
+
+    #[cfg(not(feature = "pedersen"))]
+    match gates.is_empty() {
+        true => Ok(()),
+        false => Err(SynthesisError::Unsatisfiable),
+    }
+}
+
+#[cfg(feature = "pedersen")]
+pub(crate) mod hash {
+    use std::collections::BTreeMap;
+
+    use acvm::acir::native_types::Witness;
+    use ark_ff::{BigInteger, PrimeField};
+    use ark_relations::{
+        lc,
+        r1cs::{ConstraintSystemRef, SynthesisError, Variable},
+    };
+
+    use super::PedersenHashGate;
+    use crate::concrete_cfg::Fr;
+    use crate::embedded_curve::{add_values, alloc_sum, coefficients};
+    use crate::gadgets::enforce_bit_decomposition;
+    use crate::R1csSize;
+
+    const GENERATOR_DOMAIN: &[u8] = b"DEFAULT_DOMAIN_SEPARATOR";
+    const LENGTH_DOMAIN: &[u8] = b"pedersen_hash_length";
+
+    /// A constant point as `[x, y, is_infinite]`.
+    pub(crate) type Point<F> = [F; 3];
+
+    /// Derives `count` generators of the embedded curve `y^2 = x^3 + a x + b` for `domain`,
+    /// starting at index `start`.
+    fn generators<F: PrimeField>(
+        domain: &[u8],
+        start: u32,
+        count: usize,
+        (a, b): (F, F),
+    ) -> Vec<Point<F>> {
+        let domain_hash = blake3::hash(domain);
+        (start..)
+            .take(count)
+            .map(|index| {
+                let mut preimage = domain_hash.as_bytes().to_vec();
+                preimage.extend(index.to_be_bytes());
+                preimage.resize(64, 0);
+                hash_to_curve(&preimage, (a, b))
+            })
+            .collect()
+    }
+
+    fn hash_to_curve<F: PrimeField>(seed: &[u8], (a, b): (F, F)) -> Point<F> {
+        for attempt in 0u8..=u8::MAX {
+            let mut preimage = seed.to_vec();
+            preimage.extend([attempt, 0]);
+            let high = blake3::hash(&preimage);
+            *preimage.last_mut().unwrap() = 1;
+            let low = blake3::hash(&preimage);
+
+            let x = F::from_be_bytes_mod_order(&[*high.as_bytes(), *low.as_bytes()].concat());
+            let y_is_odd = high.as_bytes()[0] > 127;
+            if let Some(y) = (x.square() * x + a * x + b).sqrt() {
+                let y = match y.into_bigint().is_odd() == y_is_odd {
+                    true => y,
+                    false => -y,
+                };
+                return [x, y, F::zero()];
+            }
+        }
+        unreachable!("half of the x coordinates are on the curve")
+    }
+
+    /// Returns `scalar * point`, by double-and-add from the most significant bit.
+    fn multiply<F: PrimeField>(point: Point<F>, scalar: u64, a: F) -> Point<F> {
+        let mut product = [F::zero(), F::zero(), F::one()];
+        for i in (0..64).rev() {
+            product = add_values(product, product, a);
+            if scalar >> i & 1 == 1 {
+                product = add_values(product, point, a);
+            }
+        }
+        product
+    }
+
+    /// The number of bits an input is decomposed into.
+    fn num_bits() -> u32 {
+        Fr::MODULUS_BIT_SIZE
+    }
+
+    pub(super) fn r1cs_size(gates: &[PedersenHashGate]) -> R1csSize {
+        let mut size = R1csSize::default();
+        for gate in gates {
+            // The output tied to the hash
+            size += R1csSize {
+                constraints: 1,
+                witnesses: 0,
+                public_inputs: 0,
+            };
+            size += hash_r1cs_size(gate.inputs.len());
+        }
+        size
+    }
+
+    /// Counts the constraints and witnesses [`enforce_hash`] allocates for `num_inputs` inputs.
+    pub(crate) fn hash_r1cs_size(num_inputs: usize) -> R1csSize {
+        let num_bits = num_bits() as usize;
+        // The decomposition and its bound, then per bit a selection and an addition
+        let mut per_input = canonical_bits_r1cs_size();
+        per_input += R1csSize {
+            constraints: num_bits * (3 + 34),
+            witnesses: num_bits * (3 + 29),
+            public_inputs: 0,
+        };
+
+        // The constant `n * H`
+        let mut size = R1csSize {
+            constraints: 3,
+            witnesses: 3,
+            public_inputs: 0,
+        };
+        for _ in 0..num_inputs {
+            size += per_input;
+        }
+        size
+    }
+
+    /// Counts the constraints and witnesses [`enforce_canonical_bits`] allocates.
+    pub(crate) fn canonical_bits_r1cs_size() -> R1csSize {
+        let num_bits = num_bits() as usize;
+        // The bits of `p - 1` below the top one: a zero costs a constraint, a one a product
+        let max_bits = (-Fr::from(1u64)).into_bigint().to_bits_le();
+        let max_bits = &max_bits[..num_bits - 1];
+        let ones = max_bits.iter().filter(|bit| **bit).count();
+        R1csSize {
+            constraints: num_bits + 1 + (num_bits - 1),
+            witnesses: num_bits + ones,
+            public_inputs: 0,
+        }
+    }
+
+    pub(super) fn enforce_pedersen_hash_gates<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        gates: &[PedersenHashGate],
+        variables: &BTreeMap<Witness, Variable>,
+    ) -> Result<(), SynthesisError> {
+        if gates.is_empty() {
+            return Ok(());
+        }
+        let coefficients = coefficients::<F>().ok_or(SynthesisError::Unsatisfiable)?;
+
+        for gate in gates {
+            let inputs: Vec<Variable> = gate.inputs.iter().map(|input| variables[input]).collect();
+            let hash = enforce_hash(cs, &inputs, gate.domain_separator, coefficients)?;
+            cs.enforce_constraint(
+                lc!() + Variable::One,
+                lc!() + hash,
+                lc!() + variables[&gate.output],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Allocates the hash of `inputs`, the first using the generator at `domain_separator`.
+    pub(crate) fn enforce_hash<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        inputs: &[Variable],
+        domain_separator: u32,
+        coefficients: (F, F),
+    ) -> Result<Variable, SynthesisError> {
+        let a = coefficients.0;
+        let length_generator = generators(LENGTH_DOMAIN, 0, 1, coefficients)[0];
+        let num_inputs = inputs.len() as u64;
+        let mut sum = alloc_constant(cs, multiply(length_generator, num_inputs, a))?;
+
+        let generators = generators(
+            GENERATOR_DOMAIN,
+            domain_separator,
+            inputs.len(),
+            coefficients,
+        );
+        for (input, generator) in inputs.iter().zip(generators) {
+            let bits = enforce_canonical_bits(cs, *input)?;
+
+            // `2^j * G` for the bit `j`
+            let mut multiple = generator;
+            for bit in bits {
+                let selected = select_constant(cs, bit, multiple)?;
+                sum = alloc_sum(cs, sum, selected, coefficients)?;
+                multiple = add_values(multiple, multiple, a);
+            }
+        }
+        Ok(sum[0])
+    }
+
+    /// Decomposes `value` into its little-endian bits, constrained to lie below the field
+    /// modulus so that the decomposition is unique.
+    pub(crate) fn enforce_canonical_bits<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        value: Variable,
+    ) -> Result<Vec<Variable>, SynthesisError> {
+        let bits = enforce_bit_decomposition(cs, value, F::MODULUS_BIT_SIZE)?;
+        enforce_below_modulus(cs, &bits)?;
+        Ok(bits)
+    }
+
+    /// Allocates the constant `point`, in 3 constraints.
+    pub(crate) fn alloc_constant<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        point: Point<F>,
+    ) -> Result<[Variable; 3], SynthesisError> {
+        let mut variables = [Variable::One; 3];
+        for (variable, coordinate) in variables.iter_mut().zip(point) {
+            *variable = cs.new_witness_variable(|| Ok(coordinate))?;
+            cs.enforce_constraint(
+                lc!() + Variable::One,
+                lc!() + (coordinate, Variable::One),
+                lc!() + *variable,
+            )?;
+        }
+        Ok(variables)
+    }
+
+    /// Allocates the finite constant `point` if `bit` is one and the point at infinity
+    /// otherwise, in 3 constraints.
+    pub(crate) fn select_constant<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        bit: Variable,
+        point: Point<F>,
+    ) -> Result<[Variable; 3], SynthesisError> {
+        let bit_value = cs.assigned_value(bit);
+        let [x, y, _] = point;
+        let selected = [(x, F::zero()), (y, F::zero()), (F::zero(), F::one())];
+        let mut variables = [Variable::One; 3];
+        for (variable, (when_true, when_false)) in variables.iter_mut().zip(selected) {
+            *variable = cs.new_witness_variable(|| {
+                let bit = bit_value.ok_or(SynthesisError::AssignmentMissing)?;
+                Ok(bit * (when_true - when_false) + when_false)
+            })?;
+            // bit * (when_true - when_false) == selected - when_false
+            cs.enforce_constraint(
+                lc!() + bit,
+                lc!() + (when_true - when_false, Variable::One),
+                lc!() + *variable - (when_false, Variable::One),
+            )?;
+        }
+        Ok(variables)
+    }
+
+    /// Enforces that the little-endian `bits` are at most `p - 1`, in a constraint per bit
+    /// below the top one.
+    fn enforce_below_modulus<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        bits: &[Variable],
+    ) -> Result<(), SynthesisError> {
+        let max_bits = (-F::one()).into_bigint().to_bits_le();
+        // One while the bits read so far, from the most significant, equal those of `p - 1`.
+        // Before the top bit of `p - 1`, which is one, they are all zero.
+        let mut prefix_equal: Option<Variable> = None;
+        for (bit, max_bit) in bits.iter().zip(max_bits).rev() {
+            match (prefix_equal, max_bit) {
+                (None, true) => prefix_equal = Some(*bit),
+                (Some(equal), true) => {
+                    let value = cs.assigned_value(equal).zip(cs.assigned_value(*bit));
+                    let product = cs.new_witness_variable(|| {
+                        value
+                            .map(|(equal, bit)| equal * bit)
+                            .ok_or(SynthesisError::AssignmentMissing)
+                    })?;
+                    cs.enforce_constraint(lc!() + equal, lc!() + *bit, lc!() + product)?;
+                    prefix_equal = Some(product);
+                }
+                // A one where `p - 1` has a zero after an equal prefix would exceed it
+                (equal, false) => cs.enforce_constraint(
+                    lc!() + equal.unwrap_or(Variable::One),
+                    lc!() + *bit,
+                    lc!(),
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "pedersen"))]
+mod test {
+    use acvm::acir::circuit::opcodes::{BlackBoxFuncCall, FunctionInput};
+    use acvm::acir::circuit::Opcode;
+    use acvm::FieldElement;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+
+    use super::*;
+    use crate::field::field_from_hex;
+    use crate::test::{circuit_with_opcodes, witness_map};
+    use crate::{CurveAcir, Fr};
+
+    #[test]
+    fn hashes_two_field_elements() {
+        let hash = Opcode::BlackBoxFuncCall(BlackBoxFuncCall::PedersenHash {
+            inputs: (0..2)
+                .map(|index| FunctionInput {
+                    witness: Witness(index),
+                    num_bits: 254,
+                })
+                .collect(),
+            domain_separator: 0,
+            output: Witness(2),
+        });
+        let circuit = circuit_with_opcodes(2, vec![hash], &[]);
+        // pedersen_hash([1, 1]), from barretenberg's tests
+        let digest: Fr =
+            field_from_hex("07ebfbf4df29888c6cd6dca13d4bb9d1a923013ddbbcbdc3378ab8845463297b")
+                .unwrap();
+
+        let is_satisfied = |digest: Fr| {
+            let mut values = witness_map(&[(0, 1), (1, 1)]);
+            values.insert(Witness(2), FieldElement::from_repr(digest));
+            let circuit = CurveAcir::from((&circuit, values));
+            let size = circuit.r1cs_size();
+            let cs = ConstraintSystem::new_ref();
+            circuit.generate_constraints(cs.clone()).unwrap();
+            assert_eq!(cs.num_constraints(), size.constraints);
+            cs.is_satisfied().unwrap()
+        };
+        assert!(is_satisfied(digest));
+        assert!(!is_satisfied(digest + Fr::from(1u64)));
+    }
+}
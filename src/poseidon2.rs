@@ -0,0 +1,525 @@
+use std::collections::BTreeMap;
+
+use acvm::acir::native_types::Witness;
+use ark_ff::{BigInteger, Field, PrimeField};
+use ark_relations::{
+    lc,
+    r1cs::{ConstraintSystemRef, LinearCombination, SynthesisError, Variable},
+};
+
+use crate::bridge::BridgeError;
+use crate::field::field_from_hex;
+use crate::R1csSize;
+
+// Lowering of `BlackBoxFuncCall::Poseidon2Permutation` to R1CS.
+//
+// The permutation is unrolled round by round. The linear layers are folded into the linear
+// combinations of the state, so only the S-boxes cost constraints: one per multiplication of
+// their square-and-multiply chain, three for `x^5`. The final state is constrained to equal the
+// output witnesses.
+//
+// The round constants are generated with the Grain LFSR of the reference implementation, so a
+// `PoseidonConfig` only needs the round numbers, the S-box exponent and the internal matrix to
+// reproduce a parameter set.
+
+/// The parameters of a Poseidon2 permutation over `F`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PoseidonConfig<F: Field> {
+    /// The number of full rounds, half of which run before the partial rounds.
+    pub full_rounds: usize,
+    pub partial_rounds: usize,
+    /// The exponent of the S-box `x^alpha`.
+    pub alpha: u64,
+    /// The constants added to the state in each round: one per element in full rounds, one
+    /// for the first element in partial rounds.
+    pub round_constants: Vec<Vec<F>>,
+    /// The diagonal `d` of the internal matrix `diag(d) + 1`, whose length is the state width.
+    pub internal_diagonal: Vec<F>,
+}
+
+impl<F: Field> PoseidonConfig<F> {
+    /// The number of elements the permutation acts on.
+    pub fn width(&self) -> usize {
+        self.internal_diagonal.len()
+    }
+}
+
+impl<F: PrimeField> PoseidonConfig<F> {
+    /// Creates a configuration for a state of `internal_diagonal.len()` elements, generating
+    /// the round constants as the reference implementation does.
+    ///
+    /// # Panics
+    ///
+    /// If `full_rounds` is odd, `alpha` is zero, or the width is neither 2, 3 nor a multiple of
+    /// 4, for which Poseidon2 defines no external matrix.
+    pub fn new(
+        full_rounds: usize,
+        partial_rounds: usize,
+        alpha: u64,
+        internal_diagonal: Vec<F>,
+    ) -> Self {
+        let width = internal_diagonal.len();
+        assert!(full_rounds % 2 == 0, "full rounds must be even");
+        assert!(alpha > 0, "the S-box exponent must be positive");
+        assert!(
+            matches!(width, 2 | 3) || (width > 0 && width % 4 == 0),
+            "unsupported state width {width}"
+        );
+
+        let mut grain = GrainLfsr::new(F::MODULUS_BIT_SIZE, width, full_rounds, partial_rounds);
+        let round_constants = (0..full_rounds + partial_rounds)
+            .map(|round| {
+                let is_partial =
+                    (full_rounds / 2..full_rounds / 2 + partial_rounds).contains(&round);
+                let num_constants = if is_partial { 1 } else { width };
+                (0..num_constants).map(|_| grain.field_element()).collect()
+            })
+            .collect();
+
+        PoseidonConfig {
+            full_rounds,
+            partial_rounds,
+            alpha,
+            round_constants,
+            internal_diagonal,
+        }
+    }
+
+    /// The parameters of Noir's `Poseidon2Permutation` black box: a width of 4, 8 full and 56
+    /// partial rounds and `x^5`, over the BN254 scalar field.
+    ///
+    /// Returns `None` if `F` is not the BN254 scalar field, as Noir defines no other.
+    pub fn noir() -> Option<Self> {
+        const BN254_MODULUS: &str =
+            "30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001";
+        const INTERNAL_DIAGONAL: [&str; 4] = [
+            "10dc6e9c006ea38b04b1e03b4bd9490c0d03f98929ca1d7fb56821fd19d3b6e7",
+            "0c28145b6a44df3e0149b3d0a30b3bb599df9756d4dd9b84a86b38cfb45a740b",
+            "00544b8338791518b2c7645a50392798b21f75bb60e3596170067d00141cac15",
+            "222c01175718386f2e2e82eb122789e352e105a3b8fa852613bc534433ee428b",
+        ];
+
+        let modulus: String = F::MODULUS
+            .to_bytes_be()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        if modulus != BN254_MODULUS {
+            return None;
+        }
+        let internal_diagonal = INTERNAL_DIAGONAL
+            .iter()
+            .map(|hex| field_from_hex(hex).expect("diagonal entries are canonical"))
+            .collect();
+        Some(PoseidonConfig::new(8, 56, 5, internal_diagonal))
+    }
+
+    /// Applies the permutation to `state`, which must have [`PoseidonConfig::width`] elements.
+    pub fn permute(&self, state: &[F]) -> Vec<F> {
+        let external = self.external_matrix();
+        let internal = self.internal_matrix();
+
+        let mut state = apply_matrix(&external, state);
+        for (round, constants) in self.round_constants.iter().enumerate() {
+            for (element, constant) in state.iter_mut().zip(constants) {
+                *element = (*element + constant).pow([self.alpha]);
+            }
+            let matrix = if self.is_partial_round(round) {
+                &internal
+            } else {
+                &external
+            };
+            state = apply_matrix(matrix, &state);
+        }
+        state
+    }
+
+    fn is_partial_round(&self, round: usize) -> bool {
+        let first_partial = self.full_rounds / 2;
+        (first_partial..first_partial + self.partial_rounds).contains(&round)
+    }
+
+    /// The matrix of the full rounds, found by applying the external layer to the unit vectors.
+    fn external_matrix(&self) -> Vec<Vec<F>> {
+        let width = self.width();
+        let columns: Vec<Vec<F>> = (0..width)
+            .map(|column| {
+                let mut unit = vec![F::zero(); width];
+                unit[column] = F::one();
+                external_layer(&mut unit);
+                unit
+            })
+            .collect();
+        (0..width)
+            .map(|row| columns.iter().map(|column| column[row]).collect())
+            .collect()
+    }
+
+    fn internal_matrix(&self) -> Vec<Vec<F>> {
+        let width = self.width();
+        (0..width)
+            .map(|row| {
+                (0..width)
+                    .map(|column| match row == column {
+                        true => self.internal_diagonal[row] + F::one(),
+                        false => F::one(),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Multiplies `state` by the external matrix of Poseidon2: `circ(2, 1, ...)` for widths 2 and
+/// 3, `M4` for width 4 and `circ(2 * M4, M4, ..., M4)` for larger multiples of 4.
+fn external_layer<F: Field>(state: &mut [F]) {
+    if state.len() < 4 {
+        let sum: F = state.iter().sum();
+        for element in state.iter_mut() {
+            *element += sum;
+        }
+        return;
+    }
+
+    for chunk in state.chunks_mut(4) {
+        let t0 = chunk[0] + chunk[1];
+        let t1 = chunk[2] + chunk[3];
+        let t2 = chunk[1].double() + t1;
+        let t3 = chunk[3].double() + t0;
+        let t4 = t1.double().double() + t3;
+        let t5 = t0.double().double() + t2;
+        chunk.copy_from_slice(&[t3 + t5, t5, t2 + t4, t4]);
+    }
+    if state.len() == 4 {
+        return;
+    }
+    let sums: Vec<F> = (0..4)
+        .map(|offset| state.iter().skip(offset).step_by(4).sum())
+        .collect();
+    for (index, element) in state.iter_mut().enumerate() {
+        *element += sums[index % 4];
+    }
+}
+
+fn apply_matrix<F: Field>(matrix: &[Vec<F>], state: &[F]) -> Vec<F> {
+    matrix
+        .iter()
+        .map(|row| row.iter().zip(state).map(|(coeff, x)| *coeff * x).sum())
+        .collect()
+}
+
+/// The Grain LFSR the reference implementation of Poseidon generates its constants with.
+struct GrainLfsr {
+    state: Vec<bool>,
+}
+
+impl GrainLfsr {
+    fn new(field_bits: u32, width: usize, full_rounds: usize, partial_rounds: usize) -> Self {
+        let bits = |value: u64, len: usize| (0..len).rev().map(move |bit| value >> bit & 1 == 1);
+        // A prime field, the S-box `x^alpha`, then the parameters, padded with ones
+        let state = bits(1, 2)
+            .chain(bits(0, 4))
+            .chain(bits(field_bits as u64, 12))
+            .chain(bits(width as u64, 12))
+            .chain(bits(full_rounds as u64, 10))
+            .chain(bits(partial_rounds as u64, 10))
+            .chain([true; 30])
+            .collect();
+
+        let mut grain = GrainLfsr { state };
+        for _ in 0..160 {
+            grain.step();
+        }
+        grain
+    }
+
+    fn step(&mut self) -> bool {
+        let bit = [62, 51, 38, 23, 13, 0]
+            .iter()
+            .fold(false, |acc, tap| acc ^ self.state[*tap]);
+        self.state.remove(0);
+        self.state.push(bit);
+        bit
+    }
+
+    /// Outputs the second bit of each pair whose first bit is set.
+    fn next_bit(&mut self) -> bool {
+        while !self.step() {
+            self.step();
+        }
+        self.step()
+    }
+
+    /// Samples field elements from `MODULUS_BIT_SIZE` bits until one is below the modulus.
+    fn field_element<F: PrimeField>(&mut self) -> F {
+        loop {
+            let bits: Vec<bool> = (0..F::MODULUS_BIT_SIZE).map(|_| self.next_bit()).collect();
+            if let Some(element) = F::from_bigint(F::BigInt::from_bits_be(&bits)) {
+                return element;
+            }
+        }
+    }
+}
+
+/// A single `BlackBoxFuncCall::Poseidon2Permutation`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Poseidon2Gate {
+    pub(crate) inputs: Vec<Witness>,
+    pub(crate) outputs: Vec<Witness>,
+    pub(crate) source_opcode: usize,
+}
+
+impl Poseidon2Gate {
+    pub(crate) fn witnesses(&self) -> impl Iterator<Item = Witness> + '_ {
+        self.inputs.iter().chain(&self.outputs).copied()
+    }
+}
+
+/// Reports the first permutation which `config` cannot constrain, because there is none or
+/// because the permutation has a different width.
+pub(crate) fn check_gates<F: Field>(
+    gates: &[Poseidon2Gate],
+    config: Option<&PoseidonConfig<F>>,
+) -> Result<(), BridgeError> {
+    let Some(gate) = gates.first() else {
+        return Ok(());
+    };
+    let Some(config) = config else {
+        return Err(BridgeError::MissingPoseidonConfig {
+            opcode: gate.source_opcode,
+        });
+    };
+    let width = config.width();
+    match gates
+        .iter()
+        .find(|gate| gate.inputs.len() != width || gate.outputs.len() != width)
+    {
+        Some(gate) => Err(BridgeError::PoseidonWidthMismatch {
+            opcode: gate.source_opcode,
+            width: gate.inputs.len(),
+            expected: width,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Enforces every permutation. Fails with `SynthesisError::Unsatisfiable` if `config` cannot
+/// constrain them, see [`check_gates`] for the details.
+pub(crate) fn enforce_poseidon2_gates<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    gates: &[Poseidon2Gate],
+    config: Option<&PoseidonConfig<F>>,
+    variables: &BTreeMap<Witness, Variable>,
+) -> Result<(), SynthesisError> {
+    check_gates(gates, config).map_err(|_| SynthesisError::Unsatisfiable)?;
+    let Some(config) = config else {
+        return Ok(());
+    };
+    let external = config.external_matrix();
+    let internal = config.internal_matrix();
+
+    for gate in gates {
+        let mut state: Vec<LinearCombination<F>> = gate
+            .inputs
+            .iter()
+            .map(|input| lc!() + variables[input])
+            .collect();
+        let mut values: Option<Vec<F>> = gate
+            .inputs
+            .iter()
+            .map(|input| cs.assigned_value(variables[input]))
+            .collect();
+
+        state = apply_matrix_lc(&external, &state);
+        values = values.map(|values| apply_matrix(&external, &values));
+        for (round, constants) in config.round_constants.iter().enumerate() {
+            for (index, constant) in constants.iter().enumerate() {
+                let input = state[index].clone() + (*constant, Variable::One);
+                let value = values.as_ref().map(|values| values[index] + constant);
+                state[index] = lc!() + enforce_pow(cs, &input, value, config.alpha)?;
+                if let (Some(values), Some(value)) = (values.as_mut(), value) {
+                    values[index] = value.pow([config.alpha]);
+                }
+            }
+            let matrix = if config.is_partial_round(round) {
+                &internal
+            } else {
+                &external
+            };
+            state = apply_matrix_lc(matrix, &state);
+            values = values.map(|values| apply_matrix(matrix, &values));
+        }
+
+        for (element, output) in state.into_iter().zip(&gate.outputs) {
+            cs.enforce_constraint(lc!() + Variable::One, element - variables[output], lc!())?;
+        }
+    }
+    Ok(())
+}
+
+/// Counts the constraints and witnesses [`enforce_poseidon2_gates`] allocates.
+pub(crate) fn r1cs_size<F: Field>(
+    gates: &[Poseidon2Gate],
+    config: Option<&PoseidonConfig<F>>,
+) -> R1csSize {
+    let Some(config) = config else {
+        return R1csSize::default();
+    };
+    let num_sboxes: usize = config.round_constants.iter().map(Vec::len).sum();
+    // A squaring per bit below the leading one of `alpha`, and a multiplication per set bit
+    let num_multiplications = match config.alpha {
+        1 => 1,
+        alpha => (63 - alpha.leading_zeros() + alpha.count_ones() - 1) as usize,
+    };
+    let mut size = R1csSize::default();
+    for gate in gates {
+        size.constraints += num_sboxes * num_multiplications + gate.outputs.len();
+        size.witnesses += num_sboxes * num_multiplications;
+    }
+    size
+}
+
+/// Allocates `base^exponent` by square-and-multiply, constraining each multiplication.
+fn enforce_pow<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    base: &LinearCombination<F>,
+    value: Option<F>,
+    exponent: u64,
+) -> Result<Variable, SynthesisError> {
+    // lhs * rhs == product
+    let multiply =
+        |lhs: LinearCombination<F>, rhs: LinearCombination<F>, product_value: Option<F>| {
+            let product =
+                cs.new_witness_variable(|| product_value.ok_or(SynthesisError::AssignmentMissing))?;
+            cs.enforce_constraint(lhs, rhs, lc!() + product)?;
+            Ok::<_, SynthesisError>(product)
+        };
+
+    if exponent == 1 {
+        return multiply(lc!() + Variable::One, base.clone(), value);
+    }
+    let (mut acc, mut acc_value) = (base.clone(), value);
+    let mut acc_var = Variable::One;
+    for bit in (0..63 - exponent.leading_zeros()).rev() {
+        acc_value = acc_value.map(|acc| acc.square());
+        acc_var = multiply(acc.clone(), acc, acc_value)?;
+        acc = lc!() + acc_var;
+        if exponent >> bit & 1 == 1 {
+            acc_value = acc_value.zip(value).map(|(acc, base)| acc * base);
+            acc_var = multiply(acc, base.clone(), acc_value)?;
+            acc = lc!() + acc_var;
+        }
+    }
+    Ok(acc_var)
+}
+
+fn apply_matrix_lc<F: Field>(
+    matrix: &[Vec<F>],
+    state: &[LinearCombination<F>],
+) -> Vec<LinearCombination<F>> {
+    matrix
+        .iter()
+        .map(|row| {
+            row.iter()
+                .zip(state)
+                .filter(|(coeff, _)| !coeff.is_zero())
+                .fold(lc!(), |acc, (coeff, element)| acc + (*coeff, element))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use acvm::acir::circuit::opcodes::{BlackBoxFuncCall, FunctionInput};
+    use acvm::acir::circuit::Opcode;
+    use acvm::acir::native_types::WitnessMap;
+    use acvm::FieldElement;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+
+    use super::*;
+    use crate::test::circuit_with_opcodes;
+    use crate::{CurveAcir, Fr};
+
+    fn fr(hex: &str) -> Fr {
+        field_from_hex(hex).unwrap()
+    }
+
+    #[test]
+    fn permutation_matches_noir() {
+        assert!(PoseidonConfig::<ark_bls12_381::Fr>::noir().is_none());
+
+        // The test vector of Noir's BN254 black box solver
+        let config = PoseidonConfig::<Fr>::noir().unwrap();
+        let state = [0u64, 1, 2, 3].map(Fr::from);
+        assert_eq!(
+            config.permute(&state),
+            [
+                fr("0x01bd538c2ee014ed5141b29e9ae240bf8db3fe5b9a38629a9647cf8d76c01737"),
+                fr("0x239b62e7db98aa3a2a8f6a0d2fa1709e7a35959aa6c7034814d9daa90cbac662"),
+                fr("0x04cbb44c61d928ed06808456bf758cbf0c18d1e15a7b6dbc8245fa7515d5e3cb"),
+                fr("0x2e11c5cff2a22c64d01304b778d78f6998eff1ab73163a35603f54794c30847a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn constrains_two_element_hash() {
+        let permutation = Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Poseidon2Permutation {
+            inputs: (0..4)
+                .map(|index| FunctionInput {
+                    witness: Witness(index),
+                    num_bits: 254,
+                })
+                .collect(),
+            outputs: (4..8).map(Witness).collect(),
+            len: 4,
+        });
+        let circuit = circuit_with_opcodes(7, vec![permutation], &[]);
+
+        // Noir's `poseidon2::Poseidon2::hash([1, 2], 2)`: the inputs, padding and the length
+        // times 2^64 as the initial value of the capacity element
+        let inputs = [
+            Fr::from(1u64),
+            Fr::from(2u64),
+            Fr::from(0u64),
+            Fr::from(2u128 << 64),
+        ];
+        let outputs = PoseidonConfig::noir().unwrap().permute(&inputs);
+        assert_eq!(
+            outputs[0],
+            fr("0x038682aa1cb5ae4e0a3f13da432a95c77c5c111f6f030faf9cad641ce1ed7383")
+        );
+
+        let lower = |outputs: &[Fr]| {
+            let mut witness_map = WitnessMap::new();
+            for (index, value) in inputs.iter().chain(outputs).enumerate() {
+                witness_map.insert(Witness(index as u32), FieldElement::from_repr(*value));
+            }
+            CurveAcir::from((&circuit, witness_map))
+        };
+        let is_satisfied = |circuit: CurveAcir| {
+            let cs = ConstraintSystem::new_ref();
+            circuit.generate_constraints(cs.clone()).unwrap();
+            // Three constraints per S-box of the 8 full and 56 partial rounds, one per output
+            assert_eq!(cs.num_constraints(), 3 * (8 * 4 + 56) + 4);
+            cs.is_satisfied().unwrap()
+        };
+        assert!(is_satisfied(lower(&outputs)));
+
+        let mut tampered = outputs.clone();
+        tampered[0] += Fr::from(1u64);
+        assert!(!is_satisfied(lower(&tampered)));
+
+        let narrow = PoseidonConfig::new(8, 56, 5, vec![Fr::from(1u64); 3]);
+        assert_eq!(
+            lower(&outputs)
+                .with_poseidon_config(narrow)
+                .check_poseidon2_gates(),
+            Err(BridgeError::PoseidonWidthMismatch {
+                opcode: 0,
+                width: 4,
+                expected: 3,
+            })
+        );
+    }
+}
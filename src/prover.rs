@@ -0,0 +1,99 @@
+use ark_groth16::{Groth16, Proof, ProvingKey, VerifyingKey};
+use ark_std::rand::{CryptoRng, RngCore};
+
+use crate::concrete_cfg::{Curve, CurveAcir, Fr};
+use crate::groth16::{self, ProveError};
+
+// A common interface over the proving backends, so that code driving a backend (benchmarks,
+// tooling) can be written once and run against any of them.
+
+/// A proving backend for circuits over the curve selected in `concrete_cfg`.
+///
+/// The circuit passed to [`ProofSystem::setup`] only needs to have the right shape, the one
+/// passed to [`ProofSystem::prove`] must carry the solved witness values.
+pub trait ProofSystem {
+    type ProvingKey;
+    type VerifyingKey;
+    type Proof;
+    type Error;
+
+    fn setup<R: RngCore + CryptoRng>(
+        circuit: &CurveAcir,
+        rng: &mut R,
+    ) -> Result<(Self::ProvingKey, Self::VerifyingKey), Self::Error>;
+
+    fn prove<R: RngCore + CryptoRng>(
+        pk: &Self::ProvingKey,
+        circuit: &CurveAcir,
+        rng: &mut R,
+    ) -> Result<Self::Proof, Self::Error>;
+
+    /// Verifies `proof` against the public inputs, given in ascending `Witness` order.
+    fn verify(
+        vk: &Self::VerifyingKey,
+        public_inputs: &[Fr],
+        proof: &Self::Proof,
+    ) -> Result<bool, Self::Error>;
+}
+
+impl ProofSystem for Groth16<Curve> {
+    type ProvingKey = ProvingKey<Curve>;
+    type VerifyingKey = VerifyingKey<Curve>;
+    type Proof = Proof<Curve>;
+    type Error = ProveError;
+
+    fn setup<R: RngCore + CryptoRng>(
+        circuit: &CurveAcir,
+        rng: &mut R,
+    ) -> Result<(Self::ProvingKey, Self::VerifyingKey), ProveError> {
+        Ok(groth16::setup(circuit, rng)?)
+    }
+
+    fn prove<R: RngCore + CryptoRng>(
+        pk: &Self::ProvingKey,
+        circuit: &CurveAcir,
+        rng: &mut R,
+    ) -> Result<Self::Proof, ProveError> {
+        groth16::prove(pk, circuit, rng)
+    }
+
+    fn verify(
+        vk: &Self::VerifyingKey,
+        public_inputs: &[Fr],
+        proof: &Self::Proof,
+    ) -> Result<bool, ProveError> {
+        Ok(groth16::verify(vk, public_inputs, proof)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fmt::Debug;
+
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::test::{test_circuit, test_circuit_witness_map};
+
+    fn proves_and_verifies<P: ProofSystem>()
+    where
+        P::Error: Debug,
+    {
+        let circuit = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        let public_inputs: Vec<Fr> = [2u64, 3, 8, 15].into_iter().map(Fr::from).collect();
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let (pk, vk) = P::setup(&circuit, &mut rng).unwrap();
+        let proof = P::prove(&pk, &circuit, &mut rng).unwrap();
+        assert!(P::verify(&vk, &public_inputs, &proof).unwrap());
+
+        let mut tampered = public_inputs;
+        tampered[0] += Fr::from(1u64);
+        assert!(!P::verify(&vk, &tampered, &proof).unwrap());
+    }
+
+    #[test]
+    fn groth16_proves_through_the_trait() {
+        proves_and_verifies::<Groth16<Curve>>();
+    }
+}
@@ -0,0 +1,199 @@
+use std::io::{self, Write};
+
+use ark_ff::{BigInteger, PrimeField};
+use ark_relations::r1cs::{
+    ConstraintMatrices, ConstraintSynthesizer, ConstraintSystem, OptimizationGoal, SynthesisError,
+    SynthesisMode,
+};
+
+use crate::concrete_cfg::{CurveAcir, Fr};
+use crate::wtns::{write_wtns_to, WtnsError};
+
+// Export of the constraint system to the iden3 `.r1cs` format read by circom tooling and snarkjs.
+//
+// The wires are numbered like arkworks' variables: the constant `1`, the public inputs in
+// ascending `Witness` order, then the private witnesses. A witness exported with
+// [`export_wtns`] therefore lines up with the exported constraints.
+//
+// Layout (all integers little-endian):
+// - the magic `r1cs`, the format version (`u32`) and the number of sections (`u32`)
+// - each section starts with its id (`u32`) and size in bytes (`u64`)
+// - section 1 (header): the field element size `n8` (`u32`), the field prime (`n8` bytes), the
+//   number of wires, public outputs, public inputs and private inputs (`u32` each), the number
+//   of labels (`u64`) and the number of constraints (`u32`)
+// - section 2 (constraints): for each constraint the linear combinations `A`, `B` and `C`, each
+//   as its number of terms (`u32`) followed by `(wire (u32), coefficient (n8 bytes))` pairs, the
+//   coefficients in canonical (non-Montgomery) form
+// - section 3 (wire to label map): the label (`u64`) of each wire
+//
+// Public outputs are not told apart from public inputs, and the private inputs are not placed
+// before the other private witnesses, so all public inputs are counted as inputs and no wire is
+// counted as a private input.
+
+const MAGIC: &[u8; 4] = b"r1cs";
+const VERSION: u32 = 1;
+const HEADER_SECTION: u32 = 1;
+const CONSTRAINT_SECTION: u32 = 2;
+const WIRE_TO_LABEL_SECTION: u32 = 3;
+
+/// Writes the constraints [`CurveAcir::generate_constraints`] emits for `circuit` as a `.r1cs`
+/// file. The circuit's values are ignored.
+///
+/// [`CurveAcir::generate_constraints`]: ark_relations::r1cs::ConstraintSynthesizer::generate_constraints
+pub fn export_r1cs<W: Write>(circuit: &CurveAcir, mut writer: W) -> io::Result<()> {
+    let matrices = constraint_matrices(circuit).map_err(invalid_input)?;
+    let n8 = field_size();
+    let num_wires = matrices.num_instance_variables + matrices.num_witness_variables;
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&3u32.to_le_bytes())?;
+
+    writer.write_all(&HEADER_SECTION.to_le_bytes())?;
+    writer.write_all(&(4 + n8 as u64 + 4 * 4 + 8 + 4).to_le_bytes())?;
+    writer.write_all(&(n8 as u32).to_le_bytes())?;
+    writer.write_all(&Fr::MODULUS.to_bytes_le())?;
+    writer.write_all(&(num_wires as u32).to_le_bytes())?;
+    writer.write_all(&0u32.to_le_bytes())?;
+    writer.write_all(&(matrices.num_instance_variables as u32 - 1).to_le_bytes())?;
+    writer.write_all(&0u32.to_le_bytes())?;
+    writer.write_all(&(num_wires as u64).to_le_bytes())?;
+    writer.write_all(&(matrices.num_constraints as u32).to_le_bytes())?;
+
+    let rows = || matrices.a.iter().chain(&matrices.b).chain(&matrices.c);
+    let constraints_size: usize = rows().map(|row| 4 + row.len() * (4 + n8)).sum();
+    writer.write_all(&CONSTRAINT_SECTION.to_le_bytes())?;
+    writer.write_all(&(constraints_size as u64).to_le_bytes())?;
+    for constraint in 0..matrices.num_constraints {
+        for matrix in [&matrices.a, &matrices.b, &matrices.c] {
+            let row = &matrix[constraint];
+            writer.write_all(&(row.len() as u32).to_le_bytes())?;
+            for (coeff, wire) in row {
+                writer.write_all(&(*wire as u32).to_le_bytes())?;
+                writer.write_all(&coeff.into_bigint().to_bytes_le())?;
+            }
+        }
+    }
+
+    writer.write_all(&WIRE_TO_LABEL_SECTION.to_le_bytes())?;
+    writer.write_all(&(8 * num_wires as u64).to_le_bytes())?;
+    for wire in 0..num_wires as u64 {
+        writer.write_all(&wire.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Writes the assignment of `circuit`'s wires, numbered as in [`export_r1cs`], as a snarkjs
+/// `.wtns` file.
+pub fn export_wtns<W: Write>(circuit: &CurveAcir, writer: W) -> Result<(), WtnsError> {
+    let cs = ConstraintSystem::new_ref();
+    cs.set_optimization_goal(OptimizationGoal::Constraints);
+    circuit
+        .clone()
+        .generate_constraints(cs.clone())
+        .map_err(invalid_input)?;
+    let cs = cs.borrow().unwrap();
+    let assignment = [
+        cs.instance_assignment.as_slice(),
+        cs.witness_assignment.as_slice(),
+    ]
+    .concat();
+    write_wtns_to(writer, &assignment)
+}
+
+fn constraint_matrices(circuit: &CurveAcir) -> Result<ConstraintMatrices<Fr>, SynthesisError> {
+    let cs = ConstraintSystem::new_ref();
+    cs.set_optimization_goal(OptimizationGoal::Constraints);
+    cs.set_mode(SynthesisMode::Setup);
+    circuit.clone().generate_constraints(cs.clone())?;
+    cs.finalize();
+    Ok(cs
+        .to_matrices()
+        .expect("matrices are constructed in setup mode"))
+}
+
+fn invalid_input(error: SynthesisError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, error.to_string())
+}
+
+// Size in bytes of a serialized field element
+fn field_size() -> usize {
+    Fr::MODULUS.to_bytes_le().len()
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use super::*;
+    use crate::test::{test_circuit, test_circuit_witness_map};
+    use crate::wtns::read_wtns_from;
+
+    fn read_u32<R: Read>(reader: &mut R) -> u32 {
+        let mut bytes = [0u8; 4];
+        reader.read_exact(&mut bytes).unwrap();
+        u32::from_le_bytes(bytes)
+    }
+
+    fn read_u64<R: Read>(reader: &mut R) -> u64 {
+        let mut bytes = [0u8; 8];
+        reader.read_exact(&mut bytes).unwrap();
+        u64::from_le_bytes(bytes)
+    }
+
+    // Returns the prime, the number of wires and the constraints counted in the constraint
+    // section, skipping every other section
+    fn parse_r1cs(mut reader: &[u8]) -> (Vec<u8>, u32, u32) {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic).unwrap();
+        assert_eq!(&magic, MAGIC);
+        assert_eq!(read_u32(&mut reader), VERSION);
+
+        let (mut prime, mut num_wires, mut num_constraints) = (Vec::new(), 0, 0);
+        for _ in 0..read_u32(&mut reader) {
+            let section_id = read_u32(&mut reader);
+            let section_size = read_u64(&mut reader) as usize;
+            let (mut section, rest) = reader.split_at(section_size);
+            reader = rest;
+            match section_id {
+                HEADER_SECTION => {
+                    prime = vec![0u8; read_u32(&mut section) as usize];
+                    section.read_exact(&mut prime).unwrap();
+                    num_wires = read_u32(&mut section);
+                }
+                CONSTRAINT_SECTION => {
+                    while !section.is_empty() {
+                        for _ in 0..3 {
+                            let num_terms = read_u32(&mut section) as usize;
+                            section = &section[num_terms * (4 + prime.len())..];
+                        }
+                        num_constraints += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        (prime, num_wires, num_constraints)
+    }
+
+    #[test]
+    fn exported_r1cs_round_trips() {
+        let circuit = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        let mut bytes = Vec::new();
+        export_r1cs(&circuit, &mut bytes).unwrap();
+
+        let cs = ConstraintSystem::new_ref();
+        cs.set_optimization_goal(OptimizationGoal::Constraints);
+        circuit.clone().generate_constraints(cs.clone()).unwrap();
+        let num_wires = cs.num_instance_variables() + cs.num_witness_variables();
+
+        let (prime, wires, constraints) = parse_r1cs(&bytes);
+        assert_eq!(prime, Fr::MODULUS.to_bytes_le());
+        assert_eq!(wires as usize, num_wires);
+        assert_eq!(constraints as usize, cs.num_constraints());
+
+        let mut wtns = Vec::new();
+        export_wtns(&circuit, &mut wtns).unwrap();
+        assert_eq!(read_wtns_from(wtns.as_slice()).unwrap().len(), num_wires);
+    }
+}
@@ -0,0 +1,387 @@
+use std::collections::BTreeMap;
+
+use acvm::acir::native_types::Witness;
+use ark_ff::PrimeField;
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError, Variable};
+
+use crate::R1csSize;
+
+// Lowering of `BlackBoxFuncCall::SchnorrVerify` to R1CS.
+//
+// Following barretenberg and the ACVM solver, a signature is the pair of 32-byte big-endian
+// integers `(s, e)`, and it is valid for the public key `P` when:
+// - `P` is a point of the embedded curve,
+// - neither `s` nor `e` is zero modulo the order of the curve,
+// - `R = s * G + e * P` is not the point at infinity, `G` being the generator `(1, y)` with the
+//   smaller of the two `y` coordinates,
+// - `e` is the BLAKE2s digest of the 32 big-endian bytes of the Pedersen hash of
+//   `(R.x, P.x, P.y)`, with the first generator, followed by the message.
+//
+// The signature and message bytes are decomposed into bits, which also range-constrains them.
+// `s * G` sums the selected constant multiples `2^j * G` as in the `pedersen` module, and
+// `e * P` is computed by double-and-add as a multi-scalar multiplication term is. The order of
+// the curve is prime, so a scalar is zero modulo the order exactly when its product is the point
+// at infinity. A key off the curve is replaced by `G` before the multiplication, so that the
+// circuit stays satisfiable with an output of zero. The Pedersen hash is decomposed into bits
+// below the modulus and hashed with the message as in the `blake2s` module, and the digest is
+// compared with `e` in two 128-bit halves. The output must be the product of the conditions.
+//
+// The constraints are only generated with the `schnorr` feature, which enables the `pedersen`
+// and `blake2s` features.
+
+/// A single `BlackBoxFuncCall::SchnorrVerify`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SchnorrVerifyGate {
+    pub(crate) public_key_x: Witness,
+    pub(crate) public_key_y: Witness,
+    pub(crate) signature: Vec<Witness>,
+    pub(crate) message: Vec<Witness>,
+    pub(crate) output: Witness,
+    pub(crate) source_opcode: usize,
+}
+
+impl SchnorrVerifyGate {
+    pub(crate) fn witnesses(&self) -> impl Iterator<Item = Witness> + '_ {
+        [self.public_key_x, self.public_key_y]
+            .into_iter()
+            .chain(self.signature.iter().copied())
+            .chain(self.message.iter().copied())
+            .chain(std::iter::once(self.output))
+    }
+
+    pub(crate) fn witnesses_mut(&mut self) -> impl Iterator<Item = &mut Witness> {
+        [&mut self.public_key_x, &mut self.public_key_y]
+            .into_iter()
+            .chain(self.signature.iter_mut())
+            .chain(self.message.iter_mut())
+            .chain(std::iter::once(&mut self.output))
+    }
+}
+
+/// Counts the constraints and witnesses [`enforce_schnorr_verify_gates`] allocates, none
+/// without the `schnorr` feature.
+#[cfg_attr(not(feature = "schnorr"), allow(unused_variables))]
+pub(crate) fn r1cs_size(gates: &[SchnorrVerifyGate]) -> R1csSize {
+    #[cfg(feature = "schnorr")]
+    return verification::r1cs_size(gates);
+
+    #[cfg(not(feature = "schnorr"))]
+    R1csSize::default()
+}
+
+/// Enforces every signature verification. Fails with `SynthesisError::Unsatisfiable` if there
+/// are any but the field has no embedded curve, or the `schnorr` feature is disabled.
+#[cfg_attr(not(feature = "schnorr"), allow(unused_variables))]
+pub(crate) fn enforce_schnorr_verify_gates<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    gates: &[SchnorrVerifyGate],
+    variables: &BTreeMap<Witness, Variable>,
+) -> Result<(), SynthesisError> {
+    #[cfg(feature = "schnorr")]
+    return verification::enforce_schnorr_verify_gates(cs, gates, variables);
+
+    #[cfg(not(feature = "schnorr"))]
+    match gates.is_empty() {
+        true => Ok(()),
+        false => Err(SynthesisError::Unsatisfiable),
+    }
+}
+
+#[cfg(feature = "schnorr")]
+mod verification {
+    use std::collections::BTreeMap;
+
+    use acvm::acir::native_types::Witness;
+    use ark_ff::PrimeField;
+    use ark_relations::{
+        lc,
+        r1cs::{ConstraintSystemRef, LinearCombination, SynthesisError, Variable},
+    };
+
+    use super::SchnorrVerifyGate;
+    use crate::blake2s::hash::{self as blake2s, Bit};
+    use crate::embedded_curve::{add_values, alloc_product, alloc_sum, coefficients, select};
+    use crate::gadgets::enforce_bit_decomposition;
+    use crate::pedersen::hash::{self as pedersen, alloc_constant, select_constant, Point};
+    use crate::R1csSize;
+
+    const SCALAR_BYTES: usize = 32;
+
+    /// The generator `(1, y)` of the curve `y^2 = x^3 + a x + b`, with the smaller `y`, if
+    /// `1 + a + b` is a square.
+    fn generator<F: PrimeField>((a, b): (F, F)) -> Option<Point<F>> {
+        let y = (F::one() + a + b).sqrt()?;
+        let y = match y.into_bigint() < (-y).into_bigint() {
+            true => y,
+            false => -y,
+        };
+        Some([F::one(), y, F::zero()])
+    }
+
+    pub(super) fn r1cs_size(gates: &[SchnorrVerifyGate]) -> R1csSize {
+        let size = |constraints: usize, witnesses: usize| R1csSize {
+            constraints,
+            witnesses,
+            public_inputs: 0,
+        };
+        let scalar_bits = 8 * SCALAR_BYTES;
+
+        let mut total = R1csSize::default();
+        for gate in gates {
+            let num_bytes = gate.signature.len() + gate.message.len();
+            // The decomposition of the bytes, the generator and the point at infinity, then
+            // the key's check and selection
+            total += size(9 * num_bytes + 6 + 5 + 3, 8 * num_bytes + 6 + 5 + 3);
+            // `s * G` by a selection and an addition per bit, `e * P` by a selection, then a
+            // doubling, an addition and a selection per bit, and their sum
+            total += size(
+                scalar_bits * (3 + 34) + 3 + (scalar_bits - 1) * (2 * 34 + 3) + 34,
+                scalar_bits * (3 + 29) + 3 + (scalar_bits - 1) * (2 * 29 + 3) + 29,
+            );
+            // The challenge: the Pedersen hash and its bits, then its digest with the message
+            total += pedersen::hash_r1cs_size(3);
+            total += pedersen::canonical_bits_r1cs_size();
+            total += blake2s::hash_r1cs_size(SCALAR_BYTES + gate.message.len());
+            // The comparison of both halves, and the product of the six conditions
+            total += size(2 * 2 + 5, 2 * 2 + 4);
+        }
+        total
+    }
+
+    pub(super) fn enforce_schnorr_verify_gates<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        gates: &[SchnorrVerifyGate],
+        variables: &BTreeMap<Witness, Variable>,
+    ) -> Result<(), SynthesisError> {
+        if gates.is_empty() {
+            return Ok(());
+        }
+        let coefficients = coefficients::<F>().ok_or(SynthesisError::Unsatisfiable)?;
+        let a = coefficients.0;
+        let generator_point = generator(coefficients).ok_or(SynthesisError::Unsatisfiable)?;
+        let value = |variable| cs.assigned_value(variable);
+
+        for gate in gates {
+            // The little-endian bits of each byte
+            let byte_bits = |witnesses: &[Witness]| {
+                witnesses
+                    .iter()
+                    .map(|witness| enforce_bit_decomposition(cs, variables[witness], 8))
+                    .collect::<Result<Vec<_>, _>>()
+            };
+            let signature = byte_bits(&gate.signature)?;
+            let message = byte_bits(&gate.message)?;
+            let (s, e) = signature.split_at(SCALAR_BYTES);
+
+            let generator = alloc_constant(cs, generator_point)?;
+            let infinity = alloc_constant(cs, [F::zero(), F::zero(), F::one()])?;
+
+            // The key, or the generator if it is off the curve. The infinity flag of the
+            // generator is a witness constrained to zero.
+            let (x, y) = (variables[&gate.public_key_x], variables[&gate.public_key_y]);
+            let on_curve = is_on_curve(cs, x, y, coefficients)?;
+            let key = select(cs, on_curve, [x, y, generator[2]], generator)?;
+
+            // `s * G`, from the least significant bit
+            let mut s_product = infinity;
+            let mut multiple = generator_point;
+            for bit in s.iter().rev().flatten() {
+                let selected = select_constant(cs, *bit, multiple)?;
+                s_product = alloc_sum(cs, s_product, selected, coefficients)?;
+                multiple = add_values(multiple, multiple, a);
+            }
+            // `e * P`, from the most significant bit
+            let e_bits: Vec<Variable> = e
+                .iter()
+                .flat_map(|bits| bits.iter().rev())
+                .copied()
+                .collect();
+            let e_product = alloc_product(cs, &e_bits, key, infinity, coefficients)?;
+            let r = alloc_sum(cs, s_product, e_product, coefficients)?;
+
+            // The challenge, from the big-endian bytes of the Pedersen hash and the message
+            let hash = pedersen::enforce_hash(cs, &[r[0], key[0], key[1]], 0, coefficients)?;
+            let hash_bits = pedersen::enforce_canonical_bits(cs, hash)?;
+            let mut preimage = Vec::new();
+            for byte in (0..SCALAR_BYTES).rev() {
+                preimage.extend((8 * byte..8 * byte + 8).map(|i| match hash_bits.get(i) {
+                    Some(bit) => Bit::from_variable(cs, *bit),
+                    None => Bit::constant(false),
+                }));
+            }
+            preimage.extend(
+                message
+                    .iter()
+                    .flatten()
+                    .map(|bit| Bit::from_variable(cs, *bit)),
+            );
+            let digest = blake2s::hash_bits(cs, preimage)?;
+
+            let not = |is_infinite: Variable| {
+                (
+                    lc!() + Variable::One - is_infinite,
+                    value(is_infinite).map(|value| F::one() - value),
+                )
+            };
+            let mut conditions = vec![
+                (lc!() + on_curve, value(on_curve)),
+                not(s_product[2]),
+                not(e_product[2]),
+                not(r[2]),
+            ];
+            // Each half of the digest packed against the same half of `e`
+            for half in [0, 1] {
+                let (mut difference, mut difference_value) = (lc!(), Some(F::zero()));
+                for (j, byte) in (16 * half..16 * half + 16).enumerate() {
+                    for (i, e_bit) in e[byte].iter().enumerate() {
+                        let weight = F::from(2u64).pow([(8 * j + i) as u64]);
+                        let digest_bit = &digest[8 * byte + i];
+                        difference = difference + (weight, &digest_bit.lc) - (weight, *e_bit);
+                        difference_value = difference_value
+                            .zip(digest_bit.value)
+                            .zip(value(*e_bit))
+                            .map(|((sum, lhs), rhs)| sum + weight * (F::from(lhs) - rhs));
+                    }
+                }
+                let equal = is_zero(cs, difference, difference_value)?;
+                conditions.push((lc!() + equal, value(equal)));
+            }
+
+            let (last, _) = conditions.pop().expect("there are conditions");
+            let mut conditions = conditions.into_iter();
+            let (mut all, mut all_value) = conditions.next().expect("there are conditions");
+            for (condition, condition_value) in conditions {
+                all_value = all_value.zip(condition_value).map(|(lhs, rhs)| lhs * rhs);
+                let product =
+                    cs.new_witness_variable(|| all_value.ok_or(SynthesisError::AssignmentMissing))?;
+                cs.enforce_constraint(all, condition, lc!() + product)?;
+                all = lc!() + product;
+            }
+            cs.enforce_constraint(all, last, lc!() + variables[&gate.output])?;
+        }
+        Ok(())
+    }
+
+    /// Returns a flag set when `(x, y)` is on the curve, in 5 constraints.
+    fn is_on_curve<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        x: Variable,
+        y: Variable,
+        (a, b): (F, F),
+    ) -> Result<Variable, SynthesisError> {
+        let (x_value, y_value) = (cs.assigned_value(x), cs.assigned_value(y));
+        // lhs * rhs == product
+        let multiply = |lhs: Variable, rhs: Variable, value: Option<F>| {
+            let product =
+                cs.new_witness_variable(|| value.ok_or(SynthesisError::AssignmentMissing))?;
+            cs.enforce_constraint(lc!() + lhs, lc!() + rhs, lc!() + product)?;
+            Ok::<_, SynthesisError>(product)
+        };
+        let square = multiply(x, x, x_value.map(|x| x.square()))?;
+        let cube = multiply(square, x, x_value.map(|x| x.square() * x))?;
+        let y_square = multiply(y, y, y_value.map(|y| y.square()))?;
+        is_zero(
+            cs,
+            lc!() + y_square - cube - (a, x) - (b, Variable::One),
+            x_value
+                .zip(y_value)
+                .map(|(x, y)| y.square() - x.square() * x - a * x - b),
+        )
+    }
+
+    /// Returns a flag set when `lc`, of the given value, is zero, in 2 constraints.
+    fn is_zero<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        lc: LinearCombination<F>,
+        value: Option<F>,
+    ) -> Result<Variable, SynthesisError> {
+        let inverse = cs.new_witness_variable(|| {
+            let value = value.ok_or(SynthesisError::AssignmentMissing)?;
+            Ok(value.inverse().unwrap_or_default())
+        })?;
+        let flag = cs.new_witness_variable(|| {
+            let value = value.ok_or(SynthesisError::AssignmentMissing)?;
+            Ok(F::from(value.is_zero()))
+        })?;
+        // value * inverse == 1 - flag, so the flag is set if the value has no inverse
+        cs.enforce_constraint(lc.clone(), lc!() + inverse, lc!() + Variable::One - flag)?;
+        // value * flag == 0, so the flag is clear if the value is not zero
+        cs.enforce_constraint(lc, lc!() + flag, lc!())?;
+        Ok(flag)
+    }
+}
+
+#[cfg(all(test, feature = "schnorr"))]
+mod test {
+    use acvm::acir::circuit::opcodes::{BlackBoxFuncCall, FunctionInput};
+    use acvm::acir::circuit::Opcode;
+    use acvm::acir::native_types::WitnessMap;
+    use acvm::FieldElement;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+
+    use super::*;
+    use crate::field::field_from_hex;
+    use crate::test::circuit_with_opcodes;
+    use crate::{CurveAcir, Fr};
+
+    #[test]
+    fn verifies_a_signature() {
+        let message: Vec<u8> = (0..10).collect();
+        // Signed with barretenberg's scheme, under the key of `0x5eed * 0x123456789abcdef1`
+        let public_key: [Fr; 2] = [
+            "26808eed9bac93254340cf95dd616467ea3be6e68cd871816b65d67bc542ac6c",
+            "0207df079d170a1f590ab2acdaa02b750cd1df087aa6ac5633c85df3aeb5b20d",
+        ]
+        .map(|hex| field_from_hex(hex).unwrap());
+        let signature: [u8; 64] = [
+            2, 240, 42, 9, 152, 51, 181, 249, 50, 120, 167, 248, 197, 45, 147, 110, 80, 211, 22,
+            122, 119, 78, 2, 51, 122, 22, 41, 159, 201, 215, 37, 35, 38, 167, 175, 222, 232, 228,
+            107, 158, 183, 172, 153, 127, 76, 16, 43, 50, 126, 179, 5, 98, 160, 247, 174, 251, 7,
+            56, 207, 251, 162, 130, 22, 229,
+        ];
+
+        let byte = |index: usize| FunctionInput {
+            witness: Witness(index as u32),
+            num_bits: 8,
+        };
+        let verify = Opcode::BlackBoxFuncCall(BlackBoxFuncCall::SchnorrVerify {
+            public_key_x: FunctionInput {
+                witness: Witness(0),
+                num_bits: 254,
+            },
+            public_key_y: FunctionInput {
+                witness: Witness(1),
+                num_bits: 254,
+            },
+            signature: Box::new(std::array::from_fn(|index| byte(2 + index))),
+            message: (0..message.len()).map(|index| byte(66 + index)).collect(),
+            output: Witness(76),
+        });
+        let circuit = circuit_with_opcodes(76, vec![verify], &[]);
+
+        let is_satisfied = |signature: &[u8; 64], output: u64| {
+            let values = public_key
+                .into_iter()
+                .chain(signature.iter().chain(&message).map(|byte| Fr::from(*byte)))
+                .chain([Fr::from(output)]);
+            let mut witness_map = WitnessMap::new();
+            for (index, value) in values.enumerate() {
+                witness_map.insert(Witness(index as u32), FieldElement::from_repr(value));
+            }
+            let circuit = CurveAcir::from((&circuit, witness_map));
+            let size = circuit.r1cs_size();
+            let cs = ConstraintSystem::new_ref();
+            circuit.generate_constraints(cs.clone()).unwrap();
+            assert_eq!(cs.num_constraints(), size.constraints);
+            cs.is_satisfied().unwrap()
+        };
+        assert!(is_satisfied(&signature, 1));
+        assert!(!is_satisfied(&signature, 0));
+
+        // A forged `s` verifies to zero
+        let mut forged = signature;
+        forged[31] ^= 1;
+        assert!(is_satisfied(&forged, 0));
+        assert!(!is_satisfied(&forged, 1));
+    }
+}
@@ -1,15 +1,33 @@
-use crate::bridge::{AcirArithGate, AcirCircuit};
+use crate::abi::{integer_witnesses, IntegerOverflow};
+use crate::aes::Aes128EncryptGate;
+use crate::bigint::{BigIntCall, BigIntGate, BigIntOperation};
+use crate::bitwise::{BitwiseGate, BitwiseOperation};
+use crate::blake2s::Blake2sGate;
+use crate::bridge::{AcirArithGate, AcirCircuit, CommitmentLayout, MemoryMode};
 use crate::concrete_cfg::CurveAcir;
+use crate::ecdsa::EcdsaSecp256k1Gate;
+use crate::embedded_curve::{EmbeddedCurveAddGate, MultiScalarMulGate, PointWitnesses};
+use crate::keccak::KeccakGate;
+use crate::memory::{MemoryAccess, MemoryIndex, MemoryOperation};
+use crate::pedersen::PedersenHashGate;
+use crate::poseidon2::{Poseidon2Gate, PoseidonConfig};
+use crate::schnorr::SchnorrVerifyGate;
+use crate::sha256::Sha256Gate;
+use crate::FilesystemError;
 use acvm::acir::acir_field::GenericFieldElement;
 use acvm::{
     acir::{
-        circuit::{Circuit, Opcode},
+        circuit::{
+            opcodes::{BlackBoxFuncCall, BlockId},
+            AssertionPayload, Circuit, Opcode, OpcodeLocation, Program,
+        },
         native_types::{Expression, Witness, WitnessMap},
     },
-    FieldElement,
+    AcirField, FieldElement,
 };
-use ark_ff::PrimeField;
-use std::{collections::BTreeMap, convert::TryInto};
+use ark_ff::{BigInteger, PrimeField};
+use noirc_abi::Abi;
+use std::collections::{BTreeMap, BTreeSet};
 
 impl From<&Circuit<FieldElement>> for CurveAcir {
     fn from(circuit: &Circuit<FieldElement>) -> CurveAcir {
@@ -29,46 +47,728 @@ impl<F: PrimeField>
             WitnessMap<GenericFieldElement<F>>,
         ),
     ) -> AcirCircuit<F> {
-        // Currently non-arithmetic gates are not supported
-        // so we extract all of the arithmetic gates only
-        let (circuit, witness_map) = circ_val;
+        AcirCircuit::from_with_transform(circ_val, |_, value| value)
+    }
+}
 
-        let public_inputs = circuit.public_inputs();
-        let arith_gates: Vec<_> = circuit
-            .opcodes
-            .iter()
-            .filter_map(|opcode| {
-                if let Opcode::AssertZero(code) = opcode {
-                    Some(AcirArithGate::<F>::from(code.clone()))
-                } else {
-                    None
-                }
-            })
-            .collect();
+impl<F: PrimeField>
+    From<(
+        &Circuit<GenericFieldElement<F>>,
+        WitnessMap<GenericFieldElement<F>>,
+        PoseidonConfig<F>,
+    )> for AcirCircuit<F>
+{
+    /// Lowers the circuit like the conversion from a circuit and witness map, constraining its
+    /// Poseidon2 permutations with the given parameters rather than Noir's.
+    fn from(
+        circ_val: (
+            &Circuit<GenericFieldElement<F>>,
+            WitnessMap<GenericFieldElement<F>>,
+            PoseidonConfig<F>,
+        ),
+    ) -> AcirCircuit<F> {
+        let (circuit, witness_map, config) = circ_val;
+        AcirCircuit::from((circuit, witness_map)).with_poseidon_config(config)
+    }
+}
+
+impl<F: PrimeField> AcirCircuit<F> {
+    /// Lowers the circuit like the conversion from a circuit and witness map, then removes
+    /// the linear gates which the other linear gates imply, see
+    /// [`AcirCircuit::eliminate_linear_dependencies`].
+    pub fn from_optimized(
+        circuit: &Circuit<GenericFieldElement<F>>,
+        witness_map: WitnessMap<GenericFieldElement<F>>,
+    ) -> AcirCircuit<F> {
+        AcirCircuit::from((circuit, witness_map)).eliminate_linear_dependencies()
+    }
 
-        let num_variables: usize = circuit.num_vars().try_into().unwrap();
+    /// Lowers a circuit from its ACIR bytecode, as serialized by `Program::serialize_program`,
+    /// without going through a program artifact.
+    ///
+    /// The program must consist of the single circuit, see [`AcirCircuit::from_program`] for
+    /// programs with several functions. Without a `witness_map`, every witness is zero.
+    pub fn from_acir_bytes(
+        bytes: &[u8],
+        witness_map: Option<WitnessMap<GenericFieldElement<F>>>,
+    ) -> Result<AcirCircuit<F>, FilesystemError> {
+        let program = Program::<GenericFieldElement<F>>::deserialize_program(bytes)
+            .map_err(|err| FilesystemError::ProgramSerializationError(err.to_string()))?;
+        let [circuit] = &program.functions[..] else {
+            return Err(FilesystemError::ProgramSerializationError(format!(
+                "expected a single circuit, found {} functions",
+                program.functions.len()
+            )));
+        };
+        let witness_map = witness_map.unwrap_or_else(WitnessMap::new);
+        Ok(AcirCircuit::from((circuit, witness_map)))
+    }
 
-        let values: BTreeMap<Witness, _> = (0..num_variables)
-            .map(|witness_index| {
-                // Get the value if it exists. If i does not, then we fill it with the zero value
-                let witness = Witness(witness_index as u32);
+    /// Lowers `circuit` in the same way as the `From` conversion, but passes every
+    /// witness value through `transform` as it is inserted into `values`.
+    ///
+    /// This is an extension point for field-level preprocessing of the assignment
+    /// (e.g. domain separation); the `From` conversion uses the identity.
+    ///
+    /// Only the public inputs and the witnesses some constraint refers to are given a value and
+    /// allocated by `generate_constraints`, so unused witnesses cost no variables.
+    pub fn from_with_transform(
+        circ_val: (
+            &Circuit<GenericFieldElement<F>>,
+            WitnessMap<GenericFieldElement<F>>,
+        ),
+        transform: impl Fn(Witness, F) -> F,
+    ) -> AcirCircuit<F> {
+        let (circuit, witness_map) = circ_val;
+
+        let mut acir = lower_without_values(circuit);
+        acir.values = referenced_witnesses(&acir)
+            .into_iter()
+            .map(|witness| {
+                // Get the value if it exists. If it does not, then we fill it with the zero value
                 let value = witness_map
                     .get(&witness)
                     .map_or(F::zero(), |field| field.into_repr());
-
-                (witness, value)
+                (witness, transform(witness, value))
             })
             .collect();
+        acir
+    }
+}
 
-        AcirCircuit {
-            gates: arith_gates,
-            values,
-            // num_variables,
-            public_inputs,
+impl<F: PrimeField> AcirCircuit<F> {
+    /// Replaces the value of every witness of the circuit with its value in `witness_map`, or
+    /// zero if it has none.
+    ///
+    /// This attaches a new assignment to a circuit lowered once and reused across proofs.
+    pub fn with_witness_map(mut self, witness_map: WitnessMap<GenericFieldElement<F>>) -> Self {
+        for (witness, value) in self.values.iter_mut() {
+            *value = witness_map
+                .get(witness)
+                .map_or(F::zero(), |field| field.into_repr());
         }
+        self
+    }
+
+    /// Lowers `circuit`, pulling witness values lazily from `provider` instead of a
+    /// materialized `WitnessMap`.
+    ///
+    /// Only the witnesses referenced by a gate or declared as public inputs are queried, once
+    /// each, rather than every witness in `0..num_vars`. Witnesses the provider has no value
+    /// for are set to zero.
+    pub fn from_provider(
+        circuit: &Circuit<GenericFieldElement<F>>,
+        provider: impl Fn(Witness) -> Option<F>,
+    ) -> AcirCircuit<F> {
+        let mut acir = lower_without_values(circuit);
+        acir.values = referenced_witnesses(&acir)
+            .into_iter()
+            .map(|witness| (witness, provider(witness).unwrap_or_else(F::zero)))
+            .collect();
+        acir
+    }
+
+    /// Lowers `circuit` like the `From` conversion, then checks that every witness the ABI
+    /// declares as an integer holds a value within its type's range.
+    ///
+    /// Witnesses are checked in the order the gates evaluate them, so the error points at the
+    /// first gate producing an out-of-range value. Integer witnesses which no gate refers to are
+    /// checked last.
+    pub fn from_checked(
+        circ_val: (
+            &Circuit<GenericFieldElement<F>>,
+            WitnessMap<GenericFieldElement<F>>,
+        ),
+        abi: &Abi,
+    ) -> Result<AcirCircuit<F>, IntegerOverflow> {
+        let return_witnesses: Vec<_> = circ_val.0.return_values.0.iter().copied().collect();
+        let integer_witnesses = integer_witnesses(abi, &return_witnesses);
+        // Witnesses no constraint refers to have no value in the lowered circuit
+        let witness_map = circ_val.1.clone();
+        let circuit = AcirCircuit::from(circ_val);
+
+        let gate_witnesses =
+            circuit.gates.iter().enumerate().flat_map(|(index, gate)| {
+                gate.witnesses().map(move |witness| (Some(index), witness))
+            });
+        let unused_witnesses = integer_witnesses.keys().map(|witness| (None, *witness));
+
+        for (gate_index, witness) in gate_witnesses.chain(unused_witnesses) {
+            let Some(typ) = integer_witnesses.get(&witness) else {
+                continue;
+            };
+            let value = witness_map
+                .get(&witness)
+                .map_or(F::zero(), |field| field.into_repr());
+            if value.into_bigint().num_bits() > typ.width {
+                return Err(IntegerOverflow {
+                    witness,
+                    gate_index,
+                    typ: *typ,
+                    value: value.to_string(),
+                });
+            }
+        }
+
+        Ok(circuit)
     }
 }
 
+// Lowers every opcode of `circuit`, leaving the values to be filled in
+fn lower_without_values<F: PrimeField>(
+    circuit: &Circuit<GenericFieldElement<F>>,
+) -> AcirCircuit<F> {
+    let poseidon2_gates = lower_poseidon2_gates(circuit);
+    AcirCircuit {
+        gates: lower_arith_gates(circuit),
+        values: BTreeMap::new(),
+        public_inputs: circuit.public_inputs(),
+        layout: CommitmentLayout::default(),
+        domain: Vec::new(),
+        memory_blocks: lower_memory_blocks(circuit),
+        private_parameters: circuit.private_parameters.clone(),
+        return_values: circuit.return_values.0.clone(),
+        memory_ops: lower_memory_ops(circuit),
+        memory_mode: MemoryMode::default(),
+        assert_messages: lower_assert_messages(circuit),
+        range_checks: lower_range_checks(circuit),
+        bitwise_gates: lower_bitwise_gates(circuit),
+        poseidon_config: default_poseidon_config(&poseidon2_gates),
+        poseidon2_gates,
+        keccak_gates: lower_keccak_gates(circuit),
+        embedded_curve_adds: lower_embedded_curve_adds(circuit),
+        multi_scalar_muls: lower_multi_scalar_muls(circuit),
+        sha256_gates: lower_sha256_gates(circuit),
+        ecdsa_secp256k1_gates: lower_ecdsa_secp256k1_gates(circuit),
+        blake2s_gates: lower_blake2s_gates(circuit),
+        pedersen_hash_gates: lower_pedersen_hash_gates(circuit),
+        schnorr_verify_gates: lower_schnorr_verify_gates(circuit),
+        aes128_encrypt_gates: lower_aes128_encrypt_gates(circuit),
+        bigint_gates: lower_bigint_gates(circuit),
+    }
+}
+
+// The witnesses which a lowered constraint refers to or which are public inputs. Any other
+// witness, such as an intermediate value only a Brillig call uses, is left unconstrained by the
+// circuit, so it is never allocated.
+fn referenced_witnesses<F: PrimeField>(circuit: &AcirCircuit<F>) -> BTreeSet<Witness> {
+    let AcirCircuit {
+        gates,
+        public_inputs,
+        memory_blocks,
+        memory_ops,
+        range_checks,
+        bitwise_gates,
+        poseidon2_gates,
+        keccak_gates,
+        embedded_curve_adds,
+        multi_scalar_muls,
+        sha256_gates,
+        ecdsa_secp256k1_gates,
+        blake2s_gates,
+        pedersen_hash_gates,
+        schnorr_verify_gates,
+        aes128_encrypt_gates,
+        bigint_gates,
+        ..
+    } = circuit;
+    gates
+        .iter()
+        .flat_map(|gate| gate.witnesses())
+        .chain(public_inputs.0.iter().copied())
+        .chain(memory_blocks.values().flatten().copied())
+        .chain(memory_ops.iter().flat_map(|access| access.witnesses()))
+        .chain(range_checks.keys().copied())
+        .chain(bitwise_gates.iter().flat_map(|gate| gate.witnesses()))
+        .chain(poseidon2_gates.iter().flat_map(|gate| gate.witnesses()))
+        .chain(keccak_gates.iter().flat_map(|gate| gate.witnesses()))
+        .chain(embedded_curve_adds.iter().flat_map(|gate| gate.witnesses()))
+        .chain(multi_scalar_muls.iter().flat_map(|gate| gate.witnesses()))
+        .chain(sha256_gates.iter().flat_map(|gate| gate.witnesses()))
+        .chain(
+            ecdsa_secp256k1_gates
+                .iter()
+                .flat_map(|gate| gate.witnesses()),
+        )
+        .chain(blake2s_gates.iter().flat_map(|gate| gate.witnesses()))
+        .chain(pedersen_hash_gates.iter().flat_map(|gate| gate.witnesses()))
+        .chain(
+            schnorr_verify_gates
+                .iter()
+                .flat_map(|gate| gate.witnesses()),
+        )
+        .chain(
+            aes128_encrypt_gates
+                .iter()
+                .flat_map(|gate| gate.witnesses()),
+        )
+        .chain(bigint_gates.iter().flat_map(|gate| gate.witnesses()))
+        .collect()
+}
+
+// Extracts the `AssertZero` opcodes; the other opcodes are lowered by their own passes
+fn lower_arith_gates<F: PrimeField>(
+    circuit: &Circuit<GenericFieldElement<F>>,
+) -> Vec<AcirArithGate<F>> {
+    circuit
+        .opcodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, opcode)| {
+            if let Opcode::AssertZero(code) = opcode {
+                Some(AcirArithGate {
+                    source_opcode: index,
+                    ..AcirArithGate::<F>::from(code.clone())
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn lower_memory_blocks<F: PrimeField>(
+    circuit: &Circuit<GenericFieldElement<F>>,
+) -> BTreeMap<BlockId, Vec<Witness>> {
+    circuit
+        .opcodes
+        .iter()
+        .filter_map(|opcode| match opcode {
+            Opcode::MemoryInit { block_id, init, .. } => Some((*block_id, init.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+// Only static messages are kept: dynamic payloads are formatted from witness values by nargo.
+fn lower_assert_messages<F: PrimeField>(
+    circuit: &Circuit<GenericFieldElement<F>>,
+) -> BTreeMap<usize, String> {
+    circuit
+        .assert_messages
+        .iter()
+        .filter_map(|(location, payload)| match (location, payload) {
+            (OpcodeLocation::Acir(index), AssertionPayload::StaticString(message)) => {
+                Some((*index, message.clone()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn lower_range_checks<F: PrimeField>(
+    circuit: &Circuit<GenericFieldElement<F>>,
+) -> BTreeMap<Witness, u32> {
+    let mut range_checks = BTreeMap::new();
+    for opcode in &circuit.opcodes {
+        if let Opcode::BlackBoxFuncCall(BlackBoxFuncCall::RANGE { input }) = opcode {
+            let width = range_checks.entry(input.witness).or_insert(input.num_bits);
+            *width = (*width).min(input.num_bits);
+        }
+    }
+    range_checks
+}
+
+fn lower_bitwise_gates<F: PrimeField>(
+    circuit: &Circuit<GenericFieldElement<F>>,
+) -> Vec<BitwiseGate> {
+    circuit
+        .opcodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, opcode)| match opcode {
+            Opcode::BlackBoxFuncCall(call) => {
+                let (operation, lhs, rhs, output) = match call {
+                    BlackBoxFuncCall::AND { lhs, rhs, output } => {
+                        (BitwiseOperation::And, lhs, rhs, output)
+                    }
+                    BlackBoxFuncCall::XOR { lhs, rhs, output } => {
+                        (BitwiseOperation::Xor, lhs, rhs, output)
+                    }
+                    _ => return None,
+                };
+                Some(BitwiseGate {
+                    operation,
+                    lhs: lhs.witness,
+                    lhs_bits: lhs.num_bits,
+                    rhs: rhs.witness,
+                    rhs_bits: rhs.num_bits,
+                    output: *output,
+                    source_opcode: index,
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn lower_poseidon2_gates<F: PrimeField>(
+    circuit: &Circuit<GenericFieldElement<F>>,
+) -> Vec<Poseidon2Gate> {
+    circuit
+        .opcodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, opcode)| match opcode {
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Poseidon2Permutation {
+                inputs,
+                outputs,
+                ..
+            }) => Some(Poseidon2Gate {
+                inputs: inputs.iter().map(|input| input.witness).collect(),
+                outputs: outputs.clone(),
+                source_opcode: index,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+fn lower_keccak_gates<F: PrimeField>(circuit: &Circuit<GenericFieldElement<F>>) -> Vec<KeccakGate> {
+    circuit
+        .opcodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, opcode)| match opcode {
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Keccakf1600 { inputs, outputs }) => {
+                Some(KeccakGate {
+                    inputs: inputs.iter().map(|input| input.witness).collect(),
+                    outputs: outputs.to_vec(),
+                    source_opcode: index,
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn lower_sha256_gates<F: PrimeField>(circuit: &Circuit<GenericFieldElement<F>>) -> Vec<Sha256Gate> {
+    circuit
+        .opcodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, opcode)| match opcode {
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Sha256Compression {
+                inputs,
+                hash_values,
+                outputs,
+            }) => Some(Sha256Gate {
+                inputs: inputs.iter().map(|input| input.witness).collect(),
+                hash_values: hash_values.iter().map(|input| input.witness).collect(),
+                outputs: outputs.to_vec(),
+                source_opcode: index,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+fn lower_blake2s_gates<F: PrimeField>(
+    circuit: &Circuit<GenericFieldElement<F>>,
+) -> Vec<Blake2sGate> {
+    circuit
+        .opcodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, opcode)| match opcode {
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Blake2s { inputs, outputs }) => {
+                Some(Blake2sGate {
+                    inputs: inputs.iter().map(|input| input.witness).collect(),
+                    outputs: outputs.to_vec(),
+                    source_opcode: index,
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn lower_pedersen_hash_gates<F: PrimeField>(
+    circuit: &Circuit<GenericFieldElement<F>>,
+) -> Vec<PedersenHashGate> {
+    circuit
+        .opcodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, opcode)| match opcode {
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::PedersenHash {
+                inputs,
+                domain_separator,
+                output,
+            }) => Some(PedersenHashGate {
+                inputs: inputs.iter().map(|input| input.witness).collect(),
+                domain_separator: *domain_separator,
+                output: *output,
+                source_opcode: index,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+fn lower_schnorr_verify_gates<F: PrimeField>(
+    circuit: &Circuit<GenericFieldElement<F>>,
+) -> Vec<SchnorrVerifyGate> {
+    circuit
+        .opcodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, opcode)| match opcode {
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::SchnorrVerify {
+                public_key_x,
+                public_key_y,
+                signature,
+                message,
+                output,
+            }) => Some(SchnorrVerifyGate {
+                public_key_x: public_key_x.witness,
+                public_key_y: public_key_y.witness,
+                signature: signature.iter().map(|input| input.witness).collect(),
+                message: message.iter().map(|input| input.witness).collect(),
+                output: *output,
+                source_opcode: index,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+fn lower_aes128_encrypt_gates<F: PrimeField>(
+    circuit: &Circuit<GenericFieldElement<F>>,
+) -> Vec<Aes128EncryptGate> {
+    circuit
+        .opcodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, opcode)| match opcode {
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::AES128Encrypt {
+                inputs,
+                iv,
+                key,
+                outputs,
+            }) => Some(Aes128EncryptGate {
+                inputs: inputs.iter().map(|input| input.witness).collect(),
+                iv: iv.iter().map(|input| input.witness).collect(),
+                key: key.iter().map(|input| input.witness).collect(),
+                outputs: outputs.clone(),
+                source_opcode: index,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+fn lower_bigint_gates<F: PrimeField>(circuit: &Circuit<GenericFieldElement<F>>) -> Vec<BigIntGate> {
+    let operation = |operation, lhs: &u32, rhs: &u32, output: &u32| BigIntCall::Operation {
+        operation,
+        lhs: *lhs,
+        rhs: *rhs,
+        output: *output,
+    };
+    circuit
+        .opcodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, opcode)| {
+            let Opcode::BlackBoxFuncCall(call) = opcode else {
+                return None;
+            };
+            let call = match call {
+                BlackBoxFuncCall::BigIntFromLeBytes {
+                    inputs,
+                    modulus,
+                    output,
+                } => BigIntCall::FromLeBytes {
+                    inputs: inputs.iter().map(|input| input.witness).collect(),
+                    modulus: modulus.clone(),
+                    output: *output,
+                },
+                BlackBoxFuncCall::BigIntAdd { lhs, rhs, output } => {
+                    operation(BigIntOperation::Add, lhs, rhs, output)
+                }
+                BlackBoxFuncCall::BigIntSub { lhs, rhs, output } => {
+                    operation(BigIntOperation::Sub, lhs, rhs, output)
+                }
+                BlackBoxFuncCall::BigIntMul { lhs, rhs, output } => {
+                    operation(BigIntOperation::Mul, lhs, rhs, output)
+                }
+                BlackBoxFuncCall::BigIntDiv { lhs, rhs, output } => {
+                    operation(BigIntOperation::Div, lhs, rhs, output)
+                }
+                BlackBoxFuncCall::BigIntToLeBytes { input, outputs } => BigIntCall::ToLeBytes {
+                    input: *input,
+                    outputs: outputs.clone(),
+                },
+                _ => return None,
+            };
+            Some(BigIntGate {
+                call,
+                source_opcode: index,
+            })
+        })
+        .collect()
+}
+
+fn lower_ecdsa_secp256k1_gates<F: PrimeField>(
+    circuit: &Circuit<GenericFieldElement<F>>,
+) -> Vec<EcdsaSecp256k1Gate> {
+    circuit
+        .opcodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, opcode)| match opcode {
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::EcdsaSecp256k1 {
+                public_key_x,
+                public_key_y,
+                signature,
+                hashed_message,
+                output,
+            }) => Some(EcdsaSecp256k1Gate {
+                public_key_x: public_key_x.iter().map(|input| input.witness).collect(),
+                public_key_y: public_key_y.iter().map(|input| input.witness).collect(),
+                signature: signature.iter().map(|input| input.witness).collect(),
+                hashed_message: hashed_message.iter().map(|input| input.witness).collect(),
+                output: *output,
+                source_opcode: index,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+fn lower_embedded_curve_adds<F: PrimeField>(
+    circuit: &Circuit<GenericFieldElement<F>>,
+) -> Vec<EmbeddedCurveAddGate> {
+    circuit
+        .opcodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, opcode)| match opcode {
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::EmbeddedCurveAdd {
+                input1_x,
+                input1_y,
+                input1_infinite,
+                input2_x,
+                input2_y,
+                input2_infinite,
+                outputs,
+            }) => Some(EmbeddedCurveAddGate {
+                lhs: PointWitnesses {
+                    x: input1_x.witness,
+                    y: input1_y.witness,
+                    is_infinite: input1_infinite.witness,
+                },
+                rhs: PointWitnesses {
+                    x: input2_x.witness,
+                    y: input2_y.witness,
+                    is_infinite: input2_infinite.witness,
+                },
+                output: PointWitnesses {
+                    x: outputs.0,
+                    y: outputs.1,
+                    is_infinite: outputs.2,
+                },
+                source_opcode: index,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+fn lower_multi_scalar_muls<F: PrimeField>(
+    circuit: &Circuit<GenericFieldElement<F>>,
+) -> Vec<MultiScalarMulGate> {
+    circuit
+        .opcodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, opcode)| match opcode {
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::MultiScalarMul {
+                points,
+                scalars,
+                outputs,
+            }) => Some(MultiScalarMulGate {
+                // Each point is its x and y coordinates and infinity flag
+                points: points
+                    .chunks_exact(3)
+                    .map(|point| PointWitnesses {
+                        x: point[0].witness,
+                        y: point[1].witness,
+                        is_infinite: point[2].witness,
+                    })
+                    .collect(),
+                // Each scalar is its low and high limbs
+                scalars: scalars
+                    .chunks_exact(2)
+                    .map(|scalar| (scalar[0].witness, scalar[1].witness))
+                    .collect(),
+                output: PointWitnesses {
+                    x: outputs.0,
+                    y: outputs.1,
+                    is_infinite: outputs.2,
+                },
+                source_opcode: index,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+// Noir's parameters, which are only generated for circuits with permutations
+fn default_poseidon_config<F: PrimeField>(gates: &[Poseidon2Gate]) -> Option<PoseidonConfig<F>> {
+    if gates.is_empty() {
+        None
+    } else {
+        PoseidonConfig::noir()
+    }
+}
+
+// Memory operations with a constant zero predicate are never executed and are dropped, and
+// those with a constant non-zero predicate are always executed. Any other predicate is kept
+// and decides whether the access takes effect.
+fn lower_memory_ops<F: PrimeField>(
+    circuit: &Circuit<GenericFieldElement<F>>,
+) -> Vec<MemoryAccess<F>> {
+    let is_zero = |expr: &Expression<GenericFieldElement<F>>| {
+        expr.to_const().map_or(false, |value| value.is_zero())
+    };
+    circuit
+        .opcodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, opcode)| match opcode {
+            Opcode::MemoryOp {
+                block_id,
+                op,
+                predicate,
+            } if !predicate.as_ref().map_or(false, is_zero) => Some(MemoryAccess {
+                block_id: *block_id,
+                operation: if is_zero(&op.operation) {
+                    MemoryOperation::Read
+                } else {
+                    MemoryOperation::Write
+                },
+                index: match op.index.to_const() {
+                    Some(value) => value
+                        .try_to_u64()
+                        .and_then(|value| usize::try_from(value).ok())
+                        .map_or(MemoryIndex::OutOfRange, MemoryIndex::Constant),
+                    None => MemoryIndex::Dynamic(AcirArithGate::from(op.index.clone())),
+                },
+                value: AcirArithGate::from(op.value.clone()),
+                predicate: predicate
+                    .as_ref()
+                    .filter(|predicate| predicate.to_const().is_none())
+                    .map(|predicate| AcirArithGate::from(predicate.clone())),
+                source_opcode: index,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+// Without the surrounding circuit there is no opcode index to record, so the gate
+// is attributed to opcode 0 until the caller sets `source_opcode`.
 impl<F: PrimeField> From<Expression<GenericFieldElement<F>>> for AcirArithGate<F> {
     fn from(arith_gate: Expression<GenericFieldElement<F>>) -> AcirArithGate<F> {
         let converted_mul_terms: Vec<_> = arith_gate
@@ -87,6 +787,7 @@ impl<F: PrimeField> From<Expression<GenericFieldElement<F>>> for AcirArithGate<F
             mul_terms: converted_mul_terms,
             add_terms: converted_linear_combinations,
             constant_term: arith_gate.q_c.into_repr(),
+            source_opcode: 0,
         }
     }
 }
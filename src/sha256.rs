@@ -0,0 +1,452 @@
+use std::collections::BTreeMap;
+
+use acvm::acir::native_types::Witness;
+use ark_ff::PrimeField;
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError, Variable};
+
+use crate::R1csSize;
+
+// Lowering of `BlackBoxFuncCall::Sha256Compression` to R1CS.
+//
+// The 16 message words and the 8 words of the input state are decomposed into 32
+// little-endian bits, which also range-constrains them, and the message schedule and the 64
+// rounds are computed on the bits. Rotations and shifts only rewire bits, every XOR of two bits
+// costs one constraint, `Ch` one per bit and `Maj` two. Additions modulo 2^32 are taken on the
+// recomposed words: the sum is decomposed into bits, including its carries, and only the low 32
+// bits are kept. The output words must be the low 32 bits of the sums of the final and input
+// states; a compression lowers to 27216 constraints.
+//
+// The constraints are only generated with the `sha256` feature, as they dwarf most circuits.
+
+/// A single `BlackBoxFuncCall::Sha256Compression`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Sha256Gate {
+    pub(crate) inputs: Vec<Witness>,
+    pub(crate) hash_values: Vec<Witness>,
+    pub(crate) outputs: Vec<Witness>,
+    pub(crate) source_opcode: usize,
+}
+
+impl Sha256Gate {
+    pub(crate) fn witnesses(&self) -> impl Iterator<Item = Witness> + '_ {
+        self.inputs
+            .iter()
+            .chain(&self.hash_values)
+            .chain(&self.outputs)
+            .copied()
+    }
+
+    pub(crate) fn witnesses_mut(&mut self) -> impl Iterator<Item = &mut Witness> {
+        self.inputs
+            .iter_mut()
+            .chain(self.hash_values.iter_mut())
+            .chain(self.outputs.iter_mut())
+    }
+}
+
+/// Counts the constraints and witnesses [`enforce_sha256_gates`] allocates, none without the
+/// `sha256` feature.
+#[cfg_attr(not(feature = "sha256"), allow(unused_variables))]
+pub(crate) fn r1cs_size(gates: &[Sha256Gate]) -> R1csSize {
+    #[cfg(feature = "sha256")]
+    return compression::r1cs_size(gates);
+
+    #[cfg(not(feature = "sha256"))]
+    R1csSize::default()
+}
+
+/// Enforces every compression. Without the `sha256` feature this fails with
+/// `SynthesisError::Unsatisfiable` if there are any.
+#[cfg_attr(not(feature = "sha256"), allow(unused_variables))]
+pub(crate) fn enforce_sha256_gates<F: PrimeField>(
+    cs: &ConstraintSystemRef<F>,
+    gates: &[Sha256Gate],
+    variables: &BTreeMap<Witness, Variable>,
+) -> Result<(), SynthesisError> {
+    #[cfg(feature = "sha256")]
+    return compression::enforce_sha256_gates(cs, gates, variables);
+
+    #[cfg(not(feature = "sha256"))]
+    match gates.is_empty() {
+        true => Ok(()),
+        false => Err(SynthesisError::Unsatisfiable),
+    }
+}
+
+#[cfg(feature = "sha256")]
+mod compression {
+    use std::collections::BTreeMap;
+
+    use acvm::acir::native_types::Witness;
+    use ark_ff::PrimeField;
+    use ark_relations::{
+        lc,
+        r1cs::{ConstraintSystemRef, LinearCombination, SynthesisError, Variable},
+    };
+
+    use super::Sha256Gate;
+    use crate::gadgets::{alloc_bits, enforce_bit_decomposition, pack_bits};
+    use crate::R1csSize;
+
+    pub(super) const WORD_BITS: u32 = 32;
+
+    /// The constants added in each round.
+    pub(super) const ROUND_CONSTANTS: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+        0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+        0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+        0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+        0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+        0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+        0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+        0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+        0xc67178f2,
+    ];
+
+    /// A bit of a word, with its value unless in setup mode. A bit shifted in is a constant
+    /// zero, with an empty linear combination.
+    #[derive(Clone)]
+    struct Bit<F: PrimeField> {
+        lc: LinearCombination<F>,
+        value: Option<bool>,
+    }
+
+    impl<F: PrimeField> Bit<F> {
+        fn zero() -> Self {
+            Bit {
+                lc: lc!(),
+                value: Some(false),
+            }
+        }
+
+        fn is_zero(&self) -> bool {
+            self.lc.0.is_empty()
+        }
+    }
+
+    /// The little-endian bits of a 32-bit word.
+    type Word<F> = Vec<Bit<F>>;
+
+    pub(super) fn r1cs_size(gates: &[Sha256Gate]) -> R1csSize {
+        let word_bits = WORD_BITS as usize;
+        // The bits of a sum of words with `carry_bits` carries, and their recomposition
+        let sum = |carry_bits: usize| (word_bits + carry_bits + 1, word_bits + carry_bits);
+        // sigma0 and sigma1 each XOR three words, less the bits shifted in by 3 and 10
+        let sigmas = (2 * word_bits - 3) + (2 * word_bits - 10);
+        // Sigma0 and Sigma1 XOR three words, Ch costs one constraint per bit and Maj two
+        let functions = 2 * (2 * word_bits) + word_bits + 2 * word_bits;
+        // A schedule word sums four words, the new `e` and `a` up to seven and an output two
+        let (schedule_sum, state_sum, output_sum) = (sum(2), sum(3), sum(1));
+
+        let constraints = 24 * (word_bits + 1)
+            + 48 * (sigmas + schedule_sum.0)
+            + 64 * (functions + 2 * state_sum.0)
+            + 8 * (output_sum.0 + 1);
+        let witnesses = 24 * word_bits
+            + 48 * (sigmas + schedule_sum.1)
+            + 64 * (functions + 2 * state_sum.1)
+            + 8 * output_sum.1;
+        R1csSize {
+            constraints: gates.len() * constraints,
+            witnesses: gates.len() * witnesses,
+            public_inputs: 0,
+        }
+    }
+
+    pub(super) fn enforce_sha256_gates<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        gates: &[Sha256Gate],
+        variables: &BTreeMap<Witness, Variable>,
+    ) -> Result<(), SynthesisError> {
+        let decompose = |witness: &Witness| {
+            let bits = enforce_bit_decomposition(cs, variables[witness], WORD_BITS)?;
+            Ok::<_, SynthesisError>(
+                bits.into_iter()
+                    .map(|bit| Bit {
+                        lc: lc!() + bit,
+                        value: cs.assigned_value(bit).map(|value| value.is_one()),
+                    })
+                    .collect::<Word<F>>(),
+            )
+        };
+
+        for gate in gates {
+            let mut schedule = gate
+                .inputs
+                .iter()
+                .map(decompose)
+                .collect::<Result<Vec<_>, _>>()?;
+            let hash_values = gate
+                .hash_values
+                .iter()
+                .map(decompose)
+                .collect::<Result<Vec<_>, _>>()?;
+
+            for t in 16..64 {
+                let sigma0 = xor3(
+                    cs,
+                    &rotate(&schedule[t - 15], 7),
+                    &rotate(&schedule[t - 15], 18),
+                    &shift(&schedule[t - 15], 3),
+                )?;
+                let sigma1 = xor3(
+                    cs,
+                    &rotate(&schedule[t - 2], 17),
+                    &rotate(&schedule[t - 2], 19),
+                    &shift(&schedule[t - 2], 10),
+                )?;
+                let word = add(
+                    cs,
+                    &[&sigma1, &schedule[t - 7], &sigma0, &schedule[t - 16]],
+                    0,
+                )?;
+                schedule.push(word);
+            }
+
+            let mut state = hash_values.clone();
+            for (round_constant, word) in ROUND_CONSTANTS.iter().zip(&schedule) {
+                let [a, b, c, d, e, f, g, h] =
+                    <[Word<F>; 8]>::try_from(state).map_err(|_| SynthesisError::Unsatisfiable)?;
+                let big_sigma1 = xor3(cs, &rotate(&e, 6), &rotate(&e, 11), &rotate(&e, 25))?;
+                let choice = choose(cs, &e, &f, &g)?;
+                let big_sigma0 = xor3(cs, &rotate(&a, 2), &rotate(&a, 13), &rotate(&a, 22))?;
+                let majority = majority(cs, &a, &b, &c)?;
+
+                // T1 = h + Sigma1(e) + Ch(e, f, g) + K + W, T2 = Sigma0(a) + Maj(a, b, c)
+                let t1 = [&h, &big_sigma1, &choice, word];
+                let new_e = add(cs, &[&t1[..], &[&d]].concat(), *round_constant)?;
+                let new_a = add(
+                    cs,
+                    &[&t1[..], &[&big_sigma0, &majority]].concat(),
+                    *round_constant,
+                )?;
+                state = vec![new_a, a, b, c, new_e, e, f, g];
+            }
+
+            for ((word, input), output) in state.iter().zip(&hash_values).zip(&gate.outputs) {
+                let sum = add(cs, &[word, input], 0)?;
+                let packed = sum.iter().enumerate().fold(lc!(), |packed, (i, bit)| {
+                    packed + (F::from(1u64 << i), &bit.lc)
+                });
+                cs.enforce_constraint(lc!() + Variable::One, packed, lc!() + variables[output])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Rotates a word towards its least significant bit.
+    fn rotate<F: PrimeField>(word: &[Bit<F>], amount: usize) -> Word<F> {
+        let mut rotated = word.to_vec();
+        rotated.rotate_left(amount);
+        rotated
+    }
+
+    /// Shifts a word towards its least significant bit.
+    fn shift<F: PrimeField>(word: &[Bit<F>], amount: usize) -> Word<F> {
+        let mut shifted = word[amount..].to_vec();
+        shifted.resize(word.len(), Bit::zero());
+        shifted
+    }
+
+    fn xor3<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        x: &[Bit<F>],
+        y: &[Bit<F>],
+        z: &[Bit<F>],
+    ) -> Result<Word<F>, SynthesisError> {
+        x.iter()
+            .zip(y)
+            .zip(z)
+            .map(|((x, y), z)| xor(cs, &xor(cs, x, y)?, z))
+            .collect()
+    }
+
+    fn xor<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        lhs: &Bit<F>,
+        rhs: &Bit<F>,
+    ) -> Result<Bit<F>, SynthesisError> {
+        if lhs.is_zero() {
+            return Ok(rhs.clone());
+        }
+        if rhs.is_zero() {
+            return Ok(lhs.clone());
+        }
+        let value = lhs.value.zip(rhs.value).map(|(lhs, rhs)| lhs ^ rhs);
+        let output = cs
+            .new_witness_variable(|| value.map(F::from).ok_or(SynthesisError::AssignmentMissing))?;
+        // 2 * lhs * rhs == lhs + rhs - output
+        cs.enforce_constraint(
+            lhs.lc.clone() * F::from(2u64),
+            rhs.lc.clone(),
+            lhs.lc.clone() + &rhs.lc - output,
+        )?;
+        Ok(Bit {
+            lc: lc!() + output,
+            value,
+        })
+    }
+
+    /// Returns `Ch(e, f, g) = (e & f) ^ (!e & g)`, bit by bit.
+    fn choose<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        e: &[Bit<F>],
+        f: &[Bit<F>],
+        g: &[Bit<F>],
+    ) -> Result<Word<F>, SynthesisError> {
+        e.iter()
+            .zip(f)
+            .zip(g)
+            .map(|((e, f), g)| {
+                let value = e
+                    .value
+                    .zip(f.value.zip(g.value))
+                    .map(|(e, (f, g))| if e { f } else { g });
+                let output = cs.new_witness_variable(|| {
+                    value.map(F::from).ok_or(SynthesisError::AssignmentMissing)
+                })?;
+                // e * (f - g) == output - g
+                cs.enforce_constraint(e.lc.clone(), f.lc.clone() - &g.lc, lc!() + output - &g.lc)?;
+                Ok(Bit {
+                    lc: lc!() + output,
+                    value,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns `Maj(a, b, c)`, the majority of each three bits.
+    fn majority<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        a: &[Bit<F>],
+        b: &[Bit<F>],
+        c: &[Bit<F>],
+    ) -> Result<Word<F>, SynthesisError> {
+        a.iter()
+            .zip(b)
+            .zip(c)
+            .map(|((a, b), c)| {
+                let both = b.value.zip(c.value).map(|(b, c)| b & c);
+                let both_var = cs.new_witness_variable(|| {
+                    both.map(F::from).ok_or(SynthesisError::AssignmentMissing)
+                })?;
+                // b * c == both
+                cs.enforce_constraint(b.lc.clone(), c.lc.clone(), lc!() + both_var)?;
+
+                let value = a
+                    .value
+                    .zip(b.value.zip(c.value))
+                    .map(|(a, (b, c))| (a & b) | (a & c) | (b & c));
+                let output = cs.new_witness_variable(|| {
+                    value.map(F::from).ok_or(SynthesisError::AssignmentMissing)
+                })?;
+                // a * (b + c - 2 * both) == output - both
+                cs.enforce_constraint(
+                    a.lc.clone(),
+                    b.lc.clone() + &c.lc - (F::from(2u64), both_var),
+                    lc!() + output - both_var,
+                )?;
+                Ok(Bit {
+                    lc: lc!() + output,
+                    value,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the sum of `words` and `constant` modulo 2^32. The sum is decomposed into bits,
+    /// with enough carry bits for it not to overflow, and the carries are dropped.
+    fn add<F: PrimeField>(
+        cs: &ConstraintSystemRef<F>,
+        words: &[&Word<F>],
+        constant: u32,
+    ) -> Result<Word<F>, SynthesisError> {
+        let num_terms = words.len() + usize::from(constant != 0);
+        let carry_bits = usize::BITS - (num_terms - 1).leading_zeros();
+
+        let mut sum = lc!() + (F::from(constant), Variable::One);
+        let mut value = Some(u64::from(constant));
+        for word in words {
+            for (i, bit) in word.iter().enumerate() {
+                sum = sum + (F::from(1u64 << i), &bit.lc);
+            }
+            let word_value = word.iter().enumerate().try_fold(0u64, |acc, (i, bit)| {
+                bit.value.map(|bit| acc | u64::from(bit) << i)
+            });
+            value = value.zip(word_value).map(|(sum, word)| sum + word);
+        }
+
+        let bits = alloc_bits(cs, value.map(F::from), WORD_BITS + carry_bits)?;
+        cs.enforce_constraint(lc!() + Variable::One, pack_bits(&bits), sum)?;
+        Ok(bits
+            .into_iter()
+            .take(WORD_BITS as usize)
+            .enumerate()
+            .map(|(i, bit)| Bit {
+                lc: lc!() + bit,
+                value: value.map(|value| value >> i & 1 == 1),
+            })
+            .collect())
+    }
+}
+
+#[cfg(all(test, feature = "sha256"))]
+mod test {
+    use acvm::acir::circuit::opcodes::{BlackBoxFuncCall, FunctionInput};
+    use acvm::acir::circuit::Opcode;
+    use acvm::acir::native_types::WitnessMap;
+    use acvm::FieldElement;
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+
+    use super::*;
+    use crate::test::circuit_with_opcodes;
+    use crate::CurveAcir;
+
+    #[test]
+    fn compresses_the_abc_block() {
+        // The padded message "abc" and the initial hash values
+        let mut block = [0u32; 16];
+        block[0] = 0x61626380;
+        block[15] = 0x18;
+        let initial_state = [
+            0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+            0x5be0cd19,
+        ];
+        // sha256("abc")
+        let digest = [
+            0xba7816bf, 0x8f01cfea, 0x414140de, 0x5dae2223, 0xb00361a3, 0x96177a9c, 0xb410ff61,
+            0xf20015ad,
+        ];
+
+        let word = |index: usize| FunctionInput {
+            witness: Witness(index as u32),
+            num_bits: 32,
+        };
+        let compression = Opcode::BlackBoxFuncCall(BlackBoxFuncCall::Sha256Compression {
+            inputs: Box::new(std::array::from_fn(word)),
+            hash_values: Box::new(std::array::from_fn(|index| word(16 + index))),
+            outputs: Box::new(std::array::from_fn(|index| Witness(24 + index as u32))),
+        });
+        let circuit = circuit_with_opcodes(31, vec![compression], &[]);
+        let lower = |outputs: &[u32; 8]| {
+            let mut witness_map = WitnessMap::new();
+            let words = block.iter().chain(&initial_state).chain(outputs);
+            for (index, word) in words.enumerate() {
+                witness_map.insert(Witness(index as u32), FieldElement::from(*word as u128));
+            }
+            CurveAcir::from((&circuit, witness_map))
+        };
+        let is_satisfied = |circuit: CurveAcir| {
+            let cs = ConstraintSystem::new_ref();
+            circuit.generate_constraints(cs.clone()).unwrap();
+            assert_eq!(cs.num_constraints(), 27216);
+            cs.is_satisfied().unwrap()
+        };
+        assert!(is_satisfied(lower(&digest)));
+
+        let mut tampered = digest;
+        tampered[7] ^= 1;
+        assert!(!is_satisfied(lower(&tampered)));
+    }
+}
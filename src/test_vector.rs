@@ -0,0 +1,285 @@
+use std::collections::BTreeMap;
+
+use acvm::acir::acir_field::GenericFieldElement;
+use acvm::acir::circuit::Circuit;
+use acvm::acir::native_types::{Witness, WitnessMap};
+use acvm::blackbox_solver::{BlackBoxFunctionSolver, StubbedBlackBoxSolver};
+use acvm::pwg::{ACVMStatus, ACVM};
+use acvm::AcirField;
+use ark_ff::PrimeField;
+use ark_std::rand::RngCore;
+use ark_std::UniformRand;
+use noirc_abi::Abi;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::abi::{integer_witnesses, param_witnesses};
+use crate::field::field_from_hex;
+
+// Test vectors for differential testing of the bridge against Noir's native proving.
+//
+// Values are stored as the hex strings `nargo` uses for field elements, keyed by witness index,
+// so that a vector can be compared against the witness `nargo execute` produces.
+
+#[derive(Debug, Error)]
+pub enum TestVectorError {
+    #[error("Error: could not solve the circuit for the sampled inputs: {0}")]
+    Unsolvable(String),
+    #[error("Error: witness {0} is not a valid field element")]
+    InvalidValue(u32),
+}
+
+#[derive(Debug, Error)]
+pub enum SampleError {
+    #[error("Error: could not solve the circuit for the sampled inputs: {0}")]
+    Unsolvable(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestVector {
+    /// The values of the public and private parameters the circuit was solved with.
+    pub inputs: BTreeMap<u32, String>,
+    /// The values of the circuit's return witnesses.
+    pub expected_outputs: BTreeMap<u32, String>,
+    /// The full solved witness.
+    pub witness: BTreeMap<u32, String>,
+}
+
+/// Solves `circuit` for uniformly random parameter values and records the resulting witness.
+///
+/// Inputs are sampled as arbitrary field elements, so circuits which constrain their inputs
+/// further (e.g. integer ranges) may fail to solve.
+pub fn generate_test_vector<F: PrimeField, R: RngCore>(
+    circuit: &Circuit<GenericFieldElement<F>>,
+    rng: &mut R,
+) -> Result<TestVector, TestVectorError> {
+    let mut initial_witness = WitnessMap::new();
+    let parameters = circuit
+        .public_parameters
+        .0
+        .iter()
+        .chain(circuit.private_parameters.iter());
+    for witness in parameters {
+        initial_witness.insert(*witness, GenericFieldElement::from_repr(F::rand(rng)));
+    }
+
+    let mut acvm = ACVM::new(
+        &StubbedBlackBoxSolver,
+        &circuit.opcodes,
+        initial_witness.clone(),
+        &[],
+        &[],
+    );
+    match acvm.solve() {
+        ACVMStatus::Solved => {}
+        status => return Err(TestVectorError::Unsolvable(format!("{status:?}"))),
+    }
+    let witness = acvm.finalize();
+
+    let encode = |witness_map: &WitnessMap<GenericFieldElement<F>>, witnesses: &[Witness]| {
+        witnesses
+            .iter()
+            .filter_map(|witness| {
+                let value = witness_map.get(witness)?;
+                Some((witness.0, value.to_hex()))
+            })
+            .collect()
+    };
+    let input_witnesses: Vec<Witness> = initial_witness
+        .clone()
+        .into_iter()
+        .map(|(witness, _)| witness)
+        .collect();
+    let output_witnesses: Vec<Witness> = circuit.return_values.0.iter().copied().collect();
+    let all_witnesses: Vec<Witness> = witness
+        .clone()
+        .into_iter()
+        .map(|(witness, _)| witness)
+        .collect();
+
+    Ok(TestVector {
+        inputs: encode(&initial_witness, &input_witnesses),
+        expected_outputs: encode(&witness, &output_witnesses),
+        witness: encode(&witness, &all_witnesses),
+    })
+}
+
+/// Solves `circuit` for random values of the parameters declared in `abi`, returning the full
+/// witness.
+///
+/// Each parameter is sampled within its ABI type: integers uniformly over their bit width (as
+/// two's complement for signed integers), booleans as `0` or `1`, and field elements over the
+/// whole field. Circuits which constrain their inputs further (e.g. `x != 0`) may fail to solve.
+pub fn sample_valid_witness<F, B, R>(
+    circuit: &Circuit<GenericFieldElement<F>>,
+    abi: &Abi,
+    solver: &B,
+    rng: &mut R,
+) -> Result<WitnessMap<GenericFieldElement<F>>, SampleError>
+where
+    F: PrimeField,
+    B: BlackBoxFunctionSolver<GenericFieldElement<F>>,
+    R: RngCore,
+{
+    let integer_types = integer_witnesses(abi, &[]);
+
+    let mut initial_witness = WitnessMap::new();
+    for witness in param_witnesses(abi).into_values().flatten() {
+        let value = match integer_types.get(&witness) {
+            Some(typ) => {
+                let bits = (u128::from(rng.next_u64()) << 64) | u128::from(rng.next_u64());
+                F::from(bits & (u128::MAX >> (128 - typ.width.min(128))))
+            }
+            None => F::rand(rng),
+        };
+        initial_witness.insert(witness, GenericFieldElement::from_repr(value));
+    }
+
+    let mut acvm = ACVM::new(solver, &circuit.opcodes, initial_witness, &[], &[]);
+    match acvm.solve() {
+        ACVMStatus::Solved => Ok(acvm.finalize()),
+        status => Err(SampleError::Unsolvable(format!("{status:?}"))),
+    }
+}
+
+impl TestVector {
+    /// Decodes the recorded witness, rejecting values which are not canonical field elements.
+    pub fn witness_map<F: PrimeField>(
+        &self,
+    ) -> Result<WitnessMap<GenericFieldElement<F>>, TestVectorError> {
+        let mut witness_map = WitnessMap::new();
+        for (index, value) in &self.witness {
+            let value =
+                field_from_hex::<F>(value).map_err(|_| TestVectorError::InvalidValue(*index))?;
+            witness_map.insert(Witness(*index), GenericFieldElement::from_repr(value));
+        }
+        Ok(witness_map)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+    use std::env;
+
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+
+    use noirc_abi::{AbiParameter, AbiType, AbiVisibility, Sign};
+
+    use super::*;
+    use crate::test::{circuit_with_opcodes, test_circuit};
+    use crate::{read_program_with_abi_from_file, CurveAcir, Fr};
+
+    #[test]
+    fn test_vector_round_trips_and_satisfies() {
+        let circuit = test_circuit();
+        let vector = generate_test_vector(&circuit, &mut StdRng::seed_from_u64(0)).unwrap();
+        assert_eq!(vector.inputs.len(), 4);
+        assert_eq!(vector.expected_outputs.len(), 2);
+
+        let json = serde_json::to_string(&vector).unwrap();
+        let decoded: TestVector = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, vector);
+
+        let cs = ConstraintSystem::new_ref();
+        CurveAcir::from((&circuit, decoded.witness_map::<Fr>().unwrap()))
+            .generate_constraints(cs.clone())
+            .unwrap();
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn sampled_witness_satisfies_test_circuit() {
+        let circuit_path = env::current_dir()
+            .unwrap()
+            .join("src/artifacts/test_circuit");
+        let program = read_program_with_abi_from_file::<Fr, _>(circuit_path).unwrap();
+        let circuit = &program.bytecode.functions[0];
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..4 {
+            let witness =
+                sample_valid_witness(circuit, &program.abi, &StubbedBlackBoxSolver, &mut rng)
+                    .unwrap();
+
+            let cs = ConstraintSystem::new_ref();
+            CurveAcir::from((circuit, witness))
+                .generate_constraints(cs.clone())
+                .unwrap();
+            assert!(cs.is_satisfied().unwrap());
+        }
+    }
+
+    #[test]
+    fn sampled_witness_respects_abi_types() {
+        use acvm::acir::circuit::opcodes::{BlackBoxFuncCall, FunctionInput};
+        use acvm::acir::circuit::Opcode;
+        use acvm::acir::native_types::Expression;
+        use acvm::FieldElement;
+
+        let param = |name: &str, typ, visibility| AbiParameter {
+            name: name.to_owned(),
+            typ,
+            visibility,
+        };
+        // fn main(a: pub Field, b: pub u8, c: i32, d: bool)
+        let abi = Abi {
+            parameters: vec![
+                param("a", AbiType::Field, AbiVisibility::Public),
+                param(
+                    "b",
+                    AbiType::Integer {
+                        sign: Sign::Unsigned,
+                        width: 8,
+                    },
+                    AbiVisibility::Public,
+                ),
+                param(
+                    "c",
+                    AbiType::Integer {
+                        sign: Sign::Signed,
+                        width: 32,
+                    },
+                    AbiVisibility::Private,
+                ),
+                param("d", AbiType::Boolean, AbiVisibility::Private),
+            ],
+            return_type: None,
+            error_types: BTreeMap::new(),
+        };
+        let range = |witness: u32, num_bits: u32| {
+            Opcode::BlackBoxFuncCall(BlackBoxFuncCall::RANGE {
+                input: FunctionInput {
+                    witness: Witness(witness),
+                    num_bits,
+                },
+            })
+        };
+        // d * d - d == 0
+        let boolean = Opcode::AssertZero(Expression {
+            mul_terms: vec![(FieldElement::one(), Witness(3), Witness(3))],
+            linear_combinations: vec![(-FieldElement::one(), Witness(3))],
+            q_c: FieldElement::zero(),
+        });
+        let mut circuit =
+            circuit_with_opcodes(3, vec![range(1, 8), range(2, 32), boolean], &[0, 1]);
+        circuit.private_parameters = BTreeSet::from([Witness(2), Witness(3)]);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..8 {
+            let witness =
+                sample_valid_witness(&circuit, &abi, &StubbedBlackBoxSolver, &mut rng).unwrap();
+            let value = |index: u32| witness[&Witness(index)].into_repr();
+            assert!(value(1) < Fr::from(1u64 << 8));
+            assert!(value(2) < Fr::from(1u64 << 32));
+            assert!(value(3) == Fr::from(0u64) || value(3) == Fr::from(1u64));
+
+            let cs = ConstraintSystem::new_ref();
+            CurveAcir::from((&circuit, witness))
+                .generate_constraints(cs.clone())
+                .unwrap();
+            assert!(cs.is_satisfied().unwrap());
+        }
+    }
+}
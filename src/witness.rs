@@ -0,0 +1,504 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use acvm::acir::acir_field::GenericFieldElement;
+use acvm::acir::circuit::brillig::{BrilligBytecode, BrilligOutputs};
+use acvm::acir::circuit::{Circuit, Opcode};
+use acvm::acir::native_types::{Witness, WitnessMap};
+use acvm::blackbox_solver::StubbedBlackBoxSolver;
+use acvm::pwg::{ACVMStatus, ACVM};
+use acvm::FieldElement;
+use ark_ff::PrimeField;
+use noirc_abi::input_parser::InputValue;
+use thiserror::Error;
+
+use crate::abi::{flatten_input_value, param_witnesses};
+use crate::bridge::AcirCircuit;
+use crate::concrete_cfg::{CurveAcir, Fr};
+use crate::inline::{inline_calls, InlineError};
+use crate::{ProgramWithAbi, UnsupportedOpcodeError};
+
+/// Two witness maps assign the same witness different values.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("witness {} is assigned conflicting values", .witness.0)]
+pub struct MergeConflict {
+    pub witness: Witness,
+}
+
+/// A witness which a Brillig call outputs and a constraint refers to has no value.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("witness {} is output by the Brillig call at opcode {opcode} but has no value", .witness.0)]
+pub struct MissingWitnessError {
+    pub witness: Witness,
+    pub opcode: usize,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SolveError {
+    #[error("Error: no value given for parameter `{0}`")]
+    MissingInput(String),
+    #[error("Error: value given for parameter `{0}` does not match its ABI type")]
+    InvalidInput(String),
+    #[error("Error: could not solve the circuit: {0}")]
+    Unsolvable(String),
+    #[error("Error: the program has no function with index {0}")]
+    UnknownFunction(usize),
+    #[error("{0}")]
+    Unsupported(#[from] UnsupportedOpcodeError),
+}
+
+impl From<InlineError> for SolveError {
+    fn from(error: InlineError) -> Self {
+        match error {
+            InlineError::UnknownFunction(index) => SolveError::UnknownFunction(index),
+            InlineError::Unsupported(error) => SolveError::Unsupported(error),
+        }
+    }
+}
+
+/// Solves the main function of `program` for the parameter values in `inputs`, keyed by the
+/// parameter names the ABI declares.
+///
+/// Every ABI parameter must be given; the values are laid out over the parameter's witnesses
+/// as its ABI type prescribes, so arrays and structs are given as a whole.
+pub fn solve_witness(
+    program: &ProgramWithAbi<Fr>,
+    inputs: &BTreeMap<String, InputValue>,
+) -> Result<WitnessMap<FieldElement>, SolveError> {
+    let main = program
+        .bytecode
+        .functions
+        .first()
+        .ok_or(SolveError::UnknownFunction(0))?;
+    let initial_witness = initial_witness(program, inputs)?;
+    solve(
+        main,
+        &program.bytecode.unconstrained_functions,
+        initial_witness,
+    )
+}
+
+/// Solves the function with index `fn_index` of `program` for the parameter values in `inputs`,
+/// as [`solve_witness`] does, and lowers it with the solved witness.
+///
+/// The inputs are laid out by the program's ABI, which describes its main function. A function
+/// which calls others is first flattened with [`inline_calls`], which drops its assertion
+/// messages. Brillig calls, including those of inlined functions, are run against the
+/// program's unconstrained functions.
+pub fn solve_and_build(
+    program: &ProgramWithAbi<Fr>,
+    inputs: &BTreeMap<String, InputValue>,
+    fn_index: usize,
+) -> Result<CurveAcir, SolveError> {
+    let function = program
+        .bytecode
+        .functions
+        .get(fn_index)
+        .ok_or(SolveError::UnknownFunction(fn_index))?;
+    let has_calls = function
+        .opcodes
+        .iter()
+        .any(|opcode| matches!(opcode, Opcode::Call { .. }));
+    let inlined;
+    let circuit = match has_calls {
+        true => {
+            inlined = inline_calls(&program.bytecode, fn_index)?;
+            &inlined
+        }
+        false => function,
+    };
+
+    let witness = solve(
+        circuit,
+        &program.bytecode.unconstrained_functions,
+        initial_witness(program, inputs)?,
+    )?;
+    Ok(CurveAcir::from((circuit, witness)))
+}
+
+// Lays out the named parameter values over the witnesses the ABI assigns them
+fn initial_witness(
+    program: &ProgramWithAbi<Fr>,
+    inputs: &BTreeMap<String, InputValue>,
+) -> Result<WitnessMap<FieldElement>, SolveError> {
+    let witnesses = param_witnesses(&program.abi);
+
+    let mut initial_witness = WitnessMap::new();
+    for param in &program.abi.parameters {
+        let value = inputs
+            .get(&param.name)
+            .ok_or_else(|| SolveError::MissingInput(param.name.clone()))?;
+        let values = flatten_input_value(&param.typ, value)
+            .ok_or_else(|| SolveError::InvalidInput(param.name.clone()))?;
+        for (witness, value) in witnesses[&param.name].iter().zip(values) {
+            initial_witness.insert(*witness, value);
+        }
+    }
+    Ok(initial_witness)
+}
+
+// Solves `circuit`, running its Brillig calls against the program's unconstrained functions
+fn solve(
+    circuit: &Circuit<FieldElement>,
+    unconstrained_functions: &[BrilligBytecode<FieldElement>],
+    initial_witness: WitnessMap<FieldElement>,
+) -> Result<WitnessMap<FieldElement>, SolveError> {
+    let mut acvm = ACVM::new(
+        &StubbedBlackBoxSolver,
+        &circuit.opcodes,
+        initial_witness,
+        unconstrained_functions,
+        &circuit.assert_messages,
+    );
+    match acvm.solve() {
+        ACVMStatus::Solved => Ok(acvm.finalize()),
+        status => Err(SolveError::Unsolvable(format!("{status:?}"))),
+    }
+}
+
+/// Checks that `witness_map` has a value for every output of a `BrilligCall` which a lowered
+/// constraint refers to.
+///
+/// Brillig calls are unconstrained and add no constraints when lowering; their outputs are
+/// only given values by solving. Lowering silently sets a missing output to zero, which
+/// typically makes the constraints depending on it unsatisfiable.
+pub fn validate_brillig_outputs<F: PrimeField>(
+    circuit: &Circuit<GenericFieldElement<F>>,
+    witness_map: &WitnessMap<GenericFieldElement<F>>,
+) -> Result<(), MissingWitnessError> {
+    let referenced = AcirCircuit::from((circuit, WitnessMap::new())).values;
+
+    for (opcode, call) in circuit.opcodes.iter().enumerate() {
+        let Opcode::BrilligCall { outputs, .. } = call else {
+            continue;
+        };
+        let witnesses = outputs.iter().flat_map(|output| match output {
+            BrilligOutputs::Simple(witness) => vec![*witness],
+            BrilligOutputs::Array(witnesses) => witnesses.clone(),
+        });
+        for witness in witnesses {
+            if referenced.contains_key(&witness) && witness_map.get(&witness).is_none() {
+                return Err(MissingWitnessError { witness, opcode });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the witnesses whose value differs between two solves of the same circuit, mapped to
+/// their value in `after`.
+///
+/// A witness missing from either map is treated as zero, the same as when lowering a circuit
+/// into an [`crate::bridge::AcirCircuit`].
+pub fn witness_delta<F: PrimeField>(
+    before: &WitnessMap<GenericFieldElement<F>>,
+    after: &WitnessMap<GenericFieldElement<F>>,
+) -> BTreeMap<Witness, F> {
+    let before = into_values(before);
+    let after = into_values(after);
+    let witnesses: BTreeSet<Witness> = before.keys().chain(after.keys()).copied().collect();
+
+    witnesses
+        .into_iter()
+        .filter_map(|witness| {
+            let old = before.get(&witness).copied().unwrap_or_else(F::zero);
+            let new = after.get(&witness).copied().unwrap_or_else(F::zero);
+            (old != new).then_some((witness, new))
+        })
+        .collect()
+}
+
+/// Unions `maps` into a single witness map.
+///
+/// A witness may appear in several maps as long as they all assign it the same value.
+pub fn merge_witness_maps<F: PrimeField>(
+    maps: &[WitnessMap<GenericFieldElement<F>>],
+) -> Result<WitnessMap<GenericFieldElement<F>>, MergeConflict> {
+    let mut merged = WitnessMap::new();
+    for map in maps {
+        for (witness, value) in map.clone() {
+            match merged.get(&witness) {
+                Some(existing) if *existing != value => return Err(MergeConflict { witness }),
+                Some(_) => {}
+                None => {
+                    merged.insert(witness, value);
+                }
+            }
+        }
+    }
+    Ok(merged)
+}
+
+fn into_values<F: PrimeField>(
+    witness_map: &WitnessMap<GenericFieldElement<F>>,
+) -> BTreeMap<Witness, F> {
+    witness_map
+        .clone()
+        .into_iter()
+        .map(|(witness, value)| (witness, value.into_repr()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+
+    use super::*;
+    use crate::test::{test_circuit, witness_map};
+    use crate::{read_program_with_abi_from_file, CurveAcir};
+
+    #[test]
+    fn delta_contains_only_affected_witnesses() {
+        let circuit = test_circuit();
+        let solve = |inputs: &[(u32, i128)]| {
+            let mut acvm = ACVM::new(
+                &StubbedBlackBoxSolver,
+                &circuit.opcodes,
+                witness_map(inputs),
+                &[],
+                &[],
+            );
+            assert_eq!(acvm.solve(), ACVMStatus::Solved);
+            acvm.finalize()
+        };
+
+        let before = solve(&[(0, 2), (1, 3), (2, 4), (3, 5)]);
+        let after = solve(&[(0, 2), (1, 3), (2, 4), (3, 6)]);
+
+        // Only w3 and the product w5 = w1 * w3 depend on the changed input
+        let expected =
+            BTreeMap::from([(Witness(3), Fr::from(6u64)), (Witness(5), Fr::from(18u64))]);
+        assert_eq!(witness_delta(&before, &after), expected);
+        assert!(witness_delta(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn merge_detects_conflicts() {
+        let inputs = witness_map(&[(0, 2), (1, 3)]);
+        let outputs = witness_map(&[(4, 8), (5, 15)]);
+        let merged = merge_witness_maps(&[inputs.clone(), outputs]).unwrap();
+        assert_eq!(merged, witness_map(&[(0, 2), (1, 3), (4, 8), (5, 15)]));
+
+        let consistent = witness_map(&[(1, 3), (2, 4)]);
+        let merged = merge_witness_maps(&[inputs.clone(), consistent]).unwrap();
+        assert_eq!(merged, witness_map(&[(0, 2), (1, 3), (2, 4)]));
+
+        let conflicting = witness_map(&[(1, 4)]);
+        assert_eq!(
+            merge_witness_maps(&[inputs, conflicting]),
+            Err(MergeConflict {
+                witness: Witness(1)
+            })
+        );
+    }
+
+    #[test]
+    fn brillig_outputs_must_have_values() {
+        use acvm::acir::circuit::brillig::BrilligInputs;
+        use acvm::acir::native_types::Expression;
+        use acvm::AcirField;
+
+        use crate::test::circuit_with_opcodes;
+
+        // w1 = 1 / w0 is computed by Brillig, then constrained by w0 * w1 == 1
+        let circuit = circuit_with_opcodes(
+            1,
+            vec![
+                Opcode::BrilligCall {
+                    id: 0,
+                    inputs: vec![BrilligInputs::Single(Expression::from(Witness(0)))],
+                    outputs: vec![BrilligOutputs::Simple(Witness(1))],
+                    predicate: None,
+                },
+                Opcode::AssertZero(Expression {
+                    mul_terms: vec![(FieldElement::one(), Witness(0), Witness(1))],
+                    linear_combinations: vec![],
+                    q_c: -FieldElement::one(),
+                }),
+            ],
+            &[],
+        );
+
+        let mut witness_map = WitnessMap::new();
+        witness_map.insert(Witness(0), FieldElement::from(4_i128));
+        assert_eq!(
+            validate_brillig_outputs(&circuit, &witness_map),
+            Err(MissingWitnessError {
+                witness: Witness(1),
+                opcode: 0
+            })
+        );
+
+        witness_map.insert(Witness(1), FieldElement::from(4_i128).inverse());
+        assert_eq!(validate_brillig_outputs(&circuit, &witness_map), Ok(()));
+        let circuit = CurveAcir::from((&circuit, witness_map));
+        assert_eq!(circuit.check_satisfied(), Ok(()));
+    }
+
+    #[test]
+    fn solves_named_inputs() {
+        let circuit_path = env::current_dir()
+            .unwrap()
+            .join("src/artifacts/test_circuit");
+        let program = read_program_with_abi_from_file(circuit_path).unwrap();
+        let fields = |values: [i128; 2]| {
+            InputValue::Vec(
+                values
+                    .into_iter()
+                    .map(|value| InputValue::Field(FieldElement::from(value)))
+                    .collect(),
+            )
+        };
+
+        let mut inputs = BTreeMap::from([("public_inputs".to_string(), fields([2, 3]))]);
+        assert_eq!(
+            solve_witness(&program, &inputs),
+            Err(SolveError::MissingInput("private_inputs".to_string()))
+        );
+
+        inputs.insert("private_inputs".to_string(), fields([4, 5]));
+        let witness = solve_witness(&program, &inputs).unwrap();
+        let outputs: Vec<FieldElement> = program.bytecode.functions[0]
+            .return_values
+            .0
+            .iter()
+            .map(|witness_index| witness[witness_index])
+            .collect();
+        assert_eq!(
+            outputs,
+            [FieldElement::from(8_i128), FieldElement::from(15_i128)]
+        );
+    }
+
+    #[test]
+    fn solves_brillig_calls() {
+        use acvm::acir::circuit::Program;
+        use acvm::AcirField;
+        use noirc_abi::{Abi, AbiParameter, AbiType, AbiVisibility};
+
+        use crate::test::{brillig_inverse_circuit, inverse_brillig};
+
+        let mut program = ProgramWithAbi {
+            bytecode: Program {
+                functions: vec![brillig_inverse_circuit()],
+                unconstrained_functions: vec![inverse_brillig()],
+            },
+            abi: Abi {
+                parameters: vec![AbiParameter {
+                    name: "x".to_string(),
+                    typ: AbiType::Field,
+                    visibility: AbiVisibility::Private,
+                }],
+                return_type: None,
+                error_types: BTreeMap::new(),
+            },
+            names: vec!["main".to_string()],
+        };
+        let inputs = BTreeMap::from([(
+            "x".to_string(),
+            InputValue::Field(FieldElement::from(4_i128)),
+        )]);
+
+        let witness = solve_witness(&program, &inputs).unwrap();
+        assert_eq!(witness[&Witness(1)], FieldElement::from(4_i128).inverse());
+
+        program.bytecode.functions.clear();
+        assert_eq!(
+            solve_witness(&program, &inputs),
+            Err(SolveError::UnknownFunction(0))
+        );
+    }
+
+    #[test]
+    fn solves_and_builds_test_circuit() {
+        use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+
+        let circuit_path = env::current_dir()
+            .unwrap()
+            .join("src/artifacts/test_circuit");
+        let program = read_program_with_abi_from_file(circuit_path).unwrap();
+        let fields = |values: [i128; 2]| {
+            InputValue::Vec(
+                values
+                    .into_iter()
+                    .map(|value| InputValue::Field(FieldElement::from(value)))
+                    .collect(),
+            )
+        };
+        let inputs = BTreeMap::from([
+            ("public_inputs".to_string(), fields([2, 3])),
+            ("private_inputs".to_string(), fields([4, 5])),
+        ]);
+
+        let circuit = solve_and_build(&program, &inputs, 0).unwrap();
+        assert_eq!(
+            circuit.public_output_values(),
+            [Fr::from(8u64), Fr::from(15u64)]
+        );
+        let cs = ConstraintSystem::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        assert_eq!(
+            solve_and_build(&program, &inputs, 1).unwrap_err(),
+            SolveError::UnknownFunction(1)
+        );
+    }
+
+    #[test]
+    fn solves_and_builds_brillig_callee() {
+        use acvm::acir::circuit::{Program, PublicInputs};
+        use acvm::acir::native_types::Expression;
+        use acvm::AcirField;
+        use ark_ff::Field;
+        use noirc_abi::{Abi, AbiParameter, AbiType, AbiVisibility};
+
+        use crate::test::{brillig_inverse_circuit, circuit_with_opcodes, inverse_brillig};
+
+        // main(x: w0) -> w2: w1 = inverse(w0), w2 = w1 + 1, where inverse runs a Brillig call
+        let one = FieldElement::one();
+        let mut main = circuit_with_opcodes(
+            2,
+            vec![
+                Opcode::Call {
+                    id: 1,
+                    inputs: vec![Witness(0)],
+                    outputs: vec![Witness(1)],
+                    predicate: None,
+                },
+                Opcode::AssertZero(Expression {
+                    mul_terms: vec![],
+                    linear_combinations: vec![(one, Witness(1)), (-one, Witness(2))],
+                    q_c: one,
+                }),
+            ],
+            &[],
+        );
+        main.private_parameters = BTreeSet::from([Witness(0)]);
+        main.return_values = PublicInputs(BTreeSet::from([Witness(2)]));
+        let program = ProgramWithAbi {
+            bytecode: Program {
+                functions: vec![main, brillig_inverse_circuit()],
+                unconstrained_functions: vec![inverse_brillig()],
+            },
+            abi: Abi {
+                parameters: vec![AbiParameter {
+                    name: "x".to_string(),
+                    typ: AbiType::Field,
+                    visibility: AbiVisibility::Private,
+                }],
+                return_type: None,
+                error_types: BTreeMap::new(),
+            },
+            names: vec!["main".to_string(), "inverse".to_string()],
+        };
+        let inputs = BTreeMap::from([(
+            "x".to_string(),
+            InputValue::Field(FieldElement::from(4_i128)),
+        )]);
+
+        let circuit = solve_and_build(&program, &inputs, 0).unwrap();
+        let inverse = Fr::from(4u64).inverse().unwrap();
+        assert_eq!(circuit.public_output_values(), [inverse + Fr::from(1u64)]);
+        assert_eq!(circuit.check_satisfied(), Ok(()));
+    }
+}
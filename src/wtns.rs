@@ -0,0 +1,201 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use ark_ff::{BigInteger, PrimeField};
+use thiserror::Error;
+
+use crate::concrete_cfg::Fr;
+
+// Reading and writing of snarkjs `.wtns` witness files.
+//
+// A `.wtns` file holds the full assignment `[1, public inputs.., witnesses..]`, which is the
+// same layout as arkworks' `instance_assignment` followed by its `witness_assignment`.
+//
+// Layout (all integers little-endian):
+// - the magic `wtns`, the format version (`u32`) and the number of sections (`u32`)
+// - each section starts with its id (`u32`) and size in bytes (`u64`)
+// - section 1 (header): the field element size `n8` (`u32`), the field prime (`n8` bytes)
+//   and the number of witness values (`u32`)
+// - section 2 (witness): the values, `n8` bytes each, in canonical (non-Montgomery) form
+
+const MAGIC: &[u8; 4] = b"wtns";
+const VERSION: u32 = 2;
+const HEADER_SECTION: u32 = 1;
+const WITNESS_SECTION: u32 = 2;
+
+#[derive(Debug, Error)]
+pub enum WtnsError {
+    #[error("Error: could not read or write witness file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Error: not a .wtns witness file")]
+    InvalidMagic,
+    #[error("Error: unsupported .wtns version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Error: witness file is for a different field than the configured curve")]
+    PrimeMismatch,
+    #[error("Error: witness file is missing section {0}")]
+    MissingSection(u32),
+    #[error("Error: witness value {0} is not a canonical field element")]
+    NonCanonicalValue(usize),
+}
+
+/// Reads the assignment stored in the snarkjs witness file at `path`.
+pub fn read_wtns<P: AsRef<Path>>(path: P) -> Result<Vec<Fr>, WtnsError> {
+    read_wtns_from(BufReader::new(File::open(path)?))
+}
+
+/// Writes `assignment` to `path` as a snarkjs witness file.
+pub fn write_wtns<P: AsRef<Path>>(path: P, assignment: &[Fr]) -> Result<(), WtnsError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_wtns_to(&mut writer, assignment)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn read_wtns_from<R: Read>(mut reader: R) -> Result<Vec<Fr>, WtnsError> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(WtnsError::InvalidMagic);
+    }
+    let version = read_u32(&mut reader)?;
+    if version != VERSION {
+        return Err(WtnsError::UnsupportedVersion(version));
+    }
+    let num_sections = read_u32(&mut reader)?;
+
+    let mut num_values = None;
+    let mut values = None;
+    for _ in 0..num_sections {
+        let section_id = read_u32(&mut reader)?;
+        let section_size = read_u64(&mut reader)?;
+        match section_id {
+            HEADER_SECTION => {
+                let n8 = read_u32(&mut reader)? as usize;
+                let mut prime = vec![0u8; n8];
+                reader.read_exact(&mut prime)?;
+                if n8 != field_size() || prime != Fr::MODULUS.to_bytes_le() {
+                    return Err(WtnsError::PrimeMismatch);
+                }
+                num_values = Some(read_u32(&mut reader)? as usize);
+            }
+            WITNESS_SECTION => {
+                // The witness section can only be decoded once the header is known
+                let num_values = num_values.ok_or(WtnsError::MissingSection(HEADER_SECTION))?;
+                let mut bytes = vec![0u8; field_size()];
+                values = Some(
+                    (0..num_values)
+                        .map(|index| {
+                            reader.read_exact(&mut bytes)?;
+                            let value = Fr::from_le_bytes_mod_order(&bytes);
+                            if value.into_bigint().to_bytes_le() != bytes {
+                                return Err(WtnsError::NonCanonicalValue(index));
+                            }
+                            Ok(value)
+                        })
+                        .collect::<Result<Vec<_>, _>>()?,
+                );
+            }
+            _ => {
+                std::io::copy(&mut (&mut reader).take(section_size), &mut std::io::sink())?;
+            }
+        }
+    }
+
+    values.ok_or(WtnsError::MissingSection(WITNESS_SECTION))
+}
+
+pub fn write_wtns_to<W: Write>(mut writer: W, assignment: &[Fr]) -> Result<(), WtnsError> {
+    let n8 = field_size();
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&2u32.to_le_bytes())?;
+
+    writer.write_all(&HEADER_SECTION.to_le_bytes())?;
+    writer.write_all(&(4 + n8 as u64 + 4).to_le_bytes())?;
+    writer.write_all(&(n8 as u32).to_le_bytes())?;
+    writer.write_all(&Fr::MODULUS.to_bytes_le())?;
+    writer.write_all(&(assignment.len() as u32).to_le_bytes())?;
+
+    writer.write_all(&WITNESS_SECTION.to_le_bytes())?;
+    writer.write_all(&((n8 * assignment.len()) as u64).to_le_bytes())?;
+    for value in assignment {
+        writer.write_all(&value.into_bigint().to_bytes_le())?;
+    }
+    Ok(())
+}
+
+// Size in bytes of a serialized field element
+fn field_size() -> usize {
+    Fr::MODULUS.to_bytes_le().len()
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, std::io::Error> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64, std::io::Error> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+    use ark_std::rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::groth16::{prepare_pk, prove_from_assignment, setup, verify};
+    use crate::test::{test_circuit, test_circuit_witness_map};
+    use crate::CurveAcir;
+
+    #[test]
+    fn wtns_round_trip() {
+        let assignment: Vec<Fr> = [1u64, 2, 3, 8, 15].into_iter().map(Fr::from).collect();
+        let path = env::temp_dir().join("arkworks_backend_round_trip.wtns");
+        write_wtns(&path, &assignment).unwrap();
+        assert_eq!(read_wtns(&path).unwrap(), assignment);
+    }
+
+    #[test]
+    fn wtns_rejects_other_fields() {
+        let mut bytes = Vec::new();
+        write_wtns_to(&mut bytes, &[Fr::from(1u64)]).unwrap();
+        // Corrupt the lowest byte of the prime in the header section
+        bytes[4 + 4 + 4 + 4 + 8 + 4] ^= 1;
+        assert!(matches!(
+            read_wtns_from(bytes.as_slice()),
+            Err(WtnsError::PrimeMismatch)
+        ));
+    }
+
+    #[test]
+    fn proves_from_wtns_file() {
+        let circuit = CurveAcir::from((&test_circuit(), test_circuit_witness_map()));
+        let cs = ConstraintSystem::new_ref();
+        circuit.clone().generate_constraints(cs.clone()).unwrap();
+        let cs = cs.borrow().unwrap();
+        let assignment = [
+            cs.instance_assignment.as_slice(),
+            cs.witness_assignment.as_slice(),
+        ]
+        .concat();
+
+        let path = env::temp_dir().join("arkworks_backend_proves_from.wtns");
+        write_wtns(&path, &assignment).unwrap();
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let (pk, vk) = setup(&circuit, &mut rng).unwrap();
+        let ppk = prepare_pk(pk, &circuit).unwrap();
+        let proof = prove_from_assignment(&ppk, &read_wtns(&path).unwrap(), &mut rng).unwrap();
+
+        let public_inputs: Vec<Fr> = [2u64, 3, 8, 15].into_iter().map(Fr::from).collect();
+        assert!(verify(&vk, &public_inputs, &proof).unwrap());
+    }
+}
@@ -0,0 +1,75 @@
+//! Reads the arithmetic gates of a lowered circuit through the public API, as a crate building
+//! its own constraint system from them would.
+
+use std::collections::BTreeSet;
+
+use acvm::acir::circuit::{Circuit, ExpressionWidth, Opcode, PublicInputs};
+use acvm::acir::native_types::{Expression, Witness};
+use acvm::{AcirField, FieldElement};
+use ark_ff::{One, Zero};
+use arkworks_backend::{CurveAcir, Fr};
+
+/// `w4 = w0 * w2` and `3 * w1 * w3 + 7 = w5`, with public inputs `w0, w1`.
+fn product_circuit() -> Circuit<FieldElement> {
+    Circuit {
+        expression_width: ExpressionWidth::Unbounded,
+        recursive: false,
+        current_witness_index: 5,
+        opcodes: vec![
+            Opcode::AssertZero(Expression {
+                mul_terms: vec![(FieldElement::one(), Witness(0), Witness(2))],
+                linear_combinations: vec![(-FieldElement::one(), Witness(4))],
+                q_c: FieldElement::zero(),
+            }),
+            Opcode::AssertZero(Expression {
+                mul_terms: vec![(FieldElement::from(3_u128), Witness(1), Witness(3))],
+                linear_combinations: vec![(-FieldElement::one(), Witness(5))],
+                q_c: FieldElement::from(7_u128),
+            }),
+        ],
+        private_parameters: BTreeSet::from([Witness(2), Witness(3)]),
+        public_parameters: PublicInputs(BTreeSet::from([Witness(0), Witness(1)])),
+        return_values: PublicInputs(BTreeSet::from([Witness(4), Witness(5)])),
+        assert_messages: Vec::new(),
+    }
+}
+
+#[test]
+fn reads_gate_terms() {
+    let circuit = CurveAcir::from(&product_circuit());
+    let gates: Vec<_> = circuit.gates_with_origin().collect();
+    assert_eq!(gates.len(), 2);
+
+    let (origin, gate) = gates[0];
+    assert_eq!(origin, 0);
+    assert_eq!(gate.mul_terms(), [(Fr::one(), Witness(0), Witness(2))]);
+    assert_eq!(gate.add_terms(), [(-Fr::one(), Witness(4))]);
+    assert_eq!(gate.constant_term(), Fr::zero());
+
+    let (origin, gate) = gates[1];
+    assert_eq!(origin, 1);
+    assert_eq!(gate.mul_terms(), [(Fr::from(3u64), Witness(1), Witness(3))]);
+    assert_eq!(gate.add_terms(), [(-Fr::one(), Witness(5))]);
+    assert_eq!(gate.constant_term(), Fr::from(7u64));
+}
+
+#[test]
+fn evaluates_gates_outside_the_crate() {
+    // w0..w5 = 2, 3, 4, 5, 8, 3 * 3 * 5 + 7
+    let values = [2u64, 3, 4, 5, 8, 52].map(Fr::from);
+    let value = |witness: &Witness| values[witness.0 as usize];
+
+    let circuit = CurveAcir::from(&product_circuit());
+    for (_, gate) in circuit.gates_with_origin() {
+        let mul_terms = gate
+            .mul_terms()
+            .iter()
+            .map(|(coeff, lhs, rhs)| *coeff * value(lhs) * value(rhs));
+        let add_terms = gate
+            .add_terms()
+            .iter()
+            .map(|(coeff, witness)| *coeff * value(witness));
+        let sum = mul_terms.chain(add_terms).sum::<Fr>() + gate.constant_term();
+        assert!(sum.is_zero());
+    }
+}